@@ -1,18 +1,52 @@
-from collections.abc import Iterator
-from typing import Optional, overload
+from array import array
+from collections.abc import Callable, Iterator
+from enum import Enum
+from typing import BinaryIO, Literal, Optional, TypeVar, overload
+
+_T = TypeVar("_T")
+
+TagValue = int | float | str | bytes | list[int] | list[float]
+
+class Tags:
+    def __getitem__(self, name: str) -> TagValue: ...
+    @overload
+    def get(self, name: str) -> Optional[TagValue]: ...
+    @overload
+    def get(self, name: str, default: _T) -> TagValue | _T: ...
+    def keys(self) -> list[str]: ...
+    def __contains__(self, name: str) -> bool: ...
+    def __iter__(self) -> Iterator[str]: ...
+    def __len__(self) -> int: ...
 
 class Segment:
     id: int
-    name: int
+    name: int | str
+    tags: Tags
+    rgfa: Optional[tuple[str, int, int]]
 
     def sequence(self) -> bytes: ...
+    def sequence_slice(self, start: int, end: int) -> bytes: ...
+    def base_counts(self) -> list[int]: ...
+    def gc_content(self) -> float: ...
     def __len__(self) -> int: ...
 
+class Orientation(Enum):
+    Forward = 0
+    Backward = 1
+
+    @staticmethod
+    def parse(text: str) -> Orientation: ...
+    def flip(self) -> Orientation: ...
+
 class Handle:
     seg_id: int
     segment: Segment
+    orient: Orientation
     is_forward: bool
 
+    def next(self) -> list[Handle]: ...
+    def prev(self) -> list[Handle]: ...
+
 class StepList:
     def __iter__(self) -> Iterator[Handle]: ...
     def __len__(self) -> int: ...
@@ -24,32 +58,71 @@ class StepList:
 class Path:
     id: int
     name: bytes
+    pansn: Optional[tuple[str, str, str]]
+    overlaps: list[Alignment]
+    tags: Tags
 
+    def sequence(self) -> bytes: ...
+    def position_at(self, offset: int) -> Optional[tuple[int, Handle, int]]: ...
+    def liftover(self, offset: int, other: Path) -> list[int]: ...
     def __iter__(self) -> Iterator[Handle]: ...
     @overload
     def __getitem__(self, idx: int) -> Handle: ...
     @overload
     def __getitem__(self, slice: slice) -> StepList: ...
 
+class Alignment:
+    ops: list[tuple[int, str]]
+
 class Link:
     id: int
     from_: Handle
     to: Handle
+    tags: Tags
+    inverting: bool
+
+class Jump:
+    id: int
+    from_: Handle
+    to: Handle
+    distance: Optional[int]
+
+class GraphDiff:
+    segments_removed: list[str]
+    segments_added: list[str]
+    segments_changed: list[str]
+    links_removed: list[tuple[str, str]]
+    links_added: list[tuple[str, str]]
+    paths_removed: list[str]
+    paths_added: list[str]
+    paths_changed: list[str]
+
+    def __bool__(self) -> bool: ...
+
+class KmerIndex:
+    k: int
+
+    def query(self, kmer: bytes) -> list[tuple[Segment, int]]: ...
+    def __len__(self) -> int: ...
 
 class SegmentList:
     @overload
     def __getitem__(self, idx: int) -> Segment: ...
     @overload
     def __getitem__(self, slice: slice) -> SegmentList: ...
+    def get_many(self, indices: list[int]) -> list[Segment]: ...
+    def get_range(self, start: int, end: int) -> list[Segment]: ...
     def __iter__(self) -> Iterator[Segment]: ...
     def __len__(self) -> int: ...
-    def find(self, name: int) -> Optional[Segment]: ...
+    def find(self, name: int | str) -> Optional[Segment]: ...
 
 class PathList:
     @overload
     def __getitem__(self, idx: int) -> Path: ...
     @overload
     def __getitem__(self, slice: slice) -> PathList: ...
+    def get_many(self, indices: list[int]) -> list[Path]: ...
+    def get_range(self, start: int, end: int) -> list[Path]: ...
     def __iter__(self) -> Iterator[Path]: ...
     def __len__(self) -> int: ...
     def find(self, name: bytes) -> Optional[Path]: ...
@@ -59,17 +132,126 @@ class LinkList:
     def __getitem__(self, idx: int) -> Link: ...
     @overload
     def __getitem__(self, slice: slice) -> LinkList: ...
+    def get_many(self, indices: list[int]) -> list[Link]: ...
+    def get_range(self, start: int, end: int) -> list[Link]: ...
     def __iter__(self) -> Iterator[Link]: ...
     def __len__(self) -> int: ...
 
+class JumpList:
+    @overload
+    def __getitem__(self, idx: int) -> Jump: ...
+    @overload
+    def __getitem__(self, slice: slice) -> JumpList: ...
+    def get_many(self, indices: list[int]) -> list[Jump]: ...
+    def get_range(self, start: int, end: int) -> list[Jump]: ...
+    def __iter__(self) -> Iterator[Jump]: ...
+    def __len__(self) -> int: ...
+
 class FlatGFA:
     segments: SegmentList
     paths: PathList
     links: LinkList
+    jumps: JumpList
+    closed: bool
+    parse_stats: Optional[dict[str, int | float]]
 
+    def find_sequence(
+        self, query: bytes, ignore_case: bool = False
+    ) -> list[tuple[Segment, int, str]]: ...
+    def segments_sorted(self) -> list[Segment]: ...
+    def segments_longer_than(self, length: int) -> list[Segment]: ...
+    def segments_no_longer_than(self, length: int) -> list[Segment]: ...
+    def links_incident(self, seg_ids: list[int]) -> list[Link]: ...
+    def tips(self) -> list[Handle]: ...
+    def self_loops(self) -> list[Link]: ...
+    def link_handles(
+        self,
+    ) -> Iterator[tuple[tuple[Segment, Orientation], tuple[Segment, Orientation]]]: ...
+    def paths_by_sample(self) -> dict[str, list[Path]]: ...
+    def superbubbles(self) -> list[tuple[Handle, Handle]]: ...
+    def complexity(self) -> dict[str, int | float | bool]: ...
+    def gc_content(self) -> float: ...
+    def segment_base_counts(self, threads: Optional[int] = None) -> list[list[int]]: ...
+    def coverage(self, threads: Optional[int] = None) -> list[int]: ...
+    # `array.array` rather than NumPy, to avoid the dependency: typecode "I" (uint32)
+    # for segments and "Q" (uint64) for paths. Use `numpy.frombuffer` for NumPy.
+    def segment_lengths(self) -> array[int]: ...
+    def path_lengths(self) -> array[int]: ...
+    def segment_n50(self) -> tuple[int, int]: ...
+    def path_n50(self) -> tuple[int, int]: ...
+    def is_rgfa(self) -> bool: ...
+    def lint(self) -> list[str]: ...
+    def diff(self, other: FlatGFA) -> GraphDiff: ...
+    def build_kmer_index(self, k: int) -> KmerIndex: ...
+    def to_mutable(self) -> MutableFlatGFA: ...
     def write_flatgfa(self, filename: str) -> None: ...
-    def write_gfa(self, filename: str) -> None: ...
+    def write_gfa(
+        self,
+        filename: str,
+        sorted: bool = False,
+        sort_segments: Optional[Literal["name", "length"]] = None,
+        compress: Optional[bool] = None,
+    ) -> None: ...
+    def write_gfa_paths(
+        self,
+        filename: str,
+        path_ids: list[int],
+        prune: bool = False,
+        compress: Optional[bool] = None,
+    ) -> None: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> FlatGFA: ...
+    def __exit__(self, *args: object) -> None: ...
+
+class IndexedGFA:
+    def sequence(self, name: int | str) -> Optional[bytes]: ...
+    def __contains__(self, name: int | str) -> bool: ...
+    def __len__(self) -> int: ...
+
+class MutableFlatGFA:
+    def add_link(
+        self,
+        from_: int | str,
+        from_orient: str,
+        to: int | str,
+        to_orient: str,
+        overlap: str = "0M",
+    ) -> int: ...
+    def add_path(self, name: str, steps: str) -> int: ...
+    def remove_segment(self, id: int) -> None: ...
+    def set_segment_tag(self, id: int, tag: str, value: int | float | str) -> None: ...
+    def set_path_tag(self, id: int, tag: str, value: int | float | str) -> None: ...
+    def rename_segments(self, rename: Callable[[str], str]) -> None: ...
+    def compact(self) -> None: ...
+    def freeze(self) -> FlatGFA: ...
 
-def parse(filename: str) -> FlatGFA: ...
+def parse(
+    filename: str,
+    sequences: bool = True,
+    *,
+    paths: bool = True,
+    strict: bool = False,
+    keep_first_duplicates: bool = False,
+    keep_unknown: bool = False,
+) -> FlatGFA: ...
 def load(filename: str) -> FlatGFA: ...
 def parse_bytes(gfa: bytes) -> FlatGFA: ...
+def parse_stream(
+    file: BinaryIO,
+    sequences: bool = True,
+    *,
+    paths: bool = True,
+    strict: bool = False,
+    keep_first_duplicates: bool = False,
+    keep_unknown: bool = False,
+) -> FlatGFA: ...
+def load_url(
+    url: str,
+    sequences: bool = True,
+    *,
+    paths: bool = True,
+    strict: bool = False,
+    keep_first_duplicates: bool = False,
+    keep_unknown: bool = False,
+) -> FlatGFA: ...
+def open_bgzf(filename: str) -> IndexedGFA: ...
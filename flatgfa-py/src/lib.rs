@@ -1,130 +1,1603 @@
-use flatgfa::pool::Id;
-use flatgfa::{self, file, memfile, print, FlatGFA, HeapGFAStore};
-use pyo3::exceptions::PyIndexError;
+use flatgfa::adjacency::AdjacencyIndex;
+use flatgfa::bgzf::{self, IndexedGfa};
+use flatgfa::diff::GraphDiff;
+use flatgfa::parse::{ParseError, ParseStats, ParserConfig};
+use flatgfa::pool::{CompactSpan, Id, Span, Store as _};
+use flatgfa::search::KmerIndex;
+use flatgfa::{self, file, gfaline, memfile, print, FlatGFA, HeapGFAStore, SegName, Tag, Tagged};
+use pyo3::exceptions::{PyIOError, PyIndexError, PyKeyError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyBytes, PySlice};
-use std::io::Write;
-use std::sync::Arc;
-
-/// Storage for a FlatGFA.
+use pyo3::types::{PyBytes, PyDict, PyIterator, PyList, PySlice};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
+
+/// The backing data for a FlatGFA.
 ///
-/// This may be either an in-memory data structure or a memory-mapped file. It exposes a
-/// uniform interface to the FlatGFA data via `view`.
-enum Store {
+/// This may be either an in-memory data structure or a memory-mapped file.
+enum Backing {
     Heap(Box<HeapGFAStore>),
     File(memmap::Mmap),
 }
 
+/// Storage for a FlatGFA.
+///
+/// This wraps the backing data and exposes a uniform interface to the FlatGFA data via
+/// `read`. It also holds indices that we build lazily, on first use.
+///
+/// A `Store` is shared (via `Arc`) by every Python object that refers into the graph,
+/// and Python threads may use those objects concurrently. So the data is read-only
+/// after construction, and the lazy indices are guarded by `OnceLock`s. The only
+/// change is `close`, which drops the backing data once no reader is using it.
+struct Store {
+    backing: RwLock<Option<Backing>>,
+    adjacency: OnceLock<AdjacencyIndex>,
+    seg_order: OnceLock<Vec<Id<flatgfa::Segment>>>,
+
+    /// Statistics from parsing the graph, if it came from text GFA.
+    parse_stats: Option<ParseStats>,
+}
+
 impl Store {
-    /// Parse a text GFA file.
-    fn parse_file(filename: &str) -> Self {
-        let file = memfile::map_file(filename);
-        Self::parse_gfa(file.as_ref())
+    fn new(backing: Backing) -> Self {
+        Self {
+            backing: RwLock::new(Some(backing)),
+            adjacency: OnceLock::new(),
+            seg_order: OnceLock::new(),
+            parse_stats: None,
+        }
+    }
+
+    /// Wrap a graph that we parsed from text GFA.
+    fn parsed(store: HeapGFAStore, stats: &Stats) -> Self {
+        Self {
+            parse_stats: stats.take(),
+            ..Self::new(Backing::Heap(Box::new(store)))
+        }
+    }
+
+    /// Parse a text GFA file, which may be gzip- or BGZF-compressed.
+    fn parse_file(filename: &str, config: ParserConfig) -> PyResult<Self> {
+        let warnings = Warnings::default();
+        let stats = Stats::default();
+        let store = text_parser(config, &warnings, &stats)
+            .parse_file(filename)
+            .map_err(parse_error)?;
+        warnings.emit()?;
+        Ok(Self::parsed(store, &stats))
+    }
+
+    /// Parse a text GFA file, which may be compressed, from a Python binary file
+    /// object.
+    ///
+    /// An exception from the file object's `read` method propagates unchanged.
+    fn parse_pyfile(file: &Bound<'_, PyAny>, config: ParserConfig) -> PyResult<Self> {
+        let error = Cell::new(None);
+        let reader = PyFileReader {
+            file,
+            error: &error,
+        };
+        let warnings = Warnings::default();
+        let stats = Stats::default();
+        let result = text_parser(config, &warnings, &stats)
+            .parse_stream(BufReader::with_capacity(1 << 16, reader));
+        if let Some(err) = error.take() {
+            return Err(err);
+        }
+        let store = result.map_err(parse_error)?;
+        warnings.emit()?;
+        Ok(Self::parsed(store, &stats))
     }
 
     /// Parse a GFA graph from a byte buffer.
-    fn parse_gfa(data: &[u8]) -> Self {
-        let store = flatgfa::parse::Parser::for_heap().parse_mem(data);
-        Self::Heap(Box::new(store))
+    fn parse_gfa(data: &[u8]) -> PyResult<Self> {
+        let warnings = Warnings::default();
+        let stats = Stats::default();
+        let store = text_parser(ParserConfig::default(), &warnings, &stats)
+            .parse_mem(data)
+            .map_err(parse_error)?;
+        warnings.emit()?;
+        Ok(Self::parsed(store, &stats))
     }
 
-    /// Load a FlatGFA binary file.
-    fn load(filename: &str) -> Self {
+    /// Load a FlatGFA binary file, raising a `ValueError` if it is not in the format
+    /// we can read.
+    fn load(filename: &str) -> PyResult<Self> {
         let mmap = memfile::map_file(filename);
-        Self::File(mmap)
+        file::view(&mmap).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Self::new(Backing::File(mmap)))
+    }
+
+    /// Get access to the data stored here, or raise a `ValueError` if the graph has
+    /// been closed.
+    ///
+    /// The data stays available until the returned guard is dropped.
+    fn read(&self) -> PyResult<StoreGuard<'_>> {
+        let guard = self.backing.read().unwrap();
+        if guard.is_none() {
+            return Err(PyValueError::new_err("operation on a closed FlatGFA"));
+        }
+        Ok(StoreGuard(guard))
     }
 
+    /// Drop the backing data (unmapping the file, if there is one).
+    ///
+    /// This waits for any readers to finish.
+    fn close(&self) {
+        self.backing.write().unwrap().take();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.backing.read().unwrap().is_none()
+    }
+
+    /// Get the adjacency index for the graph, building it if necessary.
+    ///
+    /// Building the index can take a while, so we release the GIL. If several threads
+    /// ask for the index at once, one builds it and the others wait.
+    ///
+    /// We only take the read lock once the GIL is released, and we drop it before we
+    /// take the GIL back. Otherwise, we could deadlock with a pending `close` and a
+    /// thread that holds the GIL while it waits to read.
+    fn adjacency(&self, py: Python) -> PyResult<&AdjacencyIndex> {
+        py.allow_threads(|| {
+            let data = self.read()?;
+            Ok(self
+                .adjacency
+                .get_or_init(|| AdjacencyIndex::build(&data.view())))
+        })
+    }
+
+    /// Get the segments' IDs in natural name order, sorting them if necessary. Like
+    /// `adjacency`, this holds the read lock only while the GIL is released.
+    fn seg_order(&self, py: Python) -> PyResult<&[Id<flatgfa::Segment>]> {
+        py.allow_threads(|| {
+            let data = self.read()?;
+            Ok(self
+                .seg_order
+                .get_or_init(|| data.view().segments_by_name().collect())
+                .as_slice())
+        })
+    }
+}
+
+/// Read access to the data in an open `Store`.
+struct StoreGuard<'a>(RwLockReadGuard<'a, Option<Backing>>);
+
+impl StoreGuard<'_> {
     /// Get the FlatGFA stored here.
-    fn view(&self) -> FlatGFA {
+    fn view(&self) -> FlatGFA<'_> {
         // TK It seems wasteful to check the type of store every time... and to construct
         // the view every time. It's probably possible to fix this with a self-reference,
         // e.g., with the `owning_ref` crate.
-        match self {
-            Store::Heap(ref store) => (**store).as_ref(),
-            Store::File(ref mmap) => file::view(mmap),
+        match self.0.as_ref().expect("store is open") {
+            Backing::Heap(store) => (**store).as_ref(),
+            Backing::File(mmap) => file::view(mmap).expect("checked when loaded"),
         }
     }
 }
 
+// All the read APIs share a `Store` across Python threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Store>();
+};
+
 /// An efficient representation of a Graphical Fragment Assembly (GFA) file.
 #[pyclass(frozen)]
 #[pyo3(name = "FlatGFA", module = "flatgfa")]
 struct PyFlatGFA(Arc<Store>);
 
+/// Get a parser for text GFA with the given policies. Its warnings go to `warnings`,
+/// and its statistics go to `stats`.
+fn text_parser(
+    config: ParserConfig,
+    warnings: &Warnings,
+    stats: &Stats,
+) -> flatgfa::parse::Parser<'static, flatgfa::HeapFamily> {
+    let sink = warnings.0.clone();
+    let stats = stats.clone();
+    flatgfa::parse::Parser::for_heap()
+        .with_config(config)
+        .with_warnings(move |warning| sink.borrow_mut().push(warning.to_string()))
+        .with_stats(move |report| *stats.borrow_mut() = Some(report.clone()))
+}
+
+/// Get the parsing policies for the keyword arguments that the parsing functions
+/// share.
+fn parser_config(
+    sequences: bool,
+    paths: bool,
+    strict: bool,
+    keep_first_duplicates: bool,
+    keep_unknown: bool,
+) -> ParserConfig {
+    ParserConfig {
+        strict,
+        keep_first_duplicates,
+        keep_unknown_records: keep_unknown,
+        skip_sequences: !sequences,
+        skip_paths: !paths,
+        ..ParserConfig::default()
+    }
+}
+
+/// The warnings from parsing a GFA file, which we collect while parsing and then
+/// report as Python `UserWarning`s.
+#[derive(Default)]
+struct Warnings(Rc<RefCell<Vec<String>>>);
+
+/// Where a parser reports its statistics.
+type Stats = Rc<RefCell<Option<ParseStats>>>;
+
+impl Warnings {
+    fn emit(&self) -> PyResult<()> {
+        Python::with_gil(|py| {
+            let category = py.get_type_bound::<PyUserWarning>();
+            for message in self.0.take() {
+                PyErr::warn_bound(py, &category, &message, 1)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A Rust reader over a Python binary file object, like an open file or an HTTP
+/// response.
+///
+/// If the file object raises an exception, we stash it in `error` (so the caller can
+/// re-raise it) and report a generic I/O error to the parser.
+struct PyFileReader<'a, 'py> {
+    file: &'a Bound<'py, PyAny>,
+    error: &'a Cell<Option<PyErr>>,
+}
+
+impl Read for PyFileReader<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = self
+            .file
+            .call_method1("read", (buf.len(),))
+            .and_then(|chunk| Ok(chunk.downcast_into::<PyBytes>()?));
+        match chunk {
+            Ok(chunk) => {
+                let data = chunk.as_bytes();
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok(len)
+            }
+            Err(err) => {
+                let msg = err.to_string();
+                self.error.set(Some(err));
+                Err(io::Error::other(msg))
+            }
+        }
+    }
+}
+
+/// Convert a GFA parsing error to a Python exception.
+///
+/// Malformed input raises a `ValueError`, and I/O problems raise an `OSError`. The
+/// message includes the line number.
+fn parse_error(err: ParseError) -> PyErr {
+    match err {
+        ParseError::Io { .. } => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// Raise a `ValueError` if the graph was parsed without its sequences.
+fn check_sequences(gfa: &flatgfa::FlatGFA) -> PyResult<()> {
+    gfa.check_sequences()
+        .map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+/// Raise a `ValueError` if the graph was parsed without its paths.
+fn check_paths(gfa: &flatgfa::FlatGFA) -> PyResult<()> {
+    gfa.check_paths()
+        .map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+/// Create a GFA text file and fill it in with `write`. The text is compressed with
+/// gzip if `compress` is true or, when it is `None`, if the name ends in `.gz`.
+fn write_text_file(
+    filename: &str,
+    compress: Option<bool>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(filename)?);
+    if compress.unwrap_or_else(|| bgzf::is_gzip_name(filename)) {
+        let mut enc = bgzf::compress_writer(&mut file);
+        write(&mut enc)?;
+        enc.finish()?;
+    } else {
+        write(&mut file)?;
+    }
+    file.flush()
+}
+
+/// Make a Python `array.array` with the given typecode from the bytes of its
+/// elements (in native byte order).
+fn py_array(py: Python, typecode: &str, bytes: &[u8]) -> PyResult<PyObject> {
+    let array = py
+        .import_bound("array")?
+        .getattr("array")?
+        .call1((typecode,))?;
+    array.call_method1("frombytes", (PyBytes::new_bound(py, bytes),))?;
+    Ok(array.unbind())
+}
+
+/// Run `f` in a thread pool with the given number of threads, or in the global pool
+/// (which uses every core) if that is `None`.
+fn with_threads<T: Send>(threads: Option<usize>, f: impl FnOnce() -> T + Send) -> PyResult<T> {
+    match threads {
+        None => Ok(f()),
+        Some(0) => Err(PyValueError::new_err("threads must be positive")),
+        Some(threads) => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            Ok(pool.install(f))
+        }
+    }
+}
+
 /// Parse a GFA file into our FlatGFA representation.
+///
+/// The file may be compressed with gzip or BGZF, and the filename ``"-"`` reads from
+/// stdin. Raises a `ValueError` if the file is malformed. Problems that we can work
+/// around, like two paths with the same name (the second gets renamed to
+/// ``name.1``), issue a `UserWarning` instead.
+///
+/// With ``sequences=False``, the graph omits segment sequences to save memory.
+/// ``len(segment)`` still works, but operations that need the bases, like
+/// :meth:`Segment.sequence`, raise a `ValueError`. With ``paths=False``, it leaves
+/// out the paths and walks, which can be most of a large graph. Printing the graph
+/// then omits them too, and operations that need paths, like :attr:`FlatGFA.paths`,
+/// raise a `ValueError`.
+///
+/// The other keyword arguments choose how to handle problems in the input:
+///
+/// * ``strict=True`` rejects optional fields that are not well-formed tags and
+///   repeated path names, instead of keeping or renaming them.
+/// * ``keep_first_duplicates=True`` keeps the first of several segments with the
+///   same name (with a warning) instead of raising an error.
+/// * ``keep_unknown=True`` keeps lines with unknown record types, like comments,
+///   verbatim instead of raising an error. They are written back out in place.
+#[pyfunction]
+#[pyo3(signature = (
+    filename,
+    sequences = true,
+    *,
+    paths = true,
+    strict = false,
+    keep_first_duplicates = false,
+    keep_unknown = false,
+))]
+fn parse(
+    filename: &str,
+    sequences: bool,
+    paths: bool,
+    strict: bool,
+    keep_first_duplicates: bool,
+    keep_unknown: bool,
+) -> PyResult<PyFlatGFA> {
+    let config = parser_config(
+        sequences,
+        paths,
+        strict,
+        keep_first_duplicates,
+        keep_unknown,
+    );
+    let store = Store::parse_file(filename, config)?;
+    Ok(PyFlatGFA(Arc::new(store)))
+}
+
+/// Parse a GFA file from a bytestring into our FlatGFA representation.
+///
+/// Raises a `ValueError` if the data is malformed.
+#[pyfunction]
+fn parse_bytes(bytes: &[u8]) -> PyResult<PyFlatGFA> {
+    let store = Store::parse_gfa(bytes)?;
+    Ok(PyFlatGFA(Arc::new(store)))
+}
+
+/// Parse a GFA file from a binary file object, such as ``sys.stdin.buffer``.
+///
+/// This reads the data in chunks, without holding all of the text in memory. The data
+/// may be compressed with gzip or BGZF. Raises a `ValueError` if the data is malformed;
+/// exceptions from the file object propagate. The keyword arguments are the same as
+/// for :func:`parse`.
 #[pyfunction]
-fn parse(filename: &str) -> PyFlatGFA {
-    PyFlatGFA(Arc::new(Store::parse_file(filename)))
+#[pyo3(signature = (
+    file,
+    sequences = true,
+    *,
+    paths = true,
+    strict = false,
+    keep_first_duplicates = false,
+    keep_unknown = false,
+))]
+fn parse_stream(
+    file: &Bound<'_, PyAny>,
+    sequences: bool,
+    paths: bool,
+    strict: bool,
+    keep_first_duplicates: bool,
+    keep_unknown: bool,
+) -> PyResult<PyFlatGFA> {
+    let config = parser_config(
+        sequences,
+        paths,
+        strict,
+        keep_first_duplicates,
+        keep_unknown,
+    );
+    let store = Store::parse_pyfile(file, config)?;
+    Ok(PyFlatGFA(Arc::new(store)))
+}
+
+/// Fetch and parse a GFA file from a URL.
+///
+/// This uses Python's :mod:`urllib.request`, so it supports the same URL schemes
+/// (including ``https://`` and ``file://``), and it streams the data like
+/// :func:`parse_stream`. Compressed data works too, whether it comes from a ``.gz``
+/// file or a gzip ``Content-Encoding``. HTTP errors (like a 404) raise
+/// :class:`urllib.error.HTTPError` and other network problems raise an `OSError`.
+/// The keyword arguments are the same as for :func:`parse`.
+#[pyfunction]
+#[pyo3(signature = (
+    url,
+    sequences = true,
+    *,
+    paths = true,
+    strict = false,
+    keep_first_duplicates = false,
+    keep_unknown = false,
+))]
+fn load_url(
+    py: Python,
+    url: &str,
+    sequences: bool,
+    paths: bool,
+    strict: bool,
+    keep_first_duplicates: bool,
+    keep_unknown: bool,
+) -> PyResult<PyFlatGFA> {
+    let config = parser_config(
+        sequences,
+        paths,
+        strict,
+        keep_first_duplicates,
+        keep_unknown,
+    );
+    let response = py
+        .import_bound("urllib.request")?
+        .call_method1("urlopen", (url,))?;
+    let store = Store::parse_pyfile(&response, config);
+    response.call_method0("close")?;
+    Ok(PyFlatGFA(Arc::new(store?)))
+}
+
+/// Load a binary FlatGFA file.
+///
+/// This function should be fast to call because it does not actually read the file's data.
+/// It memory-maps the file so subsequent accesses will actually read the data "on demand."
+/// You can produce these files with :meth:`FlatGFA.write_flatgfa`. Raises a
+/// `ValueError` if the file is not a FlatGFA file, or if it was written by a version of
+/// FlatGFA with a different file format.
+#[pyfunction]
+fn load(filename: &str) -> PyResult<PyFlatGFA> {
+    Ok(PyFlatGFA(Arc::new(Store::load(filename)?)))
+}
+
+/// Open a BGZF-compressed GFA file for fetching individual segments.
+///
+/// This scans the file once to index its segments, but it does not keep the graph in
+/// memory. Use :func:`parse` to read the whole graph instead.
+#[pyfunction]
+fn open_bgzf(filename: &str) -> PyResult<PyIndexedGFA> {
+    let gfa = IndexedGfa::open(filename).map_err(|err| PyIOError::new_err(err.to_string()))?;
+    Ok(PyIndexedGFA(gfa))
+}
+
+#[pymethods]
+impl PyFlatGFA {
+    /// The segments (nodes) in the graph, as a :class:`SegmentList`.
+    #[getter]
+    fn segments(&self) -> PyResult<SegmentList> {
+        Ok(SegmentList(ListRef {
+            store: self.0.clone(),
+            start: 0,
+            end: self.0.read()?.view().segs.len() as u32,
+        }))
+    }
+
+    /// The paths in the graph, as a :class:`PathList`.
+    #[getter]
+    fn paths(&self) -> PyResult<PathList> {
+        let data = self.0.read()?;
+        let gfa = data.view();
+        check_paths(&gfa)?;
+        Ok(PathList(ListRef {
+            store: self.0.clone(),
+            start: 0,
+            end: gfa.paths.len() as u32,
+        }))
+    }
+
+    /// The links (edges) in the graph, as a :class:`LinkList`.
+    #[getter]
+    fn links(&self) -> PyResult<LinkList> {
+        Ok(LinkList(ListRef {
+            store: self.0.clone(),
+            start: 0,
+            end: self.0.read()?.view().links.len() as u32,
+        }))
+    }
+
+    /// The jumps (long-range J-line edges) in the graph, as a :class:`JumpList`.
+    #[getter]
+    fn jumps(&self) -> PyResult<JumpList> {
+        Ok(JumpList(ListRef {
+            store: self.0.clone(),
+            start: 0,
+            end: self.0.read()?.view().jumps.len() as u32,
+        }))
+    }
+
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        py.allow_threads(|| Ok(format!("{}", &self.0.read()?.view())))
+    }
+
+    /// A summary of the graph's size, like ``<FlatGFA: 4 segments, 4 links, 2 paths>``.
+    ///
+    /// This only counts records, so it is cheap even for large graphs.
+    fn __repr__(&self) -> String {
+        match self.0.read() {
+            Ok(data) => {
+                let gfa = data.view();
+                format!(
+                    "<FlatGFA: {} segments, {} links, {} paths>",
+                    gfa.segs.len(),
+                    gfa.links.len(),
+                    gfa.paths.len()
+                )
+            }
+            Err(_) => "<FlatGFA: closed>".into(),
+        }
+    }
+
+    /// Find all exact occurrences of a nucleotide sequence in the graph's segments.
+    ///
+    /// This searches both strands. It returns a list of `(segment, offset, strand)`
+    /// tuples, where `strand` is ``"+"`` or ``"-"`` and `offset` counts from the start
+    /// of the segment's sequence *on that strand*. With ``ignore_case=True``,
+    /// lowercase (e.g., soft-masked) bases match too.
+    #[pyo3(signature = (query, ignore_case = false))]
+    fn find_sequence(
+        &self,
+        py: Python,
+        query: &[u8],
+        ignore_case: bool,
+    ) -> PyResult<Vec<(PySegment, usize, String)>> {
+        let hits = py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_sequences(&gfa)?;
+            PyResult::Ok(if ignore_case {
+                gfa.find_sequence_ignore_case(query)
+            } else {
+                gfa.find_sequence(query)
+            })
+        })?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| {
+                let seg = PySegment(EntityRef {
+                    store: self.0.clone(),
+                    index: hit.seg.into(),
+                });
+                (seg, hit.offset, hit.orient.to_string())
+            })
+            .collect())
+    }
+
+    /// Get all the segments, sorted by name in natural order, so ``s9`` comes before
+    /// ``s10`` (and numeric names sort by value).
+    ///
+    /// We sort the segments on the first call and reuse the order afterward.
+    fn segments_sorted(&self, py: Python) -> PyResult<Vec<PySegment>> {
+        Ok(self
+            .0
+            .seg_order(py)?
+            .iter()
+            .map(|&id| {
+                PySegment(EntityRef {
+                    store: self.0.clone(),
+                    index: id.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Get the segments that are strictly longer than ``length``.
+    ///
+    /// A segment without a stored sequence counts as having the length in its ``LN``
+    /// tag, or 0 if it has none.
+    fn segments_longer_than(&self, length: usize) -> PyResult<Vec<PySegment>> {
+        let data = self.0.read()?;
+        Ok(data
+            .view()
+            .segments_longer_than(length)
+            .map(|id| {
+                PySegment(EntityRef {
+                    store: self.0.clone(),
+                    index: id.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Get the segments that are at most ``length`` long: the ones that
+    /// :meth:`segments_longer_than` leaves out.
+    fn segments_no_longer_than(&self, length: usize) -> PyResult<Vec<PySegment>> {
+        let data = self.0.read()?;
+        Ok(data
+            .view()
+            .segments_no_longer_than(length)
+            .map(|id| {
+                PySegment(EntityRef {
+                    store: self.0.clone(),
+                    index: id.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Get the links with at least one endpoint among the given segment IDs.
+    ///
+    /// This uses an index, so it avoids scanning links that do not touch the segments.
+    fn links_incident(&self, py: Python, seg_ids: Vec<u32>) -> PyResult<Vec<PyLink>> {
+        let seg_count = self.0.read()?.view().segs.len();
+        if seg_ids.iter().any(|&id| id as usize >= seg_count) {
+            return Err(PyIndexError::new_err("segment ID out of range"));
+        }
+        let seg_ids: HashSet<Id<flatgfa::Segment>> = seg_ids.into_iter().map(Id::from).collect();
+        let links = self.0.adjacency(py)?.links_incident(&seg_ids);
+        Ok(links
+            .into_iter()
+            .map(|id| {
+                PyLink(EntityRef {
+                    store: self.0.clone(),
+                    index: id.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Find the tips: the dead-end :class:`Handle` objects with no links leading out.
+    ///
+    /// A forward handle means the end of its segment is a dead end (a "right" tip),
+    /// and a backward handle means its start is (a "left" tip). Segments with no
+    /// links at all count as tips on both sides.
+    fn tips(&self, py: Python) -> PyResult<Vec<PyHandle>> {
+        Ok(self
+            .0
+            .adjacency(py)?
+            .tips()
+            .into_iter()
+            .map(|handle| PyHandle {
+                store: self.0.clone(),
+                handle,
+            })
+            .collect())
+    }
+
+    /// Find the self-loops: the links from a segment to itself, in ID order.
+    ///
+    /// See :attr:`Link.inverting` to tell hairpins from tandem repeats.
+    fn self_loops(&self) -> PyResult<Vec<PyLink>> {
+        let data = self.0.read()?;
+        Ok(data
+            .view()
+            .self_loops()
+            .into_iter()
+            .map(|id| {
+                PyLink(EntityRef {
+                    store: self.0.clone(),
+                    index: id.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Iterate over the links as ``((segment, orient), (segment, orient))`` pairs, in
+    /// ID order, where each ``segment`` is a :class:`Segment` and each ``orient`` is an
+    /// :class:`Orientation`.
+    ///
+    /// Each link also connects the second end, flipped, to the first end, flipped.
+    fn link_handles(&self) -> PyResult<LinkHandleIter> {
+        Ok(LinkHandleIter {
+            store: self.0.clone(),
+            index: 0,
+            end: self.0.read()?.view().links.len() as u32,
+        })
+    }
+
+    /// Find the superbubbles, as (entrance, exit) pairs of :class:`Handle` objects.
+    ///
+    /// In a superbubble, every walk from the entrance reaches the exit without
+    /// leaving the bubble or crossing a cycle, like the alleles at a variant site.
+    /// Each bubble is reported once (not once per strand), and bubbles where the
+    /// entrance leads straight to the exit are skipped.
+    fn superbubbles(&self, py: Python) -> PyResult<Vec<(PyHandle, PyHandle)>> {
+        let handle = |handle| PyHandle {
+            store: self.0.clone(),
+            handle,
+        };
+        Ok(self
+            .0
+            .adjacency(py)?
+            .superbubbles()
+            .into_iter()
+            .map(|(entrance, exit)| (handle(entrance), handle(exit)))
+            .collect())
+    }
+
+    /// Measure how tangled the graph is, as a dict. Only links count as edges.
+    ///
+    /// The entries are the numbers of ``segments``, ``links``, and connected
+    /// ``components``; the ``mean_degree`` (twice the links per segment); the
+    /// ``branching_fraction`` of segments that link to more than one neighbor from
+    /// the same side; the ``cycle_rank`` (links minus segments plus components, or the
+    /// number of independent cycles); and ``is_dag``, which is true when following
+    /// links in their direction never leads in a cycle.
+    fn complexity<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let adjacency = self.0.adjacency(py)?;
+        let report = py.allow_threads(|| adjacency.complexity());
+        let dict = PyDict::new_bound(py);
+        for (name, value) in [
+            ("segments", report.segments),
+            ("links", report.links),
+            ("components", report.components),
+            ("cycle_rank", report.cycle_rank),
+        ] {
+            dict.set_item(name, value)?;
+        }
+        dict.set_item("mean_degree", report.mean_degree)?;
+        dict.set_item("branching_fraction", report.branching_fraction)?;
+        dict.set_item("is_dag", report.is_dag)?;
+        Ok(dict)
+    }
+
+    /// The GC content over all the segments in the graph.
+    ///
+    /// See :meth:`Segment.gc_content`.
+    fn gc_content(&self, py: Python) -> PyResult<f64> {
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_sequences(&gfa)?;
+            Ok(gfa.total_gc_content())
+        })
+    }
+
+    /// Count the bases in every segment, in parallel. Returns a list with one
+    /// ``[a, c, g, t, other]`` list per segment, in ID order.
+    ///
+    /// See :meth:`Segment.base_counts`. By default, this uses all the CPU cores; pass
+    /// ``threads`` to use a specific number of threads.
+    #[pyo3(signature = (threads = None))]
+    fn segment_base_counts(
+        &self,
+        py: Python,
+        threads: Option<usize>,
+    ) -> PyResult<Vec<flatgfa::composition::BaseCounts>> {
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_sequences(&gfa)?;
+            with_threads(threads, || gfa.base_counts_parallel())
+        })
+    }
+
+    /// Count the path steps that visit each segment, in parallel. Returns a list with
+    /// one count per segment, in ID order. See :meth:`segment_base_counts` for
+    /// ``threads``.
+    #[pyo3(signature = (threads = None))]
+    fn coverage(&self, py: Python, threads: Option<usize>) -> PyResult<Vec<u64>> {
+        py.allow_threads(|| {
+            let gfa = self.0.read()?;
+            with_threads(threads, || gfa.view().coverage_parallel())
+        })
+    }
+
+    /// The lengths of all the segments, in ID order.
+    ///
+    /// This is an :class:`array.array` of unsigned 32-bit integers (typecode ``"I"``),
+    /// not a NumPy array, so that FlatGFA does not depend on NumPy. It supports the
+    /// buffer protocol, so ``numpy.frombuffer(gfa.segment_lengths(), numpy.uint32)``
+    /// makes a NumPy array without copying.
+    fn segment_lengths(&self, py: Python) -> PyResult<PyObject> {
+        let lengths = py.allow_threads(|| PyResult::Ok(self.0.read()?.view().segment_lengths()))?;
+        let bytes: Vec<u8> = lengths.iter().flat_map(|len| len.to_ne_bytes()).collect();
+        py_array(py, "I", &bytes)
+    }
+
+    /// Group the paths with PanSN-style names (``sample#hap#contig``) by sample, as a
+    /// dict from each sample name to a list of its :class:`Path` objects in ID order.
+    ///
+    /// Paths with other names are left out. See :attr:`Path.pansn`.
+    fn paths_by_sample(&self) -> PyResult<BTreeMap<String, Vec<PyPath>>> {
+        let data = self.0.read()?;
+        let gfa = data.view();
+        check_paths(&gfa)?;
+        Ok(gfa
+            .paths_by_sample()
+            .into_iter()
+            .map(|(sample, ids)| {
+                let paths = ids
+                    .into_iter()
+                    .map(|id| {
+                        PyPath(EntityRef {
+                            store: self.0.clone(),
+                            index: id.into(),
+                        })
+                    })
+                    .collect();
+                (sample, paths)
+            })
+            .collect())
+    }
+
+    /// The sequence lengths of all the paths, in ID order.
+    ///
+    /// This is an :class:`array.array` of unsigned 64-bit integers (typecode ``"Q"``),
+    /// which NumPy treats as ``uint64``. See :meth:`segment_lengths`.
+    fn path_lengths(&self, py: Python) -> PyResult<PyObject> {
+        let lengths = py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_paths(&gfa)?;
+            PyResult::Ok(gfa.path_lengths())
+        })?;
+        let bytes: Vec<u8> = lengths.iter().flat_map(|len| len.to_ne_bytes()).collect();
+        py_array(py, "Q", &bytes)
+    }
+
+    /// The N50 and L50 of the segments' lengths, as an ``(n50, l50)`` tuple.
+    ///
+    /// Sorting from longest to shortest, the L50 is the smallest number of segments
+    /// that make up at least half the total length, and the N50 is the length of the
+    /// shortest of those. Both are 0 for a graph with no segments.
+    fn segment_n50(&self, py: Python) -> PyResult<(u64, usize)> {
+        py.allow_threads(|| Ok(self.0.read()?.view().segment_n50()))
+    }
+
+    /// The N50 and L50 of the paths' sequence lengths. See :meth:`segment_n50`.
+    fn path_n50(&self, py: Python) -> PyResult<(u64, usize)> {
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_paths(&gfa)?;
+            Ok(gfa.path_n50())
+        })
+    }
+
+    /// Check whether this is an rGFA graph: every segment has rGFA coordinates (see
+    /// :attr:`Segment.rgfa`). This is ``False`` for a graph with no segments.
+    fn is_rgfa(&self) -> PyResult<bool> {
+        Ok(self.0.read()?.view().is_rgfa())
+    }
+
+    /// Check the graph for suspicious (but parseable) conditions.
+    ///
+    /// For example, this reports links to nonexistent segments, segments with no
+    /// links, and paths that traverse edges that no link allows. Returns a list of
+    /// warning messages, which is empty if the graph looks fine.
+    fn lint(&self, py: Python) -> PyResult<Vec<String>> {
+        py.allow_threads(|| {
+            Ok(self
+                .0
+                .read()?
+                .view()
+                .lint()
+                .iter()
+                .map(|warning| warning.to_string())
+                .collect())
+        })
+    }
+
+    /// Compare this graph to ``other`` structurally, returning a :class:`GraphDiff`.
+    ///
+    /// The comparison goes by segment and path names, so it ignores differences in
+    /// IDs and in the order of records.
+    fn diff(&self, py: Python, other: &PyFlatGFA) -> PyResult<PyGraphDiff> {
+        py.allow_threads(|| {
+            let old = self.0.read()?;
+            let old = old.view();
+            // Reuse the lock for the same store, since a second read lock could deadlock
+            // (if a writer is waiting).
+            let diff = if Arc::ptr_eq(&self.0, &other.0) {
+                old.diff(&old)
+            } else {
+                old.diff(&other.0.read()?.view())
+            };
+            Ok(PyGraphDiff(diff))
+        })
+    }
+
+    /// Check whether two graphs have the same content: the same segments (by name and
+    /// sequence), links, and paths.
+    ///
+    /// The check ignores IDs and the order of records, and it takes O(n) time. Unlike
+    /// :meth:`diff`, it distinguishes a link from its reverse complement, links with
+    /// different overlaps, and duplicated links. Because graphs compare by content,
+    /// they are not hashable.
+    fn __eq__(&self, py: Python, other: &PyFlatGFA) -> PyResult<bool> {
+        py.allow_threads(|| {
+            let this = self.0.read()?;
+            // Taking a second read lock on the same store could deadlock (if a writer
+            // is waiting), and a graph always equals itself.
+            if Arc::ptr_eq(&self.0, &other.0) {
+                return Ok(true);
+            }
+            let other = other.0.read()?;
+            Ok(this.view().content_eq(&other.view()))
+        })
+    }
+
+    /// Build a :class:`KmerIndex` of every ``k``-mer in the segment sequences, for
+    /// fast seed lookups.
+    ///
+    /// The index takes about 8 bytes per base of sequence in the graph. Raises a
+    /// `ValueError` unless ``k`` is between 1 and 32.
+    fn build_kmer_index(&self, py: Python, k: usize) -> PyResult<PyKmerIndex> {
+        if !(1..=KmerIndex::MAX_K).contains(&k) {
+            return Err(PyValueError::new_err(format!(
+                "k must be between 1 and {}",
+                KmerIndex::MAX_K
+            )));
+        }
+        let index = py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_sequences(&gfa)?;
+            PyResult::Ok(gfa.build_kmer_index(k))
+        })?;
+        Ok(PyKmerIndex {
+            store: self.0.clone(),
+            index,
+        })
+    }
+
+    /// Copy the graph into a :class:`MutableFlatGFA`, which supports editing.
+    fn to_mutable(&self) -> PyResult<PyMutableFlatGFA> {
+        Ok(PyMutableFlatGFA(HeapGFAStore::from_flat(
+            &self.0.read()?.view(),
+        )))
+    }
+
+    /// Write the graph as a GFA text file.
+    ///
+    /// The lines appear in the same order as in the original GFA file. With
+    /// ``sorted=True``, they appear in a normalized order instead: segments, then
+    /// paths, then links.
+    ///
+    /// For a canonical file that you can compare with ``diff``, use
+    /// ``sort_segments="name"`` or ``sort_segments="length"``. This sorts the
+    /// segments by name (in natural order, so ``s2`` comes before ``s10``) or by
+    /// length, and then sorts the links by their segments' positions in that order
+    /// and the paths by name.
+    ///
+    /// The file is compressed with gzip if its name ends in ``.gz``, or according to
+    /// ``compress`` if you give it.
+    #[pyo3(signature = (filename, sorted = false, sort_segments = None, compress = None))]
+    fn write_gfa(
+        &self,
+        py: Python,
+        filename: &str,
+        sorted: bool,
+        sort_segments: Option<&str>,
+        compress: Option<bool>,
+    ) -> PyResult<()> {
+        let order = match sort_segments {
+            None => None,
+            Some("name") => Some(print::SegmentOrder::Name),
+            Some("length") => Some(print::SegmentOrder::Length),
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown segment order `{}` (expected \"name\" or \"length\")",
+                    other
+                )))
+            }
+        };
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            write_text_file(filename, compress, |mut out| {
+                if let Some(order) = order {
+                    print::SortedBy(&gfa, order).write(&mut out)
+                } else if sorted {
+                    print::Sorted(&gfa).write(&mut out)
+                } else {
+                    gfa.write_gfa(&mut out)
+                }
+            })?;
+            Ok(())
+        })
+    }
+
+    /// Write a GFA text file with only some of the paths, given by their IDs.
+    ///
+    /// The paths appear in the order given, and the other lines appear in a
+    /// normalized order. With ``prune=True``, the file also omits the segments that
+    /// the paths do not visit and the links that they do not traverse, which makes
+    /// a sub-graph for (say) one sample's haplotypes. Compression works the same way
+    /// as for :meth:`write_gfa`.
+    #[pyo3(signature = (filename, path_ids, prune = false, compress = None))]
+    fn write_gfa_paths(
+        &self,
+        py: Python,
+        filename: &str,
+        path_ids: Vec<u32>,
+        prune: bool,
+        compress: Option<bool>,
+    ) -> PyResult<()> {
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            check_paths(&gfa)?;
+            if path_ids.iter().any(|&id| id as usize >= gfa.paths.len()) {
+                return Err(PyIndexError::new_err("path ID out of range"));
+            }
+            let ids: Vec<Id<flatgfa::Path>> = path_ids.into_iter().map(Id::from).collect();
+            write_text_file(filename, compress, |mut out| {
+                gfa.write_gfa_paths(&mut out, &ids, prune)
+            })?;
+            Ok(())
+        })
+    }
+
+    /// Write the graph as a binary FlatGFA file.
+    ///
+    /// You can read the resulting file with :func:`load`.
+    fn write_flatgfa(&self, py: Python, filename: &str) -> PyResult<()> {
+        py.allow_threads(|| {
+            let data = self.0.read()?;
+            let gfa = data.view();
+            let mut mmap = memfile::map_new_file(filename, file::size(&gfa) as u64);
+            file::dump(&gfa, &mut mmap);
+            mmap.flush()?;
+            Ok(())
+        })
+    }
+
+    /// Release the graph's data. For a graph from :func:`load`, this unmaps the file.
+    ///
+    /// Afterward, using the graph or any object that refers into it (like a
+    /// :class:`Segment`) raises a `ValueError`. Closing a closed graph does nothing.
+    /// A graph is also a context manager that closes itself on exit::
+    ///
+    ///     with flatgfa.load("graph.flatgfa") as graph:
+    ///         print(len(graph.segments))
+    fn close(&self, py: Python) {
+        // Wait for other threads' readers without holding the GIL they may need.
+        py.allow_threads(|| self.0.close())
+    }
+
+    /// Whether the graph has been closed.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    /// Statistics from parsing the graph, as a dict, or `None` for a graph loaded
+    /// from a FlatGFA file.
+    ///
+    /// The counts include ``lines``, ``bytes``, the number of each kind of record
+    /// (``segs``, ``links``, ``paths``, etc.), ``seq_len``, the total sequence length,
+    /// and ``length_mismatches``, the segments whose ``LN`` tag disagrees with their
+    /// sequence. ``deferred_lines`` and ``fixups`` count the references that the parser
+    /// had to resolve after reading later lines. The times (``scan_time``,
+    /// ``deferred_time``, and ``finish_time``) are in seconds.
+    #[getter]
+    fn parse_stats<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(stats) = &self.0.parse_stats else {
+            return Ok(None);
+        };
+        let dict = PyDict::new_bound(py);
+        dict.set_item("bytes", stats.bytes)?;
+        for (name, value) in [
+            ("lines", stats.lines),
+            ("headers", stats.headers),
+            ("segs", stats.segs),
+            ("links", stats.links),
+            ("paths", stats.paths),
+            ("walks", stats.walks),
+            ("jumps", stats.jumps),
+            ("containments", stats.containments),
+            ("other_lines", stats.other_lines),
+            ("seq_len", stats.seq_len),
+            ("length_mismatches", stats.length_mismatches),
+            ("normalized_overlaps", stats.normalized_overlaps),
+            ("deferred_lines", stats.deferred_lines),
+            ("deferred_bytes", stats.deferred_bytes),
+            ("fixups", stats.fixups),
+            ("fixup_bytes", stats.fixup_bytes),
+        ] {
+            dict.set_item(name, value)?;
+        }
+        for (name, time) in [
+            ("scan_time", stats.scan_time),
+            ("deferred_time", stats.deferred_time),
+            ("finish_time", stats.finish_time),
+        ] {
+            dict.set_item(name, time.as_secs_f64())?;
+        }
+        Ok(Some(dict))
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) {
+        self.close(py)
+    }
 }
 
-/// Parse a GFA file from a bytestring into our FlatGFA representation.
-#[pyfunction]
-fn parse_bytes(bytes: &[u8]) -> PyFlatGFA {
-    PyFlatGFA(Arc::new(Store::parse_gfa(bytes)))
+/// An editable, in-memory copy of a FlatGFA.
+///
+/// Get one of these with :meth:`FlatGFA.to_mutable`. To inspect the result of your
+/// edits, use :meth:`freeze` to get an ordinary (immutable) :class:`FlatGFA`.
+///
+/// Editing operations can renumber the graph's segments and links, so IDs obtained
+/// before an edit may refer to different objects afterward.
+#[pyclass]
+#[pyo3(name = "MutableFlatGFA", module = "flatgfa")]
+struct PyMutableFlatGFA(HeapGFAStore);
+
+#[pymethods]
+impl PyMutableFlatGFA {
+    /// Add a link between two segments, given by name, and return its ID.
+    ///
+    /// The orientations are ``"+"`` or ``"-"``, and ``overlap`` is a CIGAR string (or
+    /// ``"*"``). Raises a `ValueError` if either segment does not exist.
+    #[pyo3(signature = (from_, from_orient, to, to_orient, overlap = "0M"))]
+    fn add_link(
+        &mut self,
+        from_: NameArg,
+        from_orient: &str,
+        to: NameArg,
+        to_orient: &str,
+        overlap: &str,
+    ) -> PyResult<u32> {
+        let bad_overlap = || PyValueError::new_err(format!("bad overlap `{}`", overlap));
+        let overlap = match gfaline::parse_maybe_overlap_list(overlap.as_bytes()) {
+            Ok((overlaps, rest)) if rest.is_empty() && overlaps.len() <= 1 => {
+                overlaps.into_iter().next().unwrap_or_default()
+            }
+            _ => return Err(bad_overlap()),
+        };
+        if overlap.len() > CompactSpan::<flatgfa::AlignOp>::MAX_LEN {
+            return Err(PyValueError::new_err(
+                "bad overlap: too many operations for a link",
+            ));
+        }
+        let id = self
+            .0
+            .add_link_by_name(
+                from_.to_string().as_bytes(),
+                PyOrientation::parse(from_orient)?.into(),
+                to.to_string().as_bytes(),
+                PyOrientation::parse(to_orient)?.into(),
+                overlap,
+            )
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(id.into())
+    }
+
+    /// Add a path and return its ID.
+    ///
+    /// The steps are a GFA path string like ``"1+,2-,3+"``. Raises a `ValueError` if
+    /// the steps are malformed or name a segment that does not exist, in which case
+    /// the graph is unchanged.
+    fn add_path(&mut self, name: &str, steps: &str) -> PyResult<u32> {
+        let parsed = gfaline::StepsParser::new(steps.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let id = self
+            .0
+            .add_path_by_name(name.as_bytes(), parsed)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(id.into())
+    }
+
+    /// Remove a segment (by ID), along with the links and path steps that refer to it.
+    ///
+    /// The removal takes effect at the next :meth:`compact`, so all IDs remain valid
+    /// until then and you can remove several segments by their original IDs. (Snapshots
+    /// from :meth:`freeze` already leave the segment out.)
+    fn remove_segment(&mut self, id: u32) -> PyResult<()> {
+        if id as usize >= self.0.segs.len() {
+            return Err(PyIndexError::new_err("segment ID out of range"));
+        }
+        self.0.remove_segment(id.into());
+        Ok(())
+    }
+
+    /// Set an optional field (tag) on a segment, replacing any existing value.
+    ///
+    /// The tag is a two-character name like ``"DP"``. The value may be an integer,
+    /// a float, or a string; the GFA field type (``i``, ``f``, or ``Z``) follows
+    /// from its Python type. Raises a `ValueError` for a string with characters
+    /// other than printable ASCII (e.g., a tab or newline), an infinite or NaN
+    /// float, or a `bool`.
+    fn set_segment_tag(&mut self, id: u32, tag: &str, value: TagValue) -> PyResult<()> {
+        if id as usize >= self.0.segs.len() {
+            return Err(PyIndexError::new_err("segment ID out of range"));
+        }
+        let (kind, value) = value.encode()?;
+        self.0
+            .set_seg_tag(id.into(), parse_tag(tag)?, kind, &value)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Set an optional field (tag) on a path. See :meth:`set_segment_tag`.
+    fn set_path_tag(&mut self, id: u32, tag: &str, value: TagValue) -> PyResult<()> {
+        if id as usize >= self.0.paths.len() {
+            return Err(PyIndexError::new_err("path ID out of range"));
+        }
+        let (kind, value) = value.encode()?;
+        self.0
+            .set_path_tag(id.into(), parse_tag(tag)?, kind, &value)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Rename every segment by calling ``rename`` on its name (as a string), which
+    /// must return the new name.
+    ///
+    /// Links and paths refer to segments by ID, so they follow the renamed segments.
+    /// If ``rename`` raises an exception, no segments are renamed. The new names must
+    /// be unique and nonempty, without whitespace, ``,``, ``+``, or ``-``; otherwise,
+    /// this raises a `ValueError` and renames nothing.
+    fn rename_segments(&mut self, rename: &Bound<'_, PyAny>) -> PyResult<()> {
+        let gfa = self.0.as_ref();
+        let names = gfa
+            .segs
+            .all()
+            .iter()
+            .map(|seg| {
+                rename
+                    .call1((gfa.get_seg_name(seg).to_string(),))?
+                    .extract()
+            })
+            .collect::<PyResult<Vec<String>>>()?;
+        let mut names = names.into_iter();
+        self.0
+            .rename_segments(|_| names.next().expect("one name per segment"))
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Apply pending removals and reclaim the memory used by removed data.
+    ///
+    /// Segment IDs remain dense, so when segments were removed, every later segment
+    /// gets a new ID. Link IDs also change. Path IDs remain valid.
+    fn compact(&mut self) {
+        self.0.compact();
+    }
+
+    /// Get an immutable snapshot of the graph, as a :class:`FlatGFA`.
+    ///
+    /// The snapshot leaves out segments that :meth:`remove_segment` has marked, as if
+    /// :meth:`compact` had run, so its IDs may differ from this graph's. This graph
+    /// itself keeps the pending removals (and its IDs) until the next :meth:`compact`.
+    fn freeze(&self) -> PyFlatGFA {
+        PyFlatGFA(Arc::new(Store::new(Backing::Heap(Box::new(
+            self.snapshot(),
+        )))))
+    }
+
+    /// Print the graph as GFA text, without any segments pending removal.
+    fn __str__(&self) -> String {
+        if self.0.removed.is_empty() {
+            format!("{}", &self.0.as_ref())
+        } else {
+            format!("{}", &self.snapshot().as_ref())
+        }
+    }
+}
+
+impl PyMutableFlatGFA {
+    /// Copy the graph, applying any pending removals to the copy.
+    fn snapshot(&self) -> HeapGFAStore {
+        let mut store = HeapGFAStore::from_flat(&self.0.as_ref());
+        if !self.0.removed.is_empty() {
+            store.removed.clone_from(&self.0.removed);
+            store.compact();
+        }
+        store
+    }
+}
+
+/// The value of a GFA optional field, as given from Python.
+#[derive(FromPyObject)]
+enum TagValue {
+    // This comes first because Python's `bool` is a subclass of `int`.
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl TagValue {
+    /// Get the GFA type character and the text for the value.
+    ///
+    /// GFA has no type for booleans or for infinite and NaN floats, so these are
+    /// errors.
+    fn encode(self) -> PyResult<(u8, Vec<u8>)> {
+        match self {
+            TagValue::Bool(b) => Err(PyValueError::new_err(format!(
+                "tag values cannot be booleans (use {} instead)",
+                b as u8
+            ))),
+            TagValue::Int(i) => Ok((b'i', i.to_string().into_bytes())),
+            TagValue::Float(f) if !f.is_finite() => {
+                Err(PyValueError::new_err("float tag values must be finite"))
+            }
+            TagValue::Float(f) => Ok((b'f', f.to_string().into_bytes())),
+            TagValue::Str(s) => Ok((b'Z', s.into_bytes())),
+        }
+    }
+}
+
+/// Check that a tag name is two alphanumeric characters, starting with a letter.
+fn parse_tag(tag: &str) -> PyResult<[u8; 2]> {
+    match *tag.as_bytes() {
+        [a, b] if a.is_ascii_alphabetic() && b.is_ascii_alphanumeric() => Ok([a, b]),
+        _ => Err(PyValueError::new_err(
+            "tags must be two alphanumeric characters",
+        )),
+    }
+}
+
+/// The parsed tags (optional fields) on a segment, link, or path.
+///
+/// This is a read-only mapping from two-character tag names to values. Each value has
+/// the natural Python type for its GFA type: an `int` for ``i``, a `float` for ``f``,
+/// a `bytes` for ``H``, a `list` of numbers for ``B``, and a `str` otherwise.
+#[pyclass(frozen)]
+#[pyo3(name = "Tags", module = "flatgfa")]
+struct TagMap {
+    store: Arc<Store>,
+    tags: Span<Tag>,
+}
+
+impl TagMap {
+    /// Find a tag by name and convert its value for Python.
+    fn lookup(&self, py: Python, name: &str) -> PyResult<Option<PyObject>> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        let tag = gfa.tags[self.tags]
+            .iter()
+            .find(|tag| tag.name == name.as_bytes());
+        tag.map(|tag| tag_to_py(py, tag.kind, gfa.get_tag_value(tag)))
+            .transpose()
+    }
+}
+
+#[pymethods]
+impl TagMap {
+    fn __getitem__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        self.lookup(py, name)?
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    /// Get a tag's value, or `default` if there is no such tag.
+    #[pyo3(signature = (name, default = None))]
+    fn get(&self, py: Python, name: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        Ok(self
+            .lookup(py, name)?
+            .unwrap_or_else(|| default.unwrap_or_else(|| py.None())))
+    }
+
+    /// The names of all the tags, in the order they appear.
+    fn keys(&self) -> PyResult<Vec<String>> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.tags[self.tags]
+            .iter()
+            .map(|tag| String::from_utf8_lossy(&tag.name).into_owned())
+            .collect())
+    }
+
+    fn __contains__(&self, name: &str) -> PyResult<bool> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.tags[self.tags]
+            .iter()
+            .any(|tag| tag.name == name.as_bytes()))
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyIterator::from_bound_object(&PyList::new_bound(py, self.keys()?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tags.len()
+    }
+}
+
+/// Convert a tag's value text to a Python object, according to its GFA type.
+fn tag_to_py(py: Python, kind: u8, value: &[u8]) -> PyResult<PyObject> {
+    let text = String::from_utf8_lossy(value);
+    let bad = || {
+        PyValueError::new_err(format!(
+            "malformed value for {} tag: {}",
+            kind as char, text
+        ))
+    };
+
+    Ok(match kind {
+        b'i' => text.parse::<i64>().map_err(|_| bad())?.into_py(py),
+        b'f' => text.parse::<f64>().map_err(|_| bad())?.into_py(py),
+        b'H' => {
+            if !value.len().is_multiple_of(2) {
+                return Err(bad());
+            }
+            let bytes = value
+                .chunks(2)
+                .map(|pair| {
+                    std::str::from_utf8(pair)
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(bad)?;
+            PyBytes::new_bound(py, &bytes).into_py(py)
+        }
+        b'B' => {
+            // A numeric array, like `f,0.5,1.5`: a subtype, then the elements.
+            let (subtype, elems) = text.split_once(',').unwrap_or((&text, ""));
+            let elems = elems.split(',').filter(|elem| !elem.is_empty());
+            match subtype {
+                "f" => elems
+                    .map(|elem| elem.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| bad())?
+                    .into_py(py),
+                "c" | "C" | "s" | "S" | "i" | "I" => elems
+                    .map(|elem| elem.parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| bad())?
+                    .into_py(py),
+                _ => return Err(bad()),
+            }
+        }
+        _ => text.into_py(py),
+    })
+}
+
+/// An index of the k-mers in a graph's segments, from :meth:`FlatGFA.build_kmer_index`.
+///
+/// This covers the forward strand of each segment, so query the reverse complement
+/// to search the other strand. K-mers with bases other than ``A``, ``C``, ``G``, and
+/// ``T`` (in either case) are not indexed.
+#[pyclass(frozen)]
+#[pyo3(name = "KmerIndex", module = "flatgfa")]
+struct PyKmerIndex {
+    store: Arc<Store>,
+    index: KmerIndex,
+}
+
+#[pymethods]
+impl PyKmerIndex {
+    /// The length of the indexed k-mers.
+    #[getter]
+    fn k(&self) -> usize {
+        self.index.k()
+    }
+
+    /// Find every occurrence of ``kmer`` as a list of ``(segment, offset)`` pairs, in
+    /// segment order. The offset counts from the start of the segment's sequence.
+    fn query(&self, kmer: &[u8]) -> Vec<(PySegment, u32)> {
+        self.index
+            .query(kmer)
+            .iter()
+            .map(|loc| {
+                let seg = PySegment(EntityRef {
+                    store: self.store.clone(),
+                    index: loc.seg.into(),
+                });
+                (seg, loc.offset)
+            })
+            .collect()
+    }
+
+    /// The number of distinct k-mers.
+    fn __len__(&self) -> usize {
+        self.index.len()
+    }
 }
 
-/// Load a binary FlatGFA file.
+/// The structural differences between two graphs, from :meth:`FlatGFA.diff`.
 ///
-/// This function should be fast to call because it does not actually read the file's data.
-/// It memory-maps the file so subsequent accesses will actually read the data "on demand."
-/// You can produce these files with :meth:`FlatGFA.write_flatgfa`.
-#[pyfunction]
-fn load(filename: &str) -> PyFlatGFA {
-    PyFlatGFA(Arc::new(Store::load(filename)))
-}
+/// Segments and paths appear by name, in natural order. Links appear as pairs of
+/// oriented segment names, like ``("1+", "2-")``; a link and its reverse complement
+/// count as the same link.
+#[pyclass(frozen)]
+#[pyo3(name = "GraphDiff", module = "flatgfa")]
+struct PyGraphDiff(GraphDiff);
 
 #[pymethods]
-impl PyFlatGFA {
-    /// The segments (nodes) in the graph, as a :class:`SegmentList`.
+impl PyGraphDiff {
+    /// Segments that only the first graph has.
     #[getter]
-    fn segments(&self) -> SegmentList {
-        SegmentList(ListRef {
-            store: self.0.clone(),
-            start: 0,
-            end: self.0.view().segs.len() as u32,
-        })
+    fn segments_removed(&self) -> Vec<String> {
+        self.0.segs_removed.clone()
     }
 
-    /// The paths in the graph, as a :class:`PathList`.
+    /// Segments that only the second graph has.
     #[getter]
-    fn paths(&self) -> PathList {
-        PathList(ListRef {
-            store: self.0.clone(),
-            start: 0,
-            end: self.0.view().paths.len() as u32,
-        })
+    fn segments_added(&self) -> Vec<String> {
+        self.0.segs_added.clone()
     }
 
-    /// The links (edges) in the graph, as a :class:`LinkList`.
+    /// Segments in both graphs whose sequences differ.
     #[getter]
-    fn links(&self) -> LinkList {
-        LinkList(ListRef {
-            store: self.0.clone(),
-            start: 0,
-            end: self.0.view().links.len() as u32,
-        })
+    fn segments_changed(&self) -> Vec<String> {
+        self.0.segs_changed.clone()
     }
 
-    fn __str__(&self) -> String {
-        format!("{}", &self.0.view())
+    /// Links that only the first graph has.
+    #[getter]
+    fn links_removed(&self) -> Vec<(String, String)> {
+        self.0.links_removed.clone()
     }
 
-    /// Write the graph as a GFA text file.
-    fn write_gfa(&self, filename: &str) -> PyResult<()> {
-        let mut file = std::fs::File::create(filename)?;
-        write!(file, "{}", &self.0.view())?;
-        Ok(())
+    /// Links that only the second graph has.
+    #[getter]
+    fn links_added(&self) -> Vec<(String, String)> {
+        self.0.links_added.clone()
     }
 
-    /// Write the graph as a binary FlatGFA file.
-    ///
-    /// You can read the resulting file with :func:`load`.
-    fn write_flatgfa(&self, filename: &str) -> PyResult<()> {
-        let gfa = self.0.view();
-        let mut mmap = memfile::map_new_file(filename, file::size(&gfa) as u64);
-        file::dump(&gfa, &mut mmap);
-        mmap.flush()?;
-        Ok(())
+    /// Paths that only the first graph has.
+    #[getter]
+    fn paths_removed(&self) -> Vec<String> {
+        self.0.paths_removed.clone()
+    }
+
+    /// Paths that only the second graph has.
+    #[getter]
+    fn paths_added(&self) -> Vec<String> {
+        self.0.paths_added.clone()
+    }
+
+    /// Paths in both graphs that visit different steps.
+    #[getter]
+    fn paths_changed(&self) -> Vec<String> {
+        self.0.paths_changed.clone()
+    }
+
+    /// A diff is true if the graphs differ at all.
+    fn __bool__(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
     }
 }
 
@@ -161,6 +1634,14 @@ impl ListRef {
         }
     }
 
+    /// Get references to many items at once, by their indices within the list.
+    fn get_many<E: From<EntityRef>>(&self, indices: &[u32]) -> PyResult<Vec<E>> {
+        if indices.iter().any(|&i| i >= self.len()) {
+            return Err(PyIndexError::new_err("index out of range"));
+        }
+        Ok(indices.iter().map(|&i| E::from(self.index(i))).collect())
+    }
+
     /// A suitable implementation of `__getitem__` for Python classes.
     fn py_getitem<L, E>(&self, arg: SliceOrInt, py: Python) -> PyResult<PyObject>
     where
@@ -169,7 +1650,7 @@ impl ListRef {
     {
         match arg {
             SliceOrInt::Slice(slice) => {
-                let indices = slice.indices(self.len().try_into().unwrap())?;
+                let indices = slice.indices(self.len().into())?;
                 if indices.step == 1 {
                     Ok(L::from(self.slice(indices.start as u32, indices.stop as u32)).into_py(py))
                 } else {
@@ -228,6 +1709,25 @@ macro_rules! gen_container {
                 self.0.py_getitem::<$list, $pytype>(arg, py)
             }
 
+            /// Get the items at several indices in one call.
+            ///
+            /// This is equivalent to ``[lst[i] for i in indices]``, but it avoids a
+            /// Python call per item.
+            fn get_many(&self, indices: Vec<u32>) -> PyResult<Vec<$pytype>> {
+                self.0.get_many(&indices)
+            }
+
+            /// Get the items with indices from `start` up to (but not including) `end`,
+            /// as a list.
+            fn get_range(&self, start: u32, end: u32) -> PyResult<Vec<$pytype>> {
+                if start > end || end > self.0.len() {
+                    return Err(PyIndexError::new_err("range out of bounds"));
+                }
+                Ok((start..end)
+                    .map(|i| $pytype::from(self.0.index(i)))
+                    .collect())
+            }
+
             fn __iter__(&self) -> $iter {
                 $iter {
                     store: self.0.store.clone(),
@@ -274,6 +1774,7 @@ macro_rules! gen_container {
 gen_container!(Segment, segs, PySegment, SegmentList, SegmentIter);
 gen_container!(Path, paths, PyPath, PathList, PathIter);
 gen_container!(Link, links, PyLink, LinkList, LinkIter);
+gen_container!(Jump, jumps, PyJump, JumpList, JumpIter);
 
 /// A segment in a GFA graph.
 ///
@@ -295,18 +1796,70 @@ impl PySegment {
     ///
     /// This copies the underlying sequence data to contruct the Python bytes object,
     /// so it is slow to use for large sequences.
-    fn sequence<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
-        let gfa = self.0.store.view();
+    fn sequence<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        check_sequences(&gfa)?;
         let seg = &gfa.segs[self.0.id()];
         let seq = gfa.get_seq(seg);
-        PyBytes::new_bound(py, seq)
+        Ok(PyBytes::new_bound(py, seq))
+    }
+
+    /// Get the bases ``start:end`` of the segment's sequence as a byte string.
+    ///
+    /// This copies only the requested range, so it is much cheaper than slicing
+    /// :meth:`sequence` for large segments. Unlike Python slicing, an out-of-range
+    /// (or reversed) range raises an `IndexError` instead of being clamped.
+    fn sequence_slice<'py>(
+        &self,
+        py: Python<'py>,
+        start: usize,
+        end: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        check_sequences(&gfa)?;
+        let seg = &gfa.segs[self.0.id()];
+        let seq = gfa
+            .get_seq_range(seg, start, end)
+            .ok_or_else(|| PyIndexError::new_err("sequence range out of bounds"))?;
+        Ok(PyBytes::new_bound(py, seq))
     }
 
-    /// The segment's name as declared in the GFA file, an `int`.
+    /// The segment's name as declared in the GFA file.
+    ///
+    /// This is an `int` for numerically named segments (the common case) and a `str`
+    /// for segments with other names, like ``utg000013l``.
     #[getter]
-    fn name(&self) -> usize {
-        let seg = self.0.store.view().segs[self.0.id()];
-        seg.name
+    fn name(&self, py: Python) -> PyResult<PyObject> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let seg = &gfa.segs[self.0.id()];
+        Ok(match gfa.get_seg_name(seg) {
+            SegName::Num(num) => num.into_py(py),
+            SegName::Text(text) => text.to_string().into_py(py),
+        })
+    }
+
+    /// Count the bases in the segment's sequence, ignoring case.
+    ///
+    /// This returns a list of five counts: A, C, G, T, and any other character.
+    fn base_counts(&self) -> PyResult<[u64; 5]> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        check_sequences(&gfa)?;
+        Ok(gfa.base_counts(&gfa.segs[self.0.id()]))
+    }
+
+    /// The fraction of the segment's A, C, G, and T bases that are G or C.
+    ///
+    /// Other characters (like N) are ignored. This is 0.0 for a sequence with no A, C,
+    /// G, or T bases.
+    fn gc_content(&self) -> PyResult<f64> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        check_sequences(&gfa)?;
+        Ok(gfa.gc_content(&gfa.segs[self.0.id()]))
     }
 
     /// The unique identifier for the segment, an `int`.
@@ -315,14 +1868,41 @@ impl PySegment {
         self.0.index
     }
 
+    /// The segment's tags (optional fields), as a :class:`Tags` mapping.
+    #[getter]
+    fn tags(&self) -> PyResult<TagMap> {
+        let tags = self.0.store.read()?.view().segs[self.0.id()].tags();
+        Ok(TagMap {
+            store: self.0.store.clone(),
+            tags,
+        })
+    }
+
+    /// The segment's rGFA coordinates, from its ``SN``, ``SO``, and ``SR`` tags, as a
+    /// ``(stable_name, offset, rank)`` tuple. This is ``None`` unless the segment has
+    /// all three tags.
+    #[getter]
+    fn rgfa(&self) -> PyResult<Option<(String, u64, u32)>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.get_rgfa(self.0.id()).map(|coords| {
+            (
+                gfa.get_stable_name(coords).to_string(),
+                coords.offset,
+                coords.rank,
+            )
+        }))
+    }
+
     fn __repr__(&self) -> String {
         self.0.py_repr("Segment")
     }
 
-    fn __str__(&self) -> String {
-        let gfa = self.0.store.view();
+    fn __str__(&self) -> PyResult<String> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
         let seg = gfa.segs[self.0.id()];
-        format!("{}", print::Display(&gfa, &seg))
+        Ok(format!("{}", print::Display(&gfa, &seg)))
     }
 
     fn __eq__(&self, other: &PySegment) -> bool {
@@ -333,9 +1913,10 @@ impl PySegment {
         self.0.index as isize
     }
 
-    fn __len__(&self) -> usize {
-        let seg = self.0.store.view().segs[self.0.id()];
-        seg.len()
+    fn __len__(&self) -> PyResult<usize> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.seg_len(&gfa.segs[self.0.id()]))
     }
 }
 
@@ -352,17 +1933,70 @@ impl From<ListRef> for SegmentList {
 
 #[pymethods]
 impl SegmentList {
-    /// Find a segment by its name (an `int`), or return `None` if not found.
-    fn find(&self, name: usize) -> Option<PySegment> {
-        let gfa = self.0.store.view();
-        let id = gfa.find_seg(name)?;
-        Some(PySegment(EntityRef {
-            store: self.0.store.clone(),
-            index: id.into(),
+    /// Find a segment by its name (an `int` or a `str`), or return `None` if not found.
+    fn find(&self, name: NameArg) -> PyResult<Option<PySegment>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let id = match name {
+            NameArg::Num(num) => gfa.find_seg(num),
+            NameArg::Text(text) => gfa.find_named_seg(SegName::parse(text.as_bytes())),
+        };
+        Ok(id.map(|id| {
+            PySegment(EntityRef {
+                store: self.0.store.clone(),
+                index: id.into(),
+            })
         }))
     }
 }
 
+/// A BGZF-compressed GFA file with an index of its segments.
+///
+/// Fetching a segment decompresses only the part of the file that holds it.
+#[pyclass(name = "IndexedGFA", module = "flatgfa")]
+struct PyIndexedGFA(IndexedGfa<BufReader<File>>);
+
+#[pymethods]
+impl PyIndexedGFA {
+    /// Get a segment's sequence by its name (an `int` or a `str`), or return `None`
+    /// if there is no such segment.
+    fn sequence<'py>(
+        &mut self,
+        py: Python<'py>,
+        name: NameArg,
+    ) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let seq = self
+            .0
+            .segment_seq(name.to_string().as_bytes())
+            .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        Ok(seq.map(|seq| PyBytes::new_bound(py, &seq)))
+    }
+
+    fn __contains__(&self, name: NameArg) -> bool {
+        self.0.index().get(name.to_string().as_bytes()).is_some()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.index().len()
+    }
+}
+
+/// A segment name, as given from Python.
+#[derive(FromPyObject)]
+enum NameArg {
+    Num(usize),
+    Text(String),
+}
+
+impl fmt::Display for NameArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Num(num) => write!(f, "{}", num),
+            Self::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
 /// A path in a GFA graph.
 ///
 /// Paths are walks through the GFA graph, where each step is an oriented segment.
@@ -393,21 +2027,50 @@ impl PyPath {
 
     /// Get the name of this path as declared in the GFA file, as a string.
     #[getter]
-    fn name(&self) -> String {
-        let gfa = self.0.store.view();
+    fn name(&self) -> PyResult<String> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
         let path = &gfa.paths[self.0.id()];
         let name = gfa.get_path_name(path);
-        name.try_into().unwrap()
+        Ok(name.try_into().unwrap())
+    }
+
+    /// The ``(sample, haplotype, contig)`` parts of the path's name, if it follows the
+    /// PanSN convention (``sample#hap#contig``), or `None` otherwise.
+    ///
+    /// The contig is everything after the second ``#``, including any ``:start-end``
+    /// range.
+    #[getter]
+    fn pansn(&self) -> PyResult<Option<(String, String, String)>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let path = &gfa.paths[self.0.id()];
+        Ok(gfa
+            .parse_pansn(path)
+            .map(|(sample, hap, contig)| (sample.to_string(), hap.to_string(), contig.to_string())))
+    }
+
+    /// The path's tags (optional fields), as a :class:`Tags` mapping.
+    #[getter]
+    fn tags(&self) -> PyResult<TagMap> {
+        let tags = self.0.store.read()?.view().paths[self.0.id()].tags();
+        Ok(TagMap {
+            store: self.0.store.clone(),
+            tags,
+        })
     }
 
     fn __repr__(&self) -> String {
         self.0.py_repr("Path")
     }
 
-    fn __str__(&self) -> String {
-        let gfa = self.0.store.view();
-        let path = gfa.paths[self.0.id()];
-        format!("{}", print::Display(&gfa, &path))
+    fn __str__(&self) -> PyResult<String> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(match gfa.get_walk(self.0.id()) {
+            Some(walk) => format!("{}", print::Display(&gfa, walk)),
+            None => format!("{}", print::Display(&gfa, &gfa.paths[self.0.id()])),
+        })
     }
 
     fn __eq__(&self, other: &PyPath) -> bool {
@@ -423,25 +2086,86 @@ impl PyPath {
     /// For convenience, the path itself provides direct access to the step list. So, for
     /// example, ``path.steps[4]`` is the same as ``path[4]``.
     #[getter]
-    fn steps(&self) -> StepList {
-        let path = self.0.store.view().paths[self.0.id()];
-        StepList(ListRef {
+    fn steps(&self) -> PyResult<StepList> {
+        let path = self.0.store.read()?.view().paths[self.0.id()];
+        Ok(StepList(ListRef {
             store: self.0.store.clone(),
             start: path.steps.start.into(),
             end: path.steps.end.into(),
-        })
+        }))
     }
 
-    fn __iter__(&self) -> StepIter {
-        self.steps().__iter__()
+    /// The overlaps between consecutive steps, as a list of :class:`Alignment` objects.
+    ///
+    /// The list is empty when the path's overlaps are unspecified (``*``).
+    #[getter]
+    fn overlaps(&self) -> PyResult<Vec<PyAlignment>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(gfa
+            .path_overlaps(&gfa.paths[self.0.id()])
+            .map(|align| PyAlignment(align.ops.to_vec()))
+            .collect())
+    }
+
+    /// Spell out the path's sequence as a byte string: its steps' sequences, in
+    /// their orientations, concatenated.
+    ///
+    /// Raises a `ValueError` if any step's segment has no sequence (e.g., its sequence
+    /// is ``*`` or the graph was parsed with ``sequences=False``).
+    fn sequence<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let seq = gfa
+            .get_path_seq(&gfa.paths[self.0.id()])
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(PyBytes::new_bound(py, &seq))
+    }
+
+    /// Find where a base offset along the path's sequence lands.
+    ///
+    /// Returns a tuple ``(index, handle, offset)``: the index of the step in the path
+    /// (so ``path[index]`` is the step), its :class:`Handle`, and the offset within
+    /// the step, counting along its orientation. Returns ``None`` if the offset is
+    /// past the end of the path.
+    fn position_at(&self, offset: u64) -> PyResult<Option<(usize, PyHandle, u32)>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let path = &gfa.paths[self.0.id()];
+        Ok(gfa.path_position(path, offset).map(|(step, _, offset)| {
+            (
+                step.index() - path.steps.start.index(),
+                PyHandle {
+                    store: self.0.store.clone(),
+                    handle: gfa.steps[step],
+                },
+                offset,
+            )
+        }))
+    }
+
+    /// Project a base offset along this path onto another path in the same graph,
+    /// through the segment they share at that position.
+    ///
+    /// Returns a list of offsets along `other`: one for each time it visits that base
+    /// of the segment (in either orientation). The list is empty if `other` never
+    /// visits the segment or the offset is past the end of this path.
+    fn liftover(&self, offset: u64, other: &PyPath) -> PyResult<Vec<u64>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.liftover(&gfa.paths[self.0.id()], offset, &gfa.paths[other.0.id()]))
+    }
+
+    fn __iter__(&self) -> PyResult<StepIter> {
+        Ok(self.steps()?.__iter__())
     }
 
     fn __getitem__(&self, arg: SliceOrInt, py: Python) -> PyResult<PyObject> {
-        self.steps().__getitem__(arg, py)
+        self.steps()?.__getitem__(arg, py)
     }
 
-    fn __len__(&self) -> usize {
-        self.steps().__len__()
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(self.steps()?.__len__())
     }
 }
 
@@ -459,16 +2183,96 @@ impl From<ListRef> for PathList {
 #[pymethods]
 impl PathList {
     /// Find a path by its name (a string), or return `None` if not found.
-    fn find(&self, name: &str) -> Option<PyPath> {
-        let gfa = self.0.store.view();
-        let id = gfa.find_path(name.as_ref())?;
-        Some(PyPath(EntityRef {
-            store: self.0.store.clone(),
-            index: id.into(),
+    fn find(&self, name: &str) -> PyResult<Option<PyPath>> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        Ok(gfa.find_path(name.as_ref()).map(|id| {
+            PyPath(EntityRef {
+                store: self.0.store.clone(),
+                index: id.into(),
+            })
         }))
     }
 }
 
+/// A CIGAR alignment, like ``3M1I2M``, describing an overlap.
+#[pyclass(frozen)]
+#[pyo3(name = "Alignment", module = "flatgfa")]
+struct PyAlignment(Vec<flatgfa::AlignOp>);
+
+#[pymethods]
+impl PyAlignment {
+    /// The operations in the alignment, as (length, operation) pairs, like
+    /// ``(3, "M")``.
+    #[getter]
+    fn ops(&self) -> Vec<(u32, String)> {
+        self.0
+            .iter()
+            .map(|op| (op.len(), op.op().to_string()))
+            .collect()
+    }
+
+    fn __str__(&self) -> String {
+        flatgfa::Alignment { ops: &self.0 }.to_string()
+    }
+}
+
+/// The direction in which a path or link traverses a segment: forward (``+``) or
+/// backward (``-``).
+#[pyclass]
+#[pyo3(name = "Orientation", module = "flatgfa")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PyOrientation {
+    Forward,
+    Backward,
+}
+
+impl From<flatgfa::Orientation> for PyOrientation {
+    fn from(orient: flatgfa::Orientation) -> Self {
+        match orient {
+            flatgfa::Orientation::Forward => Self::Forward,
+            flatgfa::Orientation::Backward => Self::Backward,
+        }
+    }
+}
+
+impl From<PyOrientation> for flatgfa::Orientation {
+    fn from(orient: PyOrientation) -> Self {
+        match orient {
+            PyOrientation::Forward => Self::Forward,
+            PyOrientation::Backward => Self::Backward,
+        }
+    }
+}
+
+#[pymethods]
+impl PyOrientation {
+    /// Get the orientation for a ``"+"`` or ``"-"`` string.
+    ///
+    /// Raises a `ValueError` for any other string.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        text.parse::<flatgfa::Orientation>()
+            .map(Self::from)
+            .map_err(|_| {
+                PyValueError::new_err(format!("bad orientation `{}` (expected + or -)", text))
+            })
+    }
+
+    /// The opposite orientation.
+    fn flip(&self) -> Self {
+        flatgfa::Orientation::from(*self).flip().into()
+    }
+
+    fn __str__(&self) -> String {
+        flatgfa::Orientation::from(*self).to_string()
+    }
+
+    fn __hash__(&self) -> isize {
+        *self as isize
+    }
+}
+
 /// An oriented segment reference.
 ///
 /// Because both paths and links connect *oriented* segments rather than the segments themselves,
@@ -488,7 +2292,13 @@ impl PyHandle {
         self.handle.segment().into()
     }
 
-    /// The orientation.
+    /// The orientation, as an :class:`Orientation`.
+    #[getter]
+    fn orient(&self) -> PyOrientation {
+        self.handle.orient().into()
+    }
+
+    /// Whether the orientation is forward.
     #[getter]
     fn is_forward(&self) -> bool {
         self.handle.orient() == flatgfa::Orientation::Forward
@@ -503,6 +2313,35 @@ impl PyHandle {
         })
     }
 
+    /// The handles reachable from this one by following a single link.
+    ///
+    /// This respects orientation: walking into a segment backward means leaving it
+    /// from its start, so the result is a list of oriented handles.
+    fn next(&self, py: Python) -> PyResult<Vec<PyHandle>> {
+        Ok(self
+            .store
+            .adjacency(py)?
+            .next(self.handle)
+            .map(|handle| PyHandle {
+                store: self.store.clone(),
+                handle,
+            })
+            .collect())
+    }
+
+    /// The handles from which a single link leads to this one.
+    fn prev(&self, py: Python) -> PyResult<Vec<PyHandle>> {
+        Ok(self
+            .store
+            .adjacency(py)?
+            .prev(self.handle)
+            .map(|handle| PyHandle {
+                store: self.store.clone(),
+                handle,
+            })
+            .collect())
+    }
+
     fn __repr__(&self) -> String {
         format!(
             "<Handle {}{}>",
@@ -511,9 +2350,10 @@ impl PyHandle {
         )
     }
 
-    fn __str__(&self) -> String {
-        let gfa = self.store.view();
-        format!("{}", print::Display(&gfa, self.handle))
+    fn __str__(&self) -> PyResult<String> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        Ok(format!("{}", print::Display(&gfa, self.handle)))
     }
 
     fn __eq__(&self, other: &PyHandle) -> bool {
@@ -547,7 +2387,7 @@ impl StepList {
     fn __getitem__(&self, arg: SliceOrInt, py: Python) -> PyResult<PyObject> {
         match arg {
             SliceOrInt::Slice(slice) => {
-                let indices = slice.indices(self.0.len().try_into().unwrap())?;
+                let indices = slice.indices(self.0.len().into())?;
                 if indices.step == 1 {
                     let list = self.0.slice(indices.start as u32, indices.stop as u32);
                     Ok(Self(list).into_py(py))
@@ -557,7 +2397,7 @@ impl StepList {
             }
             SliceOrInt::Int(int) => {
                 let index = self.0.start + (int as u32);
-                let handle = self.0.store.view().steps[Id::from(index)];
+                let handle = self.0.store.read()?.view().steps[Id::from(index)];
                 Ok(PyHandle {
                     store: self.0.store.clone(),
                     handle,
@@ -583,9 +2423,10 @@ impl StepIter {
         self_
     }
 
-    fn __next__(&mut self) -> Option<PyHandle> {
-        let gfa = self.store.view();
-        if self.index < self.end {
+    fn __next__(&mut self) -> PyResult<Option<PyHandle>> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        Ok(if self.index < self.end {
             let handle = PyHandle {
                 store: self.store.clone(),
                 handle: gfa.steps[Id::from(self.index)],
@@ -594,7 +2435,44 @@ impl StepIter {
             Some(handle)
         } else {
             None
+        })
+    }
+}
+
+/// A segment and an orientation: one end of a link, from `FlatGFA.link_handles`.
+type OrientedSeg = (PySegment, PyOrientation);
+
+/// An iterator over the links' ends.
+#[pyclass]
+#[pyo3(module = "flatgfa")]
+struct LinkHandleIter {
+    store: Arc<Store>,
+    index: u32,
+    end: u32,
+}
+
+#[pymethods]
+impl LinkHandleIter {
+    fn __iter__(self_: Py<Self>) -> Py<Self> {
+        self_
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<(OrientedSeg, OrientedSeg)>> {
+        let data = self.store.read()?;
+        let gfa = data.view();
+        if self.index >= self.end {
+            return Ok(None);
         }
+        let link = &gfa.links[Id::from(self.index)];
+        let end = |handle: flatgfa::Handle| {
+            let seg = PySegment(EntityRef {
+                store: self.store.clone(),
+                index: handle.segment().into(),
+            });
+            (seg, handle.orient().into())
+        };
+        self.index += 1;
+        Ok(Some((end(link.from), end(link.to))))
     }
 }
 
@@ -620,14 +2498,25 @@ impl PyLink {
         self.0.index
     }
 
+    /// The link's tags (optional fields), as a :class:`Tags` mapping.
+    #[getter]
+    fn tags(&self) -> PyResult<TagMap> {
+        let tags = self.0.store.read()?.view().links[self.0.id()].tags();
+        Ok(TagMap {
+            store: self.0.store.clone(),
+            tags,
+        })
+    }
+
     fn __repr__(&self) -> String {
         self.0.py_repr("Link")
     }
 
-    fn __str__(&self) -> String {
-        let gfa = self.0.store.view();
+    fn __str__(&self) -> PyResult<String> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
         let link = gfa.links[self.0.id()];
-        format!("{}", print::Display(&gfa, &link))
+        Ok(format!("{}", print::Display(&gfa, &link)))
     }
 
     fn __eq__(&self, other: &PyLink) -> bool {
@@ -640,20 +2529,27 @@ impl PyLink {
 
     /// The edge's source handle.
     #[getter]
-    fn from_(&self) -> PyHandle {
-        PyHandle {
+    fn from_(&self) -> PyResult<PyHandle> {
+        Ok(PyHandle {
             store: self.0.store.clone(),
-            handle: self.0.store.view().links[self.0.id()].from,
-        }
+            handle: self.0.store.read()?.view().links[self.0.id()].from,
+        })
     }
 
     /// The edge's sink handle.
     #[getter]
-    fn to(&self) -> PyHandle {
-        PyHandle {
+    fn to(&self) -> PyResult<PyHandle> {
+        Ok(PyHandle {
             store: self.0.store.clone(),
-            handle: self.0.store.view().links[self.0.id()].to,
-        }
+            handle: self.0.store.read()?.view().links[self.0.id()].to,
+        })
+    }
+
+    /// Whether the link changes orientation, like ``L 1 + 2 -``. For a self-loop,
+    /// this tells a hairpin from a tandem repeat.
+    #[getter]
+    fn inverting(&self) -> PyResult<bool> {
+        Ok(self.0.store.read()?.view().links[self.0.id()].is_inverting())
     }
 }
 
@@ -668,20 +2564,109 @@ impl From<ListRef> for LinkList {
     }
 }
 
+/// A jump in a GFA graph.
+///
+/// Jumps (GFA 1.2 J-lines) connect oriented segments like links, but they represent
+/// a gap of some estimated distance rather than an overlap.
+#[pyclass(frozen)]
+#[pyo3(name = "Jump", module = "flatgfa")]
+struct PyJump(EntityRef);
+
+impl From<EntityRef> for PyJump {
+    fn from(entity: EntityRef) -> Self {
+        Self(entity)
+    }
+}
+
+#[pymethods]
+impl PyJump {
+    /// The unique identifier for the jump.
+    #[getter]
+    fn id(&self) -> u32 {
+        self.0.index
+    }
+
+    fn __repr__(&self) -> String {
+        self.0.py_repr("Jump")
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        let data = self.0.store.read()?;
+        let gfa = data.view();
+        let jump = gfa.jumps[self.0.id()];
+        Ok(format!("{}", print::Display(&gfa, &jump)))
+    }
+
+    fn __eq__(&self, other: &PyJump) -> bool {
+        self.0 == other.0
+    }
+
+    fn __hash__(&self) -> isize {
+        self.0.index as isize
+    }
+
+    /// The jump's source handle.
+    #[getter]
+    fn from_(&self) -> PyResult<PyHandle> {
+        Ok(PyHandle {
+            store: self.0.store.clone(),
+            handle: self.0.store.read()?.view().jumps[self.0.id()].from,
+        })
+    }
+
+    /// The jump's sink handle.
+    #[getter]
+    fn to(&self) -> PyResult<PyHandle> {
+        Ok(PyHandle {
+            store: self.0.store.clone(),
+            handle: self.0.store.read()?.view().jumps[self.0.id()].to,
+        })
+    }
+
+    /// The estimated distance spanned by the jump, or `None` if it is unknown (`*`).
+    #[getter]
+    fn distance(&self) -> PyResult<Option<i64>> {
+        Ok(self.0.store.read()?.view().jumps[self.0.id()].distance())
+    }
+}
+
+/// A sequence of :class:`Jump` objects.
+#[pyclass]
+#[pyo3(module = "flatgfa")]
+struct JumpList(ListRef);
+
+impl From<ListRef> for JumpList {
+    fn from(list: ListRef) -> Self {
+        Self(list)
+    }
+}
+
 #[pymodule]
 #[pyo3(name = "flatgfa")]
 fn pymod(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyFlatGFA>()?;
+    m.add_class::<PyMutableFlatGFA>()?;
     m.add_function(wrap_pyfunction!(parse, m)?)?;
     m.add_function(wrap_pyfunction!(parse_bytes, m)?)?;
     m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(load_url, m)?)?;
+    m.add_function(wrap_pyfunction!(open_bgzf, m)?)?;
     m.add_class::<PySegment>()?;
     m.add_class::<PyPath>()?;
     m.add_class::<PyHandle>()?;
+    m.add_class::<PyOrientation>()?;
+    m.add_class::<PyAlignment>()?;
     m.add_class::<PyLink>()?;
+    m.add_class::<PyJump>()?;
+    m.add_class::<TagMap>()?;
+    m.add_class::<PyGraphDiff>()?;
+    m.add_class::<PyKmerIndex>()?;
     m.add_class::<SegmentList>()?;
     m.add_class::<PathList>()?;
     m.add_class::<LinkList>()?;
+    m.add_class::<JumpList>()?;
     m.add_class::<StepList>()?;
+    m.add_class::<PyIndexedGFA>()?;
     Ok(())
 }
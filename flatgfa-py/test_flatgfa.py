@@ -1,5 +1,11 @@
+import gzip
+import io
+import struct
+import warnings
+import zlib
 import pytest
 import flatgfa
+from concurrent.futures import ThreadPoolExecutor
 
 TINY_GFA = b"""H	VN:Z:1.0
 S	1	CAAATAAG
@@ -20,6 +26,14 @@ def gfa():
     return flatgfa.parse_bytes(TINY_GFA)
 
 
+def test_parse_error():
+    # Malformed input raises an exception that points to the offending line.
+    with pytest.raises(ValueError, match="line 3: bad orientation"):
+        flatgfa.parse_bytes(b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t*\t0M\n")
+    with pytest.raises(ValueError, match="unknown segment `3`"):
+        flatgfa.parse_bytes(b"S\t1\tA\nP\tp\t1+,3+\t*\n")
+
+
 def test_segs(gfa):
     # `gfa.segments` acts like a list.
     assert len(gfa.segments) == 4
@@ -38,6 +52,43 @@ def test_segs(gfa):
     assert str(seg) == "S	3	TTG"
 
 
+def test_sequence_slice(gfa):
+    seg = gfa.segments[1]  # AAATTTTCTGGAGTTCTAT
+    assert seg.sequence_slice(3, 7) == b"TTTT"
+    assert seg.sequence_slice(0, len(seg)) == seg.sequence()
+    assert seg.sequence_slice(5, 5) == b""
+
+    # Out-of-range slices are an error rather than being clamped.
+    with pytest.raises(IndexError):
+        seg.sequence_slice(10, 20)
+    with pytest.raises(IndexError):
+        seg.sequence_slice(4, 3)
+
+
+def test_gc_content(gfa):
+    seg = gfa.segments[0]  # CAAATAAG
+    assert seg.base_counts() == [5, 1, 1, 1, 0]
+    assert seg.gc_content() == pytest.approx(2 / 8)
+
+    # The whole-graph GC content pools all the bases.
+    total = [0] * 5
+    for seg in gfa.segments:
+        total = [a + b for a, b in zip(total, seg.base_counts())]
+    assert gfa.gc_content() == pytest.approx((total[1] + total[2]) / sum(total[:4]))
+
+    # Empty sequences have no GC content.
+    empty = flatgfa.parse_bytes(b"S\t1\t*\n")
+    assert empty.segments[0].gc_content() == 0.0
+
+
+def test_n50(gfa):
+    # The segment lengths are 8, 19, 3, and 11, so 19 + 11 is at least half.
+    assert gfa.segment_n50() == (11, 2)
+    # The paths are 38 and 41 bases long.
+    assert gfa.path_n50() == (41, 1)
+    assert flatgfa.parse_bytes(b"").segment_n50() == (0, 0)
+
+
 def test_segs_find(gfa):
     # There is a method to find a segment by its name (with linear search).
     seg = gfa.segments.find(3)
@@ -58,6 +109,68 @@ def test_paths(gfa):
     assert str(path) == "P	one	1+,2+,4-	*"
 
 
+def test_walks():
+    # Walks (W lines) act just like paths, named with the PanSN convention.
+    walks = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nW\tHG002\t1\tchr1\t0\t2\t>1<2\n"
+    )
+    assert len(walks.paths) == 1
+    path = walks.paths[0]
+    assert path.name == "HG002#1#chr1:0-2"
+    assert [str(step) for step in path] == ["1+", "2-"]
+    assert str(path) == "W\tHG002\t1\tchr1\t0\t2\t>1<2"
+
+
+def test_pansn():
+    gfa = flatgfa.parse_bytes(
+        b"S\t1\tA\nP\tHG002#1#chr1\t1+\t*\nP\tref\t1+\t*\n"
+        b"P\tHG003#2#chr1\t1+\t*\nW\tHG002\t2\tchr1\t0\t1\t>1\n"
+    )
+    assert [path.pansn for path in gfa.paths] == [
+        ("HG002", "1", "chr1"),
+        None,
+        ("HG003", "2", "chr1"),
+        ("HG002", "2", "chr1:0-1"),
+    ]
+    by_sample = gfa.paths_by_sample()
+    assert list(by_sample) == ["HG002", "HG003"]
+    assert [path.id for path in by_sample["HG002"]] == [0, 3]
+    assert by_sample["HG003"] == [gfa.paths[2]]
+
+
+def test_parse_stats(tmp_path):
+    gfa = flatgfa.parse_bytes(b"P\tp\t1+,2+\t*\nS\t1\tACGT\nS\t2\tCA\n")
+    stats = gfa.parse_stats
+    assert (stats["lines"], stats["segs"], stats["paths"]) == (3, 2, 1)
+    assert stats["seq_len"] == 6
+    assert stats["deferred_lines"] == 1
+    assert stats["length_mismatches"] == 0
+    assert stats["normalized_overlaps"] == 0
+    assert stats["scan_time"] >= 0.0
+
+    # A FlatGFA file has no parsing statistics.
+    flat_path = str(tmp_path / "stats.flatgfa")
+    gfa.write_flatgfa(flat_path)
+    assert flatgfa.load(flat_path).parse_stats is None
+
+
+def test_content_eq(gfa, tmp_path):
+    # A graph equals its reparsed serialization, and its binary file.
+    assert gfa == flatgfa.parse_bytes(str(gfa).encode())
+    assert gfa == gfa
+    flat_path = str(tmp_path / "eq.flatgfa")
+    gfa.write_flatgfa(flat_path)
+    assert flatgfa.load(flat_path) == gfa
+
+    # Reordering the records does not matter, but the sequences do.
+    text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n"
+    reordered = b"L\t1\t+\t2\t+\t0M\nS\t2\tC\nS\t1\tA\n"
+    changed = b"S\t1\tA\nS\t2\tG\nL\t1\t+\t2\t+\t0M\n"
+    assert flatgfa.parse_bytes(text) == flatgfa.parse_bytes(reordered)
+    assert flatgfa.parse_bytes(text) != flatgfa.parse_bytes(changed)
+    assert gfa != "not a graph"
+
+
 def test_paths_find(gfa):
     # There is a method to find a path by its name.
     path = gfa.paths.find("two")
@@ -74,12 +187,107 @@ def test_path_steps(gfa):
 
     # A step (handle) is a reference to a segment and an orientation.
     assert step.segment.name == 1
+    assert step.orient == flatgfa.Orientation.Forward
     assert step.is_forward
 
     # GFA representation.
     assert str(step) == "1+"
 
 
+
+def test_segment_stats():
+    text = b"S\t1\tACGN\nS\t2\tGG\nP\tx\t1+,2-,1+\t*\nP\ty\t2+\t*\n"
+    graph = flatgfa.parse_bytes(text)
+    assert graph.segment_base_counts() == [[1, 1, 1, 0, 1], [0, 0, 2, 0, 0]]
+    assert graph.segment_base_counts(threads=2) == graph.segment_base_counts()
+    assert graph.coverage() == [2, 2]
+    assert graph.coverage(threads=1) == [2, 2]
+
+
+def test_rgfa():
+    text = b"S\t1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\nS\t2\tGG\tSN:Z:chr1\tSO:i:4\tSR:i:1\n"
+    graph = flatgfa.parse_bytes(text)
+    assert graph.is_rgfa()
+    assert graph.segments[1].rgfa == ("chr1", 4, 1)
+
+    graph = flatgfa.parse_bytes(b"S\t1\tACGT\tSN:Z:chr1\n")
+    assert not graph.is_rgfa()
+    assert graph.segments[0].rgfa is None
+
+
+def test_path_sequence():
+    text = b"S\t1\tACG\nS\t2\t*\tLN:i:5\nP\tp\t1+,1-\t*\nP\tq\t1+,2+\t*\n"
+    graph = flatgfa.parse_bytes(text)
+    assert graph.paths[0].sequence() == b"ACGCGT"
+
+    # Segments with `*` sequences have no bases, but they have lengths.
+    seg = graph.segments[1]
+    assert seg.sequence() == b""
+    assert len(seg) == 5
+    assert str(seg) == "S\t2\t*\tLN:i:5"
+    with pytest.raises(ValueError, match="segment `2` has no sequence"):
+        graph.paths[1].sequence()
+
+
+def test_path_position():
+    text = b"S\t1\tAA\nS\t2\tCCC\nS\t3\tGGGG\nP\tp\t1+,2-,3+\t*\n"
+    path = flatgfa.parse_bytes(text).paths[0]
+
+    # Offset 3 is the second base of the middle step.
+    index, handle, offset = path.position_at(3)
+    assert index == 1
+    assert str(handle) == "2-"
+    assert offset == 1
+    assert path.position_at(8)[0] == 2
+    assert path.position_at(9) is None
+
+
+def test_liftover():
+    text = b"S\t1\tAA\nS\t2\tCCC\nS\t3\tGGGG\nP\ta\t1+,2+,3+\t*\nP\tb\t2-,3+,2+\t*\n"
+    graph = flatgfa.parse_bytes(text)
+    a, b = graph.paths
+    assert a.liftover(3, b) == [1, 8]
+    assert a.liftover(0, b) == []
+
+
+def test_path_overlaps(gfa):
+    # The tiny graph's paths have unspecified (`*`) overlaps.
+    assert gfa.paths[0].overlaps == []
+
+    text = b"S\t1\tACGT\nS\t2\tCGTA\nS\t3\tT\nP\tp\t1+,2-,3+\t2M1I1D,0M\n"
+    path = flatgfa.parse_bytes(text).paths[0]
+    assert [str(align) for align in path.overlaps] == ["2M1I1D", "0M"]
+    assert path.overlaps[0].ops == [(2, "M"), (1, "I"), (1, "D")]
+    assert str(path) == "P\tp\t1+,2-,3+\t2M1I1D,0M"
+
+
+def test_orientation(gfa):
+    # Orientations are an enum, and they convert to and from `+`/`-` strings.
+    Orientation = flatgfa.Orientation
+    assert [str(step.orient) for step in gfa.paths[0]] == ["+", "+", "-"]
+    assert Orientation.parse("-") == Orientation.Backward
+    assert Orientation.parse("+").flip() == Orientation.Backward
+    assert str(Orientation.Backward) == "-"
+    assert len({Orientation.Forward, Orientation.parse("+")}) == 1
+    with pytest.raises(ValueError, match="bad orientation"):
+        Orientation.parse("*")
+
+
+def test_handle_walk(gfa):
+    # Handles know which other handles you can reach by following one link.
+    step = gfa.paths[0][1]
+    assert str(step) == "2+"
+    assert sorted(str(h) for h in step.next()) == ["3+", "4-"]
+    assert [str(h) for h in step.prev()] == ["1+"]
+
+    # Orientation matters: leaving `4-` means leaving the start of segment 4, which
+    # no link does, but you can arrive there from `2+` or `3+`.
+    end = gfa.paths[0][2]
+    assert str(end) == "4-"
+    assert end.next() == []
+    assert sorted(str(h) for h in end.prev()) == ["2+", "3+"]
+
+
 def test_links(gfa):
     # You guessed it: `gfa.links` behaves as a list too.
     assert len(gfa.links) == 4
@@ -91,16 +299,492 @@ def test_links(gfa):
     assert link.from_.is_forward
     assert link.to.segment.name == 4
     assert not link.to.is_forward
+    assert link.to.orient == flatgfa.Orientation.Backward
 
     # GFA representation.
     assert str(link) == "L	2	+	4	-	0M"
 
 
+JUMP_GFA = b"""H	VN:Z:1.2
+S	1	ACGT
+S	2	GG
+J	1	+	2	-	-12	SC:i:1
+J	2	+	1	+	*
+"""
+
+
+NAMED_GFA = b"""H	VN:Z:1.0
+S	utg000013l	ACGT
+S	NC_000001.11	GG
+S	007	T
+S	2	CA
+P	p	utg000013l+,NC_000001.11-,007+,2+	*
+L	utg000013l	+	NC_000001.11	-	0M
+L	007	+	2	+	0M
+"""
+
+
+def test_text_names():
+    gfa = flatgfa.parse_bytes(NAMED_GFA)
+    assert [seg.name for seg in gfa.segments] == [
+        "utg000013l",
+        "NC_000001.11",
+        "007",
+        2,
+    ]
+    assert gfa.segments.find("NC_000001.11").id == 1
+    assert gfa.segments.find(2).id == 3
+    assert gfa.segments.find("2").id == 3
+    assert [step.segment.id for step in gfa.paths[0]] == [0, 1, 2, 3]
+    assert gfa.links[0].to.segment.name == "NC_000001.11"
+
+    # Names keep their exact spelling.
+    assert str(gfa) == NAMED_GFA.decode()
+
+
+def test_jumps():
+    gfa = flatgfa.parse_bytes(JUMP_GFA)
+    assert len(gfa.jumps) == 2
+    jump = gfa.jumps[0]
+    assert jump.from_.segment.name == 1
+    assert jump.to.segment.name == 2
+    assert not jump.to.is_forward
+    assert jump.distance == -12
+    assert gfa.jumps[1].distance is None
+
+    # Jumps survive a round trip through GFA text.
+    assert str(jump) == "J	1	+	2	-	-12	SC:i:1"
+    assert str(gfa) == JUMP_GFA.decode()
+
+
+def test_links_incident(gfa):
+    # A link counts if either its source or its sink is in the set.
+    links = gfa.links_incident([2])  # Segment 3.
+    assert [str(link) for link in links] == ["L	2	+	3	+	0M", "L	3	+	4	-	0M"]
+
+    # Links between two segments in the set appear once.
+    links = gfa.links_incident([0, 1])
+    assert [link.id for link in links] == [0, 1, 2]
+
+    with pytest.raises(IndexError):
+        gfa.links_incident([4])
+
+
+def test_tips(gfa):
+    # Tips are dead ends: the starts of segments 1 and 4. (All of segment 4's links
+    # attach to its end.)
+    assert [str(tip) for tip in gfa.tips()] == ["1-", "4-"]
+
+    # A lone segment is a tip on both sides.
+    lone = flatgfa.parse_bytes(b"S\t1\tA\n")
+    assert [str(tip) for tip in lone.tips()] == ["1+", "1-"]
+
+
+def test_self_loops(gfa):
+    assert gfa.self_loops() == []
+
+    # One loop repeats segment 2 in tandem, and the other is a hairpin on segment 1.
+    looped = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t2\t+\t0M\nL\t1\t-\t1\t+\t0M\n"
+    )
+    loops = looped.self_loops()
+    assert [link.id for link in loops] == [1, 2]
+    assert [link.inverting for link in loops] == [False, True]
+    assert not looped.links[0].inverting
+
+
+def test_link_handles():
+    gfa = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t-\t0M\nL\t2\t-\t1\t+\t0M\n"
+    )
+    ends = [
+        ((a.name, a_orient), (b.name, b_orient))
+        for (a, a_orient), (b, b_orient) in gfa.link_handles()
+    ]
+    fwd, back = flatgfa.Orientation.Forward, flatgfa.Orientation.Backward
+    assert ends == [((1, fwd), (2, back)), ((2, back), (1, fwd))]
+    assert len(list(flatgfa.parse_bytes(b"S\t1\tA\n").link_handles())) == 0
+
+
+def test_superbubbles(gfa):
+    # Segment 2 leads to the end of segment 4 either directly or through segment 3.
+    bubbles = [(str(s), str(t)) for s, t in gfa.superbubbles()]
+    assert bubbles == [("2+", "4-")]
+
+    # A two-allele bubble.
+    bubble = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\n"
+        b"L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\n"
+        b"L\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n"
+    )
+    assert [(str(s), str(t)) for s, t in bubble.superbubbles()] == [("1+", "4+")]
+
+
+def test_complexity():
+    # A bubble from 1 to 4, an isolated segment 5, and a cycle through 6 and 7.
+    graph = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\nS\t5\tA\nS\t6\tC\nS\t7\tG\n"
+        b"L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\n"
+        b"L\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n"
+        b"L\t6\t+\t7\t+\t0M\nL\t7\t+\t6\t+\t0M\n"
+    )
+    assert graph.complexity() == {
+        "segments": 7,
+        "links": 6,
+        "components": 3,
+        "cycle_rank": 2,
+        "mean_degree": 12 / 7,
+        "branching_fraction": 2 / 7,
+        "is_dag": False,
+    }
+
+
+def test_lint(gfa):
+    # The tiny graph is well-formed.
+    assert gfa.lint() == []
+
+    # Path `p` goes 1+ -> 2+, but the only link between them is 1+ -> 2-.
+    odd = flatgfa.parse_bytes(
+        b"S\t1\tA\nS\t2\tC\nS\t3\tG\nL\t1\t+\t2\t-\t0M\nP\tp\t1+,2+\t*\n"
+    )
+    assert odd.lint() == [
+        "segment 2 has no links",
+        "path 0 step 1 follows a link in the wrong orientation",
+    ]
+
+
+def test_diff(gfa):
+    assert not gfa.diff(gfa)
+
+    # Add a segment and drop the 2+ -> 3+ link.
+    text = TINY_GFA.replace(b"L\t2\t+\t3\t+\t0M\n", b"") + b"S\t5\tAC\n"
+    diff = gfa.diff(flatgfa.parse_bytes(text))
+    assert diff
+    assert diff.segments_added == ["5"]
+    assert diff.segments_removed == []
+    assert diff.links_removed == [("2+", "3+")]
+    assert diff.links_added == []
+    assert diff.paths_changed == []
+
+
+def test_find_sequence(gfa):
+    # Search for a sequence in all the segments, on both strands.
+    hits = gfa.find_sequence(b"GGAGT")
+    assert [(seg.name, off, strand) for seg, off, strand in hits] == [(2, 9, "+")]
+
+    # `CAGAAAA` is the reverse complement of `TTTTCTG`.
+    hits = gfa.find_sequence(b"CAGAAAA")
+    assert [(seg.name, off, strand) for seg, off, strand in hits] == [(2, 9, "-")]
+
+    # Matching can ignore case.
+    assert gfa.find_sequence(b"ggagt") == []
+    hits = gfa.find_sequence(b"ggagt", ignore_case=True)
+    assert [(seg.name, off, strand) for seg, off, strand in hits] == [(2, 9, "+")]
+
+
+def test_kmer_index(gfa):
+    index = gfa.build_kmer_index(4)
+    assert index.k == 4
+    hits = index.query(b"AAAT")
+    assert [(seg.name, off) for seg, off in hits] == [(1, 1), (2, 0)]
+    assert index.query(b"GGGG") == []
+    assert index.query(b"AAA") == []
+
+    with pytest.raises(ValueError):
+        gfa.build_kmer_index(33)
+
+
+def test_remove_segment(gfa):
+    # Editing requires a mutable copy of the graph.
+    mut = gfa.to_mutable()
+    mut.remove_segment(2)
+    mut.compact()
+
+    # Removing a segment removes its links and path steps and renumbers segments.
+    new = mut.freeze()
+    assert [seg.name for seg in new.segments] == [1, 2, 4]
+    assert [str(link) for link in new.links] == ["L	1	+	2	+	0M", "L	2	+	4	-	0M"]
+    assert str(new.paths[1]) == "P	two	1+,2+,4-	*"
+    assert "S\t3\t" not in str(new)
+
+    # The original graph is unaffected.
+    assert len(gfa.segments) == 4
+
+    # Removals take effect together, so earlier removals do not shift IDs.
+    mut = gfa.to_mutable()
+    mut.remove_segment(0)
+    mut.remove_segment(3)
+
+    # Snapshots and printing leave out the segments pending removal.
+    assert [seg.name for seg in mut.freeze().segments] == [2, 3]
+    assert "S\t1\t" not in str(mut)
+    mut.remove_segment(1)
+    assert [seg.name for seg in mut.freeze().segments] == [3]
+    mut.compact()
+    assert [seg.name for seg in mut.freeze().segments] == [3]
+
+
+def test_add_link_path(gfa):
+    mut = gfa.to_mutable()
+    assert mut.add_link(4, "+", "1", "-", "2M") == 4
+    assert mut.add_path("three", "3+,4+") == 2
+
+    # New records appear at the end of the GFA text.
+    new = mut.freeze()
+    assert str(new).endswith("L\t4\t+\t1\t-\t2M\nP\tthree\t3+,4+\t*\n")
+    assert str(new.links[4]) == "L\t4\t+\t1\t-\t2M"
+    assert [step.segment.name for step in new.paths[2]] == [3, 4]
+
+    with pytest.raises(ValueError):
+        mut.add_link(1, "+", 9, "+")
+    with pytest.raises(ValueError):
+        mut.add_path("bad", "1+,9-")
+    with pytest.raises(ValueError, match="bad path step `2` at byte 3"):
+        mut.add_path("bad", "1+,2")
+    with pytest.raises(ValueError, match="missing path step at byte 3"):
+        mut.add_path("bad", "1+,")
+    assert len(mut.freeze().paths) == 3
+
+
+def test_set_tag(gfa, tmp_path):
+    mut = gfa.to_mutable()
+    mut.set_segment_tag(0, "DP", 10)
+    mut.set_segment_tag(0, "DP", 12)  # Overwrites the old value.
+    mut.set_path_tag(1, "SM", "HG002")
+    new = mut.freeze()
+    assert str(new.segments[0]) == "S	1	CAAATAAG	DP:i:12"
+    assert str(new.paths[1]) == "P	two	1+,2+,3+,4-	*	SM:Z:HG002"
+
+    # Tags survive a round trip through the binary format.
+    flat_path = str(tmp_path / "tiny.flatgfa")
+    new.write_flatgfa(flat_path)
+    assert str(flatgfa.load(flat_path)) == str(new)
+
+    with pytest.raises(ValueError):
+        mut.set_segment_tag(0, "bad tag", 1)
+
+    # Values must fit in a GFA optional field, and nothing changes if they do not.
+    with pytest.raises(ValueError, match="printable ASCII"):
+        mut.set_segment_tag(0, "SM", "a\tb")
+    with pytest.raises(ValueError, match="printable ASCII"):
+        mut.set_path_tag(1, "SM", "a\nb")
+    with pytest.raises(ValueError, match="printable ASCII"):
+        mut.set_segment_tag(0, "SM", "caf\u00e9")
+    with pytest.raises(ValueError, match="printable ASCII"):
+        mut.set_segment_tag(0, "SM", "\x07")
+    with pytest.raises(ValueError, match="finite"):
+        mut.set_segment_tag(0, "DP", float("inf"))
+    with pytest.raises(ValueError, match="finite"):
+        mut.set_segment_tag(0, "DP", float("nan"))
+    with pytest.raises(ValueError, match="booleans"):
+        mut.set_segment_tag(0, "DP", True)
+    assert str(mut.freeze().segments[0]) == "S	1	CAAATAAG	DP:i:12"
+    assert str(mut.freeze().paths[1]) == "P	two	1+,2+,3+,4-	*	SM:Z:HG002"
+
+
+def test_tags():
+    gfa = flatgfa.parse_bytes(
+        b"S\t1\tACGT\tLN:i:4\tRC:i:120\tdp:f:2.5\n"
+        b"S\t2\tTT\tXB:B:i,1,-2,3\tXH:H:1AFF\n"
+        b"L\t1\t+\t2\t+\t0M\tID:Z:edge one\n"
+        b"P\tp\t1+,2+\t*\tSM:Z:HG002\n"
+    )
+    seg = gfa.segments[0]
+    assert seg.tags["LN"] == 4
+    assert seg.tags["RC"] == 120
+    assert seg.tags["dp"] == 2.5
+    assert list(seg.tags) == ["LN", "RC", "dp"]
+    assert len(seg.tags) == 3
+    assert "RC" in seg.tags
+    assert "XX" not in seg.tags
+
+    tags = gfa.segments[1].tags
+    assert tags["XB"] == [1, -2, 3]
+    assert tags["XH"] == b"\x1a\xff"
+    assert gfa.links[0].tags["ID"] == "edge one"
+    assert gfa.paths[0].tags["SM"] == "HG002"
+
+    with pytest.raises(KeyError):
+        seg.tags["XX"]
+    assert seg.tags.get("XX") is None
+    assert seg.tags.get("XX", 0) == 0
+    assert seg.tags.get("LN", 0) == 4
+
+
+def test_rename_segments(gfa):
+    mut = gfa.to_mutable()
+    mut.rename_segments(lambda name: f"chr1_{name}")
+    new = mut.freeze()
+    assert str(new.segments[0]).startswith("S\tchr1_1\t")
+    assert str(new.links[0]) == "L\tchr1_1\t+\tchr1_2\t+\t0M"
+    assert str(new.paths[0]) == "P\tone\tchr1_1+,chr1_2+,chr1_4-\t*"
+
+    # A failing rename leaves the names alone.
+    def bad(name):
+        if name == "chr1_3":
+            raise KeyError(name)
+        return name + "x"
+
+    with pytest.raises(KeyError):
+        mut.rename_segments(bad)
+    assert str(mut.freeze().links[0]) == "L\tchr1_1\t+\tchr1_2\t+\t0M"
+
+    # So do names that cannot appear in GFA or that repeat.
+    with pytest.raises(ValueError, match="cannot be empty"):
+        mut.rename_segments(lambda name: "" if name == "chr1_2" else name)
+    with pytest.raises(ValueError, match="has whitespace"):
+        mut.rename_segments(lambda name: name + " x")
+    with pytest.raises(ValueError, match="has whitespace"):
+        mut.rename_segments(lambda name: name + "\tx")
+    with pytest.raises(ValueError, match="has whitespace"):
+        mut.rename_segments(lambda name: name + ",x")
+    with pytest.raises(ValueError, match="has whitespace"):
+        mut.rename_segments(lambda name: name + "+")
+    with pytest.raises(ValueError, match="has whitespace"):
+        mut.rename_segments(lambda name: name.replace("_", "-"))
+    with pytest.raises(ValueError, match="duplicate segment name `x`"):
+        mut.rename_segments(lambda name: "x")
+    assert str(mut.freeze().links[0]) == "L\tchr1_1\t+\tchr1_2\t+\t0M"
+
+
 def test_gfa_str(gfa):
     # You can serialize a graph as GFA text.
     assert str(gfa) == TINY_GFA.decode()
 
 
+def test_duplicate_paths():
+    # A repeated path name gets a suffix, with a warning.
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        gfa = flatgfa.parse_bytes(b"S\t1\tA\nP\tp\t1+\t*\nP\tp\t1-\t*\n")
+    assert [str(w.message) for w in caught] == [
+        "line 3: duplicate path name `p` (renamed to `p.1`)"
+    ]
+    assert [path.name for path in gfa.paths] == ["p", "p.1"]
+
+
+def test_parse_options(tmp_path):
+    text = b"S\t1\tA\tbad\nS\t1\tC\n# a comment\nP\tp\t1+\t*\nP\tp\t1-\t*\n"
+    gfa_path = str(tmp_path / "messy.gfa")
+    with open(gfa_path, "wb") as f:
+        f.write(text)
+
+    # By default, a repeated segment is an error.
+    with pytest.raises(ValueError, match="already defined on line 1"):
+        flatgfa.parse(gfa_path)
+
+    # Keyword arguments make the parser more permissive...
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        gfa = flatgfa.parse(gfa_path, keep_first_duplicates=True, keep_unknown=True)
+    assert len(caught) == 2
+    assert [seg.sequence() for seg in gfa.segments] == [b"A"]
+    assert [path.name for path in gfa.paths] == ["p", "p.1"]
+    assert "# a comment\n" in str(gfa)
+
+    # ...or stricter.
+    with pytest.raises(ValueError, match="line 1:"):
+        flatgfa.parse_stream(
+            io.BytesIO(text), strict=True, keep_first_duplicates=True, keep_unknown=True
+        )
+
+
+def test_skip_paths(tmp_path):
+    text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*\n"
+    gfa_path = tmp_path / "paths.gfa"
+    gfa_path.write_bytes(text)
+    for gfa in [
+        flatgfa.parse_stream(io.BytesIO(text), paths=False),
+        flatgfa.parse(str(gfa_path), paths=False),
+    ]:
+        assert [seg.sequence() for seg in gfa.segments] == [b"A", b"C"]
+        assert [str(link) for link in gfa.links] == ["L\t1\t+\t2\t+\t0M"]
+        assert str(gfa) == "S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n"
+        with pytest.raises(ValueError, match="paths were not loaded"):
+            gfa.paths
+        with pytest.raises(ValueError, match="paths were not loaded"):
+            gfa.path_lengths()
+
+    # With the paths, everything works.
+    gfa = flatgfa.parse_stream(io.BytesIO(text))
+    assert [path.name for path in gfa.paths] == ["p"]
+
+
+def test_repr(gfa, tmp_path):
+    assert repr(gfa) == "<FlatGFA: 4 segments, 4 links, 2 paths>"
+
+    # Loaded graphs work the same way.
+    flat_path = str(tmp_path / "tiny.flatgfa")
+    gfa.write_flatgfa(flat_path)
+    loaded = flatgfa.load(flat_path)
+    assert repr(loaded) == repr(gfa)
+    loaded.close()
+    assert repr(loaded) == "<FlatGFA: closed>"
+
+
+def test_lengths(gfa):
+    seg_lengths = gfa.segment_lengths()
+    assert seg_lengths.typecode == "I"
+    assert list(seg_lengths) == [len(seg) for seg in gfa.segments]
+    path_lengths = gfa.path_lengths()
+    assert path_lengths.typecode == "Q"
+    assert list(path_lengths) == [len(path.sequence()) for path in gfa.paths]
+
+    # The arrays support the buffer protocol, for zero-copy use with NumPy.
+    view = memoryview(path_lengths)
+    assert (view.format, view.itemsize) == ("Q", 8)
+
+
+def test_empty_path():
+    # Paths with no steps can stand in for missing haplotypes.
+    text = b"S\t1\tA\nP\tmissing\t\t*\nP\tp\t1+\t*\n"
+    gfa = flatgfa.parse_bytes(text)
+    path = gfa.paths[0]
+    assert len(path) == 0
+    assert list(path) == []
+    assert path.sequence() == b""
+    assert path.position_at(0) is None
+    assert gfa.coverage() == [1]
+    assert str(gfa) == text.decode()
+
+
+def test_segments_sorted():
+    gfa = flatgfa.parse_bytes(b"S\ts10\tA\nS\ts9\tC\nS\ts100\tG\nS\ts1\tT\n")
+    names = ["s1", "s9", "s10", "s100"]
+    assert [seg.name for seg in gfa.segments_sorted()] == names
+
+    # The order is cached, and it does not change the segments' IDs.
+    assert [seg.name for seg in gfa.segments_sorted()] == names
+    assert [seg.id for seg in gfa.segments_sorted()] == [3, 1, 0, 2]
+
+
+def test_segments_by_length(gfa):
+    # Segment 1 is exactly 8 bases long, so it is not longer than 8.
+    assert [seg.name for seg in gfa.segments_longer_than(8)] == [2, 4]
+    assert [seg.name for seg in gfa.segments_no_longer_than(8)] == [1, 3]
+    assert len(gfa.segments_longer_than(0)) == 4
+    assert gfa.segments_longer_than(100) == []
+
+
+def test_write_gfa_paths(gfa, tmp_path):
+    # Write only the path `one`, with just the segments and links it uses.
+    out_path = str(tmp_path / "one.gfa")
+    gfa.write_gfa_paths(out_path, [0], prune=True)
+    one = flatgfa.parse(out_path)
+    assert [seg.name for seg in one.segments] == [1, 2, 4]
+    assert [path.name for path in one.paths] == ["one"]
+    assert len(one.links) == 2
+
+    # Without pruning, the rest of the graph stays.
+    gfa.write_gfa_paths(out_path, [1])
+    two = flatgfa.parse(out_path)
+    assert [path.name for path in two.paths] == ["two"]
+    assert (len(two.segments), len(two.links)) == (4, 4)
+
+    with pytest.raises(IndexError):
+        gfa.write_gfa_paths(out_path, [2])
+
+
 def test_read_write_gfa(gfa, tmp_path):
     # You can write FlatGFA objects as GFA text files.
     gfa_path = str(tmp_path / "tiny.gfa")
@@ -108,10 +792,131 @@ def test_read_write_gfa(gfa, tmp_path):
     with open(gfa_path, "rb") as f:
         assert f.read() == TINY_GFA
 
+    # The tiny graph's paths come before its links, so it is already sorted.
+    gfa.write_gfa(gfa_path, sorted=True)
+    with open(gfa_path, "rb") as f:
+        assert f.read() == TINY_GFA
+
+    # Sorting by length puts segment 3 first, and the links follow.
+    sorted_path = str(tmp_path / "sorted.gfa")
+    gfa.write_gfa(sorted_path, sort_segments="length")
+    with open(sorted_path, "rb") as f:
+        lines = f.read().decode().splitlines()
+    assert [line.split("\t")[1] for line in lines[1:5]] == ["3", "1", "4", "2"]
+    assert lines[-4:] == [
+        "L\t3\t+\t4\t-\t0M",
+        "L\t1\t+\t2\t+\t0M",
+        "L\t2\t+\t3\t+\t0M",
+        "L\t2\t+\t4\t-\t0M",
+    ]
+    with pytest.raises(ValueError):
+        gfa.write_gfa(sorted_path, sort_segments="color")
+
     # You can also parse GFA text files from the filesystem.
     new_gfa = flatgfa.parse(gfa_path)
     assert len(new_gfa.segments) == len(gfa.segments)
 
+    # To save memory, you can skip the sequences but keep their lengths.
+    topology = flatgfa.parse(gfa_path, sequences=False)
+    assert [len(seg) for seg in topology.segments] == [len(s) for s in gfa.segments]
+    with pytest.raises(ValueError, match="sequences were not loaded"):
+        topology.segments[0].sequence()
+    with pytest.raises(ValueError, match="sequences were not loaded"):
+        topology.paths[0].sequence()
+    assert [str(link) for link in topology.links] == [str(link) for link in gfa.links]
+
+
+def bgzf(data, block_len=16):
+    """Compress data as BGZF, in small blocks so lines span several of them."""
+    out = b""
+    for i in range(0, len(data) + 1, block_len):
+        chunk = data[i : i + block_len]
+        comp = zlib.compressobj(wbits=-15)
+        cdata = comp.compress(chunk) + comp.flush()
+        size = 12 + 6 + len(cdata) + 8 - 1
+        out += b"\x1f\x8b\x08\x04\0\0\0\0\0\xff\x06\0BC\x02\0"
+        out += struct.pack("<H", size) + cdata
+        out += struct.pack("<II", zlib.crc32(chunk), len(chunk))
+    return out
+
+
+def test_close(gfa, tmp_path):
+    flat_path = str(tmp_path / "tiny.flatgfa")
+    gfa.write_flatgfa(flat_path)
+    with flatgfa.load(flat_path) as graph:
+        seg = graph.segments[0]
+        assert seg.sequence() == b"CAAATAAG"
+        assert not graph.closed
+    assert graph.closed
+
+    # Using the graph, or anything that refers into it, is an error after closing.
+    with pytest.raises(ValueError, match="closed"):
+        graph.segments
+    with pytest.raises(ValueError, match="closed"):
+        seg.sequence()
+    with pytest.raises(ValueError, match="closed"):
+        graph.tips()
+    graph.close()  # Closing again is harmless.
+
+
+def test_compressed(tmp_path):
+    # `parse` transparently decompresses gzip and BGZF files.
+    gz_path = tmp_path / "tiny.gfa.gz"
+    gz_path.write_bytes(gzip.compress(TINY_GFA))
+    assert len(flatgfa.parse(str(gz_path)).segments) == 4
+
+    bgzf_path = tmp_path / "tiny.gfa.bgz"
+    bgzf_path.write_bytes(bgzf(TINY_GFA))
+    assert len(flatgfa.parse(str(bgzf_path)).paths) == 2
+
+    # BGZF files can also be indexed to fetch individual segments.
+    indexed = flatgfa.open_bgzf(str(bgzf_path))
+    assert len(indexed) == 4
+    assert 2 in indexed and "5" not in indexed
+    assert indexed.sequence(2) == b"AAATTTTCTGGAGTTCTAT"
+    assert indexed.sequence("4") == b"CCAACTCTCTG"
+    assert indexed.sequence(5) is None
+
+
+def test_write_compressed(gfa, tmp_path):
+    # Writing to a `.gz` name compresses the text, and `parse` reads it back.
+    gz_path = tmp_path / "out.gfa.gz"
+    gfa.write_gfa(str(gz_path))
+    assert gzip.decompress(gz_path.read_bytes()) == TINY_GFA
+    assert str(flatgfa.parse(str(gz_path))) == str(gfa)
+
+    # `compress` overrides the name.
+    plain_path = tmp_path / "plain.gfa.gz"
+    gfa.write_gfa(str(plain_path), compress=False)
+    assert plain_path.read_bytes() == TINY_GFA
+    forced_path = tmp_path / "forced.gfa"
+    gfa.write_gfa_paths(str(forced_path), [0], compress=True)
+    assert gzip.decompress(forced_path.read_bytes()).startswith(b"H\t")
+
+
+def test_parse_stream(gfa):
+    new = flatgfa.parse_stream(io.BytesIO(TINY_GFA))
+    assert str(new) == str(gfa)
+    new = flatgfa.parse_stream(io.BytesIO(gzip.compress(TINY_GFA)), sequences=False)
+    assert [len(seg) for seg in new.segments] == [len(seg) for seg in gfa.segments]
+
+    # Errors from the stream itself propagate.
+    class Broken(io.RawIOBase):
+        def read(self, size=-1):
+            raise ConnectionResetError("connection reset")
+
+    with pytest.raises(ConnectionResetError):
+        flatgfa.parse_stream(Broken())
+
+
+def test_load_url(gfa, tmp_path):
+    gz_path = tmp_path / "tiny.gfa.gz"
+    gz_path.write_bytes(gzip.compress(TINY_GFA))
+    assert str(flatgfa.load_url(gz_path.as_uri())) == str(gfa)
+
+    with pytest.raises(OSError):
+        flatgfa.load_url((tmp_path / "missing.gfa").as_uri())
+
 
 def test_read_write_flatgfa(gfa, tmp_path):
     # You can write FlatGFA graphs in our native binary format too.
@@ -121,6 +926,19 @@ def test_read_write_flatgfa(gfa, tmp_path):
     # And read them back, which should be very fast indeed.
     new_gfa = flatgfa.load(flatgfa_path)
     assert len(new_gfa.segments) == len(gfa.segments)
+    new_gfa.close()
+
+    # Files in an older layout are rejected instead of misread.
+    with open(flatgfa_path, "r+b") as f:
+        f.write(struct.pack("=Q", 0xB1011054))
+    with pytest.raises(ValueError, match="format version 0"):
+        flatgfa.load(flatgfa_path)
+
+    # So are files that are not FlatGFA at all.
+    text_path = tmp_path / "tiny.gfa"
+    text_path.write_bytes(TINY_GFA)
+    with pytest.raises(ValueError, match="not a FlatGFA file"):
+        flatgfa.load(str(text_path))
 
 
 def test_eq(gfa):
@@ -167,3 +985,42 @@ def test_slice(gfa):
     assert len(path[2:]) == len(path) - 2
     assert path[2:][0] == path[2]
     assert len(list(path[2:])) == len(path) - 2
+
+
+def test_get_many(gfa):
+    # Batched lookups agree with indexing one item at a time.
+    ids = [3, 0, 3, len(gfa.segments) - 1]
+    assert gfa.segments.get_many(ids) == [gfa.segments[i] for i in ids]
+    assert gfa.segments.get_range(1, 4) == [gfa.segments[i] for i in range(1, 4)]
+    assert gfa.segments.get_range(2, 2) == []
+    assert gfa.links.get_many([1]) == [gfa.links[1]]
+    assert gfa.paths.get_range(0, len(gfa.paths)) == list(gfa.paths)
+
+    # Indices are relative to the (possibly sliced) list.
+    sub = gfa.segments[2:]
+    assert sub.get_many([0, 1]) == [gfa.segments[2], gfa.segments[3]]
+
+    with pytest.raises(IndexError):
+        gfa.segments.get_many([len(gfa.segments)])
+    with pytest.raises(IndexError):
+        sub.get_range(0, len(sub) + 1)
+
+
+def test_concurrent_reads(gfa):
+    # All the read APIs are safe to use from several threads at once, including the
+    # ones that build indices lazily on first use.
+    def work(i):
+        out = []
+        for seg in gfa.segments:
+            out.append(seg.sequence())
+        for path in gfa.paths:
+            out.append([str(step) for step in path])
+            out.append([str(h) for step in path for h in step.next()])
+        out.append(len(gfa.find_sequence(b"TTG")))
+        out.append(str(gfa))
+        return out
+
+    with ThreadPoolExecutor(max_workers=8) as pool:
+        results = list(pool.map(work, range(200)))
+    expected = work(0)
+    assert all(res == expected for res in results)
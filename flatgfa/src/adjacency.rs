@@ -0,0 +1,222 @@
+use crate::flatgfa::{FlatGFA, Handle, Link, Orientation, Segment};
+use crate::pool::Id;
+use std::collections::HashSet;
+use zerocopy::FromZeroes;
+
+/// An index of the links in a graph, organized by the handles they connect.
+///
+/// Links are stored once in the `links` pool, so finding the neighbors of a segment
+/// requires a linear scan. This index instead records, for every handle, the list
+/// of handles you can reach by following a single link. It uses a compressed
+/// (CSR-style) layout: `offsets[h]..offsets[h+1]` is the range of `edges` for the
+/// handle with dense index `h`.
+///
+/// Edges are in the "walking" sense. A link `A -> B` lets you walk from `A` to `B`,
+/// but it also lets you walk the reverse strand, from `flip(B)` to `flip(A)`.
+pub struct AdjacencyIndex {
+    offsets: Vec<u32>,
+    edges: Vec<(Handle, Id<Link>)>,
+}
+
+impl AdjacencyIndex {
+    /// Build the index for a graph. This takes time linear in the number of links.
+    pub fn build(gfa: &FlatGFA) -> Self {
+        let slots = gfa.segs.len() * 2;
+
+        // Count the outgoing edges for each handle.
+        let mut counts = vec![0u32; slots + 1];
+        for link in gfa.links.all() {
+            counts[link.from.index()] += 1;
+            counts[link.to.flip().index()] += 1;
+        }
+
+        // Convert the counts into starting offsets.
+        let mut total = 0;
+        for count in counts.iter_mut() {
+            let n = *count;
+            *count = total;
+            total += n;
+        }
+        let offsets = counts;
+
+        // Fill in the edges.
+        let mut fill = offsets.clone();
+        let mut edges = vec![(Handle::new_zeroed(), Id::new_zeroed()); total as usize];
+        for (id, link) in gfa.links.items() {
+            let fwd = link.from.index();
+            edges[fill[fwd] as usize] = (link.to, id);
+            fill[fwd] += 1;
+
+            let rev = link.to.flip().index();
+            edges[fill[rev] as usize] = (link.from.flip(), id);
+            fill[rev] += 1;
+        }
+
+        Self { offsets, edges }
+    }
+
+    /// The number of handles in the graph (two per segment).
+    pub fn handle_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    fn edges(&self, handle: Handle) -> &[(Handle, Id<Link>)] {
+        let idx = handle.index();
+        &self.edges[self.offsets[idx] as usize..self.offsets[idx + 1] as usize]
+    }
+
+    /// Get the handles reachable by walking forward from `handle` across one link.
+    pub fn next(&self, handle: Handle) -> impl Iterator<Item = Handle> + '_ {
+        self.edges(handle).iter().map(|(h, _)| *h)
+    }
+
+    /// Get the handles from which one link leads to `handle`.
+    pub fn prev(&self, handle: Handle) -> impl Iterator<Item = Handle> + '_ {
+        self.edges(handle.flip()).iter().map(|(h, _)| h.flip())
+    }
+
+    /// Get the IDs of the links that can be followed out of `handle`.
+    pub fn next_links(&self, handle: Handle) -> impl Iterator<Item = Id<Link>> + '_ {
+        self.edges(handle).iter().map(|(_, l)| *l)
+    }
+
+    /// Find the tips: the handles that have no links leading out of them.
+    ///
+    /// A forward handle here means that the end of the segment is a dead end (a
+    /// "right" tip), and a backward handle means that its start is (a "left" tip). A
+    /// segment with no links at all contributes both. The tips are in segment order.
+    pub fn tips(&self) -> Vec<Handle> {
+        let seg_count = (self.offsets.len() - 1) / 2;
+        (0..seg_count)
+            .flat_map(|idx| {
+                [Orientation::Forward, Orientation::Backward]
+                    .map(|orient| Handle::new(Id::new(idx), orient))
+            })
+            .filter(|&handle| self.edges(handle).is_empty())
+            .collect()
+    }
+
+    /// Label each segment with its connected component, ignoring the links'
+    /// directions and orientations.
+    ///
+    /// The result has one label per segment. Components are numbered from 0 in order
+    /// of their lowest-numbered segments, so a segment with no links gets a label of
+    /// its own.
+    pub fn components(&self) -> Vec<usize> {
+        let seg_count = self.handle_count() / 2;
+        let mut labels = vec![usize::MAX; seg_count];
+        let mut count = 0;
+        let mut stack = vec![];
+        for start in 0..seg_count {
+            if labels[start] != usize::MAX {
+                continue;
+            }
+            labels[start] = count;
+            stack.push(start);
+            while let Some(idx) = stack.pop() {
+                // Every link appears in the edges of both of its endpoints' segments.
+                for orient in [Orientation::Forward, Orientation::Backward] {
+                    for next in self.next(Handle::new(Id::new(idx), orient)) {
+                        let other = next.segment().index();
+                        if labels[other] == usize::MAX {
+                            labels[other] = count;
+                            stack.push(other);
+                        }
+                    }
+                }
+            }
+            count += 1;
+        }
+        labels
+    }
+
+    /// Sort the handles so that every link leads from an earlier handle to a later
+    /// one, or return `None` if following links can lead in a cycle.
+    ///
+    /// The order includes both handles for every segment, since each link leads
+    /// forward on both strands. A graph has an order exactly when it is a DAG in
+    /// this sense. (A hairpin link, like `1+ -> 1-`, does not make a cycle by
+    /// itself.)
+    pub fn topological_order(&self) -> Option<Vec<Handle>> {
+        let count = self.handle_count();
+        let mut in_degree: Vec<usize> = (0..count)
+            .map(|idx| self.edges(Handle::from_index(idx).flip()).len())
+            .collect();
+        let mut order: Vec<Handle> = (0..count)
+            .filter(|&idx| in_degree[idx] == 0)
+            .map(Handle::from_index)
+            .collect();
+        let mut done = 0;
+        while let Some(&handle) = order.get(done) {
+            done += 1;
+            for next in self.next(handle) {
+                in_degree[next.index()] -= 1;
+                if in_degree[next.index()] == 0 {
+                    order.push(next);
+                }
+            }
+        }
+        (order.len() == count).then_some(order)
+    }
+
+    /// Get the IDs of the links with at least one endpoint in a set of segments, in
+    /// ascending order and without duplicates.
+    ///
+    /// Unlike `FlatGFA::links_incident`, this only visits links that touch the set.
+    pub fn links_incident(&self, seg_ids: &HashSet<Id<Segment>>) -> Vec<Id<Link>> {
+        let mut links: Vec<_> = seg_ids
+            .iter()
+            .flat_map(|&seg| {
+                // Every link touching a segment leaves one of its two handles.
+                let fwd = Handle::new(seg, Orientation::Forward);
+                let bwd = Handle::new(seg, Orientation::Backward);
+                self.next_links(fwd).chain(self.next_links(bwd))
+            })
+            .collect();
+        links.sort_unstable_by_key(|id| id.index());
+        links.dedup();
+        links
+    }
+}
+
+#[test]
+fn test_links_incident() {
+    let text = b"S\t1\tA\nS\t2\tA\nS\t3\tA\nS\t4\tA\n\
+        L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\nL\t4\t-\t2\t+\t0M\nL\t3\t+\t4\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let segs: Vec<Id<Segment>> = (0..4).map(Id::new).collect();
+    let gfa = store.as_ref();
+    let index = AdjacencyIndex::build(&gfa);
+
+    // Segment 2 is the source of one link and the sink of two others.
+    let set = HashSet::from([segs[1]]);
+    let ids: Vec<_> = index
+        .links_incident(&set)
+        .iter()
+        .map(|id| id.index())
+        .collect();
+    assert_eq!(ids, vec![0, 1, 2]);
+    assert_eq!(gfa.links_incident(&set).count(), 3);
+
+    // A link between two segments in the set appears once.
+    let set = HashSet::from([segs[2], segs[3]]);
+    let ids: Vec<_> = index
+        .links_incident(&set)
+        .iter()
+        .map(|id| id.index())
+        .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(gfa.links_incident(&set).count(), 3);
+}
+
+#[test]
+fn test_tips() {
+    // A linear chain, 1+ -> 2- -> 3+, has a tip at each end. Segment 4 is isolated.
+    let text = b"S\t1\tA\nS\t2\tA\nS\t3\tA\nS\t4\tA\n\
+        L\t1\t+\t2\t-\t0M\nL\t2\t-\t3\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let fwd = |i: usize| Handle::new(Id::new(i), Orientation::Forward);
+    let bwd = |i: usize| Handle::new(Id::new(i), Orientation::Backward);
+    let gfa = store.as_ref();
+    assert_eq!(gfa.tips(), vec![bwd(0), fwd(2), fwd(3), bwd(3)]);
+}
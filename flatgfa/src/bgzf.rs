@@ -0,0 +1,407 @@
+//! Reading GFA text compressed with gzip or [BGZF][bgzf], the blocked gzip variant
+//! that htslib uses, and writing it compressed with gzip.
+//!
+//! Every BGZF file is also a valid multi-member gzip file, so plain decompression
+//! works the same way for both. BGZF's independently compressed blocks also allow
+//! random access: a [`SegmentIndex`] maps segment names to *virtual offsets* so that
+//! [`IndexedGfa`] can fetch one segment without decompressing the whole file.
+//!
+//! [bgzf]: https://samtools.github.io/hts-specs/SAMv1.pdf
+
+use crate::gfaline;
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::write::GzEncoder;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+/// The first bytes of every gzip member: the magic number and the deflate method.
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+
+/// The gzip header flag indicating an "extra" field, where BGZF stores block sizes.
+const FEXTRA: u8 = 0x04;
+
+/// The length of the fixed part of a gzip header, up to and including `XLEN`.
+const HEADER_LEN: usize = 12;
+
+/// The length of the gzip trailer: a CRC32 and the uncompressed size.
+const TRAILER_LEN: usize = 8;
+
+/// The most data that a BGZF block can hold once decompressed.
+const MAX_BLOCK_LEN: usize = 1 << 16;
+
+/// A position in a BGZF file. The upper 48 bits are the offset of a compressed
+/// block in the file, and the lower 16 bits are an offset into that block's
+/// decompressed data.
+pub type VirtualOffset = u64;
+
+/// Check whether a buffer starts with a gzip header (which includes BGZF).
+pub fn is_gzip(buf: &[u8]) -> bool {
+    buf.starts_with(&GZIP_MAGIC)
+}
+
+/// Check whether a buffer starts with a BGZF block header, i.e., a gzip header with
+/// a `BC` extra subfield.
+pub fn is_bgzf(buf: &[u8]) -> bool {
+    if !is_gzip(buf) || buf.len() < HEADER_LEN || buf[3] & FEXTRA == 0 {
+        return false;
+    }
+    let xlen = u16::from_le_bytes([buf[10], buf[11]]) as usize;
+    match buf.get(HEADER_LEN..HEADER_LEN + xlen) {
+        Some(extra) => block_size(extra).is_some(),
+        None => false,
+    }
+}
+
+/// Decompress a buffer if it holds gzip or BGZF data. Other data is borrowed as-is.
+pub fn decompress_mem(buf: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    if is_gzip(buf) {
+        let mut out = vec![];
+        MultiGzDecoder::new(buf).read_to_end(&mut out)?;
+        Ok(Cow::Owned(out))
+    } else {
+        Ok(Cow::Borrowed(buf))
+    }
+}
+
+/// Wrap a stream so it is decompressed if it holds gzip or BGZF data. Other data
+/// passes through unchanged.
+pub fn decompress_stream<'r, R: BufRead + 'r>(mut stream: R) -> io::Result<Box<dyn BufRead + 'r>> {
+    if is_gzip(stream.fill_buf()?) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(stream))))
+    } else {
+        Ok(Box::new(stream))
+    }
+}
+
+/// Wrap a writer so that the data written to it is compressed with gzip, like
+/// `FlatGFA::write_gfa(&mut compress_writer(file))`.
+///
+/// Call `finish` on the encoder when you are done, which writes the end of the gzip
+/// stream and returns the inner writer. (Dropping the encoder also finishes it, but
+/// ignores errors.)
+pub fn compress_writer<W: Write>(out: W) -> GzEncoder<W> {
+    GzEncoder::new(out, flate2::Compression::default())
+}
+
+/// Check whether a file name asks for gzip-compressed output, i.e., it ends in `.gz`.
+pub fn is_gzip_name(name: &str) -> bool {
+    name.ends_with(".gz")
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Find the total size of a BGZF block from the `BC` subfield of its gzip extra field.
+fn block_size(mut extra: &[u8]) -> Option<usize> {
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if &extra[..2] == b"BC" && len == 2 {
+            return Some(u16::from_le_bytes([data[0], data[1]]) as usize + 1);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Read and decompress one BGZF block. Return its compressed size and its data, or
+/// `None` at the end of the stream.
+fn read_block<R: BufRead>(reader: &mut R) -> io::Result<Option<(u64, Vec<u8>)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if !is_gzip(&header) || header[3] & FEXTRA == 0 {
+        return Err(invalid("not a BGZF block"));
+    }
+    let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+    let mut extra = vec![0; xlen];
+    reader.read_exact(&mut extra)?;
+    let size = block_size(&extra).ok_or_else(|| invalid("BGZF block has no size"))?;
+
+    let cdata_len = size
+        .checked_sub(HEADER_LEN + xlen + TRAILER_LEN)
+        .ok_or_else(|| invalid("BGZF block is too small"))?;
+    let mut cdata = vec![0; cdata_len];
+    reader.read_exact(&mut cdata)?;
+    let mut trailer = [0; TRAILER_LEN];
+    reader.read_exact(&mut trailer)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+    if isize > MAX_BLOCK_LEN {
+        return Err(invalid("BGZF block is too large"));
+    }
+
+    // Stop decompressing just past the claimed size, so a corrupt block cannot make
+    // us decompress more than that.
+    let mut data = Vec::with_capacity(isize);
+    DeflateDecoder::new(&cdata[..])
+        .take(isize as u64 + 1)
+        .read_to_end(&mut data)?;
+    let mut check = flate2::Crc::new();
+    check.update(&data);
+    if data.len() != isize || check.sum() != crc {
+        return Err(invalid("corrupt BGZF block"));
+    }
+    Ok(Some((size as u64, data)))
+}
+
+/// Read the line that starts at a given virtual offset, without its line ending (`\n`
+/// or `\r\n`).
+pub fn read_line_at<R: BufRead + Seek>(
+    reader: &mut R,
+    offset: VirtualOffset,
+) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset >> 16))?;
+    let mut skip = (offset & 0xffff) as usize;
+    let mut line = vec![];
+    while let Some((_, data)) = read_block(reader)? {
+        let data = data.get(skip..).unwrap_or_default();
+        skip = 0;
+        match memchr::memchr(b'\n', data) {
+            Some(end) => {
+                line.extend_from_slice(&data[..end]);
+                break;
+            }
+            None => line.extend_from_slice(data),
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(line)
+}
+
+/// Check whether we need more of a line's prefix to tell whether it is a segment
+/// and, if so, what its name is.
+fn wants_more(prefix: &[u8]) -> bool {
+    match prefix {
+        [] | [b'S'] => true,
+        [b'S', b'\t', rest @ ..] => !rest.contains(&b'\t'),
+        _ => false,
+    }
+}
+
+/// A map from segment names to the virtual offsets of their `S` lines in a BGZF file.
+#[derive(Debug, Default)]
+pub struct SegmentIndex {
+    offsets: HashMap<Box<[u8]>, VirtualOffset>,
+}
+
+impl SegmentIndex {
+    /// Scan an entire BGZF stream to find the `S` lines in it.
+    ///
+    /// This decompresses every block once, but it only retains the segment names.
+    pub fn build<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        let mut index = Self::default();
+        let mut block_offset = 0;
+
+        // The current line's offset and its first few bytes, through the segment
+        // name (and the tab after it) if this is an `S` line.
+        let mut line_start: Option<VirtualOffset> = None;
+        let mut prefix = vec![];
+
+        while let Some((size, data)) = read_block(&mut reader)? {
+            let mut pos = 0;
+            while pos < data.len() {
+                let start = *line_start.get_or_insert((block_offset << 16) | pos as u64);
+                let end = memchr::memchr(b'\n', &data[pos..]).map(|len| pos + len);
+                if wants_more(&prefix) {
+                    prefix.extend_from_slice(&data[pos..end.unwrap_or(data.len())]);
+                }
+                match end {
+                    Some(end) => {
+                        index.add_line(&prefix, start);
+                        line_start = None;
+                        prefix.clear();
+                        pos = end + 1;
+                    }
+                    None => pos = data.len(),
+                }
+            }
+            block_offset += size;
+        }
+        if let Some(start) = line_start {
+            index.add_line(&prefix, start);
+        }
+
+        Ok(index)
+    }
+
+    /// Record a line's offset if it is a segment.
+    fn add_line(&mut self, prefix: &[u8], start: VirtualOffset) {
+        if let Some(rest) = prefix.strip_prefix(b"S\t") {
+            let name = rest.split(|&b| b == b'\t').next().unwrap_or(rest);
+            self.offsets.insert(name.into(), start);
+        }
+    }
+
+    /// Look up the offset of a segment's line.
+    pub fn get(&self, name: &[u8]) -> Option<VirtualOffset> {
+        self.offsets.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+/// A BGZF-compressed GFA 1 file together with an index of its segments.
+///
+/// Fetching a segment decompresses only the blocks that its line spans.
+pub struct IndexedGfa<R> {
+    reader: R,
+    index: SegmentIndex,
+}
+
+impl IndexedGfa<BufReader<std::fs::File>> {
+    /// Open a BGZF file and index it.
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+impl<R: BufRead + Seek> IndexedGfa<R> {
+    /// Index a BGZF stream from its beginning.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        reader.rewind()?;
+        let index = SegmentIndex::build(&mut reader)?;
+        Ok(Self { reader, index })
+    }
+
+    pub fn index(&self) -> &SegmentIndex {
+        &self.index
+    }
+
+    /// Fetch the entire `S` line for a segment, if the segment exists.
+    pub fn segment_line(&mut self, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        match self.index.get(name) {
+            Some(offset) => read_line_at(&mut self.reader, offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch a segment's sequence, if the segment exists. The sequence is empty if
+    /// it is `*` in the file.
+    pub fn segment_seq(&mut self, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let Some(line) = self.segment_line(name)? else {
+            return Ok(None);
+        };
+        match gfaline::parse_line(&line) {
+            Ok(gfaline::Line::Segment(seg)) if seg.seq == b"*" => Ok(Some(vec![])),
+            Ok(gfaline::Line::Segment(seg)) => Ok(Some(seg.seq.to_vec())),
+            _ => Err(invalid("malformed segment line")),
+        }
+    }
+}
+
+#[cfg(test)]
+/// Compress data into BGZF blocks holding at most `block_len` bytes each.
+pub(crate) fn bgzf(data: &[u8], block_len: usize) -> Vec<u8> {
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    let mut out = vec![];
+    for chunk in data.chunks(block_len).chain([&[][..]]) {
+        let mut enc = DeflateEncoder::new(vec![], flate2::Compression::default());
+        enc.write_all(chunk).unwrap();
+        let cdata = enc.finish().unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(chunk);
+
+        let size = (HEADER_LEN + 6 + cdata.len() + TRAILER_LEN - 1) as u16;
+        out.extend_from_slice(&[0x1f, 0x8b, 8, FEXTRA, 0, 0, 0, 0, 0, 0xff, 6, 0]);
+        out.extend_from_slice(b"BC\x02\x00");
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&cdata);
+        out.extend_from_slice(&crc.sum().to_le_bytes());
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+const TEST_GFA: &[u8] = b"H\tVN:Z:1.0\nS\t1\tCAAATAAG\nS\t2\tAAATTTTCTGGAGTTCTAT\tLN:i:19\nL\t1\t+\t2\t+\t0M\nS\t3\tTTG\n";
+
+#[test]
+fn test_decompress() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let compressed = bgzf(TEST_GFA, 10);
+    assert!(is_bgzf(&compressed));
+    assert_eq!(decompress_mem(&compressed).unwrap(), TEST_GFA);
+
+    let mut enc = GzEncoder::new(vec![], flate2::Compression::default());
+    enc.write_all(TEST_GFA).unwrap();
+    let gzip = enc.finish().unwrap();
+    assert!(is_gzip(&gzip) && !is_bgzf(&gzip));
+    let mut out = vec![];
+    decompress_stream(&gzip[..])
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, TEST_GFA);
+
+    assert!(matches!(
+        decompress_mem(TEST_GFA).unwrap(),
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_compress_writer() {
+    let store = crate::parse::Parser::for_heap()
+        .parse_mem(TEST_GFA)
+        .unwrap();
+    let mut enc = compress_writer(vec![]);
+    store.as_ref().write_gfa(&mut enc).unwrap();
+    let gzip = enc.finish().unwrap();
+    assert!(is_gzip(&gzip));
+    assert_eq!(decompress_mem(&gzip).unwrap(), TEST_GFA);
+
+    // Parsing the compressed text gets the same graph back.
+    let again = crate::parse::Parser::for_heap()
+        .parse_stream(&gzip[..])
+        .unwrap();
+    assert!(store.as_ref().content_eq(&again.as_ref()));
+
+    assert!(is_gzip_name("out.gfa.gz") && !is_gzip_name("out.gfa"));
+}
+
+#[test]
+fn test_index() {
+    // Small blocks make lines (and even segment names) span blocks.
+    for block_len in [1, 7, 64, 1024] {
+        let mut gfa = IndexedGfa::new(std::io::Cursor::new(bgzf(TEST_GFA, block_len))).unwrap();
+        assert_eq!(gfa.index().len(), 3);
+        assert_eq!(
+            gfa.segment_seq(b"2").unwrap().unwrap(),
+            b"AAATTTTCTGGAGTTCTAT"
+        );
+        assert_eq!(gfa.segment_line(b"3").unwrap().unwrap(), b"S\t3\tTTG");
+        assert_eq!(gfa.segment_seq(b"1").unwrap().unwrap(), b"CAAATAAG");
+        assert!(gfa.segment_seq(b"4").unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_block_too_large() {
+    // Claim that the first block decompresses to more than a block can hold.
+    let mut data = bgzf(TEST_GFA, 1024);
+    let size = u16::from_le_bytes([data[16], data[17]]) as usize + 1;
+    for isize in [MAX_BLOCK_LEN as u32 + 1, u32::MAX] {
+        data[size - 4..size].copy_from_slice(&isize.to_le_bytes());
+        let err = IndexedGfa::new(std::io::Cursor::new(&data)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "BGZF block is too large");
+    }
+}
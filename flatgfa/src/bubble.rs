@@ -0,0 +1,512 @@
+use crate::adjacency::AdjacencyIndex;
+use crate::flatgfa::Handle;
+
+impl AdjacencyIndex {
+    /// Find the superbubbles in the graph, as (entrance, exit) pairs of handles.
+    ///
+    /// A superbubble is an acyclic subgraph where every walk from the entrance reaches
+    /// the exit without leaving the subgraph, and where no handle between the two
+    /// could serve as the exit instead. (See Onodera et al., "Detecting superbubbles
+    /// in assembly graphs," 2013.) Each bubble appears twice in the graph of handles,
+    /// once per strand, and we report only one of the two. We also skip trivial
+    /// bubbles, where the entrance leads straight to the exit.
+    ///
+    /// This takes linear time. Following Sung et al. ("An O(m log m)-time algorithm
+    /// for detecting superbubbles," 2015) and Brankovic et al. ("Linear-time
+    /// superbubble identification algorithm for genome assembly," 2016), we unroll
+    /// the graph's cycles into a DAG and then find the bubbles in a single pass over a
+    /// topological order of the DAG. As Gärtner et al. point out ("Superbubbles
+    /// revisited," 2018), the unrolling misses the bubbles that contain the root of
+    /// its depth-first search, so we unroll twice, from two different roots.
+    pub fn superbubbles(&self) -> Vec<(Handle, Handle)> {
+        // Search from one handle in each strongly connected component that no edge
+        // enters. A handle in any other component is first reached by an edge from
+        // outside, so it cannot be inside a bubble.
+        let (components, count) = strong_components(self);
+        let mut entered = vec![false; count];
+        for handle in (0..self.handle_count()).map(Handle::from_index) {
+            for next in self.next(handle) {
+                if components[next.index()] != components[handle.index()] {
+                    entered[components[next.index()]] = true;
+                }
+            }
+        }
+        let mut roots = vec![];
+        for handle in (0..self.handle_count()).map(Handle::from_index) {
+            let component = components[handle.index()];
+            if !entered[component] {
+                entered[component] = true;
+                roots.push(handle);
+            }
+        }
+        let first = Unrolled::build(self, &roots);
+
+        // The first handle in a root's component to finish in that search cannot be
+        // inside any bubble that contains the root, so searching again from there
+        // finds the bubbles that the first search missed.
+        let mut finished = vec![None; count];
+        for &handle in &first.finished {
+            finished[components[handle.index()]].get_or_insert(handle);
+        }
+        let roots: Vec<_> = roots
+            .iter()
+            .map(|root| finished[components[root.index()]].expect("root finished"))
+            .collect();
+        let second = Unrolled::build(self, &roots);
+
+        let mut bubbles: Vec<_> = [first, second]
+            .iter()
+            .flat_map(|dag| {
+                dag.superbubbles()
+                    .into_iter()
+                    .filter_map(|(entrance, exit)| Some((dag.handle(entrance)?, dag.handle(exit)?)))
+            })
+            .filter(|&(entrance, exit)| {
+                // The unrolling can turn a cycle through the entrance into a bubble.
+                if entrance == exit || self.next(exit).any(|h| h == entrance) {
+                    return false;
+                }
+
+                // Skip trivial bubbles and the other strand's copy of each bubble.
+                let trivial = self.next(entrance).all(|h| h == exit);
+                let mirror = (exit.flip().index(), entrance.flip().index());
+                !trivial && (entrance.index(), exit.index()) <= mirror
+            })
+            .collect();
+
+        // Each bubble can appear in both searches and in both copies of the graph.
+        bubbles.sort_by_key(|&(entrance, exit)| (entrance.index(), exit.index()));
+        bubbles.dedup();
+        bubbles
+    }
+}
+
+/// Label each handle with its strongly connected component, using Tarjan's
+/// algorithm. Also return the number of components.
+fn strong_components(index: &AdjacencyIndex) -> (Vec<usize>, usize) {
+    const UNSEEN: usize = usize::MAX;
+    let handles = index.handle_count();
+    let mut order = vec![UNSEEN; handles];
+    let mut low = vec![0; handles];
+    let mut components = vec![UNSEEN; handles];
+    let mut count = 0;
+    let mut visited = 0;
+    let mut active = vec![];
+    for root in (0..handles).map(Handle::from_index) {
+        if order[root.index()] != UNSEEN {
+            continue;
+        }
+        order[root.index()] = visited;
+        low[root.index()] = visited;
+        visited += 1;
+        active.push(root.index());
+        let mut stack = vec![(root, index.next(root))];
+        while let Some((handle, children)) = stack.last_mut() {
+            let idx = handle.index();
+            if let Some(child) = children.next() {
+                let child_idx = child.index();
+                if order[child_idx] == UNSEEN {
+                    order[child_idx] = visited;
+                    low[child_idx] = visited;
+                    visited += 1;
+                    active.push(child_idx);
+                    stack.push((child, index.next(child)));
+                } else if components[child_idx] == UNSEEN {
+                    low[idx] = low[idx].min(order[child_idx]);
+                }
+                continue;
+            }
+
+            // Finish this handle, and close its component if it is the first one
+            // we reached there.
+            stack.pop();
+            if let Some((parent, _)) = stack.last() {
+                low[parent.index()] = low[parent.index()].min(low[idx]);
+            }
+            if low[idx] == order[idx] {
+                while let Some(member) = active.pop() {
+                    components[member] = count;
+                    if member == idx {
+                        break;
+                    }
+                }
+                count += 1;
+            }
+        }
+    }
+    (components, count)
+}
+
+/// A DAG that "unrolls" the cycles in the graph of handles.
+///
+/// We number the DAG's vertices densely. There is a source, a sink, and two copies of
+/// each handle. A depth-first search of the handle graph sorts its edges into back
+/// edges, which close cycles, and the rest, which form a DAG. The DAG edges connect
+/// handles within each copy, while a back edge leads from the first copy to the
+/// second, so the second copy holds what follows a trip around a cycle. A back edge
+/// from the second copy would need a third copy, so it leads to the sink instead,
+/// and likewise the source leads to the first copy of its target. Finally, the
+/// source leads to each vertex with no other parents and each vertex with no other
+/// children leads to the sink.
+///
+/// A superbubble in the graph that does not contain a root of the search is a
+/// superbubble between copies of its entrance and exit in the DAG. The DAG can also
+/// have superbubbles that map to a cycle through the entrance, which we discard.
+struct Unrolled {
+    handles: usize,
+    finished: Vec<Handle>,
+    offsets: Vec<usize>,
+    children: Vec<usize>,
+}
+
+impl Unrolled {
+    const SOURCE: usize = 0;
+    const SINK: usize = 1;
+
+    fn build(index: &AdjacencyIndex, roots: &[Handle]) -> Self {
+        let handles = index.handle_count();
+        let first = |handle: Handle| 2 + handle.index();
+        let second = |handle: Handle| 2 + handles + handle.index();
+
+        // Sort the edges with an iterative depth-first search from the roots, which
+        // reach every handle.
+        const UNSEEN: u8 = 0;
+        const ACTIVE: u8 = 1;
+        const DONE: u8 = 2;
+        let mut state = vec![UNSEEN; handles];
+        let mut edges = vec![];
+        let mut finished = Vec::with_capacity(handles);
+        for &root in roots {
+            if state[root.index()] != UNSEEN {
+                continue;
+            }
+            edges.push((Self::SOURCE, first(root)));
+            state[root.index()] = ACTIVE;
+            let mut stack = vec![(root, index.next(root))];
+            while let Some((handle, children)) = stack.last_mut() {
+                let handle = *handle;
+                let Some(child) = children.next() else {
+                    state[handle.index()] = DONE;
+                    finished.push(handle);
+                    stack.pop();
+                    continue;
+                };
+                if state[child.index()] == ACTIVE {
+                    edges.push((Self::SOURCE, first(child)));
+                    edges.push((first(handle), second(child)));
+                    edges.push((second(handle), Self::SINK));
+                } else {
+                    edges.push((first(handle), first(child)));
+                    edges.push((second(handle), second(child)));
+                    if state[child.index()] == UNSEEN {
+                        state[child.index()] = ACTIVE;
+                        stack.push((child, index.next(child)));
+                    }
+                }
+            }
+        }
+
+        // Connect the source and the sink.
+        let size = 2 + 2 * handles;
+        let mut has_parent = vec![false; size];
+        let mut has_child = vec![false; size];
+        for &(from, to) in &edges {
+            has_child[from] = true;
+            has_parent[to] = true;
+        }
+        for vertex in 2..size {
+            if !has_parent[vertex] {
+                edges.push((Self::SOURCE, vertex));
+            }
+            if !has_child[vertex] {
+                edges.push((vertex, Self::SINK));
+            }
+        }
+
+        // Lay out the edges in CSR style, like `AdjacencyIndex`.
+        let mut offsets = vec![0; size + 1];
+        for &(from, _) in &edges {
+            offsets[from + 1] += 1;
+        }
+        for vertex in 0..size {
+            offsets[vertex + 1] += offsets[vertex];
+        }
+        let mut fill = offsets.clone();
+        let mut children = vec![0; edges.len()];
+        for &(from, to) in &edges {
+            children[fill[from]] = to;
+            fill[from] += 1;
+        }
+
+        Self {
+            handles,
+            finished,
+            offsets,
+            children,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    fn children(&self, vertex: usize) -> &[usize] {
+        &self.children[self.offsets[vertex]..self.offsets[vertex + 1]]
+    }
+
+    /// Get the handle that a vertex is a copy of, or `None` for the source and sink.
+    fn handle(&self, vertex: usize) -> Option<Handle> {
+        (vertex >= 2).then(|| Handle::from_index((vertex - 2) % self.handles))
+    }
+
+    /// Sort the vertices in reverse postorder, starting from the source.
+    ///
+    /// Every vertex is reachable from the source. In this order, the vertices of each
+    /// superbubble are contiguous: a search that reaches any vertex in the bubble
+    /// must come through the entrance, and it visits everything in the bubble before
+    /// it finishes the entrance.
+    fn order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.size());
+        let mut seen = vec![false; self.size()];
+        seen[Self::SOURCE] = true;
+        let mut stack = vec![(Self::SOURCE, 0)];
+        while let Some((vertex, next)) = stack.last_mut() {
+            let vertex = *vertex;
+            match self.children(vertex).get(*next) {
+                Some(&child) => {
+                    *next += 1;
+                    if !seen[child] {
+                        seen[child] = true;
+                        stack.push((child, 0));
+                    }
+                }
+                None => {
+                    order.push(vertex);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Find the superbubbles in the DAG, as (entrance, exit) pairs of vertices.
+    ///
+    /// In a topological order where bubbles are contiguous, the vertices from
+    /// position `s` to position `t` form a bubble iff every child of a vertex in
+    /// `s..t` is at or before `t` and every parent of a vertex in `s+1..=t` is at or
+    /// after `s`. (The source and sink guarantee that these vertices have children and
+    /// parents, respectively.) The exit for `s` is the first such `t`.
+    ///
+    /// We scan the order backward. For each position `i`, the positions `t` that
+    /// satisfy the condition on children for `s = i` form a chain: `i + 1`, then the
+    /// exit for `i + 1`, then the exit for that position, and so on. We keep the chain
+    /// on a stack and pop the positions before the last child of `i`. The intervals
+    /// we pop from the chain are the ones that `i`'s interval covers, so we can also
+    /// track the first parent of any vertex in the interval.
+    fn superbubbles(&self) -> Vec<(usize, usize)> {
+        let order = self.order();
+        let mut position = vec![0; self.size()];
+        for (pos, &vertex) in order.iter().enumerate() {
+            position[vertex] = pos;
+        }
+        let mut last_child = vec![0; self.size()];
+        let mut first_parent = vec![usize::MAX; self.size()];
+        for (pos, &vertex) in order.iter().enumerate() {
+            for &child in self.children(vertex) {
+                let child_pos = position[child];
+                last_child[pos] = last_child[pos].max(child_pos);
+                first_parent[child_pos] = first_parent[child_pos].min(pos);
+            }
+        }
+
+        // The chain, as (position, first parent in the position's interval) pairs.
+        let mut chain: Vec<(usize, usize)> = vec![];
+        let mut bubbles = vec![];
+        for pos in (0..order.len()).rev() {
+            let mut parent = first_parent.get(pos + 1).copied().unwrap_or(usize::MAX);
+            while let Some(&(next, next_parent)) = chain.last() {
+                if next >= last_child[pos] {
+                    break;
+                }
+                parent = parent.min(next_parent);
+                chain.pop();
+            }
+            if let Some(&(exit, _)) = chain.last() {
+                if parent >= pos {
+                    bubbles.push((order[pos], order[exit]));
+                }
+            }
+            chain.push((pos, parent));
+        }
+        bubbles
+    }
+}
+
+/// The state for searching for superbubbles from each candidate entrance in turn,
+/// using Onodera et al.'s quadratic-time algorithm. We check the linear-time
+/// algorithm against this search.
+///
+/// To avoid clearing the per-handle marks for every search, each mark records the
+/// search that set it: a handle is marked iff its mark equals the current stamp.
+#[cfg(test)]
+struct BubbleSearch<'a> {
+    index: &'a AdjacencyIndex,
+    stamp: usize,
+    visited: Vec<usize>,
+    seen: Vec<usize>,
+    queued: Vec<usize>,
+    stack: Vec<Handle>,
+}
+
+#[cfg(test)]
+impl<'a> BubbleSearch<'a> {
+    fn new(index: &'a AdjacencyIndex) -> Self {
+        let count = index.handle_count();
+        Self {
+            index,
+            stamp: 0,
+            visited: vec![0; count],
+            seen: vec![0; count],
+            queued: vec![0; count],
+            stack: vec![],
+        }
+    }
+
+    /// Find the exit of the superbubble with a given entrance, if there is one.
+    ///
+    /// This is Onodera et al.'s algorithm. We visit handles once all their
+    /// predecessors have been visited, keeping track of the "seen" handles that are
+    /// reachable but not yet visited. When there is only one, it is the exit.
+    fn exit(&mut self, entrance: Handle) -> Option<Handle> {
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let mut seen_count = 0;
+        self.stack.clear();
+        self.stack.push(entrance);
+        self.queued[entrance.index()] = stamp;
+
+        while let Some(handle) = self.stack.pop() {
+            self.visited[handle.index()] = stamp;
+            if self.seen[handle.index()] == stamp {
+                self.seen[handle.index()] = 0;
+                seen_count -= 1;
+            }
+
+            // A tip means some walk from the entrance never reaches an exit.
+            let mut children = self.index.next(handle).peekable();
+            children.peek()?;
+
+            for child in children {
+                // A cycle back to the entrance.
+                if child == entrance {
+                    return None;
+                }
+                if self.seen[child.index()] != stamp {
+                    self.seen[child.index()] = stamp;
+                    seen_count += 1;
+                }
+                if self.queued[child.index()] != stamp
+                    && self
+                        .index
+                        .prev(child)
+                        .all(|parent| self.visited[parent.index()] == stamp)
+                {
+                    self.queued[child.index()] = stamp;
+                    self.stack.push(child);
+                }
+            }
+
+            if let ([exit], 1) = (&self.stack[..], seen_count) {
+                let exit = *exit;
+                return if self.index.next(exit).any(|h| h == entrance) {
+                    None
+                } else {
+                    Some(exit)
+                };
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_superbubbles() {
+    use crate::flatgfa::Orientation;
+    use crate::pool::Id;
+
+    // A two-allele bubble, 1 -> {2, 3} -> 4, where one allele is reversed. Then
+    // not a bubble: 4 -> {5, 6} -> 7, where 6 is a tip.
+    let text = b"S\t1\tA\nS\t2\tA\nS\t3\tA\nS\t4\tA\nS\t5\tA\nS\t6\tA\nS\t7\tA\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t-\t0M\nL\t2\t+\t4\t+\t0M\nL\t3\t-\t4\t+\t0M\n\
+        L\t4\t+\t5\t+\t0M\nL\t4\t+\t6\t+\t0M\nL\t5\t+\t7\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let fwd = |i: usize| Handle::new(Id::new(i), Orientation::Forward);
+    let gfa = store.as_ref();
+    assert_eq!(gfa.superbubbles(), vec![(fwd(0), fwd(3))]);
+}
+
+#[test]
+fn test_superbubbles_in_cycle() {
+    use crate::flatgfa::Orientation;
+    use crate::pool::Id;
+
+    // A circular graph with two bubbles, 1 -> {2, 3} -> 4 -> {5, 6} -> 1, so every
+    // segment is inside a bubble.
+    let text = b"S\t1\tA\nS\t2\tA\nS\t3\tA\nS\t4\tA\nS\t5\tA\nS\t6\tA\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+        L\t4\t+\t5\t+\t0M\nL\t4\t+\t6\t+\t0M\nL\t5\t+\t1\t+\t0M\nL\t6\t+\t1\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let fwd = |i: usize| Handle::new(Id::new(i), Orientation::Forward);
+    let gfa = store.as_ref();
+    assert_eq!(
+        gfa.superbubbles(),
+        vec![(fwd(0), fwd(3)), (fwd(0).flip(), fwd(3).flip())]
+    );
+}
+
+#[test]
+fn test_superbubbles_random() {
+    use crate::flatgfa::Orientation;
+
+    // Compare with Onodera et al.'s algorithm on small random graphs.
+    let mut state: u64 = 17;
+    let mut rand = move |n: usize| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((state >> 33) as usize) % n.max(1)
+    };
+    let orient = |n: usize| {
+        if n == 0 {
+            Orientation::Forward
+        } else {
+            Orientation::Backward
+        }
+    };
+
+    for _ in 0..5_000 {
+        let mut store = crate::HeapGFAStore::default();
+        let segs: Vec<_> = (0..1 + rand(8))
+            .map(|n| store.add_seg(n, b"A", &[]))
+            .collect();
+        for _ in 0..rand(3 * segs.len()) {
+            let from = Handle::new(segs[rand(segs.len())], orient(rand(4)));
+            let to = Handle::new(segs[rand(segs.len())], orient(rand(4)));
+            store.add_link(from, to, vec![], &[]);
+        }
+
+        let index = AdjacencyIndex::build(&store.as_ref());
+        let mut search = BubbleSearch::new(&index);
+        let expected: Vec<_> = (0..index.handle_count())
+            .filter_map(|idx| {
+                let entrance = Handle::from_index(idx);
+                let exit = search.exit(entrance)?;
+                let trivial = index.next(entrance).all(|h| h == exit);
+                let mirror = (exit.flip().index(), entrance.flip().index());
+                (!trivial && (entrance.index(), exit.index()) <= mirror).then_some((entrance, exit))
+            })
+            .collect();
+        assert_eq!(index.superbubbles(), expected);
+    }
+}
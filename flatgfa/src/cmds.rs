@@ -1,10 +1,11 @@
-use crate::flatgfa::{self, Handle, Link, Orientation, Path, Segment};
+use crate::flatgfa::{self, Handle, Link, Orientation, OverlapSpelling, Path, SegName, Segment};
 use crate::memfile;
-use crate::pool::{self, Id, Span, Store};
+use crate::pool::{self, CompactSpan, Id, Span, Store};
 use crate::{GFAStore, HeapFamily};
 use argh::FromArgs;
 use rayon::iter::ParallelIterator;
 use std::collections::{HashMap, HashSet};
+use std::io::Write;
 
 /// print the FlatGFA table of contents
 #[derive(FromArgs, PartialEq, Debug)]
@@ -14,14 +15,20 @@ pub struct Toc {}
 pub fn toc(gfa: &flatgfa::FlatGFA) {
     eprintln!("header: {}", gfa.header.len());
     eprintln!("segs: {}", gfa.segs.len());
+    eprintln!("rgfa: {}", gfa.rgfa.len());
     eprintln!("paths: {}", gfa.paths.len());
+    eprintln!("walks: {}", gfa.walks.len());
     eprintln!("links: {}", gfa.links.len());
+    eprintln!("jumps: {}", gfa.jumps.len());
+    eprintln!("containments: {}", gfa.containments.len());
     eprintln!("steps: {}", gfa.steps.len());
     eprintln!("seq_data: {}", gfa.seq_data.len());
     eprintln!("overlaps: {}", gfa.overlaps.len());
     eprintln!("alignment: {}", gfa.alignment.len());
     eprintln!("name_data: {}", gfa.name_data.len());
     eprintln!("optional_data: {}", gfa.optional_data.len());
+    eprintln!("tags: {}", gfa.tags.len());
+    eprintln!("other_lines: {}", gfa.other_lines.len());
     eprintln!("line_order: {}", gfa.line_order.len());
 }
 
@@ -30,10 +37,14 @@ pub fn toc(gfa: &flatgfa::FlatGFA) {
 #[argh(subcommand, name = "paths")]
 pub struct Paths {}
 
-pub fn paths(gfa: &flatgfa::FlatGFA) {
+pub fn paths(gfa: &flatgfa::FlatGFA) -> std::io::Result<()> {
+    // Path names need not be UTF-8, so we write their exact bytes.
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
     for path in gfa.paths.all().iter() {
-        println!("{}", gfa.get_path_name(path));
+        out.write_all(gfa.get_path_name(path))?;
+        out.write_all(b"\n")?;
     }
+    out.flush()
 }
 
 /// calculate graph statistics
@@ -63,12 +74,10 @@ pub fn stats(gfa: &flatgfa::FlatGFA, args: Stats) {
     } else if args.self_loops {
         let mut counts: HashMap<Id<Segment>, usize> = HashMap::new();
         let mut total: usize = 0;
-        for link in gfa.links.all().iter() {
-            if link.from.segment() == link.to.segment() {
-                let count = counts.entry(link.from.segment()).or_insert(0);
-                *count += 1;
-                total += 1;
-            }
+        for id in gfa.self_loops() {
+            let count = counts.entry(gfa.links[id].from.segment()).or_insert(0);
+            *count += 1;
+            total += 1;
         }
         println!("#type\tnum");
         println!("total\t{}", total);
@@ -110,7 +119,7 @@ pub fn position(gfa: &flatgfa::FlatGFA, args: Position) -> Result<(), &'static s
     let mut found = None;
     for step in &gfa.steps[path.steps] {
         let seg = gfa.get_handle_seg(*step);
-        let end_pos = cur_pos + seg.len();
+        let end_pos = cur_pos + gfa.seg_len(seg);
         if offset < end_pos {
             // Found it!
             found = Some((*step, offset - cur_pos));
@@ -122,7 +131,7 @@ pub fn position(gfa: &flatgfa::FlatGFA, args: Position) -> Result<(), &'static s
     // Print the match.
     if let Some((handle, seg_off)) = found {
         let seg = gfa.get_handle_seg(handle);
-        let seg_name = seg.name;
+        let seg_name = gfa.get_seg_name(seg);
         println!("#source.path.pos\ttarget.graph.pos");
         println!(
             "{},{},{}\t{},{},{}",
@@ -146,12 +155,16 @@ pub struct Bench {
     #[argh(option)]
     wcl: Option<String>,
 
+    /// compute every segment's base counts and path coverage
+    #[argh(switch)]
+    seg_stats: bool,
+
     /// enable parallelism when available
     #[argh(switch, short = 'p')]
     parallel: bool,
 }
 
-pub fn bench(args: Bench) {
+pub fn bench(gfa: &flatgfa::FlatGFA, args: Bench) {
     // TODO: We don't need a GFA for (some of) these? So avoid opening it.
     if let Some(filename) = args.wcl {
         let buf = memfile::map_file(&filename);
@@ -163,6 +176,15 @@ pub fn bench(args: Bench) {
         };
         println!("{}", count);
     }
+    if args.seg_stats {
+        let (counts, coverage) = if args.parallel {
+            (gfa.base_counts_parallel(), gfa.coverage_parallel())
+        } else {
+            (gfa.segment_base_counts(), gfa.coverage())
+        };
+        let bases: u64 = counts.iter().flatten().sum();
+        println!("{}\t{}", bases, coverage.iter().sum::<u64>());
+    }
 }
 
 /// create a subset graph
@@ -171,7 +193,7 @@ pub fn bench(args: Bench) {
 pub struct Extract {
     /// segment to extract around
     #[argh(option, short = 'n')]
-    seg_name: usize,
+    seg_name: String,
 
     /// number of edges "away" from the node to include
     #[argh(option, short = 'c')]
@@ -195,7 +217,9 @@ pub fn extract(
     gfa: &flatgfa::FlatGFA,
     args: Extract,
 ) -> Result<flatgfa::HeapGFAStore, &'static str> {
-    let origin_seg = gfa.find_seg(args.seg_name).ok_or("segment not found")?;
+    let origin_seg = gfa
+        .find_named_seg(SegName::parse(args.seg_name.as_bytes()))
+        .ok_or("segment not found")?;
 
     let mut subgraph = SubgraphBuilder::new(gfa);
     subgraph.add_header();
@@ -222,9 +246,13 @@ struct SubpathStart {
 
 impl<'a> SubgraphBuilder<'a> {
     fn new(old: &'a flatgfa::FlatGFA) -> Self {
+        let store = flatgfa::HeapGFAStore {
+            sequences_skipped: old.sequences_skipped,
+            ..Default::default()
+        };
         Self {
             old,
-            store: flatgfa::HeapGFAStore::default(),
+            store,
             seg_map: HashMap::new(),
         }
     }
@@ -242,8 +270,8 @@ impl<'a> SubgraphBuilder<'a> {
     /// Add a segment from the source graph to this subgraph.
     fn include_seg(&mut self, seg_id: Id<Segment>) {
         let seg = &self.old.segs[seg_id];
-        let new_seg_id = self.store.add_seg(
-            seg.name,
+        let new_seg_id = self.store.add_named_seg(
+            self.old.get_seg_name(seg),
             self.old.get_seq(seg),
             self.old.get_optional_data(seg),
         );
@@ -255,7 +283,13 @@ impl<'a> SubgraphBuilder<'a> {
         let from = self.tr_handle(link.from);
         let to = self.tr_handle(link.to);
         let overlap = self.old.get_alignment(link.overlap);
-        self.store.add_link(from, to, overlap.ops.into());
+        let id = self.store.add_link(
+            from,
+            to,
+            overlap.ops.iter().copied(),
+            self.old.get_link_optional_data(link),
+        );
+        self.store.links.get_mut(id).overlap_spelling = link.overlap_spelling;
     }
 
     /// Add a single subpath from the given path to the subgraph.
@@ -263,7 +297,7 @@ impl<'a> SubgraphBuilder<'a> {
         let steps = pool::Span::new(start.step, self.store.steps.next_id()); // why the next id?
         let name = format!("{}:{}-{}", self.old.get_path_name(path), start.pos, end_pos);
         self.store
-            .add_path(name.as_bytes(), steps, std::iter::empty());
+            .add_path(name.as_bytes(), steps, std::iter::empty(), &[]);
     }
 
     /// Identify all the subpaths in a path from the original graph that cross through
@@ -428,7 +462,7 @@ pub fn depth(gfa: &flatgfa::FlatGFA) {
     // print out depth and depth.uniq
     println!("#node.id\tdepth\tdepth.uniq");
     for (id, seg) in gfa.segs.items() {
-        let name: u32 = seg.name as u32;
+        let name = gfa.get_seg_name(seg);
         println!(
             "{}\t{}\t{}",
             name,
@@ -470,11 +504,14 @@ pub fn chop<'a>(
 
     fn link_forward(flat: &mut GFAStore<'static, HeapFamily>, span: &Span<Segment>) {
         // Link segments spanned by `span` from head to tail
-        let overlap = Span::new_empty();
+        let overlap = CompactSpan::new_empty();
         flat.add_links((span.start.index()..span.end.index() - 1).map(|idx| Link {
             from: Handle::new(Id::new(idx), Orientation::Forward),
             to: Handle::new(Id::new(idx + 1), Orientation::Forward),
             overlap,
+            overlap_spelling: OverlapSpelling::Default.into(),
+            optional: Span::new_empty(),
+            tags: Span::new_empty(),
         }));
     }
 
@@ -487,6 +524,8 @@ pub fn chop<'a>(
                 name: max_node_id,
                 seq: seg.seq,
                 optional: Span::new_empty(), // TODO: Optional data may stay valid when seg not chopped?
+                tags: Span::new_empty(),
+                text_name: Span::new_empty(),
             });
             max_node_id += 1;
             seg_map.push(Span::new(id, flat.segs.next_id()));
@@ -503,6 +542,8 @@ pub fn chop<'a>(
                     name: max_node_id,
                     seq: Span::new(Id::new(offset), Id::new(offset + args.c)),
                     optional: Span::new_empty(),
+                    tags: Span::new_empty(),
+                    text_name: Span::new_empty(),
                 });
                 offset += args.c;
                 max_node_id += 1;
@@ -512,6 +553,8 @@ pub fn chop<'a>(
                 name: max_node_id,
                 seq: Span::new(Id::new(offset), seq_end),
                 optional: Span::new_empty(),
+                tags: Span::new_empty(),
+                text_name: Span::new_empty(),
             });
             max_node_id += 1;
             let new_seg_span = Span::new(segs_start, flat.segs.next_id());
@@ -557,6 +600,9 @@ pub fn chop<'a>(
             name: path.name,
             steps: Span::new(path_start, path_end),
             overlaps: Span::new_empty(),
+            overlap_spelling: path.overlap_spelling,
+            optional: Span::new_empty(),
+            tags: Span::new_empty(),
         });
     }
 
@@ -586,7 +632,7 @@ pub fn chop<'a>(
                 };
                 Handle::new(seg_id, old_to.orient())
             };
-            flat.add_link(new_from, new_to, vec![]);
+            flat.add_link(new_from, new_to, vec![], &[]);
         }
     }
 
@@ -0,0 +1,136 @@
+use crate::adjacency::AdjacencyIndex;
+use crate::flatgfa::{Handle, Orientation};
+use crate::pool::Id;
+
+/// Summary measures of a graph's complexity, for comparing graphs built in different
+/// ways. See `AdjacencyIndex::complexity`.
+///
+/// Only links count as edges here: paths, jumps, and containments do not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplexityReport {
+    pub segments: usize,
+    pub links: usize,
+
+    /// The average number of link ends at a segment: twice the number of links per
+    /// segment. A self-loop counts twice.
+    pub mean_degree: f64,
+
+    /// The fraction of segments with links to more than one distinct neighbor from
+    /// the same side (that is, from their start or from their end).
+    pub branching_fraction: f64,
+
+    /// The number of connected components. See `AdjacencyIndex::components`.
+    pub components: usize,
+
+    /// The cycle rank (or cyclomatic number): links, minus segments, plus components.
+    /// This is the number of independent cycles when we ignore the links' directions,
+    /// so it is 0 exactly when the graph is a forest.
+    pub cycle_rank: usize,
+
+    /// Whether the links never lead in a cycle. See
+    /// `AdjacencyIndex::topological_order`.
+    pub is_dag: bool,
+}
+
+impl AdjacencyIndex {
+    /// Measure how tangled the graph is: how many links there are per segment, how
+    /// often the graph branches, how many separate pieces it has, and how many cycles.
+    pub fn complexity(&self) -> ComplexityReport {
+        let segments = self.handle_count() / 2;
+        let link_ends: usize = (0..self.handle_count())
+            .map(|idx| self.next(Handle::from_index(idx)).count())
+            .sum();
+        let links = link_ends / 2;
+
+        let branching = (0..segments)
+            .filter(|&idx| {
+                [Orientation::Forward, Orientation::Backward]
+                    .into_iter()
+                    .any(|orient| {
+                        let mut next = self.next(Handle::new(Id::new(idx), orient));
+                        let first = next.next();
+                        next.any(|handle| Some(handle) != first)
+                    })
+            })
+            .count();
+
+        let components = self
+            .components()
+            .into_iter()
+            .max()
+            .map_or(0, |label| label + 1);
+
+        let per_segment = |count: usize| {
+            if segments == 0 {
+                0.0
+            } else {
+                count as f64 / segments as f64
+            }
+        };
+        ComplexityReport {
+            segments,
+            links,
+            mean_degree: per_segment(link_ends),
+            branching_fraction: per_segment(branching),
+            components,
+            cycle_rank: links + components - segments,
+            is_dag: self.topological_order().is_some(),
+        }
+    }
+}
+
+#[test]
+fn test_complexity() {
+    // A bubble (1 to 4, through 2 or 3), an isolated segment 5, and a cycle between
+    // 6 and 7.
+    let text = b"S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\nS\t5\tA\nS\t6\tC\nS\t7\tG\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+        L\t6\t+\t7\t+\t0M\nL\t7\t+\t6\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let index = AdjacencyIndex::build(&gfa);
+    assert_eq!(index.components(), vec![0, 0, 0, 0, 1, 2, 2]);
+    assert_eq!(index.topological_order(), None);
+    assert_eq!(
+        gfa.complexity(),
+        ComplexityReport {
+            segments: 7,
+            links: 6,
+            mean_degree: 12.0 / 7.0,
+            branching_fraction: 2.0 / 7.0,
+            components: 3,
+            cycle_rank: 2,
+            is_dag: false,
+        }
+    );
+
+    // Without the cycle, it's a DAG. Each link goes forward in the order on both
+    // strands.
+    let text = &text[..text.len() - "L\t7\t+\t6\t+\t0M\n".len()];
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let order = AdjacencyIndex::build(&gfa).topological_order().unwrap();
+    assert_eq!(order.len(), 14);
+    let rank = |handle: Handle| order.iter().position(|&h| h == handle).unwrap();
+    for (from, to) in gfa.link_handles() {
+        assert!(rank(from) < rank(to));
+        assert!(rank(to.flip()) < rank(from.flip()));
+    }
+    let report = gfa.complexity();
+    assert_eq!((report.cycle_rank, report.is_dag), (1, true));
+
+    // A hairpin is not a cycle, but a self-loop is.
+    let hairpin = b"S\t1\tA\nL\t1\t+\t1\t-\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(hairpin).unwrap();
+    assert!(store.as_ref().complexity().is_dag);
+    let self_loop = b"S\t1\tA\nL\t1\t+\t1\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap()
+        .parse_mem(self_loop)
+        .unwrap();
+    let report = store.as_ref().complexity();
+    assert_eq!((report.mean_degree, report.is_dag), (2.0, false));
+
+    // An empty graph has no NaNs.
+    let report = crate::HeapGFAStore::default().as_ref().complexity();
+    assert_eq!((report.mean_degree, report.components), (0.0, 0));
+}
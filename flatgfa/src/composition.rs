@@ -0,0 +1,321 @@
+use crate::flatgfa::{FlatGFA, Handle, Path, Segment};
+use crate::pool::Id;
+use rayon::prelude::*;
+
+/// Counts of the nucleotides in a sequence: A, C, G, T, and anything else (e.g., N).
+pub type BaseCounts = [u64; 5];
+
+/// Count the bases in a sequence, ignoring case.
+pub fn count_bases(seq: &[u8]) -> BaseCounts {
+    let mut counts = [0; 5];
+    for &c in seq {
+        let idx = match c.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => 4,
+        };
+        counts[idx] += 1;
+    }
+    counts
+}
+
+/// Compute the fraction of G and C among the A, C, G, and T bases.
+///
+/// Other characters (like N) do not count toward the total. When there are no
+/// A/C/G/T bases at all, the result is 0 (not NaN).
+pub fn gc_fraction(counts: &BaseCounts) -> f64 {
+    let acgt: u64 = counts[..4].iter().sum();
+    if acgt == 0 {
+        0.0
+    } else {
+        (counts[1] + counts[2]) as f64 / acgt as f64
+    }
+}
+
+/// Compute the N50 and L50 of some sequence lengths.
+///
+/// Sorting from longest to shortest, the L50 is the smallest number of sequences whose
+/// lengths sum to at least half the total, and the N50 is the length of the last one
+/// (i.e., the shortest) of those. Both are 0 when there are no sequences.
+pub fn n50(mut lengths: Vec<u64>) -> (u64, usize) {
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let total: u64 = lengths.iter().sum();
+    let mut sum = 0;
+    for (idx, &len) in lengths.iter().enumerate() {
+        sum += len;
+        if sum * 2 >= total {
+            return (len, idx + 1);
+        }
+    }
+    (0, 0)
+}
+
+impl<'a> FlatGFA<'a> {
+    /// Count the bases in a segment's sequence.
+    pub fn base_counts(&self, seg: &Segment) -> BaseCounts {
+        count_bases(self.get_seq(seg))
+    }
+
+    /// Get the GC content of a segment's sequence. See `gc_fraction`.
+    pub fn gc_content(&self, seg: &Segment) -> f64 {
+        gc_fraction(&self.base_counts(seg))
+    }
+
+    /// Count the bases in all the segments in the graph.
+    pub fn total_base_counts(&self) -> BaseCounts {
+        self.segs
+            .all()
+            .par_iter()
+            .map(|seg| self.base_counts(seg))
+            .reduce(
+                || [0; 5],
+                |mut a, b| {
+                    for (x, y) in a.iter_mut().zip(b) {
+                        *x += y;
+                    }
+                    a
+                },
+            )
+    }
+
+    /// Count the bases in each segment, in segment ID order.
+    pub fn segment_base_counts(&self) -> Vec<BaseCounts> {
+        self.segs
+            .all()
+            .iter()
+            .map(|seg| self.base_counts(seg))
+            .collect()
+    }
+
+    /// Like `segment_base_counts`, but count the segments in parallel.
+    pub fn base_counts_parallel(&self) -> Vec<BaseCounts> {
+        self.segs
+            .all()
+            .par_iter()
+            .map(|seg| self.base_counts(seg))
+            .collect()
+    }
+
+    /// Count the path steps that visit each segment, in segment ID order.
+    pub fn coverage(&self) -> Vec<u64> {
+        let mut coverage = vec![0; self.segs.len()];
+        for path in self.paths.all() {
+            self.add_coverage(&mut coverage, path);
+        }
+        coverage
+    }
+
+    /// Like `coverage`, but count the paths' steps in parallel.
+    pub fn coverage_parallel(&self) -> Vec<u64> {
+        self.paths
+            .all()
+            .par_iter()
+            .fold(
+                || vec![0; self.segs.len()],
+                |mut coverage, path| {
+                    self.add_coverage(&mut coverage, path);
+                    coverage
+                },
+            )
+            .reduce_with(|mut a, b| {
+                for (x, y) in a.iter_mut().zip(b) {
+                    *x += y;
+                }
+                a
+            })
+            .unwrap_or_else(|| vec![0; self.segs.len()])
+    }
+
+    /// Count a path's steps toward its segments' coverage.
+    fn add_coverage(&self, coverage: &mut [u64], path: &Path) {
+        for step in &self.steps[path.steps] {
+            coverage[step.segment().index()] += 1;
+        }
+    }
+
+    /// Get the GC content over all the segments in the graph.
+    pub fn total_gc_content(&self) -> f64 {
+        gc_fraction(&self.total_base_counts())
+    }
+
+    /// Get the length of a path's sequence: the total length of its steps' segments.
+    pub fn path_len(&self, path: &Path) -> u64 {
+        self.steps[path.steps]
+            .iter()
+            .map(|step| self.seg_len(&self.segs[step.segment()]) as u64)
+            .sum()
+    }
+
+    /// Find where a base offset along a path's sequence lands: the step (an index
+    /// into the `steps` pool), its segment, and the offset within the step.
+    ///
+    /// The offset within the step counts along the step's orientation, so for a
+    /// backward step, 0 is the last base of the segment's forward sequence. Returns
+    /// `None` if the offset is past the end of the path. This walks through the steps,
+    /// so each lookup takes time linear in the path's length.
+    pub fn path_position(
+        &self,
+        path: &Path,
+        offset: u64,
+    ) -> Option<(Id<Handle>, Id<Segment>, u32)> {
+        let mut start = 0;
+        for (idx, handle) in self.steps[path.steps].iter().enumerate() {
+            let end = start + self.seg_len(&self.segs[handle.segment()]) as u64;
+            if offset < end {
+                let step = Id::new(path.steps.start.index() + idx);
+                return Some((step, handle.segment(), (offset - start) as u32));
+            }
+            start = end;
+        }
+        None
+    }
+
+    /// Project a base offset along one path onto another path, through the segment
+    /// that they share at that position.
+    ///
+    /// Returns every offset along `path_b` where it visits the same base of the segment
+    /// that `offset` lands on in `path_a`, in order. When `path_b` traverses the segment
+    /// several times, each traversal gives an offset; traversals in the opposite
+    /// orientation count too (the base is at the mirrored position within the step).
+    /// The result is empty if `path_b` never visits the segment or `offset` is past
+    /// the end of `path_a`.
+    pub fn liftover(&self, path_a: &Path, offset: u64, path_b: &Path) -> Vec<u64> {
+        let Some((step, seg, inner)) = self.path_position(path_a, offset) else {
+            return vec![];
+        };
+        let orient = self.steps[step].orient();
+        let mut hits = vec![];
+        let mut start = 0;
+        for handle in &self.steps[path_b.steps] {
+            let len = self.seg_len(&self.segs[handle.segment()]) as u64;
+            if handle.segment() == seg {
+                if handle.orient() == orient {
+                    hits.push(start + inner as u64);
+                } else {
+                    hits.push(start + len - 1 - inner as u64);
+                }
+            }
+            start += len;
+        }
+        hits
+    }
+
+    /// Get every segment's length, in ID order.
+    pub fn segment_lengths(&self) -> Vec<u32> {
+        self.segs
+            .all()
+            .iter()
+            .map(|seg| self.seg_len(seg) as u32)
+            .collect()
+    }
+
+    /// Get every path's sequence length (see `path_len`), in ID order. This handles
+    /// the paths in parallel.
+    pub fn path_lengths(&self) -> Vec<u64> {
+        self.paths
+            .all()
+            .par_iter()
+            .map(|path| self.path_len(path))
+            .collect()
+    }
+
+    /// Get the N50 and L50 of the segments' lengths. See `n50`.
+    pub fn segment_n50(&self) -> (u64, usize) {
+        n50(self.segment_lengths().into_iter().map(u64::from).collect())
+    }
+
+    /// Get the N50 and L50 of the paths' sequence lengths. See `n50`.
+    pub fn path_n50(&self) -> (u64, usize) {
+        n50(self.path_lengths())
+    }
+}
+
+#[test]
+fn test_gc_content() {
+    assert_eq!(count_bases(b"ACgtNnGG"), [1, 1, 3, 1, 2]);
+    assert_eq!(gc_fraction(&count_bases(b"ACgtNnGG")), 4.0 / 6.0);
+    assert_eq!(gc_fraction(&count_bases(b"")), 0.0);
+    assert_eq!(gc_fraction(&count_bases(b"NNN")), 0.0);
+
+    let mut store = crate::HeapGFAStore::default();
+    store.add_seg(1, b"GGCC", &[]);
+    store.add_seg(2, b"AATT", &[]);
+    store.add_seg(3, b"", &[]);
+    let gfa = store.as_ref();
+    assert_eq!(gfa.total_base_counts(), [2, 2, 2, 2, 0]);
+    assert_eq!(gfa.total_gc_content(), 0.5);
+}
+
+#[test]
+fn test_n50() {
+    // The total is 20, and 6 + 5 is the first sum to reach half of that.
+    assert_eq!(n50(vec![2, 3, 4, 5, 6]), (5, 2));
+    assert_eq!(n50(vec![4, 4, 4, 4]), (4, 2));
+    assert_eq!(n50(vec![10, 1, 1]), (10, 1));
+    assert_eq!(n50(vec![]), (0, 0));
+
+    let gfa = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t1\tAA\nS\t2\tCCC\nS\t3\tGGGG\nP\tx\t1+,2+\t*\nP\ty\t3+,3-,1+\t*\n")
+        .unwrap();
+    let gfa = gfa.as_ref();
+    assert_eq!(gfa.segment_n50(), (3, 2));
+    assert_eq!(gfa.path_len(&gfa.paths[crate::pool::Id::new(1)]), 10);
+    assert_eq!(gfa.path_n50(), (10, 1));
+    assert_eq!(gfa.segment_lengths(), [2, 3, 4]);
+    assert_eq!(gfa.path_lengths(), [5, 10]);
+}
+
+#[test]
+fn test_path_position() {
+    let gfa = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t1\tAA\nS\t2\tCCC\nS\t3\tGGGG\nP\tx\t3+,1+\t*\nP\ty\t1+,2-,3+\t*\n")
+        .unwrap();
+    let gfa = gfa.as_ref();
+    let path = &gfa.paths[Id::new(1)];
+    let step = |idx: usize| Id::new(path.steps.start.index() + idx);
+
+    // An offset in the middle segment of three.
+    assert_eq!(gfa.path_position(path, 3), Some((step(1), Id::new(1), 1)));
+    assert_eq!(gfa.path_position(path, 0), Some((step(0), Id::new(0), 0)));
+    assert_eq!(gfa.path_position(path, 2), Some((step(1), Id::new(1), 0)));
+    assert_eq!(gfa.path_position(path, 8), Some((step(2), Id::new(2), 3)));
+    assert_eq!(gfa.path_position(path, 9), None);
+}
+
+#[test]
+fn test_liftover() {
+    let gfa = crate::parse::Parser::for_heap()
+        .parse_mem(
+            b"S\t1\tAA\nS\t2\tCCC\nS\t3\tGGGG\nS\t4\tT\nS\t5\tTT\n\
+              P\ta\t1+,2+,3+\t*\nP\tb\t4+,2-,5+,2+\t*\n",
+        )
+        .unwrap();
+    let gfa = gfa.as_ref();
+    let (a, b) = (&gfa.paths[Id::new(0)], &gfa.paths[Id::new(1)]);
+
+    // The middle segment is shared, and `b` visits it twice, once backward.
+    assert_eq!(gfa.liftover(a, 3, b), vec![2, 7]);
+    assert_eq!(gfa.liftover(b, 2, a), vec![3]);
+    assert_eq!(gfa.liftover(b, 7, a), vec![3]);
+
+    // Segments that `b` does not visit, and offsets past the end, have no projection.
+    assert!(gfa.liftover(a, 0, b).is_empty());
+    assert!(gfa.liftover(a, 9, b).is_empty());
+}
+
+#[test]
+fn test_parallel_stats() {
+    let gfa = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t1\tACGN\nS\t2\tGG\nS\t3\t*\nP\tx\t1+,2-,1+\t*\nP\ty\t2+\t*\n")
+        .unwrap();
+    let gfa = gfa.as_ref();
+    assert_eq!(
+        gfa.segment_base_counts(),
+        [[1, 1, 1, 0, 1], [0, 0, 2, 0, 0], [0, 0, 0, 0, 0]]
+    );
+    assert_eq!(gfa.base_counts_parallel(), gfa.segment_base_counts());
+    assert_eq!(gfa.coverage(), [2, 2, 0]);
+    assert_eq!(gfa.coverage_parallel(), gfa.coverage());
+}
@@ -0,0 +1,277 @@
+use crate::flatgfa::{FlatGFA, Handle, Orientation};
+use crate::print::natural_cmp;
+use std::collections::{HashMap, HashSet};
+
+/// A structural comparison of two graphs.
+///
+/// Everything is keyed by name, so the comparison ignores differences in IDs and in
+/// the order of records. Links are identified by their endpoints, and a link and its
+/// reverse complement (`1+ 2-` and `2+ 1-`) count as the same link. All the lists are
+/// in natural name order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GraphDiff {
+    /// Segments that only the first graph has.
+    pub segs_removed: Vec<String>,
+
+    /// Segments that only the second graph has.
+    pub segs_added: Vec<String>,
+
+    /// Segments in both graphs whose sequences (or lengths) differ.
+    pub segs_changed: Vec<String>,
+
+    /// Links that only the first graph has, as pairs of oriented segment names like
+    /// `("1+", "2-")`.
+    pub links_removed: Vec<(String, String)>,
+
+    /// Links that only the second graph has.
+    pub links_added: Vec<(String, String)>,
+
+    /// Paths that only the first graph has.
+    pub paths_removed: Vec<String>,
+
+    /// Paths that only the second graph has.
+    pub paths_added: Vec<String>,
+
+    /// Paths in both graphs that visit different steps.
+    pub paths_changed: Vec<String>,
+}
+
+impl GraphDiff {
+    /// Check whether the graphs are structurally the same.
+    pub fn is_empty(&self) -> bool {
+        self.segs_removed.is_empty()
+            && self.segs_added.is_empty()
+            && self.segs_changed.is_empty()
+            && self.links_removed.is_empty()
+            && self.links_added.is_empty()
+            && self.paths_removed.is_empty()
+            && self.paths_added.is_empty()
+            && self.paths_changed.is_empty()
+    }
+}
+
+/// A segment name with an orientation.
+type NamedHandle = (String, Orientation);
+
+impl<'a> FlatGFA<'a> {
+    /// Compare this graph (the "old" one) to `other` (the "new" one).
+    pub fn diff(&self, other: &FlatGFA) -> GraphDiff {
+        let mut diff = GraphDiff::default();
+
+        // Segments, compared by sequence. We fall back to the length for segments
+        // without stored sequences.
+        let old_segs = self.seg_contents();
+        let new_segs = other.seg_contents();
+        for (name, contents) in &old_segs {
+            match new_segs.get(name) {
+                None => diff.segs_removed.push(name.clone()),
+                Some(new) if new != contents => diff.segs_changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.segs_added = only_in(&new_segs, &old_segs);
+
+        // Links.
+        let old_links = self.link_ends();
+        let new_links = other.link_ends();
+        diff.links_removed = link_names(old_links.difference(&new_links));
+        diff.links_added = link_names(new_links.difference(&old_links));
+
+        // Paths, compared by their steps.
+        let old_paths = self.path_steps();
+        let new_paths = other.path_steps();
+        for (name, steps) in &old_paths {
+            match new_paths.get(name) {
+                None => diff.paths_removed.push(name.clone()),
+                Some(new) if new != steps => diff.paths_changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.paths_added = only_in(&new_paths, &old_paths);
+
+        for names in [
+            &mut diff.segs_removed,
+            &mut diff.segs_added,
+            &mut diff.segs_changed,
+            &mut diff.paths_removed,
+            &mut diff.paths_added,
+            &mut diff.paths_changed,
+        ] {
+            names.sort_by(|a, b| natural_cmp(a.as_bytes(), b.as_bytes()));
+        }
+        diff
+    }
+
+    /// Check whether this graph has the same content as `other`: the same segments
+    /// (by name and sequence), links, and paths, regardless of IDs and the order of
+    /// records.
+    ///
+    /// This is stricter than an empty `diff`. Links must match in the same
+    /// orientation and with the same overlap, and a duplicated link only matches a
+    /// link that is duplicated the same number of times. The check takes O(n)
+    /// (expected) time in the size of the graphs.
+    pub fn content_eq(&self, other: &FlatGFA) -> bool {
+        self.segs.len() == other.segs.len()
+            && self.links.len() == other.links.len()
+            && self.paths.len() == other.paths.len()
+            && self.seg_contents() == other.seg_contents()
+            && self.link_counts() == other.link_counts()
+            && self.path_steps() == other.path_steps()
+    }
+
+    /// Count the links with each pair of endpoints and overlap.
+    fn link_counts(&self) -> HashMap<(NamedHandle, NamedHandle, String), usize> {
+        let mut counts = HashMap::new();
+        for link in self.links.all() {
+            let key = (
+                self.named_handle(link.from),
+                self.named_handle(link.to),
+                self.get_alignment(link.overlap).to_string(),
+            );
+            *counts.entry(key).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Map each segment's name to its sequence, or its length if it has no sequence.
+    fn seg_contents(&self) -> HashMap<String, (&[u8], usize)> {
+        self.segs
+            .all()
+            .iter()
+            .map(|seg| {
+                let name = self.get_seg_name(seg).to_string();
+                (name, (self.get_seq(seg).as_ref(), self.seg_len(seg)))
+            })
+            .collect()
+    }
+
+    /// Get the endpoints of every link, in a canonical orientation.
+    fn link_ends(&self) -> HashSet<(NamedHandle, NamedHandle)> {
+        self.links
+            .all()
+            .iter()
+            .map(|link| {
+                let (from, to) = (self.named_handle(link.from), self.named_handle(link.to));
+                let rev = (flip(&to), flip(&from));
+                let fwd = (from, to);
+                if rev < fwd {
+                    rev
+                } else {
+                    fwd
+                }
+            })
+            .collect()
+    }
+
+    /// Map each path's name to its steps.
+    fn path_steps(&self) -> HashMap<String, Vec<NamedHandle>> {
+        self.paths
+            .all()
+            .iter()
+            .map(|path| {
+                let name = self.get_path_name(path).to_string();
+                let steps = self
+                    .get_path_steps(path)
+                    .map(|&handle| self.named_handle(handle))
+                    .collect();
+                (name, steps)
+            })
+            .collect()
+    }
+
+    /// Identify a handle by its segment's name.
+    fn named_handle(&self, handle: Handle) -> NamedHandle {
+        let seg = self.get_handle_seg(handle);
+        (self.get_seg_name(seg).to_string(), handle.orient())
+    }
+}
+
+fn flip((name, orient): &NamedHandle) -> NamedHandle {
+    (name.clone(), orient.flip())
+}
+
+/// Get the names in `a` but not in `b`.
+fn only_in<T>(a: &HashMap<String, T>, b: &HashMap<String, T>) -> Vec<String> {
+    a.keys()
+        .filter(|name| !b.contains_key(*name))
+        .cloned()
+        .collect()
+}
+
+/// Format and sort some links' endpoints.
+fn link_names<'b>(
+    links: impl Iterator<Item = &'b (NamedHandle, NamedHandle)>,
+) -> Vec<(String, String)> {
+    let mut links: Vec<_> = links.collect();
+    links.sort_by(|(a, b), (c, d)| {
+        natural_cmp(a.0.as_bytes(), c.0.as_bytes())
+            .then(natural_cmp(b.0.as_bytes(), d.0.as_bytes()))
+            .then(a.1.cmp(&c.1))
+            .then(b.1.cmp(&d.1))
+    });
+    links
+        .into_iter()
+        .map(|((from, from_o), (to, to_o))| {
+            (format!("{}{}", from, from_o), format!("{}{}", to, to_o))
+        })
+        .collect()
+}
+
+#[test]
+fn test_diff() {
+    let old = b"S\t1\tA\nS\t2\tC\nS\t3\tG\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\nP\tp\t1+,2+\t*\nP\tq\t2+,3-\t*\n";
+    // Reordered, with one segment added and one link removed. The remaining link is
+    // written as its reverse complement.
+    let new =
+        b"S\t3\tG\nS\t2\tC\nS\t1\tA\nS\t4\tT\nL\t3\t+\t2\t-\t0M\nP\tq\t2+,3-\t*\nP\tp\t1+,2-\t*\n";
+    let old = crate::parse::Parser::for_heap().parse_mem(old).unwrap();
+    let new = crate::parse::Parser::for_heap().parse_mem(new).unwrap();
+
+    let diff = old.as_ref().diff(&new.as_ref());
+    assert_eq!(diff.segs_added, vec!["4"]);
+    assert!(diff.segs_removed.is_empty());
+    assert!(diff.segs_changed.is_empty());
+    assert_eq!(
+        diff.links_removed,
+        vec![("1+".to_string(), "2+".to_string())]
+    );
+    assert!(diff.links_added.is_empty());
+    assert_eq!(diff.paths_changed, vec!["p"]);
+    assert!(diff.paths_added.is_empty() && diff.paths_removed.is_empty());
+
+    assert!(old.as_ref().diff(&old.as_ref()).is_empty());
+}
+
+#[test]
+fn test_content_eq() {
+    let text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*\n";
+    let parse = |text: &[u8]| crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = parse(text);
+    let gfa = gfa.as_ref();
+
+    // Reparsing the graph's own text, or the same records in another order, gives
+    // equal content.
+    let reparsed = parse(format!("{}", &gfa).as_bytes());
+    assert!(gfa.content_eq(&reparsed.as_ref()));
+    let reordered =
+        parse(b"P\tp\t1+,2+\t*\nL\t1\t+\t2\t+\t0M\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nS\t1\tA\n");
+    assert!(gfa.content_eq(&reordered.as_ref()));
+
+    // Unlike `diff`, we distinguish a link's reverse complement, its overlap, and
+    // how many times it appears.
+    for other in [
+        &b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t2\t-\t1\t-\t0M\nP\tp\t1+,2+\t*\n"[..],
+        b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t2\t+\t1M\nP\tp\t1+,2+\t*\n",
+        b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*\n",
+    ] {
+        let other = parse(other);
+        assert!(gfa.diff(&other.as_ref()).is_empty());
+        assert!(!gfa.content_eq(&other.as_ref()));
+    }
+
+    // Segments and paths must match too.
+    let other = parse(b"S\t1\tA\nS\t2\tG\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*\n");
+    assert!(!gfa.content_eq(&other.as_ref()));
+    let other = parse(b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t2\t+\t0M\nP\tq\t1+,2+\t*\n");
+    assert!(!gfa.content_eq(&other.as_ref()));
+}
@@ -1,32 +1,85 @@
 use crate::flatgfa;
+use crate::namemap::NameMap;
+use crate::parse::SizeEstimate;
 use crate::pool::{FixedStore, Pool, Span, Store};
 use std::mem::{size_of, size_of_val};
 use tinyvec::SliceVec;
 use zerocopy::{AsBytes, FromBytes, FromZeroes};
 
+/// The magic number that starts every FlatGFA file, in the low 32 bits of the first
+/// word.
 const MAGIC_NUMBER: u64 = 0xB101_1054;
 
+/// The version of the file layout, in the high 32 bits of the first word. Bump this
+/// whenever the table of contents or any record stored in a pool changes layout.
+/// (Files from before we had versions have version 0.)
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The first word of a FlatGFA file that this build can read.
+const MAGIC_WORD: u64 = MAGIC_NUMBER | (FORMAT_VERSION as u64) << 32;
+
+/// A bit in `Toc::flags` for a graph that we parsed without its sequences (see
+/// `FlatGFA::sequences_loaded`).
+const SEQUENCES_SKIPPED: u64 = 1 << 0;
+
+/// A problem with the contents of a binary FlatGFA file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data does not start with the FlatGFA magic number.
+    NotFlatGFA,
+
+    /// The file has a different layout version than `FORMAT_VERSION`.
+    Version(u32),
+
+    /// The table of contents describes more data than there is.
+    Truncated,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFlatGFA => write!(f, "not a FlatGFA file"),
+            Self::Version(version) => write!(
+                f,
+                "FlatGFA file has format version {}, but we can only read version {} \
+                (convert it again from GFA text)",
+                version, FORMAT_VERSION
+            ),
+            Self::Truncated => write!(f, "FlatGFA file is truncated or corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
 /// A table of contents for the FlatGFA file.
 #[derive(FromBytes, FromZeroes, AsBytes, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Toc {
     magic: u64,
+    flags: u64,
     header: Size,
     segs: Size,
+    rgfa: Size,
     paths: Size,
+    walks: Size,
     links: Size,
+    jumps: Size,
+    containments: Size,
     steps: Size,
     seq_data: Size,
     overlaps: Size,
     alignment: Size,
     name_data: Size,
     optional_data: Size,
+    tags: Size,
+    other_lines: Size,
     line_order: Size,
 }
 
 /// A table-of-contents entry for a pool in the FlatGFA file.
 #[derive(FromBytes, FromZeroes, AsBytes, Clone, Copy, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 struct Size {
     /// The number of actual elements in the pool.
     len: usize,
@@ -65,48 +118,68 @@ impl Toc {
         size_of::<Self>()
             + self.header.bytes::<u8>()
             + self.segs.bytes::<flatgfa::Segment>()
+            + self.rgfa.bytes::<flatgfa::RgfaSegment>()
             + self.paths.bytes::<flatgfa::Path>()
+            + self.walks.bytes::<flatgfa::Walk>()
             + self.links.bytes::<flatgfa::Link>()
+            + self.jumps.bytes::<flatgfa::Jump>()
+            + self.containments.bytes::<flatgfa::Containment>()
             + self.steps.bytes::<flatgfa::Handle>()
             + self.seq_data.bytes::<u8>()
             + self.overlaps.bytes::<Span<flatgfa::AlignOp>>()
             + self.alignment.bytes::<flatgfa::AlignOp>()
             + self.name_data.bytes::<u8>()
             + self.optional_data.bytes::<u8>()
+            + self.tags.bytes::<flatgfa::Tag>()
+            + self.other_lines.bytes::<u8>()
             + self.line_order.bytes::<u8>()
     }
 
     /// Get a table of contents that fits a FlatGFA with no spare space.
     fn full(gfa: &flatgfa::FlatGFA) -> Self {
         Self {
-            magic: MAGIC_NUMBER,
+            magic: MAGIC_WORD,
+            flags: flags(gfa.sequences_skipped),
             header: Size::of_pool(gfa.header),
             segs: Size::of_pool(gfa.segs),
+            rgfa: Size::of_pool(gfa.rgfa),
             paths: Size::of_pool(gfa.paths),
+            walks: Size::of_pool(gfa.walks),
             links: Size::of_pool(gfa.links),
+            jumps: Size::of_pool(gfa.jumps),
+            containments: Size::of_pool(gfa.containments),
             steps: Size::of_pool(gfa.steps),
             seq_data: Size::of_pool(gfa.seq_data),
             overlaps: Size::of_pool(gfa.overlaps),
             alignment: Size::of_pool(gfa.alignment),
             name_data: Size::of_pool(gfa.name_data),
             optional_data: Size::of_pool(gfa.optional_data),
+            tags: Size::of_pool(gfa.tags),
+            other_lines: Size::of_pool(gfa.other_lines),
             line_order: Size::of_pool(gfa.line_order),
         }
     }
 
     pub fn for_fixed_store(store: &flatgfa::FixedGFAStore) -> Self {
         Self {
-            magic: MAGIC_NUMBER,
+            magic: MAGIC_WORD,
+            flags: flags(store.sequences_skipped),
             header: Size::of_store(&store.header),
             segs: Size::of_store(&store.segs),
+            rgfa: Size::of_store(&store.rgfa),
             paths: Size::of_store(&store.paths),
+            walks: Size::of_store(&store.walks),
             links: Size::of_store(&store.links),
+            jumps: Size::of_store(&store.jumps),
+            containments: Size::of_store(&store.containments),
             steps: Size::of_store(&store.steps),
             seq_data: Size::of_store(&store.seq_data),
             overlaps: Size::of_store(&store.overlaps),
             alignment: Size::of_store(&store.alignment),
             name_data: Size::of_store(&store.name_data),
             optional_data: Size::of_store(&store.optional_data),
+            tags: Size::of_store(&store.tags),
+            other_lines: Size::of_store(&store.other_lines),
             line_order: Size::of_store(&store.line_order),
         }
     }
@@ -114,151 +187,271 @@ impl Toc {
     /// Guess a reasonable set of capacities for a fresh file.
     pub fn guess(factor: usize) -> Self {
         Self {
-            magic: MAGIC_NUMBER,
+            magic: MAGIC_WORD,
+            flags: 0,
             header: Size::empty(128),
             segs: Size::empty(32 * factor * factor),
+            rgfa: Size::empty(32 * factor * factor),
             paths: Size::empty(factor),
+            walks: Size::empty(factor),
             links: Size::empty(32 * factor * factor),
+            jumps: Size::empty(factor),
+            containments: Size::empty(factor),
             steps: Size::empty(1024 * factor * factor),
             seq_data: Size::empty(512 * factor * factor),
             overlaps: Size::empty(256 * factor),
             alignment: Size::empty(64 * factor * factor),
             name_data: Size::empty(64 * factor),
             optional_data: Size::empty(512 * factor * factor),
+            tags: Size::empty(128 * factor * factor),
+            other_lines: Size::empty(64 * factor),
             line_order: Size::empty(64 * factor * factor),
         }
     }
 
     /// Estimate a reasonable set of capacities for a fresh file based on some
     /// measurements of the GFA text.
+    #[allow(clippy::too_many_arguments)]
     pub fn estimate(
         segs: usize,
+        rgfa_segs: usize,
         links: usize,
+        jumps: usize,
+        containments: usize,
         paths: usize,
+        walks: usize,
         header_bytes: usize,
         seg_bytes: usize,
+        seg_name_bytes: usize,
         path_bytes: usize,
+        optional_bytes: usize,
+        other_bytes: usize,
     ) -> Self {
+        // Every tag takes at least 6 bytes of optional data, like `DP:i:1`.
+        let optional_bytes = (links + jumps + containments) * 16 + optional_bytes;
         Self {
-            magic: MAGIC_NUMBER,
+            magic: MAGIC_WORD,
+            flags: 0,
             header: Size::empty(header_bytes),
             segs: Size::empty(segs),
-            paths: Size::empty(paths),
+            rgfa: Size::empty(rgfa_segs),
+            paths: Size::empty(paths + walks),
+            walks: Size::empty(walks),
             links: Size::empty(links),
+            jumps: Size::empty(jumps),
+            containments: Size::empty(containments),
             steps: Size::empty(path_bytes / 3),
             seq_data: Size::empty(seg_bytes),
             overlaps: Size::empty((links + paths) * 2),
-            alignment: Size::empty(links * 2 + paths * 4),
-            name_data: Size::empty(paths * 512),
-            optional_data: Size::empty(links * 16),
-            line_order: Size::empty(segs + links + paths + 8),
+            alignment: Size::empty((links + containments) * 2 + paths * 4),
+            name_data: Size::empty((paths + walks) * 512 + seg_name_bytes),
+            optional_data: Size::empty(optional_bytes),
+            tags: Size::empty(optional_bytes / 6),
+            other_lines: Size::empty(other_bytes),
+            line_order: Size::empty(
+                segs + links + jumps + containments + paths + walks + other_bytes + 8,
+            ),
         }
     }
+
+    /// Get a table of contents with exactly the capacities measured for a GFA text.
+    pub fn exact(size: &SizeEstimate) -> Self {
+        Self {
+            magic: MAGIC_WORD,
+            flags: 0,
+            header: Size::empty(size.header_bytes),
+            segs: Size::empty(size.segs),
+            rgfa: Size::empty(size.rgfa),
+            paths: Size::empty(size.paths),
+            walks: Size::empty(size.walks),
+            links: Size::empty(size.links),
+            jumps: Size::empty(size.jumps),
+            containments: Size::empty(size.containments),
+            steps: Size::empty(size.steps),
+            seq_data: Size::empty(size.seq_bytes),
+            overlaps: Size::empty(size.overlaps),
+            alignment: Size::empty(size.align_ops),
+            name_data: Size::empty(size.name_bytes),
+            optional_data: Size::empty(size.optional_bytes),
+            tags: Size::empty(size.tags),
+            other_lines: Size::empty(size.other_bytes),
+            line_order: Size::empty(size.lines),
+        }
+    }
+}
+
+/// Get the `Toc::flags` for a graph.
+fn flags(sequences_skipped: bool) -> u64 {
+    if sequences_skipped {
+        SEQUENCES_SKIPPED
+    } else {
+        0
+    }
 }
 
 /// Consume `size.len` items from a byte slice, skip the remainder of `size.capacity`
 /// elements, and return the items and the rest of the slice.
-fn slice_prefix<T: FromBytes>(data: &[u8], size: Size) -> (&[T], &[u8]) {
-    let (prefix, rest) = T::slice_from_prefix(data, size.len).unwrap();
-    let pad = size_of::<T>() * (size.capacity - size.len);
-    (prefix, &rest[pad..])
+fn slice_prefix<T: FromBytes>(data: &[u8], size: Size) -> Result<(&[T], &[u8]), FormatError> {
+    let (prefix, rest) = T::slice_from_prefix(data, size.len).ok_or(FormatError::Truncated)?;
+    let pad = size
+        .capacity
+        .checked_sub(size.len)
+        .and_then(|spare| spare.checked_mul(size_of::<T>()))
+        .ok_or(FormatError::Truncated)?;
+    Ok((prefix, rest.get(pad..).ok_or(FormatError::Truncated)?))
+}
+
+/// Check the first word of a FlatGFA file.
+fn check_magic(magic: u64) -> Result<(), FormatError> {
+    if magic & 0xffff_ffff != MAGIC_NUMBER {
+        Err(FormatError::NotFlatGFA)
+    } else if magic != MAGIC_WORD {
+        Err(FormatError::Version((magic >> 32) as u32))
+    } else {
+        Ok(())
+    }
+}
+
+/// Read the first word of a buffer, to check it before reading the whole table of
+/// contents (which may have had a different size in another version).
+fn read_magic(data: &[u8]) -> Result<(), FormatError> {
+    let magic = u64::read_from_prefix(data).ok_or(FormatError::NotFlatGFA)?;
+    check_magic(magic)
 }
 
 /// Read the table of contents from a prefix of the byte buffer.
-fn read_toc(data: &[u8]) -> (&Toc, &[u8]) {
-    let toc = Toc::ref_from_prefix(data).unwrap();
-    let rest = &data[size_of::<Toc>()..];
-    let magic = toc.magic;
-    assert_eq!(magic, MAGIC_NUMBER);
-    (toc, rest)
+fn read_toc(data: &[u8]) -> Result<(&Toc, &[u8]), FormatError> {
+    read_magic(data)?;
+    let toc = Toc::ref_from_prefix(data).ok_or(FormatError::Truncated)?;
+    Ok((toc, &data[size_of::<Toc>()..]))
 }
 
-fn read_toc_mut(data: &mut [u8]) -> (&mut Toc, &mut [u8]) {
-    let (toc_slice, rest) = Toc::mut_slice_from_prefix(data, 1).unwrap();
-    let toc = &mut toc_slice[0];
-    let magic = toc.magic;
-    assert_eq!(magic, MAGIC_NUMBER);
-    (toc, rest)
+fn read_toc_mut(data: &mut [u8]) -> Result<(&mut Toc, &mut [u8]), FormatError> {
+    read_magic(data)?;
+    let (toc_slice, rest) = Toc::mut_slice_from_prefix(data, 1).ok_or(FormatError::Truncated)?;
+    Ok((&mut toc_slice[0], rest))
 }
 
 /// Get a FlatGFA backed by the data in a byte buffer.
-pub fn view(data: &[u8]) -> flatgfa::FlatGFA {
-    let (toc, rest) = read_toc(data);
-
-    let (header, rest) = slice_prefix(rest, toc.header);
-    let (segs, rest) = slice_prefix(rest, toc.segs);
-    let (paths, rest) = slice_prefix(rest, toc.paths);
-    let (links, rest) = slice_prefix(rest, toc.links);
-    let (steps, rest) = slice_prefix(rest, toc.steps);
-    let (seq_data, rest) = slice_prefix(rest, toc.seq_data);
-    let (overlaps, rest) = slice_prefix(rest, toc.overlaps);
-    let (alignment, rest) = slice_prefix(rest, toc.alignment);
-    let (name_data, rest) = slice_prefix(rest, toc.name_data);
-    let (optional_data, rest) = slice_prefix(rest, toc.optional_data);
-    let (line_order, _) = slice_prefix(rest, toc.line_order);
-
-    flatgfa::FlatGFA {
+///
+/// This checks that the data is a FlatGFA file in the current format and that the
+/// table of contents fits the data. (Use `FlatGFA::check_bounds` to check the
+/// records themselves.)
+pub fn view(data: &[u8]) -> Result<flatgfa::FlatGFA<'_>, FormatError> {
+    let (toc, rest) = read_toc(data)?;
+
+    let (header, rest) = slice_prefix(rest, toc.header)?;
+    let (segs, rest) = slice_prefix(rest, toc.segs)?;
+    let (rgfa, rest) = slice_prefix(rest, toc.rgfa)?;
+    let (paths, rest) = slice_prefix(rest, toc.paths)?;
+    let (walks, rest) = slice_prefix(rest, toc.walks)?;
+    let (links, rest) = slice_prefix(rest, toc.links)?;
+    let (jumps, rest) = slice_prefix(rest, toc.jumps)?;
+    let (containments, rest) = slice_prefix(rest, toc.containments)?;
+    let (steps, rest) = slice_prefix(rest, toc.steps)?;
+    let (seq_data, rest) = slice_prefix(rest, toc.seq_data)?;
+    let (overlaps, rest) = slice_prefix(rest, toc.overlaps)?;
+    let (alignment, rest) = slice_prefix(rest, toc.alignment)?;
+    let (name_data, rest) = slice_prefix(rest, toc.name_data)?;
+    let (optional_data, rest) = slice_prefix(rest, toc.optional_data)?;
+    let (tags, rest) = slice_prefix(rest, toc.tags)?;
+    let (other_lines, rest) = slice_prefix(rest, toc.other_lines)?;
+    let (line_order, _) = slice_prefix(rest, toc.line_order)?;
+
+    Ok(flatgfa::FlatGFA {
         header: header.into(),
         segs: segs.into(),
+        rgfa: rgfa.into(),
         paths: paths.into(),
+        walks: walks.into(),
         links: links.into(),
+        jumps: jumps.into(),
+        containments: containments.into(),
         steps: steps.into(),
         seq_data: seq_data.into(),
         overlaps: overlaps.into(),
         alignment: alignment.into(),
         name_data: name_data.into(),
         optional_data: optional_data.into(),
+        tags: tags.into(),
+        other_lines: other_lines.into(),
         line_order: line_order.into(),
-    }
+        sequences_skipped: toc.flags & SEQUENCES_SKIPPED != 0,
+    })
 }
 
 /// Like `slice_prefix`, but produce a `SliceVec`.
 fn slice_vec_prefix<T: FromBytes + AsBytes>(
     data: &mut [u8],
     size: Size,
-) -> (SliceVec<T>, &mut [u8]) {
-    let (prefix, rest) = T::mut_slice_from_prefix(data, size.capacity).unwrap();
+) -> Result<(SliceVec<'_, T>, &mut [u8]), FormatError> {
+    if size.len > size.capacity {
+        return Err(FormatError::Truncated);
+    }
+    let (prefix, rest) =
+        T::mut_slice_from_prefix(data, size.capacity).ok_or(FormatError::Truncated)?;
     let vec = SliceVec::from_slice_len(prefix, size.len);
-    (vec, rest)
+    Ok((vec, rest))
 }
 
 /// Get a FlatGFA `SliceStore` from the suffix of a file just following the table of contents.
-fn slice_store<'a>(data: &'a mut [u8], toc: &Toc) -> flatgfa::FixedGFAStore<'a> {
-    let (header, rest) = slice_vec_prefix(data, toc.header);
-    let (segs, rest) = slice_vec_prefix(rest, toc.segs);
-    let (paths, rest) = slice_vec_prefix(rest, toc.paths);
-    let (links, rest) = slice_vec_prefix(rest, toc.links);
-    let (steps, rest) = slice_vec_prefix(rest, toc.steps);
-    let (seq_data, rest) = slice_vec_prefix(rest, toc.seq_data);
-    let (overlaps, rest) = slice_vec_prefix(rest, toc.overlaps);
-    let (alignment, rest) = slice_vec_prefix(rest, toc.alignment);
-    let (name_data, rest) = slice_vec_prefix(rest, toc.name_data);
-    let (optional_data, rest) = slice_vec_prefix(rest, toc.optional_data);
-    let (line_order, _) = slice_vec_prefix(rest, toc.line_order);
-
-    flatgfa::FixedGFAStore {
+fn slice_store<'a>(
+    data: &'a mut [u8],
+    toc: &Toc,
+) -> Result<flatgfa::FixedGFAStore<'a>, FormatError> {
+    let (header, rest) = slice_vec_prefix(data, toc.header)?;
+    let (segs, rest) = slice_vec_prefix(rest, toc.segs)?;
+    let (rgfa, rest) = slice_vec_prefix(rest, toc.rgfa)?;
+    let (paths, rest) = slice_vec_prefix(rest, toc.paths)?;
+    let (walks, rest) = slice_vec_prefix(rest, toc.walks)?;
+    let (links, rest) = slice_vec_prefix(rest, toc.links)?;
+    let (jumps, rest) = slice_vec_prefix(rest, toc.jumps)?;
+    let (containments, rest) = slice_vec_prefix(rest, toc.containments)?;
+    let (steps, rest) = slice_vec_prefix(rest, toc.steps)?;
+    let (seq_data, rest) = slice_vec_prefix(rest, toc.seq_data)?;
+    let (overlaps, rest) = slice_vec_prefix(rest, toc.overlaps)?;
+    let (alignment, rest) = slice_vec_prefix(rest, toc.alignment)?;
+    let (name_data, rest) = slice_vec_prefix(rest, toc.name_data)?;
+    let (optional_data, rest) = slice_vec_prefix(rest, toc.optional_data)?;
+    let (tags, rest) = slice_vec_prefix(rest, toc.tags)?;
+    let (other_lines, rest) = slice_vec_prefix(rest, toc.other_lines)?;
+    let (line_order, _) = slice_vec_prefix(rest, toc.line_order)?;
+
+    let mut store = flatgfa::FixedGFAStore {
         header: header.into(),
         segs: segs.into(),
+        rgfa: rgfa.into(),
         paths: paths.into(),
+        walks: walks.into(),
         links: links.into(),
+        jumps: jumps.into(),
+        containments: containments.into(),
         steps: steps.into(),
         seq_data: seq_data.into(),
         overlaps: overlaps.into(),
         alignment: alignment.into(),
         name_data: name_data.into(),
         optional_data: optional_data.into(),
+        tags: tags.into(),
+        other_lines: other_lines.into(),
         line_order: line_order.into(),
-    }
+        names: NameMap::default(),
+        removed: Default::default(),
+        sequences_skipped: toc.flags & SEQUENCES_SKIPPED != 0,
+    };
+    store.names = NameMap::build(&store.as_ref());
+    Ok(store)
 }
 
-/// Get a mutable FlatGFA `SliceStore` backed by a byte buffer.
-pub fn view_store(data: &mut [u8]) -> flatgfa::FixedGFAStore {
-    let (toc, rest) = read_toc_mut(data);
+/// Get a mutable FlatGFA `SliceStore` backed by a byte buffer, checking it like
+/// `view` does.
+pub fn view_store(data: &mut [u8]) -> Result<flatgfa::FixedGFAStore<'_>, FormatError> {
+    let (toc, rest) = read_toc_mut(data)?;
     slice_store(rest, toc)
 }
 
 /// Initialize a buffer with an empty FlatGFA store.
-pub fn init(data: &mut [u8], toc: Toc) -> (&mut Toc, flatgfa::FixedGFAStore) {
+pub fn init(data: &mut [u8], toc: Toc) -> (&mut Toc, flatgfa::FixedGFAStore<'_>) {
     // Write the table of contents.
     assert!(data.len() == toc.size());
     toc.write_to_prefix(data).unwrap();
@@ -268,7 +461,7 @@ pub fn init(data: &mut [u8], toc: Toc) -> (&mut Toc, flatgfa::FixedGFAStore) {
     let toc_mut = Toc::mut_from(toc_bytes).unwrap();
 
     // Extract a store from the remaining bytes.
-    (toc_mut, slice_store(rest, &toc))
+    (toc_mut, slice_store(rest, &toc).unwrap())
 }
 
 fn write_bump<'a, T: AsBytes + ?Sized>(buf: &'a mut [u8], data: &T) -> Option<&'a mut [u8]> {
@@ -292,14 +485,20 @@ pub fn dump(gfa: &flatgfa::FlatGFA, buf: &mut [u8]) {
     // All the slices.
     let rest = write_bytes(rest, gfa.header.all()).unwrap();
     let rest = write_bump(rest, gfa.segs.all()).unwrap();
+    let rest = write_bump(rest, gfa.rgfa.all()).unwrap();
     let rest = write_bump(rest, gfa.paths.all()).unwrap();
+    let rest = write_bump(rest, gfa.walks.all()).unwrap();
     let rest = write_bump(rest, gfa.links.all()).unwrap();
+    let rest = write_bump(rest, gfa.jumps.all()).unwrap();
+    let rest = write_bump(rest, gfa.containments.all()).unwrap();
     let rest = write_bump(rest, gfa.steps.all()).unwrap();
     let rest = write_bytes(rest, gfa.seq_data.all()).unwrap();
     let rest = write_bump(rest, gfa.overlaps.all()).unwrap();
     let rest = write_bump(rest, gfa.alignment.all()).unwrap();
     let rest = write_bytes(rest, gfa.name_data.all()).unwrap();
     let rest = write_bytes(rest, gfa.optional_data.all()).unwrap();
+    let rest = write_bump(rest, gfa.tags.all()).unwrap();
+    let rest = write_bytes(rest, gfa.other_lines.all()).unwrap();
     write_bytes(rest, gfa.line_order.all()).unwrap();
 }
 
@@ -308,3 +507,62 @@ pub fn dump(gfa: &flatgfa::FlatGFA, buf: &mut [u8]) {
 pub fn size(gfa: &flatgfa::FlatGFA) -> usize {
     Toc::full(gfa).size()
 }
+
+#[test]
+fn test_format_check() {
+    let text = b"S\t1\tACGT\nS\t2\tC\nL\t1\t+\t2\t-\t0M\nP\tp\t1+,2-\t*\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let mut buf = vec![0; size(&gfa)];
+    dump(&gfa, &mut buf);
+    assert!(view(&buf).unwrap().content_eq(&gfa));
+
+    // A file from an older version of the format is rejected, not misread.
+    let mut old = buf.clone();
+    old[..8].copy_from_slice(&MAGIC_NUMBER.to_ne_bytes());
+    assert_eq!(view(&old).err(), Some(FormatError::Version(0)));
+    assert!(view_store(&mut old).is_err());
+    assert_eq!(
+        FormatError::Version(0).to_string(),
+        "FlatGFA file has format version 0, but we can only read version 2 \
+        (convert it again from GFA text)"
+    );
+
+    // So are other files, and files that end early.
+    assert_eq!(view(text).err(), Some(FormatError::NotFlatGFA));
+    assert_eq!(view(&buf[..4]).err(), Some(FormatError::NotFlatGFA));
+    for len in [size_of::<Toc>() - 1, size_of::<Toc>() + 1, buf.len() - 1] {
+        assert_eq!(view(&buf[..len]).err(), Some(FormatError::Truncated));
+    }
+
+    // A graph parsed without its sequences stays that way in its file.
+    let store = crate::parse::Parser::for_heap()
+        .skip_sequences()
+        .parse_mem(text)
+        .unwrap();
+    let mut buf = vec![0; size(&store.as_ref())];
+    dump(&store.as_ref(), &mut buf);
+    assert!(!view(&buf).unwrap().sequences_loaded());
+    assert!(!view_store(&mut buf).unwrap().as_ref().sequences_loaded());
+}
+
+#[test]
+fn test_record_layout() {
+    // The file is the table of contents followed by the pools' raw records, so these
+    // sizes are part of the format. If one changes, bump `FORMAT_VERSION` and update
+    // the number here.
+    use crate::flatgfa::*;
+    assert_eq!(FORMAT_VERSION, 2);
+    assert_eq!(size_of::<Toc>(), 288);
+    assert_eq!(size_of::<Segment>(), 40);
+    assert_eq!(size_of::<RgfaSegment>(), 24);
+    assert_eq!(size_of::<Path>(), 41);
+    assert_eq!(size_of::<Walk>(), 40);
+    assert_eq!(size_of::<Link>(), 31);
+    assert_eq!(size_of::<Jump>(), 24);
+    assert_eq!(size_of::<Containment>(), 28);
+    assert_eq!(size_of::<Handle>(), 4);
+    assert_eq!(size_of::<Span<AlignOp>>(), 8);
+    assert_eq!(size_of::<AlignOp>(), 4);
+    assert_eq!(size_of::<Tag>(), 11);
+}
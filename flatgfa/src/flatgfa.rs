@@ -1,8 +1,15 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::str::FromStr;
 
-use crate::pool::{self, Id, Pool, Span, Store};
-use bstr::BStr;
+use crate::adjacency::AdjacencyIndex;
+use crate::complexity::ComplexityReport;
+use crate::gfaline;
+use crate::namemap::NameMap;
+use crate::pool::{self, CompactSpan, Id, Pool, Span, Store};
+use atoi::FromRadix10Checked;
+use bstr::{BStr, BString};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use zerocopy::{AsBytes, FromBytes, FromZeroes};
 
@@ -15,19 +22,31 @@ use zerocopy::{AsBytes, FromBytes, FromZeroes};
 /// its components have a fixed size; unlike the underlying `GFAStore`, it is not
 /// possible to add new objects.
 pub struct FlatGFA<'a> {
-    /// A GFA may optionally have a single header line, with a version number.
-    /// If this is empty, there is no header line.
+    /// The contents of the GFA's header lines (without the leading `H`), separated by
+    /// newlines. If this is empty, there is no header line.
     pub header: Pool<'a, u8>,
 
     /// The segment (S) lines in the GFA file.
     pub segs: Pool<'a, Segment>,
 
-    /// The path (P) lines.
+    /// The rGFA coordinates of the segments that have them, sorted by segment.
+    pub rgfa: Pool<'a, RgfaSegment>,
+
+    /// The path (P) lines, along with the paths from walk (W) lines.
     pub paths: Pool<'a, Path>,
 
+    /// The extra metadata for paths that come from walk (W) lines, sorted by path.
+    pub walks: Pool<'a, Walk>,
+
     /// The link (L) lines.
     pub links: Pool<'a, Link>,
 
+    /// The jump (J) lines.
+    pub jumps: Pool<'a, Jump>,
+
+    /// The containment (C) lines.
+    pub containments: Pool<'a, Containment>,
+
     /// Paths consist of steps. This is a flat pool of steps, chunks of which are
     /// associated with each path.
     pub steps: Pool<'a, Handle>,
@@ -49,27 +68,42 @@ pub struct FlatGFA<'a> {
     /// contains range of indices in this pool.
     pub alignment: Pool<'a, AlignOp>,
 
-    /// The string names: currenly, just of paths. (We assume segments have integer
-    /// names, so they don't need to be stored separately.)
+    /// The string names: of paths, of segments with non-numeric names, and the
+    /// sample and sequence names from walks.
     pub name_data: Pool<'a, u8>,
 
-    /// Segments can come with optional extra fields, which we store in a flat pool
-    /// as raw characters because we don't currently care about them.
+    /// Segments, paths, links, jumps, and containments can come with optional extra
+    /// fields, which we store in a flat pool as raw characters because we don't
+    /// currently care about them.
     pub optional_data: Pool<'a, u8>,
 
+    /// The well-formed optional fields of segments, links, and paths, parsed into
+    /// their names, types, and values. The values are ranges in `optional_data`.
+    pub tags: Pool<'a, Tag>,
+
+    /// Lines with record types we don't otherwise understand, kept verbatim (and
+    /// separated by newlines) so we can write them back out. Each corresponds to an
+    /// `Other` entry in `line_order`.
+    pub other_lines: Pool<'a, u8>,
+
     /// An "interleaving" order of GFA lines. This is to preserve perfect round-trip
     /// fidelity: we record the order of lines as we saw them when parsing a GFA file
     /// so we can emit them again in that order. Elements should be `LineKind` values
     /// (but they are checked before we use them).
     pub line_order: Pool<'a, u8>,
+
+    /// Whether we parsed the graph with `Parser::skip_sequences`, so it has segment
+    /// lengths but no sequences. See `sequences_loaded`.
+    pub sequences_skipped: bool,
 }
 
 /// GFA graphs consist of "segment" nodes, which are fragments of base-pair sequences
 /// that can be strung together into paths.
 #[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Segment {
-    /// The segment's name. We assume all names are just plain numbers.
+    /// The segment's name, when it is a plain number. Most GFA files use numeric
+    /// names, so we store these directly. (See `text_name` for the alternative.)
     pub name: usize,
 
     /// The base-pair sequence for the segment. This is a range in the `seq_data` pool.
@@ -77,6 +111,13 @@ pub struct Segment {
 
     /// Segments can have optional fields. This is a range in the `optional_data` pool.
     pub optional: Span<u8>,
+
+    /// The parsed tags among the optional fields. This is a range in the `tags` pool.
+    pub tags: Span<Tag>,
+
+    /// For segments whose names are not plain numbers, the name as a range in the
+    /// `name_data` pool. This is empty for numerically named segments.
+    pub text_name: Span<u8>,
 }
 
 impl Segment {
@@ -84,11 +125,108 @@ impl Segment {
     pub fn len(&self) -> usize {
         self.seq.len()
     }
+
+    /// Check whether the segment's name is an arbitrary string rather than a number.
+    pub fn has_text_name(&self) -> bool {
+        !self.text_name.is_empty()
+    }
+}
+
+/// A segment's coordinates in an rGFA graph: the position of its sequence on a
+/// "stable" sequence, like a chromosome in a linear reference.
+///
+/// These come from the segment's `SN:Z:`, `SO:i:`, and `SR:i:` tags, which stay among
+/// its optional fields too (so printing the segment writes them back out). We only
+/// keep coordinates for segments that have all three tags.
+#[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy)]
+#[repr(C, packed)]
+pub struct RgfaSegment {
+    /// The segment with these coordinates.
+    pub seg: Id<Segment>,
+
+    /// The stable sequence's name (the `SN` tag's value). This is a range in the
+    /// `optional_data` pool.
+    pub stable_name: Span<u8>,
+
+    /// The segment's offset on the stable sequence (`SO`).
+    pub offset: u64,
+
+    /// The rank (`SR`): 0 for segments on the reference, and higher for segments
+    /// added later.
+    pub rank: u32,
+}
+
+/// Find the rGFA tags among a segment's well-formed tags, given as (name, type,
+/// value) triples: get the index of the `SN:Z:` tag and the values of the `SO:i:` and
+/// `SR:i:` tags. When a segment has several tags with the same name, the first one
+/// counts.
+pub(crate) fn find_rgfa_tags<'b>(
+    tags: impl Iterator<Item = ([u8; 2], u8, &'b [u8])>,
+) -> Option<(usize, u64, u32)> {
+    fn int<T: FromStr>(kind: u8, value: &[u8]) -> Option<T> {
+        if kind != b'i' {
+            return None;
+        }
+        std::str::from_utf8(value).ok()?.parse().ok()
+    }
+
+    let (mut name, mut offset, mut rank) = (None, None, None);
+    for (idx, (tag, kind, value)) in tags.enumerate() {
+        match &tag {
+            b"SN" if name.is_none() => name = Some((kind == b'Z').then_some(idx)),
+            b"SO" if offset.is_none() => offset = Some(int(kind, value)),
+            b"SR" if rank.is_none() => rank = Some(int(kind, value)),
+            _ => {}
+        }
+    }
+    Some((name??, offset??, rank??))
+}
+
+/// A segment name, which is either a plain number or an arbitrary string.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SegName<'a> {
+    Num(usize),
+    Text(&'a BStr),
+}
+
+impl<'a> SegName<'a> {
+    /// Interpret a name from a GFA file.
+    ///
+    /// Names are numeric only if they are in canonical decimal form, so names like
+    /// `007` keep their exact spelling.
+    pub fn parse(name: &'a [u8]) -> Self {
+        let canonical = !name.is_empty() && (name[0] != b'0' || name.len() == 1);
+        if canonical {
+            if let (Some(num), used) = usize::from_radix_10_checked(name) {
+                if used == name.len() {
+                    return SegName::Num(num);
+                }
+            }
+        }
+        SegName::Text(name.as_ref())
+    }
+
+    /// Get the name's text, exactly as it appears in GFA.
+    pub fn to_bytes(&self) -> Cow<'a, [u8]> {
+        match self {
+            SegName::Num(num) => Cow::Owned(num.to_string().into_bytes()),
+            SegName::Text(text) => Cow::Borrowed(text),
+        }
+    }
+}
+
+impl<'a> std::fmt::Display for SegName<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegName::Num(num) => write!(f, "{}", num),
+            SegName::Text(text) => write!(f, "{}", text),
+        }
+    }
 }
 
 /// A path is a sequence of oriented references to segments.
 #[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Path {
     /// The path's name. This can be an arbitrary string. It is a range in the
     /// `name_data` pool.
@@ -100,17 +238,112 @@ pub struct Path {
     /// The CIGAR overlaps for each step on the path. This is a range in the
     /// `overlaps` pool.
     pub overlaps: Span<Span<AlignOp>>,
+
+    /// How the GFA text spelled the overlaps field, when there are no overlaps. This
+    /// is an `OverlapSpelling`; use `overlap_spelling()` to read it.
+    pub overlap_spelling: u8,
+
+    /// Paths can have optional fields. This is a range in the `optional_data` pool.
+    pub optional: Span<u8>,
+
+    /// The parsed tags among the optional fields. This is a range in the `tags` pool.
+    pub tags: Span<Tag>,
 }
 
 impl Path {
+    /// Get the number of steps in the path, which may be 0.
     pub fn step_count(&self) -> usize {
-        self.steps.end.index() - self.steps.start.index()
+        self.steps.len()
+    }
+
+    /// Get the way the GFA text spelled the path's overlaps, if there are none.
+    pub fn overlap_spelling(&self) -> OverlapSpelling {
+        OverlapSpelling::try_from(self.overlap_spelling).unwrap_or_default()
     }
 }
 
+/// The metadata for a path that comes from a walk (W line, from GFA 1.1).
+///
+/// We store walks as ordinary paths (so they have steps and a name like any other
+/// path) with this extra information on the side.
+#[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Walk {
+    /// The path that holds the walk's steps.
+    pub path: Id<Path>,
+
+    /// The sample name. This is a range in the `name_data` pool.
+    pub sample: Span<u8>,
+
+    /// The sequence (e.g., contig or chromosome) name. This is a range in the
+    /// `name_data` pool.
+    pub seq_id: Span<u8>,
+
+    /// The haplotype index.
+    pub hap_index: u32,
+
+    /// The start of the walk on the sequence, or `Walk::UNKNOWN_POS`. Use
+    /// `seq_start()` to read it.
+    pub seq_start: u64,
+
+    /// The end of the walk on the sequence, or `Walk::UNKNOWN_POS`. Use `seq_end()`
+    /// to read it.
+    pub seq_end: u64,
+}
+
+impl Walk {
+    /// The sentinel value we use to represent an unknown (`*`) position.
+    pub const UNKNOWN_POS: u64 = u64::MAX;
+
+    /// Get the start of the walk on its sequence, if it is known.
+    pub fn seq_start(&self) -> Option<u64> {
+        let pos = self.seq_start;
+        (pos != Self::UNKNOWN_POS).then_some(pos)
+    }
+
+    /// Get the end of the walk on its sequence, if it is known.
+    pub fn seq_end(&self) -> Option<u64> {
+        let pos = self.seq_end;
+        (pos != Self::UNKNOWN_POS).then_some(pos)
+    }
+}
+
+/// Construct the path name we use for a walk, following the PanSN convention:
+/// `sample#hap#seq`, with a `:start-end` suffix when the range is known.
+pub fn walk_path_name(
+    sample: &[u8],
+    hap_index: u32,
+    seq_id: &[u8],
+    seq_start: Option<u64>,
+    seq_end: Option<u64>,
+) -> Vec<u8> {
+    let mut name = sample.to_vec();
+    name.extend_from_slice(format!("#{}#", hap_index).as_bytes());
+    name.extend_from_slice(seq_id);
+    if let (Some(start), Some(end)) = (seq_start, seq_end) {
+        name.extend_from_slice(format!(":{}-{}", start, end).as_bytes());
+    }
+    name
+}
+
+/// Split a path name that follows the PanSN convention, `sample#hap#contig`, into
+/// those three parts.
+///
+/// The contig is everything after the second `#`, including any `:start-end` range
+/// (like the one in the names we give walks). Names with fewer than three parts, or
+/// with an empty part, get `None`.
+pub fn parse_pansn(name: &[u8]) -> Option<(&BStr, &BStr, &BStr)> {
+    let mut parts = name.splitn(3, |&c| c == b'#');
+    let (sample, hap, contig) = (parts.next()?, parts.next()?, parts.next()?);
+    if sample.is_empty() || hap.is_empty() || contig.is_empty() {
+        return None;
+    }
+    Some((sample.as_ref(), hap.as_ref(), contig.as_ref()))
+}
+
 /// An allowed edge between two oriented segments.
 #[derive(Debug, FromBytes, FromZeroes, AsBytes, Clone, Copy)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Link {
     /// The source of the edge.
     pub from: Handle,
@@ -119,11 +352,38 @@ pub struct Link {
     pub to: Handle,
 
     /// The CIGAR overlap between the segments. This is a range in the
-    /// `alignment` pool.
-    pub overlap: Span<AlignOp>,
+    /// `alignment` pool. Link overlaps are short, so we use a compact span.
+    pub overlap: CompactSpan<AlignOp>,
+
+    /// How the GFA text spelled the overlap, when it has no operations. This is an
+    /// `OverlapSpelling`; use `overlap_spelling()` to read it.
+    pub overlap_spelling: u8,
+
+    /// Links can have optional fields. This is a range in the `optional_data` pool.
+    pub optional: Span<u8>,
+
+    /// The parsed tags among the optional fields. This is a range in the `tags` pool.
+    pub tags: Span<Tag>,
 }
 
 impl Link {
+    /// Get the way the GFA text spelled the link's overlap, if it is empty.
+    pub fn overlap_spelling(&self) -> OverlapSpelling {
+        OverlapSpelling::try_from(self.overlap_spelling).unwrap_or_default()
+    }
+
+    /// Check whether the link leads from a segment back to the same segment.
+    pub fn is_self_loop(&self) -> bool {
+        self.from.segment() == self.to.segment()
+    }
+
+    /// Check whether the link changes orientation, like `L 1 + 2 -`. A self-loop that
+    /// does this is a hairpin (it turns around onto the other strand), while one that
+    /// does not (like `L 1 + 1 +`) repeats the segment in tandem.
+    pub fn is_inverting(&self) -> bool {
+        self.from.orient() != self.to.orient()
+    }
+
     /// Is either end of the link the given segment? If so, return the other end.
     pub fn incident_seg(&self, seg_id: Id<Segment>) -> Option<Id<Segment>> {
         if self.from.segment() == seg_id {
@@ -136,14 +396,122 @@ impl Link {
     }
 }
 
+/// A "jump" between two oriented segments (a J line, from GFA 1.2).
+///
+/// Jumps are like links, but they connect segments that are not adjacent in the
+/// sequence: they come with an estimate of the distance between the two ends.
+#[derive(Debug, FromBytes, FromZeroes, AsBytes, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Jump {
+    /// The source of the jump.
+    pub from: Handle,
+
+    /// The destination of the jump.
+    pub to: Handle,
+
+    /// The estimated distance, or `Jump::UNKNOWN_DISTANCE` if the distance is unknown
+    /// (`*` in the GFA text). Use `distance()` to read it.
+    pub distance: i64,
+
+    /// Jumps can have optional fields. This is a range in the `optional_data` pool.
+    pub optional: Span<u8>,
+}
+
+impl Jump {
+    /// The sentinel value we use to represent an unknown distance.
+    pub const UNKNOWN_DISTANCE: i64 = i64::MIN;
+
+    /// Get the estimated distance for the jump, if it is known.
+    pub fn distance(&self) -> Option<i64> {
+        let distance = self.distance;
+        (distance != Self::UNKNOWN_DISTANCE).then_some(distance)
+    }
+}
+
+/// A containment of one oriented segment within another (a C line).
+#[derive(Debug, FromBytes, FromZeroes, AsBytes, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Containment {
+    /// The segment that contains the other.
+    pub container: Handle,
+
+    /// The segment that is contained.
+    pub contained: Handle,
+
+    /// The offset in the container where the contained segment starts.
+    pub pos: u32,
+
+    /// The CIGAR overlap between the segments. This is a range in the `alignment`
+    /// pool.
+    pub overlap: Span<AlignOp>,
+
+    /// Containments can have optional fields. This is a range in the
+    /// `optional_data` pool.
+    pub optional: Span<u8>,
+}
+
+/// An optional field (tag), like `DP:i:12`, parsed into its parts.
+///
+/// Segments, links, and paths keep their optional fields verbatim, so we can emit them
+/// faithfully, and they also refer to these parsed tags. A malformed field has no
+/// `Tag`.
+#[derive(Debug, FromBytes, FromZeroes, AsBytes, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Tag {
+    /// The two-character tag name.
+    pub name: [u8; 2],
+
+    /// The GFA type character, like `i` or `Z`.
+    pub kind: u8,
+
+    /// The value's text. This is a range in the `optional_data` pool.
+    pub value: Span<u8>,
+}
+
+/// A GFA record that can have tags.
+pub trait Tagged {
+    /// The record's parsed tags, as a range in the `tags` pool.
+    fn tags(&self) -> Span<Tag>;
+}
+
+impl Tagged for Segment {
+    fn tags(&self) -> Span<Tag> {
+        self.tags
+    }
+}
+
+impl Tagged for Link {
+    fn tags(&self) -> Span<Tag> {
+        self.tags
+    }
+}
+
+impl Tagged for Path {
+    fn tags(&self) -> Span<Tag> {
+        self.tags
+    }
+}
+
 /// A forward or backward direction.
-#[derive(Debug, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, IntoPrimitive, TryFromPrimitive,
+)]
 #[repr(u8)]
 pub enum Orientation {
     Forward,  // +
     Backward, // -
 }
 
+impl Orientation {
+    /// Get the opposite orientation.
+    pub fn flip(&self) -> Self {
+        match self {
+            Orientation::Forward => Orientation::Backward,
+            Orientation::Backward => Orientation::Forward,
+        }
+    }
+}
+
 impl FromStr for Orientation {
     type Err = ();
 
@@ -164,7 +532,7 @@ impl FromStr for Orientation {
 /// So, logically, it consists of a pair of a segment reference (usize) and an
 /// orientation (1 bit). We pack the two values into a single word.
 #[derive(Debug, FromBytes, FromZeroes, AsBytes, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Handle(u32);
 
 impl Handle {
@@ -186,6 +554,22 @@ impl Handle {
     pub fn orient(&self) -> Orientation {
         ((self.0 & 1) as u8).try_into().unwrap()
     }
+
+    /// Get the handle for the same segment in the opposite orientation.
+    pub fn flip(&self) -> Self {
+        Self(self.0 ^ 1)
+    }
+
+    /// Get a dense index for this handle, suitable for indexing a table that has
+    /// two entries (one per orientation) for every segment.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    /// Get the handle with a given dense index. This is the inverse of `index`.
+    pub fn from_index(index: usize) -> Self {
+        Self(index.try_into().expect("index too large"))
+    }
 }
 
 /// The kind of each operation in a CIGAR alignment.
@@ -194,23 +578,26 @@ impl Handle {
 pub enum AlignOpcode {
     Match,     // M
     Gap,       // N
-    Insertion, // D
-    Deletion,  // I
+    Insertion, // I
+    Deletion,  // D
 }
 
 /// A single operation in a CIGAR alignment, like "3M" or "1D".
 ///
 /// Logically, this is a pair of a number and an `AlignOpcode`. We pack the two
 /// into a single u32.
-#[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy)]
-#[repr(packed)]
+#[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C, packed)]
 pub struct AlignOp(u32);
 
 impl AlignOp {
+    /// The longest operation we can represent, which fits in 24 bits.
+    pub const MAX_LEN: u32 = u32::MAX >> 8;
+
     /// Create a new alignment operation from an opcode and count.
     pub fn new(op: AlignOpcode, len: u32) -> Self {
         let op_byte: u8 = op.into();
-        assert!(len & !0xff == 0, "length too large");
+        assert!(len <= Self::MAX_LEN, "length too large");
         Self((len << 8) | (op_byte as u32))
     }
 
@@ -238,6 +625,30 @@ pub struct Alignment<'a> {
     pub ops: &'a [AlignOp],
 }
 
+/// The way the GFA text wrote an overlap with no alignment operations, which we keep
+/// for round-tripping.
+///
+/// Producers disagree about how to write a blunt overlap: some write `0M`, some `*`,
+/// and some leave the field empty (or, for paths, leave it out). An overlap of `0M`
+/// has an operation, so we store it as such; the others have none, and this tells
+/// them apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum OverlapSpelling {
+    /// The usual way to write no overlap: `0M` for a link and `*` for a path.
+    #[default]
+    Default,
+
+    /// `*`, for a link.
+    Star,
+
+    /// An empty field.
+    Empty,
+
+    /// No field at all, for a path with no optional fields.
+    Missing,
+}
+
 /// A kind of GFA line. We use this in `line_order` to preserve the textual order
 /// in a GFA file for round-tripping.
 #[derive(Debug, IntoPrimitive, TryFromPrimitive)]
@@ -247,6 +658,10 @@ pub enum LineKind {
     Segment,
     Path,
     Link,
+    Jump,
+    Containment,
+    Walk,
+    Other,
 }
 
 pub struct Sequence<'a> {
@@ -297,7 +712,7 @@ impl<'a> Sequence<'a> {
     }
 }
 
-fn nucleotide_complement(c: u8) -> u8 {
+pub(crate) fn nucleotide_complement(c: u8) -> u8 {
     match c {
         b'A' => b'T',
         b'T' => b'A',
@@ -329,21 +744,136 @@ impl<'a> std::fmt::Display for Sequence<'a> {
 
 impl<'a> FlatGFA<'a> {
     /// Get the base-pair sequence for a segment.
+    ///
+    /// This is empty for segments with no stored sequence: the sequence is `*` in the
+    /// GFA text, or we parsed with `Parser::skip_sequences`. Operations that need the
+    /// bases should use `check_sequences` to tell the latter case apart.
     pub fn get_seq(&self, seg: &Segment) -> &BStr {
         self.seq_data[seg.seq].as_ref()
     }
 
-    pub fn get_seq_oriented(&self, handle: Handle) -> Sequence {
+    /// Check whether a segment has a stored sequence. See `get_seq`.
+    pub fn has_seq(&self, seg: &Segment) -> bool {
+        !seg.seq.is_empty()
+    }
+
+    /// Check whether the graph's sequences were loaded.
+    ///
+    /// This is false when we parsed the graph with `Parser::skip_sequences`, which we
+    /// record in the graph (and in its binary file). A graph whose sequences are all
+    /// `*` still has its sequences loaded: there just are none.
+    pub fn sequences_loaded(&self) -> bool {
+        !self.sequences_skipped
+    }
+
+    /// Check that the sequences were loaded, for operations that need the bases. See
+    /// `sequences_loaded`.
+    pub fn check_sequences(&self) -> Result<(), MissingSequence> {
+        if self.sequences_loaded() {
+            Ok(())
+        } else {
+            Err(MissingSequence::NotLoaded)
+        }
+    }
+
+    /// Get the length of a segment's sequence.
+    ///
+    /// For segments with no stored sequence, this uses the `LN` tag, or 0 if there is
+    /// none.
+    pub fn seg_len(&self, seg: &Segment) -> usize {
+        if self.has_seq(seg) {
+            seg.seq.len()
+        } else {
+            self.get_tag_int(seg, b"LN")
+                .and_then(|len| len.try_into().ok())
+                .unwrap_or(0)
+        }
+    }
+
+    /// Get the bases `start..end` of a segment's sequence, without copying.
+    ///
+    /// Returns `None` if the range is out of bounds (or if `start > end`): we do not
+    /// clamp the range to the sequence.
+    pub fn get_seq_range(&self, seg: &Segment, start: usize, end: usize) -> Option<&BStr> {
+        self.seq_data[seg.seq]
+            .get(start..end)
+            .map(|range| range.as_ref())
+    }
+
+    /// Iterate over all the segments along with their IDs and sequences.
+    ///
+    /// The sequences borrow from the graph; nothing is copied.
+    pub fn iter_segments(&self) -> impl Iterator<Item = (Id<Segment>, &Segment, &BStr)> {
+        self.segs
+            .items()
+            .map(|(id, seg)| (id, seg, self.get_seq(seg)))
+    }
+
+    /// Iterate over the IDs of the segments that are strictly longer than `len`, as
+    /// measured by `seg_len`.
+    pub fn segments_longer_than(&self, len: usize) -> impl Iterator<Item = Id<Segment>> + '_ {
+        self.segs
+            .items()
+            .filter(move |(_, seg)| self.seg_len(seg) > len)
+            .map(|(id, _)| id)
+    }
+
+    /// Iterate over the IDs of the segments that are at most `len` long: exactly the
+    /// ones that `segments_longer_than(len)` leaves out.
+    pub fn segments_no_longer_than(&self, len: usize) -> impl Iterator<Item = Id<Segment>> + '_ {
+        self.segs
+            .items()
+            .filter(move |(_, seg)| self.seg_len(seg) <= len)
+            .map(|(id, _)| id)
+    }
+
+    pub fn get_seq_oriented(&self, handle: Handle) -> Sequence<'_> {
         let seg = self.get_handle_seg(handle);
         let seq_data = self.seq_data[seg.seq].as_ref();
         Sequence::new(seq_data, handle.orient())
     }
 
+    /// Spell out the sequence of a path: the concatenated sequences of its steps, in
+    /// their orientations. (We do not trim overlaps.)
+    ///
+    /// It is an error if any step's segment has no stored sequence, since the result
+    /// would silently be missing bases.
+    pub fn get_path_seq(&self, path: &Path) -> Result<Vec<u8>, MissingSequence> {
+        let mut seq = vec![];
+        for &handle in &self.steps[path.steps] {
+            let seg = self.get_handle_seg(handle);
+            if !self.has_seq(seg) {
+                self.check_sequences()?;
+                return Err(MissingSequence::Segment(self.get_seg_name(seg).to_string()));
+            }
+            seq.extend(self.get_seq_oriented(handle).as_vec());
+        }
+        Ok(seq)
+    }
+
     /// Look up a segment by its name.
     pub fn find_seg(&self, name: usize) -> Option<Id<Segment>> {
         // TODO Make this more efficient by maintaining the name index? This would not be
         // too hard; we already have the machinery in `parse.rs`...
-        self.segs.search(|seg| seg.name == name)
+        self.segs
+            .search(|seg| !seg.has_text_name() && seg.name == name)
+    }
+
+    /// Look up a segment by its name, which may be numeric or textual.
+    pub fn find_named_seg(&self, name: SegName) -> Option<Id<Segment>> {
+        match name {
+            SegName::Num(num) => self.find_seg(num),
+            SegName::Text(_) => self.segs.search(|seg| self.get_seg_name(seg) == name),
+        }
+    }
+
+    /// Get the name of a segment.
+    pub fn get_seg_name(&self, seg: &Segment) -> SegName<'_> {
+        if seg.has_text_name() {
+            SegName::Text(self.name_data[seg.text_name].as_ref())
+        } else {
+            SegName::Num(seg.name)
+        }
     }
 
     /// Look up a path by its name.
@@ -356,10 +886,118 @@ impl<'a> FlatGFA<'a> {
         self.name_data[path.name].as_ref()
     }
 
+    /// Get the sample, haplotype, and contig from a path's PanSN-style name, if it has
+    /// one. See `parse_pansn`.
+    pub fn parse_pansn(&self, path: &Path) -> Option<(&BStr, &BStr, &BStr)> {
+        parse_pansn(self.get_path_name(path))
+    }
+
+    /// Group the paths with PanSN-style names by their sample. Each sample's paths
+    /// are in ID order. Paths with other names are left out.
+    pub fn paths_by_sample(&self) -> HashMap<String, Vec<Id<Path>>> {
+        let mut samples: HashMap<String, Vec<Id<Path>>> = HashMap::new();
+        for (id, path) in self.paths.items() {
+            if let Some((sample, _, _)) = self.parse_pansn(path) {
+                samples.entry(sample.to_string()).or_default().push(id);
+            }
+        }
+        samples
+    }
+
+    /// Get a segment's rGFA coordinates, if it has them.
+    pub fn get_rgfa(&self, seg: Id<Segment>) -> Option<&RgfaSegment> {
+        let rgfa = self.rgfa.all();
+        let idx = rgfa
+            .binary_search_by_key(&seg.index(), |coords| coords.seg.index())
+            .ok()?;
+        Some(&rgfa[idx])
+    }
+
+    /// Get the name of the stable sequence in a segment's rGFA coordinates.
+    pub fn get_stable_name(&self, coords: &RgfaSegment) -> &BStr {
+        self.optional_data[coords.stable_name].as_ref()
+    }
+
+    /// Check whether this is an rGFA graph: it has segments, and every one of them has
+    /// rGFA coordinates.
+    pub fn is_rgfa(&self) -> bool {
+        !self.segs.is_empty() && self.rgfa.len() == self.segs.len()
+    }
+
+    /// Get the walk metadata for a path, if it comes from a W line.
+    pub fn get_walk(&self, path: Id<Path>) -> Option<&Walk> {
+        let walks = self.walks.all();
+        let idx = walks
+            .binary_search_by_key(&path.index(), |walk| walk.path.index())
+            .ok()?;
+        Some(&walks[idx])
+    }
+
+    /// Get the sample name of a walk.
+    pub fn get_walk_sample(&self, walk: &Walk) -> &BStr {
+        self.name_data[walk.sample].as_ref()
+    }
+
+    /// Get the sequence name of a walk.
+    pub fn get_walk_seq_id(&self, walk: &Walk) -> &BStr {
+        self.name_data[walk.seq_id].as_ref()
+    }
+
     pub fn get_path_steps(&self, path: &Path) -> impl Iterator<Item = &Handle> {
         self.steps[path.steps].iter()
     }
 
+    /// Iterate over the links with at least one endpoint in a set of segments.
+    ///
+    /// This scans every link. To avoid that, use `AdjacencyIndex::links_incident`.
+    pub fn links_incident<'b>(
+        &'b self,
+        seg_ids: &'b HashSet<Id<Segment>>,
+    ) -> impl Iterator<Item = &'b Link> {
+        self.links.all().iter().filter(|link| {
+            seg_ids.contains(&link.from.segment()) || seg_ids.contains(&link.to.segment())
+        })
+    }
+
+    /// Find the links from a segment to itself, in ID order. Use `Link::is_inverting`
+    /// to tell hairpins from tandem repeats.
+    pub fn self_loops(&self) -> Vec<Id<Link>> {
+        self.links
+            .items()
+            .filter(|(_, link)| link.is_self_loop())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Iterate over the links as `(from, to)` pairs of handles, in ID order.
+    ///
+    /// Each link also connects `to.flip()` to `from.flip()`, so adding both pairs gives
+    /// a bidirected adjacency.
+    pub fn link_handles(&self) -> impl Iterator<Item = (Handle, Handle)> + '_ {
+        self.links.all().iter().map(|link| (link.from, link.to))
+    }
+
+    /// Find the superbubbles in the graph. See `AdjacencyIndex::superbubbles`.
+    ///
+    /// This builds an adjacency index, which takes time linear in the number of links.
+    pub fn superbubbles(&self) -> Vec<(Handle, Handle)> {
+        AdjacencyIndex::build(self).superbubbles()
+    }
+
+    /// Find the dead ends in the graph. See `AdjacencyIndex::tips`.
+    ///
+    /// This builds an adjacency index, which takes time linear in the number of links.
+    pub fn tips(&self) -> Vec<Handle> {
+        AdjacencyIndex::build(self).tips()
+    }
+
+    /// Measure how tangled the graph is. See `AdjacencyIndex::complexity`.
+    ///
+    /// This builds an adjacency index, which takes time linear in the number of links.
+    pub fn complexity(&self) -> ComplexityReport {
+        AdjacencyIndex::build(self).complexity()
+    }
+
     /// Get a handle's associated segment.
     pub fn get_handle_seg(&self, handle: Handle) -> &Segment {
         &self.segs[handle.segment()]
@@ -370,13 +1008,278 @@ impl<'a> FlatGFA<'a> {
         self.optional_data[seg.optional].as_ref()
     }
 
+    /// Get the optional data for a path, as a tab-separated string.
+    pub fn get_path_optional_data(&self, path: &Path) -> &BStr {
+        self.optional_data[path.optional].as_ref()
+    }
+
+    /// Get the optional data for a link, as a tab-separated string.
+    pub fn get_link_optional_data(&self, link: &Link) -> &BStr {
+        self.optional_data[link.optional].as_ref()
+    }
+
+    /// Get the optional data for a jump, as a tab-separated string.
+    pub fn get_jump_optional_data(&self, jump: &Jump) -> &BStr {
+        self.optional_data[jump.optional].as_ref()
+    }
+
+    /// Get the optional data for a containment, as a tab-separated string.
+    pub fn get_containment_optional_data(&self, containment: &Containment) -> &BStr {
+        self.optional_data[containment.optional].as_ref()
+    }
+
+    /// Get the contents of each header line as it appeared in the input, in order.
+    ///
+    /// Printing the graph writes a single header line instead (see `get_header`).
+    pub fn get_header_lines(&self) -> impl Iterator<Item = &BStr> {
+        let header = self.header.all();
+        header
+            .split(|&c| c == b'\n')
+            .take(if header.is_empty() { 0 } else { usize::MAX })
+            .map(|line| line.as_ref())
+    }
+
+    /// Get the lines with unknown record types that we kept verbatim, in order.
+    pub fn get_other_lines(&self) -> impl Iterator<Item = &BStr> {
+        let lines = self.other_lines.all();
+        lines
+            .split(|&c| c == b'\n')
+            .take(if lines.is_empty() { 0 } else { usize::MAX })
+            .map(|line| line.as_ref())
+    }
+
+    /// Get the contents of the header, with the fields from every header line merged
+    /// into one line, or `None` if there is no header.
+    ///
+    /// Each tag appears once, where it first appears. (The parser rejects headers
+    /// that give a tag two different values, except for `VN`, where the first one
+    /// wins.) Other fields appear once each, also in order.
+    pub fn get_header(&self) -> Option<BString> {
+        let mut fields: Vec<&[u8]> = vec![];
+        let mut names = HashSet::new();
+        for line in self.get_header_lines() {
+            for field in line.split(|&c| c == b'\t') {
+                let new = match gfaline::parse_tag(field) {
+                    Ok(tag) => names.insert(tag.name),
+                    Err(_) => !fields.contains(&field),
+                };
+                if new {
+                    fields.push(field);
+                }
+            }
+        }
+        (!self.header.is_empty()).then(|| fields.join(&b'\t').into())
+    }
+
+    /// Get the header's tags, merged across all the header lines.
+    ///
+    /// When several lines have the same tag, the first one wins. Fields that are not
+    /// well-formed tags are skipped.
+    pub fn get_header_tags(&self) -> Vec<gfaline::Tag<'_>> {
+        let mut tags: Vec<gfaline::Tag> = vec![];
+        for line in self.get_header_lines() {
+            for field in line.split(|&c| c == b'\t') {
+                if let Ok(tag) = gfaline::parse_tag(field) {
+                    if !tags.iter().any(|t| t.name == tag.name) {
+                        tags.push(tag);
+                    }
+                }
+            }
+        }
+        tags
+    }
+
+    /// Get the parsed tags for a segment, link, or path.
+    pub fn get_tags(&self, item: &impl Tagged) -> &[Tag] {
+        &self.tags[item.tags()]
+    }
+
+    /// Find a segment's, link's, or path's tag by name.
+    pub fn get_tag(&self, item: &impl Tagged, name: &[u8; 2]) -> Option<&Tag> {
+        self.get_tags(item).iter().find(|tag| tag.name == *name)
+    }
+
+    /// Get the text of a tag's value.
+    pub fn get_tag_value(&self, tag: &Tag) -> &BStr {
+        self.optional_data[tag.value].as_ref()
+    }
+
+    /// Get the value of an integer (`i`) tag, or `None` if there is no such tag or it
+    /// has a different type.
+    pub fn get_tag_int(&self, item: &impl Tagged, name: &[u8; 2]) -> Option<i64> {
+        let tag = self.get_tag(item, name).filter(|tag| tag.kind == b'i')?;
+        std::str::from_utf8(self.get_tag_value(tag))
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Get the value of a float (`f`) tag. See `get_tag_int`.
+    pub fn get_tag_float(&self, item: &impl Tagged, name: &[u8; 2]) -> Option<f64> {
+        let tag = self.get_tag(item, name).filter(|tag| tag.kind == b'f')?;
+        std::str::from_utf8(self.get_tag_value(tag))
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Get the value of a string (`Z`) tag. See `get_tag_int`.
+    pub fn get_tag_str(&self, item: &impl Tagged, name: &[u8; 2]) -> Option<&BStr> {
+        let tag = self.get_tag(item, name).filter(|tag| tag.kind == b'Z')?;
+        Some(self.get_tag_value(tag))
+    }
+
     /// Look up a CIGAR alignment.
-    pub fn get_alignment(&self, overlap: Span<AlignOp>) -> Alignment {
+    pub fn get_alignment(&self, overlap: impl Into<Span<AlignOp>>) -> Alignment<'_> {
         Alignment {
-            ops: &self.alignment[overlap],
+            ops: &self.alignment[overlap.into()],
+        }
+    }
+
+    /// Get a path's overlaps: the CIGAR alignments between each pair of consecutive
+    /// steps.
+    ///
+    /// Paths whose overlaps are `*` (i.e., unspecified) have no overlaps at all.
+    pub fn path_overlaps(&self, path: &Path) -> impl Iterator<Item = Alignment<'_>> {
+        self.overlaps[path.overlaps]
+            .iter()
+            .map(|&overlap| self.get_alignment(overlap))
+    }
+
+    /// Check whether the graph's paths were loaded.
+    ///
+    /// They are missing when we parsed the graph with `Parser::skip_paths`: then the
+    /// line order records path or walk lines, but there are no paths to go with them.
+    pub fn paths_loaded(&self) -> bool {
+        !self.paths.is_empty()
+            || !self
+                .line_order
+                .all()
+                .iter()
+                .any(|&kind| kind == LineKind::Path as u8 || kind == LineKind::Walk as u8)
+    }
+
+    /// Check that the paths were loaded, for operations that need them. See
+    /// `paths_loaded`.
+    pub fn check_paths(&self) -> Result<(), PathsNotLoaded> {
+        if self.paths_loaded() {
+            Ok(())
+        } else {
+            Err(PathsNotLoaded)
         }
     }
 
+    /// Check that every span and ID in the graph stays within its pool, and that the
+    /// line order refers only to records that exist. Accessing a graph that passes
+    /// this check does not panic.
+    ///
+    /// The parser and stores only build graphs that pass. The check is for graphs
+    /// from elsewhere, such as a binary file that might be corrupt.
+    pub fn check_bounds(&self) -> Result<(), OutOfBounds> {
+        let fail = |record, index| Err(OutOfBounds { record, index });
+        let seg_ok = |handle: Handle| handle.segment().index() < self.segs.len();
+        let opt_ok = |span: Span<u8>| self.optional_data.get_span(span).is_some();
+        let tags_ok = |span: Span<Tag>| self.tags.get_span(span).is_some();
+        let align_ok = |span: Span<AlignOp>| self.alignment.get_span(span).is_some();
+
+        for (idx, seg) in self.segs.all().iter().enumerate() {
+            if self.name_data.get_span(seg.text_name).is_none()
+                || self.seq_data.get_span(seg.seq).is_none()
+                || !opt_ok(seg.optional)
+                || !tags_ok(seg.tags)
+            {
+                return fail("segment", idx);
+            }
+        }
+        for (idx, coords) in self.rgfa.all().iter().enumerate() {
+            if coords.seg.index() >= self.segs.len() || !opt_ok(coords.stable_name) {
+                return fail("rGFA segment", idx);
+            }
+        }
+        for (idx, path) in self.paths.all().iter().enumerate() {
+            if self.name_data.get_span(path.name).is_none()
+                || self.steps.get_span(path.steps).is_none()
+                || self.overlaps.get_span(path.overlaps).is_none()
+                || !opt_ok(path.optional)
+                || !tags_ok(path.tags)
+            {
+                return fail("path", idx);
+            }
+        }
+        for (idx, walk) in self.walks.all().iter().enumerate() {
+            if walk.path.index() >= self.paths.len()
+                || self.name_data.get_span(walk.sample).is_none()
+                || self.name_data.get_span(walk.seq_id).is_none()
+            {
+                return fail("walk", idx);
+            }
+        }
+        for (idx, link) in self.links.all().iter().enumerate() {
+            if !seg_ok(link.from)
+                || !seg_ok(link.to)
+                || !align_ok(link.overlap.into())
+                || !opt_ok(link.optional)
+                || !tags_ok(link.tags)
+            {
+                return fail("link", idx);
+            }
+        }
+        for (idx, jump) in self.jumps.all().iter().enumerate() {
+            if !seg_ok(jump.from) || !seg_ok(jump.to) || !opt_ok(jump.optional) {
+                return fail("jump", idx);
+            }
+        }
+        for (idx, containment) in self.containments.all().iter().enumerate() {
+            if !seg_ok(containment.container)
+                || !seg_ok(containment.contained)
+                || !align_ok(containment.overlap)
+                || !opt_ok(containment.optional)
+            {
+                return fail("containment", idx);
+            }
+        }
+        if let Some(idx) = self.steps.all().iter().position(|&step| !seg_ok(step)) {
+            return fail("step", idx);
+        }
+        if let Some(idx) = self.overlaps.all().iter().position(|&span| !align_ok(span)) {
+            return fail("overlap", idx);
+        }
+        if let Some(idx) = self.tags.all().iter().position(|tag| !opt_ok(tag.value)) {
+            return fail("tag", idx);
+        }
+
+        // Printing takes each record in the line order from its pool in turn.
+        let mut counts = [0; 8];
+        let others = self.get_other_lines().count();
+        for (idx, &byte) in self.line_order.all().iter().enumerate() {
+            let Ok(kind) = LineKind::try_from(byte) else {
+                return fail("line order entry", idx);
+            };
+            counts[byte as usize] += 1;
+            let count = counts[byte as usize];
+            let paths = counts[LineKind::Path as usize] + counts[LineKind::Walk as usize];
+            // Without any paths, path and walk lines are just placeholders (see
+            // `paths_loaded`).
+            let ok = match kind {
+                LineKind::Header => true,
+                LineKind::Segment => count <= self.segs.len(),
+                LineKind::Link => count <= self.links.len(),
+                LineKind::Jump => count <= self.jumps.len(),
+                LineKind::Containment => count <= self.containments.len(),
+                LineKind::Other => count <= others,
+                LineKind::Path => self.paths.is_empty() || paths <= self.paths.len(),
+                LineKind::Walk => {
+                    self.paths.is_empty()
+                        || (paths <= self.paths.len() && count <= self.walks.len())
+                }
+            };
+            if !ok {
+                return fail("line order entry", idx);
+            }
+        }
+        Ok(())
+    }
+
     /// Get the recorded order of line kinds.
     pub fn get_line_order(&self) -> impl Iterator<Item = LineKind> + 'a {
         self.line_order
@@ -391,39 +1294,257 @@ impl<'a> FlatGFA<'a> {
 pub struct GFAStore<'a, P: StoreFamily<'a>> {
     pub header: P::Store<u8>,
     pub segs: P::Store<Segment>,
+    pub rgfa: P::Store<RgfaSegment>,
     pub paths: P::Store<Path>,
+    pub walks: P::Store<Walk>,
     pub links: P::Store<Link>,
+    pub jumps: P::Store<Jump>,
+    pub containments: P::Store<Containment>,
     pub steps: P::Store<Handle>,
     pub seq_data: P::Store<u8>,
     pub overlaps: P::Store<Span<AlignOp>>,
     pub alignment: P::Store<AlignOp>,
     pub name_data: P::Store<u8>,
     pub optional_data: P::Store<u8>,
+    pub tags: P::Store<Tag>,
+    pub other_lines: P::Store<u8>,
     pub line_order: P::Store<u8>,
+
+    /// An index of the segments by name, which we keep up to date as we add segments.
+    pub names: NameMap,
+
+    /// Segments that `remove_segment` has marked for removal by the next `compact`.
+    pub removed: HashSet<Id<Segment>>,
+
+    /// Whether the segments' sequences were left out. See `FlatGFA::sequences_skipped`.
+    pub sequences_skipped: bool,
+}
+
+/// The error for a reference, by name, to a segment that does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSegment(pub String);
+
+impl std::fmt::Display for UnknownSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown segment `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSegment {}
+
+/// The error for an operation that needs segments' sequences, when they are not
+/// available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MissingSequence {
+    /// We parsed the graph without its sequences (see `FlatGFA::sequences_loaded`).
+    NotLoaded,
+
+    /// The named segment has no sequence (e.g., its sequence is `*`).
+    Segment(String),
+}
+
+impl std::fmt::Display for MissingSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotLoaded => write!(f, "sequences were not loaded"),
+            Self::Segment(name) => write!(f, "segment `{}` has no sequence", name),
+        }
+    }
+}
+
+impl std::error::Error for MissingSequence {}
+
+/// The error for an operation that needs a graph's paths, when we parsed the graph
+/// without them (see `FlatGFA::paths_loaded`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathsNotLoaded;
+
+impl std::fmt::Display for PathsNotLoaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "paths were not loaded")
+    }
+}
+
+impl std::error::Error for PathsNotLoaded {}
+
+/// The error for a tag value that would not fit in an optional field: one with a
+/// character that is not printable ASCII, like a tab or a newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadTagValue;
+
+impl std::fmt::Display for BadTagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tag values must be printable ASCII, without tabs or newlines"
+        )
+    }
+}
+
+impl std::error::Error for BadTagValue {}
+
+/// The error for a new segment name that cannot be used (see
+/// `GFAStore::rename_segments`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BadSegmentName {
+    /// The name is empty.
+    Empty,
+
+    /// The name has whitespace or a character that delimits path steps.
+    Invalid(String),
+
+    /// Another segment has the same name.
+    Duplicate(String),
+}
+
+impl std::fmt::Display for BadSegmentName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "segment names cannot be empty"),
+            Self::Invalid(name) => write!(
+                f,
+                "segment name `{}` has whitespace, `,`, `+`, or `-`",
+                name
+            ),
+            Self::Duplicate(name) => write!(f, "duplicate segment name `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for BadSegmentName {}
+
+/// The error for a graph with a span or ID that points outside its pool, or a line
+/// order that mentions more records than the graph has (see `FlatGFA::check_bounds`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+    /// The kind of record with the bad reference, like `"link"`.
+    pub record: &'static str,
+
+    /// The record's index in its pool.
+    pub index: usize,
+}
+
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} refers to data outside the graph",
+            self.record, self.index
+        )
+    }
 }
 
+impl std::error::Error for OutOfBounds {}
+
 impl<'a, P: StoreFamily<'a>> GFAStore<'a, P> {
-    /// Add a header line for the GFA file. This may only be added once.
-    pub fn add_header(&mut self, version: &[u8]) {
-        assert!(self.header.as_ref().is_empty());
-        self.header.add_slice(version);
+    /// Add a header line for the GFA file, after any existing ones.
+    pub fn add_header(&mut self, data: &[u8]) {
+        if !self.header.as_ref().is_empty() {
+            self.header.add(b'\n');
+        }
+        self.header.add_slice(data);
+    }
+
+    /// Keep a line with an unknown record type, after any existing ones. This also
+    /// records the line in the line order, so it gets printed in place.
+    pub fn add_other_line(&mut self, line: &[u8]) {
+        if !self.other_lines.as_ref().is_empty() {
+            self.other_lines.add(b'\n');
+        }
+        self.other_lines.add_slice(line);
+        self.record_line(LineKind::Other);
     }
 
     /// Add a new segment to the GFA file.
     pub fn add_seg(&mut self, name: usize, seq: &[u8], optional: &[u8]) -> Id<Segment> {
-        self.segs.add(Segment {
+        let (optional, tags) = self.add_optional(optional);
+        let id = self.segs.add(Segment {
             name,
             seq: self.seq_data.add_slice(seq),
-            optional: self.optional_data.add_slice(optional),
+            optional,
+            tags,
+            text_name: Span::new_empty(),
+        });
+        self.names.insert(name, id);
+        self.add_rgfa(id);
+        id
+    }
+
+    /// Add a new segment with a name that may be numeric or textual.
+    pub fn add_named_seg(&mut self, name: SegName, seq: &[u8], optional: &[u8]) -> Id<Segment> {
+        match name {
+            SegName::Num(num) => self.add_seg(num, seq, optional),
+            SegName::Text(text) => {
+                let (optional, tags) = self.add_optional(optional);
+                let id = self.segs.add(Segment {
+                    name: 0,
+                    seq: self.seq_data.add_slice(seq),
+                    optional,
+                    tags,
+                    text_name: self.name_data.add_slice(text),
+                });
+                self.names.insert_name(name, id);
+                self.add_rgfa(id);
+                id
+            }
+        }
+    }
+
+    /// Get a segment's rGFA coordinates from its tags, if it has them.
+    fn find_rgfa(&self, id: Id<Segment>) -> Option<RgfaSegment> {
+        let tags = &self.tags.as_ref()[self.segs.as_ref()[id].tags];
+        let data = self.optional_data.as_ref();
+        let (name, offset, rank) = find_rgfa_tags(
+            tags.iter()
+                .map(|tag| (tag.name, tag.kind, &data[tag.value])),
+        )?;
+        Some(RgfaSegment {
+            seg: id,
+            stable_name: tags[name].value,
+            offset,
+            rank,
         })
     }
 
+    /// Record a new segment's rGFA coordinates, if it has them.
+    fn add_rgfa(&mut self, id: Id<Segment>) {
+        if let Some(coords) = self.find_rgfa(id) {
+            self.rgfa.add(coords);
+        }
+    }
+
+    /// Add some optional data, along with the tags that we can parse from it.
+    fn add_optional(&mut self, optional: &[u8]) -> (Span<u8>, Span<Tag>) {
+        let optional = self.optional_data.add_slice(optional);
+        (optional, self.add_tags(optional))
+    }
+
+    /// Parse the well-formed tags in a range of the `optional_data` pool and add them
+    /// to the `tags` pool.
+    fn add_tags(&mut self, optional: Span<u8>) -> Span<Tag> {
+        let data = &self.optional_data.as_ref()[optional];
+        let mut start = optional.start.index();
+        self.tags
+            .add_iter(data.split(|&c| c == b'\t').filter_map(|field| {
+                let field_start = start;
+                start += field.len() + 1;
+                let tag = gfaline::parse_tag(field).ok()?;
+                let value_start = field_start + field.len() - tag.value.len();
+                Some(Tag {
+                    name: tag.name,
+                    kind: tag.kind,
+                    value: Span::new(Id::new(value_start), Id::new(field_start + field.len())),
+                })
+            }))
+    }
+
     /// Add a new path.
     pub fn add_path(
         &mut self,
         name: &[u8],
         steps: Span<Handle>,
         overlaps: impl Iterator<Item = Vec<AlignOp>>,
+        optional: &[u8],
     ) -> Id<Path> {
         let overlaps = self.overlaps.add_iter(
             overlaps
@@ -431,10 +1552,34 @@ impl<'a, P: StoreFamily<'a>> GFAStore<'a, P> {
                 .map(|align| self.alignment.add_iter(align)),
         );
         let name = self.name_data.add_slice(name);
+        let (optional, tags) = self.add_optional(optional);
         self.paths.add(Path {
             name,
             steps,
             overlaps,
+            overlap_spelling: OverlapSpelling::Default.into(),
+            optional,
+            tags,
+        })
+    }
+
+    /// Attach walk metadata to a path. Walks must be added in path order.
+    pub fn add_walk(
+        &mut self,
+        path: Id<Path>,
+        sample: &[u8],
+        hap_index: u32,
+        seq_id: &[u8],
+        seq_start: Option<u64>,
+        seq_end: Option<u64>,
+    ) -> Id<Walk> {
+        self.walks.add(Walk {
+            path,
+            sample: self.name_data.add_slice(sample),
+            seq_id: self.name_data.add_slice(seq_id),
+            hap_index,
+            seq_start: seq_start.unwrap_or(Walk::UNKNOWN_POS),
+            seq_end: seq_end.unwrap_or(Walk::UNKNOWN_POS),
         })
     }
 
@@ -454,11 +1599,59 @@ impl<'a, P: StoreFamily<'a>> GFAStore<'a, P> {
     }
 
     /// Add a link between two (oriented) segments.
-    pub fn add_link(&mut self, from: Handle, to: Handle, overlap: Vec<AlignOp>) -> Id<Link> {
+    ///
+    /// Panics if the overlap has more than `CompactSpan::MAX_LEN` operations. (The
+    /// parser checks for this, and reports an error instead.)
+    pub fn add_link(
+        &mut self,
+        from: Handle,
+        to: Handle,
+        overlap: impl IntoIterator<Item = AlignOp>,
+        optional: &[u8],
+    ) -> Id<Link> {
+        let overlap = self.alignment.add_iter(overlap);
+        let (optional, tags) = self.add_optional(optional);
         self.links.add(Link {
             from,
             to,
+            overlap: overlap.try_into().expect("link overlap too long"),
+            overlap_spelling: OverlapSpelling::Default.into(),
+            optional,
+            tags,
+        })
+    }
+
+    /// Add a jump between two (oriented) segments, with an optional distance.
+    pub fn add_jump(
+        &mut self,
+        from: Handle,
+        to: Handle,
+        distance: Option<i64>,
+        optional: &[u8],
+    ) -> Id<Jump> {
+        self.jumps.add(Jump {
+            from,
+            to,
+            distance: distance.unwrap_or(Jump::UNKNOWN_DISTANCE),
+            optional: self.optional_data.add_slice(optional),
+        })
+    }
+
+    /// Add a containment of one (oriented) segment in another at a given position.
+    pub fn add_containment(
+        &mut self,
+        container: Handle,
+        contained: Handle,
+        pos: u32,
+        overlap: impl IntoIterator<Item = AlignOp>,
+        optional: &[u8],
+    ) -> Id<Containment> {
+        self.containments.add(Containment {
+            container,
+            contained,
+            pos,
             overlap: self.alignment.add_iter(overlap),
+            optional: self.optional_data.add_slice(optional),
         })
     }
 
@@ -467,20 +1660,79 @@ impl<'a, P: StoreFamily<'a>> GFAStore<'a, P> {
         self.line_order.add(kind.into());
     }
 
+    /// Get a handle for a segment given by name.
+    fn named_handle(&self, name: &[u8], orient: Orientation) -> Result<Handle, UnknownSegment> {
+        match self.names.find_name(SegName::parse(name)) {
+            Some(id) => Ok(Handle::new(id, orient)),
+            None => Err(UnknownSegment(String::from_utf8_lossy(name).into_owned())),
+        }
+    }
+
+    /// Record a line type for a record added after parsing, if the store keeps a
+    /// line order. (A store without one prints in the normalized order.)
+    fn record_new_line(&mut self, kind: LineKind) {
+        if !self.line_order.as_ref().is_empty() {
+            self.record_line(kind);
+        }
+    }
+
+    /// Add a link between two segments given by name.
+    ///
+    /// Unlike `add_link`, this also records the link in the line order, so it appears
+    /// (at the end) when printing the graph.
+    pub fn add_link_by_name(
+        &mut self,
+        from: &[u8],
+        from_orient: Orientation,
+        to: &[u8],
+        to_orient: Orientation,
+        overlap: impl IntoIterator<Item = AlignOp>,
+    ) -> Result<Id<Link>, UnknownSegment> {
+        let from = self.named_handle(from, from_orient)?;
+        let to = self.named_handle(to, to_orient)?;
+        self.record_new_line(LineKind::Link);
+        Ok(self.add_link(from, to, overlap, &[]))
+    }
+
+    /// Add a path that visits segments given by name, with no overlaps.
+    ///
+    /// If any segment does not exist, this adds nothing. Like `add_link_by_name`, this
+    /// records the path in the line order.
+    pub fn add_path_by_name<'n>(
+        &mut self,
+        name: &[u8],
+        steps: impl IntoIterator<Item = (&'n [u8], Orientation)>,
+    ) -> Result<Id<Path>, UnknownSegment> {
+        let steps = steps
+            .into_iter()
+            .map(|(seg, orient)| self.named_handle(seg, orient))
+            .collect::<Result<Vec<_>, _>>()?;
+        let steps = self.add_steps(steps.into_iter());
+        self.record_new_line(LineKind::Path);
+        Ok(self.add_path(name, steps, std::iter::empty(), &[]))
+    }
+
     /// Borrow a FlatGFA view of this data store.
-    pub fn as_ref(&self) -> FlatGFA {
+    pub fn as_ref(&self) -> FlatGFA<'_> {
         FlatGFA {
             header: self.header.as_ref(),
             segs: self.segs.as_ref(),
+            rgfa: self.rgfa.as_ref(),
             paths: self.paths.as_ref(),
+            walks: self.walks.as_ref(),
             links: self.links.as_ref(),
+            jumps: self.jumps.as_ref(),
+            containments: self.containments.as_ref(),
             name_data: self.name_data.as_ref(),
             seq_data: self.seq_data.as_ref(),
             steps: self.steps.as_ref(),
             overlaps: self.overlaps.as_ref(),
             alignment: self.alignment.as_ref(),
             optional_data: self.optional_data.as_ref(),
+            tags: self.tags.as_ref(),
+            other_lines: self.other_lines.as_ref(),
             line_order: self.line_order.as_ref(),
+            sequences_skipped: self.sequences_skipped,
         }
     }
 }
@@ -513,3 +1765,876 @@ pub type FixedGFAStore<'a> = GFAStore<'a, FixedFamily>;
 /// `FlatGFA`. It exposes an API for building up a GFA data structure, so it is
 /// useful for creating new ones from scratch.
 pub type HeapGFAStore = GFAStore<'static, HeapFamily>;
+
+impl HeapGFAStore {
+    /// Copy the contents of a `FlatGFA` into a new heap store, which can then be
+    /// modified.
+    pub fn from_flat(gfa: &FlatGFA) -> Self {
+        let mut store = Self::default();
+        store.header.add_slice(gfa.header.all());
+        store.segs.add_slice(gfa.segs.all());
+        store.rgfa.add_slice(gfa.rgfa.all());
+        store.paths.add_slice(gfa.paths.all());
+        store.walks.add_slice(gfa.walks.all());
+        store.links.add_slice(gfa.links.all());
+        store.jumps.add_slice(gfa.jumps.all());
+        store.containments.add_slice(gfa.containments.all());
+        store.steps.add_slice(gfa.steps.all());
+        store.seq_data.add_slice(gfa.seq_data.all());
+        store.overlaps.add_slice(gfa.overlaps.all());
+        store.alignment.add_slice(gfa.alignment.all());
+        store.name_data.add_slice(gfa.name_data.all());
+        store.optional_data.add_slice(gfa.optional_data.all());
+        store.tags.add_slice(gfa.tags.all());
+        store.other_lines.add_slice(gfa.other_lines.all());
+        store.line_order.add_slice(gfa.line_order.all());
+        store.names = NameMap::build(gfa);
+        store.sequences_skipped = gfa.sequences_skipped;
+        store
+    }
+
+    /// Set an optional field (tag) on a segment, like `DP:i:12`.
+    ///
+    /// `kind` is the GFA type character for the value (e.g., `i` or `Z`). If the
+    /// segment already has a field with this tag, it is replaced; otherwise, the new
+    /// field goes at the end. The old optional data stays in its pool until you call
+    /// `compact`. The value must be printable ASCII text, so it cannot break up the
+    /// line; otherwise, nothing changes and we return an error.
+    pub fn set_seg_tag(
+        &mut self,
+        id: Id<Segment>,
+        tag: [u8; 2],
+        kind: u8,
+        value: &[u8],
+    ) -> Result<(), BadTagValue> {
+        let old = self.segs.as_ref()[id].optional;
+        let (optional, tags) = self.set_tag(old, tag, kind, value)?;
+        let seg = self.segs.get_mut(id);
+        seg.optional = optional;
+        seg.tags = tags;
+
+        // The tag may change the segment's rGFA coordinates.
+        let mut rgfa = self.rgfa.take();
+        rgfa.retain(|coords| { coords.seg } != id);
+        if let Some(coords) = self.find_rgfa(id) {
+            let idx = rgfa.partition_point(|other| other.seg.index() < id.index());
+            rgfa.insert(idx, coords);
+        }
+        self.rgfa = rgfa.into();
+        Ok(())
+    }
+
+    /// Set an optional field (tag) on a path. See `set_seg_tag`.
+    pub fn set_path_tag(
+        &mut self,
+        id: Id<Path>,
+        tag: [u8; 2],
+        kind: u8,
+        value: &[u8],
+    ) -> Result<(), BadTagValue> {
+        let old = self.paths.as_ref()[id].optional;
+        let (optional, tags) = self.set_tag(old, tag, kind, value)?;
+        let path = self.paths.get_mut(id);
+        path.optional = optional;
+        path.tags = tags;
+        Ok(())
+    }
+
+    /// Add a copy of some optional data with one tag set, returning the new spans for
+    /// the data and its tags.
+    fn set_tag(
+        &mut self,
+        old: Span<u8>,
+        tag: [u8; 2],
+        kind: u8,
+        value: &[u8],
+    ) -> Result<(Span<u8>, Span<Tag>), BadTagValue> {
+        if !value.iter().all(|&c| matches!(c, b' '..=b'~')) {
+            return Err(BadTagValue);
+        }
+        let mut field = vec![tag[0], tag[1], b':', kind, b':'];
+        field.extend_from_slice(value);
+
+        let old_data = &self.optional_data.as_ref()[old];
+        let mut fields: Vec<&[u8]> = old_data
+            .split(|&c| c == b'\t')
+            .filter(|f| !f.is_empty())
+            .collect();
+        match fields
+            .iter_mut()
+            .find(|f| f.len() >= 3 && f[..2] == tag && f[2] == b':')
+        {
+            Some(existing) => *existing = &field,
+            None => fields.push(&field),
+        }
+        let new_data = fields.join(&b'\t');
+        Ok(self.add_optional(&new_data))
+    }
+
+    /// Rename every segment by applying `f` to its name.
+    ///
+    /// Links, paths, and everything else refer to segments by ID, so this changes only
+    /// the names, and all IDs stay valid. Old text names stay in their pool until you
+    /// call `compact`.
+    ///
+    /// We check all the new names before renaming anything. Each must be unique and
+    /// nonempty, with no whitespace and none of the characters `,`, `+`, or `-` (which
+    /// would be ambiguous in path steps). Otherwise, we return an error for the first
+    /// bad name and leave every segment as it was.
+    pub fn rename_segments(
+        &mut self,
+        mut f: impl FnMut(&str) -> String,
+    ) -> Result<(), BadSegmentName> {
+        let gfa = self.as_ref();
+        let new_names: Vec<String> = gfa
+            .segs
+            .all()
+            .iter()
+            .map(|seg| f(&gfa.get_seg_name(seg).to_string()))
+            .collect();
+
+        let mut seen = HashSet::with_capacity(new_names.len());
+        for new in &new_names {
+            if new.is_empty() {
+                return Err(BadSegmentName::Empty);
+            }
+            if new.contains(|c: char| c.is_whitespace() || matches!(c, ',' | '+' | '-')) {
+                return Err(BadSegmentName::Invalid(new.clone()));
+            }
+            if !seen.insert(new.as_str()) {
+                return Err(BadSegmentName::Duplicate(new.clone()));
+            }
+        }
+
+        for (idx, new) in new_names.iter().enumerate() {
+            let (name, text_name) = match SegName::parse(new.as_bytes()) {
+                SegName::Num(num) => (num, Span::new_empty()),
+                SegName::Text(text) => (0, self.name_data.add_slice(text)),
+            };
+            let seg = self.segs.get_mut(Id::new(idx));
+            seg.name = name;
+            seg.text_name = text_name;
+        }
+        self.names = NameMap::build(&self.as_ref());
+        Ok(())
+    }
+
+    /// Mark a segment for removal, along with every link, jump, containment, and path
+    /// step that refers to it.
+    ///
+    /// This only records a tombstone, so it is cheap and all IDs stay valid: the
+    /// segment and its edges remain in the graph until the next `compact`, which
+    /// removes them all in one pass and renumbers the rest (see `retain_segments`).
+    pub fn remove_segment(&mut self, id: Id<Segment>) {
+        self.removed.insert(id);
+    }
+
+    /// Remove every segment that does not satisfy a predicate, along with every link,
+    /// jump, containment, and path step that refers to it.
+    ///
+    /// Segment IDs stay dense, so this renumbers segments and invalidates previously
+    /// held IDs: every segment ID shifts down past each removed segment, link, jump,
+    /// and containment IDs shift past each removed edge, and the step spans of all
+    /// paths are rewritten. Path IDs stay valid. A path that loses steps also loses its
+    /// overlaps, which would no longer line up with its steps. The removed data (e.g.,
+    /// the segment's sequence) stays in its pool until you call `compact`.
+    pub fn retain_segments(&mut self, keep: impl Fn(Id<Segment>) -> bool) {
+        // Assign new, dense IDs to the segments we keep.
+        let old_segs = self.segs.take();
+        let mut seg_map = Vec::with_capacity(old_segs.len());
+        for (idx, seg) in old_segs.into_iter().enumerate() {
+            seg_map.push(keep(Id::new(idx)).then(|| self.segs.add(seg)));
+        }
+        let translate = |handle: Handle| {
+            let seg_id = seg_map[handle.segment().index()]?;
+            Some(Handle::new(seg_id, handle.orient()))
+        };
+
+        // Note which edges survive, so we can drop the others from the line order.
+        let kept = |a: Handle, b: Handle| translate(a).is_some() && translate(b).is_some();
+        let links_kept: Vec<bool> = self
+            .links
+            .as_ref()
+            .all()
+            .iter()
+            .map(|l| kept(l.from, l.to))
+            .collect();
+        let jumps_kept: Vec<bool> = self
+            .jumps
+            .as_ref()
+            .all()
+            .iter()
+            .map(|j| kept(j.from, j.to))
+            .collect();
+        let containments_kept: Vec<bool> = self
+            .containments
+            .as_ref()
+            .all()
+            .iter()
+            .map(|c| kept(c.container, c.contained))
+            .collect();
+
+        // Drop the removed segments' rGFA coordinates.
+        let old_rgfa = self.rgfa.take();
+        self.rgfa
+            .add_iter(old_rgfa.into_iter().filter_map(|coords| {
+                Some(RgfaSegment {
+                    seg: seg_map[coords.seg.index()]?,
+                    ..coords
+                })
+            }));
+
+        // Drop links that touch removed segments.
+        let old_links = self.links.take();
+        self.links
+            .add_iter(old_links.into_iter().filter_map(|link| {
+                Some(Link {
+                    from: translate(link.from)?,
+                    to: translate(link.to)?,
+                    ..link
+                })
+            }));
+
+        // Likewise for jumps.
+        let old_jumps = self.jumps.take();
+        self.jumps
+            .add_iter(old_jumps.into_iter().filter_map(|jump| {
+                Some(Jump {
+                    from: translate(jump.from)?,
+                    to: translate(jump.to)?,
+                    ..jump
+                })
+            }));
+
+        // And for containments.
+        let old_containments = self.containments.take();
+        self.containments
+            .add_iter(old_containments.into_iter().filter_map(|containment| {
+                Some(Containment {
+                    container: translate(containment.container)?,
+                    contained: translate(containment.contained)?,
+                    ..containment
+                })
+            }));
+
+        // Drop path steps that visit removed segments.
+        let old_steps = self.steps.take();
+        let mut paths = self.paths.take();
+        for path in paths.iter_mut() {
+            let steps = &old_steps[Range::from(path.steps)];
+            let new_steps = self
+                .steps
+                .add_iter(steps.iter().filter_map(|&step| translate(step)));
+            if new_steps.len() != steps.len() {
+                path.overlaps = Span::new_empty();
+            }
+            path.steps = new_steps;
+        }
+        self.paths = paths.into();
+
+        // Drop the removed lines from the line order.
+        let old_order = self.line_order.take();
+        let (mut segs, mut links, mut jumps, mut containments) = (0, 0, 0, 0);
+        let next = |kept: &[bool], count: &mut usize| {
+            *count += 1;
+            kept[*count - 1]
+        };
+        let segs_kept: Vec<bool> = seg_map.iter().map(Option::is_some).collect();
+        self.line_order
+            .add_iter(old_order.into_iter().filter(|&kind| match kind.try_into() {
+                Ok(LineKind::Segment) => next(&segs_kept, &mut segs),
+                Ok(LineKind::Link) => next(&links_kept, &mut links),
+                Ok(LineKind::Jump) => next(&jumps_kept, &mut jumps),
+                Ok(LineKind::Containment) => next(&containments_kept, &mut containments),
+                _ => true,
+            }));
+        self.names = NameMap::build(&self.as_ref());
+    }
+
+    /// Apply pending removals and reclaim the space in the data pools that is no
+    /// longer referenced.
+    ///
+    /// This rewrites every span in the store (so spans held elsewhere become invalid).
+    /// If any segments were marked by `remove_segment`, it also drops them and
+    /// renumbers segments, links, jumps, and containments like `retain_segments`;
+    /// otherwise, it does not change any IDs.
+    pub fn compact(&mut self) {
+        if !self.removed.is_empty() {
+            let removed = std::mem::take(&mut self.removed);
+            self.retain_segments(|id| !removed.contains(&id));
+        }
+
+        // Segment names, sequences, and optional data.
+        let name_data = self.name_data.take();
+        let seq_data = self.seq_data.take();
+        let optional_data = self.optional_data.take();
+        self.tags.take();
+        let mut segs = self.segs.take();
+        for seg in segs.iter_mut() {
+            seg.text_name = self
+                .name_data
+                .add_slice(&name_data[Range::from(seg.text_name)]);
+            seg.seq = self.seq_data.add_slice(&seq_data[Range::from(seg.seq)]);
+            seg.optional = self
+                .optional_data
+                .add_slice(&optional_data[Range::from(seg.optional)]);
+            seg.tags = self.add_tags(seg.optional);
+        }
+        self.segs = segs.into();
+        self.rgfa.take();
+        for idx in 0..self.segs.len() {
+            self.add_rgfa(Id::new(idx));
+        }
+        let mut jumps = self.jumps.take();
+        for jump in jumps.iter_mut() {
+            jump.optional = self
+                .optional_data
+                .add_slice(&optional_data[Range::from(jump.optional)]);
+        }
+        self.jumps = jumps.into();
+
+        // Link overlaps.
+        let alignment = self.alignment.take();
+        let mut links = self.links.take();
+        for link in links.iter_mut() {
+            let overlap = self
+                .alignment
+                .add_slice(&alignment[Range::from(link.overlap)]);
+            link.overlap = CompactSpan::new(overlap.start, link.overlap.len);
+            link.optional = self
+                .optional_data
+                .add_slice(&optional_data[Range::from(link.optional)]);
+            link.tags = self.add_tags(link.optional);
+        }
+        self.links = links.into();
+
+        // Containment overlaps and optional data.
+        let mut containments = self.containments.take();
+        for containment in containments.iter_mut() {
+            containment.overlap = self
+                .alignment
+                .add_slice(&alignment[Range::from(containment.overlap)]);
+            containment.optional = self
+                .optional_data
+                .add_slice(&optional_data[Range::from(containment.optional)]);
+        }
+        self.containments = containments.into();
+
+        // Path names, steps, overlaps, and optional data.
+        let steps = self.steps.take();
+        let overlaps = self.overlaps.take();
+        let mut paths = self.paths.take();
+        for path in paths.iter_mut() {
+            path.name = self.name_data.add_slice(&name_data[Range::from(path.name)]);
+            path.steps = self.steps.add_slice(&steps[Range::from(path.steps)]);
+            path.optional = self
+                .optional_data
+                .add_slice(&optional_data[Range::from(path.optional)]);
+            path.tags = self.add_tags(path.optional);
+            let path_overlaps: Vec<_> = overlaps[Range::from(path.overlaps)]
+                .iter()
+                .map(|overlap| self.alignment.add_slice(&alignment[Range::from(overlap)]))
+                .collect();
+            path.overlaps = self.overlaps.add_iter(path_overlaps);
+        }
+        self.paths = paths.into();
+
+        // Walk names.
+        let mut walks = self.walks.take();
+        for walk in walks.iter_mut() {
+            walk.sample = self
+                .name_data
+                .add_slice(&name_data[Range::from(walk.sample)]);
+            walk.seq_id = self
+                .name_data
+                .add_slice(&name_data[Range::from(walk.seq_id)]);
+        }
+        self.walks = walks.into();
+    }
+}
+
+#[test]
+fn test_seg_name() {
+    assert_eq!(SegName::parse(b"42"), SegName::Num(42));
+    assert_eq!(SegName::parse(b"0"), SegName::Num(0));
+    assert_eq!(
+        SegName::parse(b"007"),
+        SegName::Text(b"007".as_ref().into())
+    );
+    assert_eq!(SegName::parse(b"s1"), SegName::Text(b"s1".as_ref().into()));
+    assert_eq!(
+        SegName::parse(b"99999999999999999999999"),
+        SegName::Text(b"99999999999999999999999".as_ref().into())
+    );
+}
+
+#[test]
+fn test_name_spelling() {
+    // Names that are not canonical numbers print exactly as written, even after a
+    // trip through the binary format.
+    let text = "S\t007\tACGT\nS\t+5\tAC\nS\t10\tGG\nS\t0\tT\n\
+        L\t007\t+\t+5\t-\t0M\nL\t10\t+\t0\t+\t0M\nP\tp\t007+,+5-,10+,0+\t*\n";
+    let store = crate::parse::Parser::for_heap()
+        .parse_mem(text.as_bytes())
+        .unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(format!("{}", &gfa), text);
+
+    let mut buf = vec![0; crate::file::size(&gfa)];
+    crate::file::dump(&gfa, &mut buf);
+    assert_eq!(format!("{}", &crate::file::view(&buf).unwrap()), text);
+}
+
+#[test]
+fn test_check_bounds() {
+    let text = b"S\t1\tACGT\nS\t2\tC\nL\t1\t+\t2\t-\t0M\nP\tp\t1+,2-\t*\n";
+    let mut store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    assert_eq!(store.as_ref().check_bounds(), Ok(()));
+
+    store.links.get_mut(Id::new(0)).to = Handle::new(Id::new(7), Orientation::Forward);
+    let err = store.as_ref().check_bounds().unwrap_err();
+    assert_eq!(err.to_string(), "link 0 refers to data outside the graph");
+
+    // The line order can't promise more records than the graph has.
+    let mut store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    store.line_order.add(LineKind::Segment as u8);
+    assert_eq!(
+        store.as_ref().check_bounds(),
+        Err(OutOfBounds {
+            record: "line order entry",
+            index: 4
+        })
+    );
+}
+
+#[test]
+fn test_set_tag() {
+    let mut store = HeapGFAStore::default();
+    let seg = store.add_seg(1, b"ACGT", b"LN:i:4\tDP:i:3");
+    store.set_seg_tag(seg, *b"DP", b'i', b"12").unwrap();
+    store.set_seg_tag(seg, *b"SM", b'Z', b"HG002").unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(
+        gfa.get_optional_data(&gfa.segs[seg]),
+        "LN:i:4\tDP:i:12\tSM:Z:HG002"
+    );
+    assert_eq!(gfa.get_tag_int(&gfa.segs[seg], b"DP"), Some(12));
+    assert_eq!(gfa.get_tag_str(&gfa.segs[seg], b"SM").unwrap(), "HG002");
+
+    // Values that would break up the line are rejected.
+    for bad in [&b"a\tb"[..], b"a\nb", b"\x07", "é".as_bytes()] {
+        assert_eq!(store.set_seg_tag(seg, *b"SM", b'Z', bad), Err(BadTagValue));
+    }
+    let gfa = store.as_ref();
+    assert_eq!(gfa.get_tag_str(&gfa.segs[seg], b"SM").unwrap(), "HG002");
+
+    // Compacting rebuilds the tags along with the data they point into.
+    store.compact();
+    let gfa = store.as_ref();
+    assert_eq!(gfa.tags.len(), 3);
+    assert_eq!(gfa.get_tag_int(&gfa.segs[seg], b"LN"), Some(4));
+    assert_eq!(gfa.get_tag_int(&gfa.segs[seg], b"DP"), Some(12));
+}
+
+#[test]
+fn test_flip() {
+    for orient in [Orientation::Forward, Orientation::Backward] {
+        assert_ne!(orient.flip(), orient);
+        assert_eq!(orient.flip().flip(), orient);
+
+        let handle = Handle::new(Id::new(42), orient);
+        assert_eq!(handle.flip().segment(), handle.segment());
+        assert_eq!(handle.flip().orient(), orient.flip());
+        assert_eq!(handle.flip().flip(), handle);
+    }
+}
+
+#[test]
+fn test_iter_segments() {
+    let mut store = HeapGFAStore::default();
+    store.add_seg(1, b"ACGT", &[]);
+    store.add_seg(7, b"G", &[]);
+    let gfa = store.as_ref();
+    let segs: Vec<_> = gfa
+        .iter_segments()
+        .map(|(id, seg, seq)| (id.index(), { seg.name }, seq.to_string()))
+        .collect();
+    assert_eq!(segs, [(0, 1, "ACGT".into()), (1, 7, "G".into())]);
+}
+
+#[test]
+fn test_segments_by_length() {
+    // Segment 3 has no sequence, so its length comes from its `LN` tag.
+    let text = b"S\t1\tACGT\nS\t2\tA\nS\t3\t*\tLN:i:10\nS\t4\tACG\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let longer = |len| -> Vec<_> { gfa.segments_longer_than(len).map(|id| id.index()).collect() };
+    let shorter = |len| -> Vec<_> {
+        gfa.segments_no_longer_than(len)
+            .map(|id| id.index())
+            .collect()
+    };
+
+    // The boundary is exclusive: a segment of length 3 is not longer than 3.
+    assert_eq!(longer(3), [0, 2]);
+    assert_eq!(shorter(3), [1, 3]);
+    assert_eq!(longer(0), [0, 1, 2, 3]);
+    assert_eq!(longer(10), [] as [usize; 0]);
+    assert_eq!(shorter(10).len(), 4);
+}
+
+#[test]
+fn test_rename_segments() {
+    let text = "S\ts1\tA\nS\ts2\tC\nS\t3\tG\nL\ts1\t+\ts2\t-\t0M\nP\tp\ts1+,s2-,3+\t*\n";
+    let mut store = crate::parse::Parser::for_heap()
+        .parse_mem(text.as_bytes())
+        .unwrap();
+    store
+        .rename_segments(|name| match name.strip_prefix('s') {
+            Some(num) => format!("chr1_{}", num),
+            None => format!("{}0", name),
+        })
+        .unwrap();
+
+    // References follow the renamed segments.
+    let gfa = store.as_ref();
+    assert_eq!(
+        format!("{}", &gfa),
+        "S\tchr1_1\tA\nS\tchr1_2\tC\nS\t30\tG\nL\tchr1_1\t+\tchr1_2\t-\t0M\nP\tp\tchr1_1+,chr1_2-,30+\t*\n"
+    );
+    let seg = gfa.find_named_seg(SegName::parse(b"chr1_2")).unwrap();
+    assert_eq!(gfa.get_seq(&gfa.segs[seg]), "C");
+    assert_eq!(gfa.find_seg(30), Some(Id::new(2)));
+
+    // Bad names are rejected before anything changes.
+    let before = format!("{}", &store.as_ref());
+    let mut rename = |new: [&str; 3]| {
+        let mut names = new.into_iter();
+        store.rename_segments(|_| names.next().unwrap().to_string())
+    };
+    assert_eq!(rename(["a", "", "b"]), Err(BadSegmentName::Empty));
+    for bad in ["a b", "a\tb", "a,b", "a+", "-a"] {
+        assert_eq!(
+            rename(["x", "y", bad]),
+            Err(BadSegmentName::Invalid(bad.to_string()))
+        );
+    }
+    assert_eq!(
+        rename(["a", "b", "a"]),
+        Err(BadSegmentName::Duplicate("a".to_string()))
+    );
+    assert_eq!(format!("{}", &store.as_ref()), before);
+}
+
+#[test]
+fn test_remove_segment() {
+    let mut store = HeapGFAStore::default();
+    let s1 = store.add_seg(1, b"AAAA", &[]);
+    let s2 = store.add_seg(2, b"CC", &[]);
+    let s3 = store.add_seg(3, b"GGG", &[]);
+    let h = |seg| Handle::new(seg, Orientation::Forward);
+    store.add_link(h(s1), h(s2), vec![], &[]);
+    store.add_link(h(s2), h(s3), vec![], &[]);
+    store.add_link(h(s1), h(s3), vec![], &[]);
+    let steps = store.add_steps([h(s1), h(s2), h(s3)].into_iter());
+    store.add_path(b"p", steps, std::iter::empty(), &[]);
+
+    // Removal is deferred, so IDs stay valid until we compact.
+    store.remove_segment(s2);
+    assert_eq!(store.as_ref().segs.len(), 3);
+    store.remove_segment(s2);
+    store.compact();
+    let gfa = store.as_ref();
+    let names: Vec<_> = gfa.segs.all().iter().map(|seg| seg.name).collect();
+    assert_eq!(names, vec![1, 3]);
+    assert_eq!(gfa.links.len(), 1);
+    assert_eq!(
+        gfa.links[Id::new(0)].to,
+        Handle::new(Id::new(1), Orientation::Forward)
+    );
+    let path_steps: Vec<_> = gfa
+        .get_path_steps(&gfa.paths[Id::new(0)])
+        .copied()
+        .collect();
+    assert_eq!(path_steps, vec![h(Id::new(0)), h(Id::new(1))]);
+    assert_eq!(gfa.seq_data.len(), 7);
+    assert_eq!(gfa.get_seq(&gfa.segs[Id::new(1)]), "GGG");
+    assert_eq!(gfa.get_path_name(&gfa.paths[Id::new(0)]), "p");
+}
+
+#[test]
+fn test_seq_range() {
+    let mut store = HeapGFAStore::default();
+    store.add_seg(1, b"GATTACA", &[]);
+    let seg = store.add_seg(2, b"ACGTACGT", &[]);
+    let gfa = store.as_ref();
+    let seg = &gfa.segs[seg];
+    assert_eq!(gfa.get_seq_range(seg, 2, 5).unwrap(), "GTA");
+    assert_eq!(gfa.get_seq_range(seg, 0, 8).unwrap(), "ACGTACGT");
+    assert_eq!(gfa.get_seq_range(seg, 8, 8).unwrap(), "");
+    assert_eq!(gfa.get_seq_range(seg, 4, 9), None);
+    assert_eq!(gfa.get_seq_range(seg, 5, 4), None);
+}
+
+#[test]
+fn test_add_by_name() {
+    let text = "S\t1\tA\nS\tx\tC\nL\t1\t+\tx\t+\t0M\n";
+    let mut store = crate::parse::Parser::for_heap()
+        .parse_mem(text.as_bytes())
+        .unwrap();
+    let (mut overlaps, _) = gfaline::parse_maybe_overlap_list(b"1M").unwrap();
+    let link = store
+        .add_link_by_name(
+            b"x",
+            Orientation::Forward,
+            b"1",
+            Orientation::Backward,
+            overlaps.remove(0),
+        )
+        .unwrap();
+    assert_eq!(link, Id::new(1));
+    store
+        .add_path_by_name(
+            b"p",
+            [
+                (&b"1"[..], Orientation::Forward),
+                (b"x", Orientation::Backward),
+            ],
+        )
+        .unwrap();
+    assert_eq!(
+        store.add_link_by_name(
+            b"1",
+            Orientation::Forward,
+            b"y",
+            Orientation::Forward,
+            vec![]
+        ),
+        Err(UnknownSegment("y".into()))
+    );
+    assert!(store
+        .add_path_by_name(
+            b"q",
+            [
+                (&b"1"[..], Orientation::Forward),
+                (b"2", Orientation::Forward)
+            ]
+        )
+        .is_err());
+
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\tx\tC\nL\t1\t+\tx\t+\t0M\nL\tx\t+\t1\t-\t1M\nP\tp\t1+,x-\t*\n"
+    );
+    assert_eq!(store.as_ref().steps.len(), 2);
+}
+
+#[test]
+fn test_rgfa() {
+    let text = b"S\ts1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\n\
+        S\ts2\tGG\tSR:i:1\tSN:Z:chr1\tSO:i:4\n\
+        S\ts3\tT\tSN:Z:chr2\tSO:i:7\tSR:i:0\n";
+    let mut store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    assert!(gfa.is_rgfa());
+    let coords = gfa.get_rgfa(Id::new(1)).unwrap();
+    assert_eq!(gfa.get_stable_name(coords), "chr1");
+    assert_eq!(({ coords.offset }, { coords.rank }), (4, 1));
+    assert_eq!(format!("{}", &gfa), std::str::from_utf8(text).unwrap());
+
+    // Setting a tag updates the coordinates, and a segment without all three tags
+    // has none.
+    store.set_seg_tag(Id::new(0), *b"SO", b'i', b"10").unwrap();
+    store.set_seg_tag(Id::new(2), *b"SR", b'Z', b"x").unwrap();
+    let gfa = store.as_ref();
+    assert_eq!({ gfa.get_rgfa(Id::new(0)).unwrap().offset }, 10);
+    assert!(gfa.get_rgfa(Id::new(2)).is_none());
+    assert!(!gfa.is_rgfa());
+
+    // Removing segments renumbers the coordinates, which survive compaction.
+    store.remove_segment(Id::new(0));
+    store.compact();
+    let gfa = store.as_ref();
+    assert_eq!(gfa.rgfa.len(), 1);
+    let coords = gfa.get_rgfa(Id::new(0)).unwrap();
+    assert_eq!(gfa.get_stable_name(coords), "chr1");
+    assert_eq!({ coords.offset }, 4);
+
+    let gfa = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t1\tA\tSN:Z:chr1\tSO:i:0\n")
+        .unwrap();
+    assert!(!gfa.as_ref().is_rgfa());
+}
+
+#[test]
+fn test_rgfa_minigraph() {
+    // Minigraph puts `LN` before the rGFA tags and tags its links with a rank too.
+    let text = b"S\ts1\tACGTA\tLN:i:5\tSN:Z:chr1\tSO:i:0\tSR:i:0\n\
+        S\ts2\tGG\tLN:i:2\tSN:Z:chr1\tSO:i:5\tSR:i:0\n\
+        S\ts3\tTTT\tLN:i:3\tSN:Z:HG002#1#chr1\tSO:i:1200\tSR:i:1\n\
+        L\ts1\t+\ts2\t+\t0M\tSR:i:0\tL1:i:5\tL2:i:2\n\
+        L\ts1\t+\ts3\t+\t0M\tSR:i:1\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    assert!(gfa.is_rgfa());
+    let coords: Vec<_> = gfa
+        .segs
+        .items()
+        .map(|(id, _)| {
+            let coords = gfa.get_rgfa(id).unwrap();
+            (
+                gfa.get_stable_name(coords).to_string(),
+                { coords.offset },
+                { coords.rank },
+            )
+        })
+        .collect();
+    assert_eq!(
+        coords,
+        [
+            ("chr1".to_string(), 0, 0),
+            ("chr1".to_string(), 5, 0),
+            ("HG002#1#chr1".to_string(), 1200, 1),
+        ]
+    );
+    assert_eq!(format!("{}", &gfa), std::str::from_utf8(text).unwrap());
+}
+
+#[test]
+fn test_unknown_seq() {
+    let text = b"S\t1\tACG\nS\t2\t*\tLN:i:5\nS\t3\t*\nP\tp\t1+,1-\t*\nP\tq\t1+,2+\t*\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+
+    // A `*` sequence is empty, and the length comes from the `LN` tag if there is one.
+    let segs = gfa.segs.all();
+    assert!(gfa.get_seq(&segs[1]).is_empty());
+    assert_eq!(
+        segs.iter().map(|seg| gfa.seg_len(seg)).collect::<Vec<_>>(),
+        [3, 5, 0]
+    );
+    assert_eq!(format!("{}", &gfa), std::str::from_utf8(text).unwrap());
+
+    // Spelling out a path needs all its sequences.
+    let paths = gfa.paths.all();
+    assert_eq!(gfa.get_path_seq(&paths[0]).unwrap(), b"ACGCGT");
+    assert_eq!(
+        gfa.get_path_seq(&paths[1]).unwrap_err().to_string(),
+        "segment `2` has no sequence"
+    );
+    assert!(gfa.sequences_loaded());
+
+    // Without the sequences, we say so instead.
+    let store = crate::parse::Parser::for_heap()
+        .skip_sequences()
+        .parse_mem(text)
+        .unwrap();
+    let gfa = store.as_ref();
+    assert!(!gfa.sequences_loaded());
+    assert_eq!(gfa.check_sequences(), Err(MissingSequence::NotLoaded));
+    assert_eq!(
+        gfa.get_path_seq(&gfa.paths[Id::new(0)])
+            .unwrap_err()
+            .to_string(),
+        "sequences were not loaded"
+    );
+    assert_eq!(
+        gfa.segs
+            .all()
+            .iter()
+            .map(|seg| gfa.seg_len(seg))
+            .collect::<Vec<_>>(),
+        [3, 5, 0]
+    );
+
+    // A graph whose sequences are all `*` still has its sequences loaded.
+    let store = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t1\t*\tLN:i:3\nS\t2\t*\tLN:i:5\nP\tp\t1+,2+\t*\n")
+        .unwrap();
+    let gfa = store.as_ref();
+    assert!(gfa.sequences_loaded());
+    assert_eq!(
+        gfa.get_path_seq(&gfa.paths[Id::new(0)])
+            .unwrap_err()
+            .to_string(),
+        "segment `1` has no sequence"
+    );
+}
+
+#[test]
+fn test_self_loops() {
+    let text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t2\t+\t0M\n\
+        L\t1\t-\t1\t+\t0M\nL\t2\t-\t1\t-\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let loops = gfa.self_loops();
+    assert_eq!(loops, vec![Id::new(1), Id::new(2)]);
+
+    // One repeats segment 2 in tandem, and the other is a hairpin on segment 1.
+    assert!(!gfa.links[loops[0]].is_inverting());
+    assert!(gfa.links[loops[1]].is_inverting());
+}
+
+#[test]
+fn test_link_handles() {
+    let text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t-\t0M\nL\t2\t-\t1\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let handle = |seg: u32, orient| Handle::new(Id::new(seg as usize), orient);
+    let pairs: Vec<_> = gfa.link_handles().collect();
+    assert_eq!(
+        pairs,
+        vec![
+            (
+                handle(0, Orientation::Forward),
+                handle(1, Orientation::Backward)
+            ),
+            (
+                handle(1, Orientation::Backward),
+                handle(0, Orientation::Forward)
+            ),
+        ]
+    );
+
+    // Flipping a link's ends walks it backward.
+    let (from, to) = pairs[0];
+    assert_eq!(
+        (to.flip(), from.flip()),
+        (
+            handle(1, Orientation::Forward),
+            handle(0, Orientation::Backward)
+        )
+    );
+}
+
+#[test]
+fn test_pansn() {
+    let split = |name: &'static [u8]| {
+        parse_pansn(name).map(|(s, h, c)| (s.to_string(), h.to_string(), c.to_string()))
+    };
+    let parts = |s: &str, h: &str, c: &str| Some((s.into(), h.into(), c.into()));
+    assert_eq!(split(b"HG002#1#chr1"), parts("HG002", "1", "chr1"));
+    assert_eq!(
+        split(b"HG002#2#chr1#alt:10-20"),
+        parts("HG002", "2", "chr1#alt:10-20")
+    );
+    assert_eq!(split(b"grch38#chr1"), None);
+    assert_eq!(split(b"HG002##chr1"), None);
+    assert_eq!(split(b"HG002#1#"), None);
+    assert_eq!(split(b"path"), None);
+
+    // Walks get PanSN names, so they group with paths from the same sample.
+    let text = b"S\t1\tA\nP\tHG002#1#chr1\t1+\t*\nP\tref\t1+\t*\nP\tHG003#1#chr1\t1+\t*\n\
+        W\tHG002\t2\tchr1\t0\t1\t>1\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let by_sample = gfa.paths_by_sample();
+    assert_eq!(by_sample.len(), 2);
+    assert_eq!(by_sample["HG002"], vec![Id::new(0), Id::new(3)]);
+    assert_eq!(by_sample["HG003"], vec![Id::new(2)]);
+    let walk = &gfa.paths[Id::new(3)];
+    assert_eq!(
+        gfa.parse_pansn(walk).map(|(_, hap, contig)| (hap, contig)),
+        Some((b"2".as_ref().into(), b"chr1:0-1".as_ref().into()))
+    );
+}
@@ -21,7 +21,7 @@ pub struct GAFLookup {
     bench: Option<u32>,
 }
 
-pub fn gaf_lookup(gfa: &flatgfa::FlatGFA, args: GAFLookup) {
+pub fn gaf_lookup(gfa: &flatgfa::FlatGFA, args: GAFLookup) -> Result<(), String> {
     // Build a map to efficiently look up segments by name.
     let name_map = NameMap::build(gfa);
 
@@ -30,10 +30,11 @@ pub fn gaf_lookup(gfa: &flatgfa::FlatGFA, args: GAFLookup) {
 
     if args.seqs {
         // Print the actual sequences for each chunk in the GAF.
+        gfa.check_sequences().map_err(|err| err.to_string())?;
         for read in parser {
             print!("{}\t", read.name);
             for event in PathChunker::new(gfa, &name_map, read) {
-                print_seq(gfa, event);
+                print_seq(gfa, event?);
             }
             println!();
         }
@@ -41,7 +42,8 @@ pub fn gaf_lookup(gfa: &flatgfa::FlatGFA, args: GAFLookup) {
         // Benchmarking mode: just process all the chunks but print nothing.
         let mut count = 0;
         for (i, read) in parser.enumerate() {
-            for _event in PathChunker::new(gfa, &name_map, read) {
+            for event in PathChunker::new(gfa, &name_map, read) {
+                event?;
                 count += 1;
             }
             if limit > 0 && i >= (limit as usize) {
@@ -54,15 +56,16 @@ pub fn gaf_lookup(gfa: &flatgfa::FlatGFA, args: GAFLookup) {
         for read in parser {
             println!("{}", read.name);
             for event in PathChunker::new(gfa, &name_map, read) {
-                print_event(gfa, event);
+                print_event(gfa, event?);
             }
         }
     }
+    Ok(())
 }
 
 fn print_event(gfa: &flatgfa::FlatGFA, event: ChunkEvent) {
     let seg = gfa.segs[event.handle.segment()];
-    let seg_name = seg.name;
+    let seg_name = gfa.get_seg_name(&seg);
     match event.range {
         ChunkRange::Partial(start, end) => {
             println!(
@@ -80,7 +83,7 @@ fn print_event(gfa: &flatgfa::FlatGFA, event: ChunkEvent) {
                 event.index,
                 seg_name,
                 event.handle.orient(),
-                seg.len()
+                gfa.seg_len(&seg)
             );
         }
         ChunkRange::None => {
@@ -137,7 +140,7 @@ impl<'a> GAFParser<'a> {
     }
 
     fn int_field(&mut self) -> Option<usize> {
-        let val = parse_int(&self.buf, &mut self.pos);
+        let val = parse_int(self.buf, &mut self.pos);
         assert!(matches!(self.buf[self.pos], b'\t' | b'\n'));
         self.pos += 1;
         val
@@ -195,6 +198,7 @@ impl<'a> Iterator for GAFParser<'a> {
 
 struct PathChunker<'a, 'b> {
     gfa: &'a flatgfa::FlatGFA<'a>,
+    read: &'b BStr,
     name_map: &'a NameMap,
     steps: PathParser<'b>,
     start: usize,
@@ -212,6 +216,7 @@ impl<'a, 'b> PathChunker<'a, 'b> {
         let steps = PathParser::new(read.path);
         Self {
             gfa,
+            read: read.name,
             name_map,
             steps,
             start: read.start,
@@ -239,13 +244,18 @@ enum ChunkRange {
 }
 
 impl<'a, 'b> Iterator for PathChunker<'a, 'b> {
-    type Item = ChunkEvent;
+    type Item = Result<ChunkEvent, String>;
 
-    fn next(&mut self) -> Option<ChunkEvent> {
+    fn next(&mut self) -> Option<Self::Item> {
         let (seg_name, forward) = self.steps.next()?;
 
         // Get the corresponding handle from the GFA.
-        let seg_id = self.name_map.get(seg_name);
+        let Some(seg_id) = self.name_map.find(seg_name) else {
+            return Some(Err(format!(
+                "read {} (step {}): unknown segment {}",
+                self.read, self.index, seg_name
+            )));
+        };
         let dir = match forward {
             true => flatgfa::Orientation::Forward,
             false => flatgfa::Orientation::Backward,
@@ -253,7 +263,7 @@ impl<'a, 'b> Iterator for PathChunker<'a, 'b> {
         let handle = flatgfa::Handle::new(seg_id, dir);
 
         // Accumulate the length to track our position in the path.
-        let seg_len = self.gfa.segs[seg_id].len();
+        let seg_len = self.gfa.seg_len(&self.gfa.segs[seg_id]);
         let next_pos = self.pos + seg_len;
         let range = if !self.started && self.start < next_pos {
             self.started = true;
@@ -283,7 +293,7 @@ impl<'a, 'b> Iterator for PathChunker<'a, 'b> {
             range,
         };
         self.index += 1;
-        Some(out)
+        Some(Ok(out))
     }
 }
 
@@ -323,9 +333,9 @@ fn parse_int(bytes: &[u8], index: &mut usize) -> Option<usize> {
     }
 
     if first_digit {
-        return None;
+        None
     } else {
-        return Some(num);
+        Some(num)
     }
 }
 
@@ -348,7 +358,7 @@ impl<'a> Iterator for PathParser<'a> {
 
         // Parse the integer segment name.
         let seg_name = parse_int(self.str, &mut self.index)?;
-        return Some((seg_name, forward));
+        Some((seg_name, forward))
     }
 }
 
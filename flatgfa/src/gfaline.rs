@@ -1,106 +1,720 @@
-use crate::flatgfa::{AlignOp, Orientation};
-use atoi::FromRadix10;
+use crate::flatgfa::{AlignOp, Orientation, OverlapSpelling};
+use atoi::{FromRadix10Checked, FromRadix10SignedChecked};
+use tinyvec::TinyVec;
 
-type ParseResult<T> = Result<T, &'static str>;
-type LineResult<'a> = ParseResult<Line<'a>>;
-type PartialParseResult<'a, T> = ParseResult<(T, &'a [u8])>;
+type ParseResult<'a, T> = Result<T, LineError<'a>>;
+type LineResult<'a> = ParseResult<'a, Line<'a>>;
+type PartialParseResult<'a, T> = ParseResult<'a, (T, &'a [u8])>;
+
+/// A problem with a single line of a GFA file.
+///
+/// These point into the line text to identify the offending field.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LineError<'a> {
+    /// The line starts with a record type that we do not support.
+    UnexpectedRecord(&'a [u8]),
+
+    /// The line ends before a required field.
+    MissingField(&'static str),
+
+    /// The line has a record type that belongs to a different GFA version than the
+    /// one we're parsing.
+    WrongVersion(&'a [u8], Version),
+
+    /// The header declares a version that conflicts with the version we're parsing.
+    VersionConflict(&'a [u8], Version),
+
+    /// An orientation field is something other than `+` or `-`.
+    BadOrientation(&'a [u8]),
+
+    /// A segment name is empty.
+    BadName(&'a [u8]),
+
+    /// A field has an invalid value.
+    BadField(&'static str, &'a [u8]),
+
+    /// A path's segment list has a malformed step.
+    BadStep(StepError<'a>),
+
+    /// A segment has a different sequence than an earlier segment with the same name
+    /// (in another file that we're merging).
+    ConflictingSegment(&'a [u8]),
+
+    /// A path has the same name as an earlier path.
+    DuplicatePath(Box<[u8]>),
+
+    /// A path's list of overlaps has the wrong length: the path's name, then the
+    /// numbers of steps and overlaps.
+    OverlapCount(&'a [u8], usize, usize),
+
+    /// A link's overlap has more operations than a link can hold: the number of
+    /// operations.
+    LongOverlap(usize),
+
+    /// A segment has the same name as an earlier segment in the same file, which was
+    /// defined on the given line.
+    DuplicateSegment(&'a [u8], usize),
+
+    /// A header tag has a different value than the same tag in an earlier header
+    /// field, given second.
+    HeaderConflict(&'a [u8], Box<[u8]>),
+
+    /// A segment's `LN` tag disagrees with its sequence: the segment's name, then the
+    /// declared and actual lengths.
+    SequenceLength(&'a [u8], usize, usize),
+}
+
+/// A version of the GFA format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// GFA 1.x, with S, L, P, W, J, and C records.
+    Gfa1,
+
+    /// GFA 2.0, with S, E, O, and U records.
+    Gfa2,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Version::Gfa1 => write!(f, "GFA 1"),
+            Version::Gfa2 => write!(f, "GFA 2"),
+        }
+    }
+}
+
+/// Get the version declared in a header line's `VN` tag, if any.
+pub fn header_version(data: &[u8]) -> ParseResult<'_, Option<(Version, &[u8])>> {
+    let Some(value) = data
+        .split(|&c| c == b'\t')
+        .find_map(|field| field.strip_prefix(b"VN:Z:"))
+    else {
+        return Ok(None);
+    };
+    let version = match value {
+        b"1.0" | b"1.1" | b"1.2" => Version::Gfa1,
+        b"2.0" => Version::Gfa2,
+        _ => return Err(LineError::BadField("version", value)),
+    };
+    Ok(Some((version, value)))
+}
+
+/// The operations in a CIGAR alignment.
+///
+/// Link and containment overlaps usually have just one or two operations, so we keep
+/// a few inline to avoid allocating for every line.
+pub type AlignOps = TinyVec<[AlignOp; 4]>;
 
 /// A parsed GFA file line.
 pub enum Line<'a> {
     Header(&'a [u8]),
     Segment(Segment<'a>),
-    Link(Link),
+    Link(Link<'a>),
     Path(Path<'a>),
+    Jump(Jump<'a>),
+    Containment(Containment<'a>),
+    Walk(Walk<'a>),
+    Edge(Edge<'a>),
+    Group(Group<'a>),
+}
+
+impl<'a> Line<'a> {
+    /// Get the line's optional fields, as tab-separated text. For headers, this is
+    /// everything after the record type.
+    pub fn data(&self) -> &'a [u8] {
+        match self {
+            Line::Header(data) => data,
+            Line::Segment(seg) => seg.data,
+            Line::Link(link) => link.data,
+            Line::Path(path) => path.data,
+            Line::Jump(jump) => jump.data,
+            Line::Containment(containment) => containment.data,
+            Line::Walk(walk) => walk.data,
+            Line::Edge(edge) => edge.data,
+            Line::Group(group) => group.data,
+        }
+    }
+}
+
+/// An optional field, like `DP:i:12`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Tag<'a> {
+    pub name: [u8; 2],
+
+    /// The type character, like `i` or `Z`.
+    pub kind: u8,
+    pub value: &'a [u8],
+}
+
+/// Parse and validate one `TAG:TYPE:VALUE` optional field.
+///
+/// The tag must be a letter followed by a letter or digit, the type must be one of
+/// GFA's (`A`, `i`, `f`, `Z`, `J`, `H`, or `B`), and the value must be well-formed for
+/// that type.
+pub fn parse_tag(field: &[u8]) -> ParseResult<'_, Tag<'_>> {
+    let bad = || LineError::BadField("tag", field);
+    let (name, kind, value) = match field {
+        [n0, n1, b':', kind, b':', value @ ..] => ([*n0, *n1], *kind, value),
+        _ => return Err(bad()),
+    };
+    if !name[0].is_ascii_alphabetic() || !name[1].is_ascii_alphanumeric() {
+        return Err(bad());
+    }
+
+    let printable = |c: &u8| (b' '..=b'~').contains(c);
+    let valid = match kind {
+        b'A' => value.len() == 1 && value[0] != b' ' && printable(&value[0]),
+        b'i' => is_int(value),
+        b'f' => is_float(value),
+        b'Z' | b'J' => !value.is_empty() && value.iter().all(printable),
+        b'H' => !value.is_empty() && value.iter().all(u8::is_ascii_hexdigit),
+        b'B' => match value
+            .split(|&c| c == b',')
+            .collect::<Vec<_>>()
+            .split_first()
+        {
+            Some((&[b'f'], nums)) => nums.iter().all(|num| is_float(num)),
+            Some((&[b'c' | b'C' | b's' | b'S' | b'i' | b'I'], nums)) => {
+                nums.iter().all(|num| is_int(num))
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    if !valid {
+        return Err(bad());
+    }
+    Ok(Tag { name, kind, value })
+}
+
+fn is_int(value: &[u8]) -> bool {
+    let digits = value.strip_prefix(b"-").or(value.strip_prefix(b"+"));
+    let digits = digits.unwrap_or(value);
+    !digits.is_empty() && digits.iter().all(u8::is_ascii_digit)
+}
+
+fn is_float(value: &[u8]) -> bool {
+    std::str::from_utf8(value).is_ok_and(|text| text.parse::<f64>().is_ok())
+}
+
+/// Check that every field in some tab-separated optional data is a valid tag.
+pub fn check_tags(data: &[u8]) -> ParseResult<'_, ()> {
+    for field in data.split(|&c| c == b'\t').filter(|f| !f.is_empty()) {
+        parse_tag(field)?;
+    }
+    Ok(())
+}
+
+/// Check that a CIGAR string is well-formed: `*`, or a sequence of operations that
+/// are each a length followed by one of `MIDNSHP=X`.
+///
+/// This accepts more operations than we can store (see `AlignOpcode`). Lengths must fit
+/// in 32 bits. On errors, we point at the rest of the string, starting with the
+/// malformed operation.
+pub fn check_cigar(s: &[u8]) -> ParseResult<'_, ()> {
+    if s == b"*" {
+        return Ok(());
+    }
+    if s.is_empty() {
+        return Err(LineError::BadField("CIGAR", s));
+    }
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+        let valid = digits > 0
+            && u32::from_radix_10_checked(&rest[..digits]).0.is_some()
+            && rest.get(digits).is_some_and(|op| b"MIDNSHP=X".contains(op));
+        if !valid {
+            return Err(LineError::BadField("CIGAR operation", rest));
+        }
+        rest = &rest[digits + 1..];
+    }
+    Ok(())
+}
+
+/// Check the CIGAR syntax of a GFA 1 line's overlaps: the overlap on an `L` or `C`
+/// line, or each overlap in a `P` line's list. Other lines have no overlaps.
+///
+/// This only looks at the overlap fields, so it does not detect other problems,
+/// like missing fields.
+pub fn check_overlaps(line: &[u8]) -> ParseResult<'_, ()> {
+    let mut fields = line.split(|&c| c == b'\t');
+    match fields.next() {
+        Some(b"L") => fields.nth(4).map_or(Ok(()), check_cigar),
+        Some(b"C") => fields.nth(5).map_or(Ok(()), check_cigar),
+        Some(b"P") => match fields.nth(2) {
+            None | Some(b"*") => Ok(()),
+            Some(list) => list.split(|&c| c == b',').try_for_each(check_cigar),
+        },
+        _ => Ok(()),
+    }
 }
 
 pub struct Segment<'a> {
-    pub name: usize,
+    pub name: &'a [u8],
     pub seq: &'a [u8],
     pub data: &'a [u8],
+
+    /// The declared sequence length. Only GFA 2 segments have this.
+    pub length: Option<u64>,
+}
+
+pub struct Link<'a> {
+    pub from_seg: &'a [u8],
+    pub from_orient: Orientation,
+    pub to_seg: &'a [u8],
+    pub to_orient: Orientation,
+    pub overlap: AlignOps,
+    pub overlap_spelling: OverlapSpelling,
+    pub data: &'a [u8],
 }
 
-pub struct Link {
-    pub from_seg: usize,
+pub struct Jump<'a> {
+    pub from_seg: &'a [u8],
     pub from_orient: Orientation,
-    pub to_seg: usize,
+    pub to_seg: &'a [u8],
     pub to_orient: Orientation,
-    pub overlap: Vec<AlignOp>,
+    pub distance: Option<i64>,
+    pub data: &'a [u8],
+}
+
+pub struct Containment<'a> {
+    pub container_seg: &'a [u8],
+    pub container_orient: Orientation,
+    pub contained_seg: &'a [u8],
+    pub contained_orient: Orientation,
+    pub pos: u32,
+    pub overlap: AlignOps,
+    pub data: &'a [u8],
+}
+
+pub struct Walk<'a> {
+    pub sample: &'a [u8],
+    pub hap_index: u32,
+    pub seq_id: &'a [u8],
+    pub seq_start: Option<u64>,
+    pub seq_end: Option<u64>,
+    pub steps: &'a [u8],
+    pub data: &'a [u8],
+}
+
+/// A GFA 2 edge (E line), which is a general alignment between two segments.
+pub struct Edge<'a> {
+    pub id: &'a [u8],
+    pub seg1: &'a [u8],
+    pub orient1: Orientation,
+    pub seg2: &'a [u8],
+    pub orient2: Orientation,
+    pub beg1: EdgePos,
+    pub end1: EdgePos,
+    pub beg2: EdgePos,
+    pub end2: EdgePos,
+    pub overlap: AlignOps,
+
+    /// A trace alignment (a list of integers), which we do not interpret.
+    pub trace: Option<&'a [u8]>,
+    pub data: &'a [u8],
+}
+
+/// A position in a GFA 2 edge, which may be marked as the end of the segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgePos {
+    pub offset: u64,
+    pub at_end: bool,
+}
+
+impl std::fmt::Display for EdgePos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.offset)?;
+        if self.at_end {
+            write!(f, "$")?;
+        }
+        Ok(())
+    }
+}
+
+/// A GFA 2 group: an ordered (O line) or unordered (U line) collection of references.
+pub struct Group<'a> {
+    pub name: &'a [u8],
+    pub ordered: bool,
+
+    /// The space-separated references.
+    pub members: &'a [u8],
+    pub data: &'a [u8],
 }
 
 pub struct Path<'a> {
     pub name: &'a [u8],
     pub steps: &'a [u8],
     pub overlaps: Vec<Vec<AlignOp>>,
+    pub overlap_spelling: OverlapSpelling,
+    pub data: &'a [u8],
 }
 
 /// Parse a single line of a GFA file.
-pub fn parse_line(line: &[u8]) -> LineResult {
-    if line.len() < 2 || line[1] != b'\t' {
-        return Err("expected marker and tab");
+pub fn parse_line(line: &[u8]) -> LineResult<'_> {
+    let mut fields = Fields::new(line);
+    let marker = fields.next("record type")?;
+    match marker {
+        b"H" => parse_header(fields),
+        b"S" => parse_seg(fields),
+        b"L" => parse_link(fields),
+        b"P" => parse_path(fields),
+        b"J" => parse_jump(fields),
+        b"C" => parse_containment(fields),
+        b"W" => parse_walk(fields),
+        b"E" | b"O" | b"U" | b"F" | b"G" => Err(LineError::WrongVersion(marker, Version::Gfa1)),
+        _ => Err(LineError::UnexpectedRecord(marker)),
+    }
+}
+
+/// Parse a single line of a GFA 2 file.
+///
+/// We support the subset of GFA 2 that has a GFA 1 equivalent: segments, edges,
+/// and groups. Fragment (F) and gap (G) records are errors.
+pub fn parse_line_gfa2(line: &[u8]) -> LineResult<'_> {
+    let mut fields = Fields::new(line);
+    let marker = fields.next("record type")?;
+    match marker {
+        b"H" => parse_header(fields),
+        b"S" => parse_seg_gfa2(fields),
+        b"E" => parse_edge(fields),
+        b"O" => parse_group(fields, true),
+        b"U" => parse_group(fields, false),
+        b"L" | b"P" | b"W" | b"J" | b"C" => Err(LineError::WrongVersion(marker, Version::Gfa2)),
+        _ => Err(LineError::UnexpectedRecord(marker)),
+    }
+}
+
+/// A cursor over the tab-separated fields in a line.
+struct Fields<'a> {
+    /// The unconsumed text, or `None` when there are no more fields. (This
+    /// distinguishes a missing field from an empty one at the end of the line.)
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: &'a [u8]) -> Self {
+        Self { rest: Some(line) }
+    }
+
+    /// Consume the next field, which is required.
+    fn next(&mut self, name: &'static str) -> ParseResult<'a, &'a [u8]> {
+        self.next_opt().ok_or(LineError::MissingField(name))
+    }
+
+    /// Consume the next field, if there is one.
+    fn next_opt(&mut self) -> Option<&'a [u8]> {
+        let rest = self.rest?;
+        let (field, tail) = match memchr::memchr(b'\t', rest) {
+            Some(end) => (&rest[..end], Some(&rest[end + 1..])),
+            None => (rest, None),
+        };
+        self.rest = tail;
+        Some(field)
+    }
+
+    /// Consume a segment name field.
+    fn name(&mut self, name: &'static str) -> ParseResult<'a, &'a [u8]> {
+        let field = self.next(name)?;
+        if field.is_empty() {
+            return Err(LineError::BadName(field));
+        }
+        Ok(field)
+    }
+
+    /// Consume an orientation field.
+    fn orient(&mut self, name: &'static str) -> ParseResult<'a, Orientation> {
+        let field = self.next(name)?;
+        match field {
+            b"+" => Ok(Orientation::Forward),
+            b"-" => Ok(Orientation::Backward),
+            _ => Err(LineError::BadOrientation(field)),
+        }
+    }
+
+    /// Consume a GFA 2 reference field: a segment name with an orientation suffix.
+    fn reference(&mut self, name: &'static str) -> ParseResult<'a, (&'a [u8], Orientation)> {
+        let field = self.next(name)?;
+        parse_reference(field)
+    }
+
+    /// Consume a GFA 2 edge position, which may have a `$` suffix.
+    fn edge_pos(&mut self, name: &'static str) -> ParseResult<'a, EdgePos> {
+        let field = self.next(name)?;
+        let (digits, at_end) = match field.strip_suffix(b"$") {
+            Some(digits) => (digits, true),
+            None => (field, false),
+        };
+        match parse_num(digits) {
+            Ok((offset, b"")) => Ok(EdgePos { offset, at_end }),
+            _ => Err(LineError::BadField(name, field)),
+        }
+    }
+
+    /// Get all the remaining text (e.g., optional fields), which may be empty.
+    fn remainder(self) -> &'a [u8] {
+        self.rest.unwrap_or_default()
     }
-    let rest = &line[2..];
-    match line[0] {
-        b'H' => parse_header(rest),
-        b'S' => parse_seg(rest),
-        b'L' => parse_link(rest),
-        b'P' => parse_path(rest),
-        _ => Err("unhandled line kind"),
+}
+
+/// Parse a GFA 2 reference, which is a name followed by `+` or `-`.
+pub fn parse_reference(field: &[u8]) -> ParseResult<'_, (&[u8], Orientation)> {
+    match field.split_last() {
+        Some((b'+', name)) if !name.is_empty() => Ok((name, Orientation::Forward)),
+        Some((b'-', name)) if !name.is_empty() => Ok((name, Orientation::Backward)),
+        _ => Err(LineError::BadField("reference", field)),
     }
 }
 
 /// Parse a header line, which looks like `H <data>`.
-fn parse_header(line: &[u8]) -> LineResult {
-    Ok(Line::Header(line))
+fn parse_header(fields: Fields<'_>) -> LineResult<'_> {
+    Ok(Line::Header(fields.remainder()))
 }
 
 /// Parse a segment line, which looks like `S <name> <seq> <data>`.
-fn parse_seg(line: &[u8]) -> LineResult {
-    let (name, rest) = parse_num(line)?;
-    let rest = parse_byte(rest, b'\t')?;
-    let (seq, data) = parse_field(rest)?;
-    Ok(Line::Segment(Segment { name, seq, data }))
-}
-
-/// Parse a link line, which looks like `L <from> <+-> <to> <+-> <CIGAR>`.
-fn parse_link(line: &[u8]) -> LineResult {
-    let (from_seg, rest) = parse_num(line)?;
-    let rest = parse_byte(rest, b'\t')?;
-    let (from_orient, rest) = parse_orient(rest)?;
-    let rest = parse_byte(rest, b'\t')?;
-    let (to_seg, rest) = parse_num(rest)?;
-    let rest = parse_byte(rest, b'\t')?;
-    let (to_orient, rest) = parse_orient(rest)?;
-    let rest = parse_byte(rest, b'\t')?;
-    let (overlap, rest) = parse_align(rest)?;
-    if !rest.is_empty() {
-        return Err("expected end of line");
-    }
+fn parse_seg(mut fields: Fields<'_>) -> LineResult<'_> {
+    let name = fields.name("name")?;
+    let seq = fields.next("sequence")?;
+    let data = fields.remainder();
+    Ok(Line::Segment(Segment {
+        name,
+        seq,
+        data,
+        length: None,
+    }))
+}
+
+/// Parse a GFA 2 segment line, which looks like `S <name> <length> <seq> <data>`.
+fn parse_seg_gfa2(mut fields: Fields<'_>) -> LineResult<'_> {
+    let name = fields.name("name")?;
+    let length_field = fields.next("length")?;
+    let length = match parse_num(length_field) {
+        Ok((length, b"")) => length,
+        _ => return Err(LineError::BadField("length", length_field)),
+    };
+    let seq = fields.next("sequence")?;
+    let data = fields.remainder();
+    Ok(Line::Segment(Segment {
+        name,
+        seq,
+        data,
+        length: Some(length),
+    }))
+}
+
+/// Parse a GFA 2 edge line, which looks like
+/// `E <id> <seg1><+-> <seg2><+-> <beg1> <end1> <beg2> <end2> <alignment> <data>`.
+///
+/// The alignment may be `*`, a CIGAR string, or a trace (a comma-separated list of
+/// integers).
+fn parse_edge(mut fields: Fields<'_>) -> LineResult<'_> {
+    let id = fields.next("edge ID")?;
+    let (seg1, orient1) = fields.reference("first segment")?;
+    let (seg2, orient2) = fields.reference("second segment")?;
+    let beg1 = fields.edge_pos("first begin position")?;
+    let end1 = fields.edge_pos("first end position")?;
+    let beg2 = fields.edge_pos("second begin position")?;
+    let end2 = fields.edge_pos("second end position")?;
+    let align_field = fields.next("alignment")?;
+    let (overlap, trace) = if align_field == b"*" {
+        (AlignOps::new(), None)
+    } else if let Ok((overlap, b"")) = parse_align(align_field) {
+        (overlap, None)
+    } else if align_field.iter().all(|&c| c.is_ascii_digit() || c == b',') {
+        (AlignOps::new(), Some(align_field))
+    } else {
+        return Err(LineError::BadField("alignment", align_field));
+    };
+    let data = fields.remainder();
+    Ok(Line::Edge(Edge {
+        id,
+        seg1,
+        orient1,
+        seg2,
+        orient2,
+        beg1,
+        end1,
+        beg2,
+        end2,
+        overlap,
+        trace,
+        data,
+    }))
+}
+
+/// Parse a GFA 2 group line, which looks like `O <name> <refs> <data>` (ordered)
+/// or `U <name> <ids> <data>` (unordered), where the members are space-separated.
+fn parse_group(mut fields: Fields<'_>, ordered: bool) -> LineResult<'_> {
+    let name = fields.next("name")?;
+    let members = fields.next("members")?;
+    let data = fields.remainder();
+    Ok(Line::Group(Group {
+        name,
+        ordered,
+        members,
+        data,
+    }))
+}
+
+/// Parse a link line, which looks like `L <from> <+-> <to> <+-> <CIGAR> <data>`.
+fn parse_link(mut fields: Fields<'_>) -> LineResult<'_> {
+    let from_seg = fields.name("source segment")?;
+    let from_orient = fields.orient("source orientation")?;
+    let to_seg = fields.name("sink segment")?;
+    let to_orient = fields.orient("sink orientation")?;
+    let overlap_field = fields.next("overlap")?;
+    let overlap_spelling = match overlap_field {
+        b"*" => OverlapSpelling::Star,
+        b"" => OverlapSpelling::Empty,
+        _ => OverlapSpelling::Default,
+    };
+    let overlap = match overlap_field {
+        b"*" => AlignOps::new(),
+        _ => match parse_align(overlap_field) {
+            Ok((overlap, b"")) => overlap,
+            _ => return Err(LineError::BadField("overlap", overlap_field)),
+        },
+    };
+    let data = fields.remainder();
     Ok(Line::Link(Link {
         from_seg,
         from_orient,
         to_seg,
         to_orient,
         overlap,
+        overlap_spelling,
+        data,
+    }))
+}
+
+/// Parse a jump line, which looks like `J <from> <+-> <to> <+-> <distance|*> <data>`.
+fn parse_jump(mut fields: Fields<'_>) -> LineResult<'_> {
+    let from_seg = fields.name("source segment")?;
+    let from_orient = fields.orient("source orientation")?;
+    let to_seg = fields.name("sink segment")?;
+    let to_orient = fields.orient("sink orientation")?;
+    let distance_field = fields.next("distance")?;
+    let distance = match parse_maybe_distance(distance_field) {
+        Ok((distance, b"")) => distance,
+        _ => return Err(LineError::BadField("distance", distance_field)),
+    };
+    let data = fields.remainder();
+    Ok(Line::Jump(Jump {
+        from_seg,
+        from_orient,
+        to_seg,
+        to_orient,
+        distance,
+        data,
+    }))
+}
+
+/// Parse a containment line, which looks like
+/// `C <container> <+-> <contained> <+-> <pos> <CIGAR> <data>`.
+fn parse_containment(mut fields: Fields<'_>) -> LineResult<'_> {
+    let container_seg = fields.name("container segment")?;
+    let container_orient = fields.orient("container orientation")?;
+    let contained_seg = fields.name("contained segment")?;
+    let contained_orient = fields.orient("contained orientation")?;
+    let pos_field = fields.next("position")?;
+    let pos = match parse_num(pos_field) {
+        Ok((pos, b"")) => pos,
+        _ => return Err(LineError::BadField("position", pos_field)),
+    };
+    let overlap_field = fields.next("overlap")?;
+    let overlap = match parse_align(overlap_field) {
+        Ok((overlap, b"")) => overlap,
+        _ => return Err(LineError::BadField("overlap", overlap_field)),
+    };
+    let data = fields.remainder();
+    Ok(Line::Containment(Containment {
+        container_seg,
+        container_orient,
+        contained_seg,
+        contained_orient,
+        pos,
+        overlap,
+        data,
     }))
 }
 
-/// Parse a path line, which looks like `P <name> <steps> <*|CIGARs>`.
-fn parse_path(line: &[u8]) -> LineResult {
-    let (name, rest) = parse_field(line)?;
-    let (steps, rest) = parse_field(rest)?;
-    let (overlaps, rest) = parse_maybe_overlap_list(rest)?;
-    if !rest.is_empty() {
-        return Err("expected end of line");
+/// Parse a jump distance, which is a signed integer or `*` (unknown).
+fn parse_maybe_distance(s: &[u8]) -> PartialParseResult<'_, Option<i64>> {
+    if s.first() == Some(&b'*') {
+        return Ok((None, &s[1..]));
     }
+    match i64::from_radix_10_signed_checked(s) {
+        (Some(num), used) if used > 0 => Ok((Some(num), &s[used..])),
+        _ => Err(LineError::BadField("distance", s)),
+    }
+}
+
+/// Parse a path line, which looks like `P <name> <steps> <*|CIGARs> <data>`.
+///
+/// We tolerate a missing overlap field, treating it like `*`.
+fn parse_path(mut fields: Fields<'_>) -> LineResult<'_> {
+    let name = fields.next("name")?;
+    let steps = fields.next("steps")?;
+    let (overlaps_field, overlap_spelling) = match fields.next_opt() {
+        None => (&b""[..], OverlapSpelling::Missing),
+        Some(b"") => (&b""[..], OverlapSpelling::Empty),
+        Some(field) => (field, OverlapSpelling::Default),
+    };
+    let overlaps = match parse_maybe_overlap_list(overlaps_field) {
+        Ok((overlaps, b"")) => overlaps,
+        _ => return Err(LineError::BadField("overlaps", overlaps_field)),
+    };
+    let data = fields.remainder();
     Ok(Line::Path(Path {
         name,
         steps,
         overlaps,
+        overlap_spelling,
+        data,
     }))
 }
 
+/// Parse a walk line, which looks like
+/// `W <sample> <hap index> <seq id> <start|*> <end|*> <walk> <data>`.
+fn parse_walk(mut fields: Fields<'_>) -> LineResult<'_> {
+    let sample = fields.next("sample")?;
+    let hap_field = fields.next("haplotype index")?;
+    let hap_index = match parse_num(hap_field) {
+        Ok((hap_index, b"")) => hap_index,
+        _ => return Err(LineError::BadField("haplotype index", hap_field)),
+    };
+    let seq_id = fields.next("sequence name")?;
+    let start_field = fields.next("start position")?;
+    let seq_start = parse_maybe_pos(start_field, "start position")?;
+    let end_field = fields.next("end position")?;
+    let seq_end = parse_maybe_pos(end_field, "end position")?;
+    let steps = fields.next("walk")?;
+    let data = fields.remainder();
+    Ok(Line::Walk(Walk {
+        sample,
+        hap_index,
+        seq_id,
+        seq_start,
+        seq_end,
+        steps,
+        data,
+    }))
+}
+
+/// Parse an entire field containing a walk position, which is an integer or `*`.
+fn parse_maybe_pos<'a>(field: &'a [u8], name: &'static str) -> ParseResult<'a, Option<u64>> {
+    if field == b"*" {
+        return Ok(None);
+    }
+    match parse_num(field) {
+        Ok((pos, b"")) => Ok(Some(pos)),
+        _ => Err(LineError::BadField(name, field)),
+    }
+}
+
 /// Parse a *possible* overlap list, which may be `*` (empty).
-pub fn parse_maybe_overlap_list(s: &[u8]) -> PartialParseResult<Vec<Vec<AlignOp>>> {
+pub fn parse_maybe_overlap_list(s: &[u8]) -> PartialParseResult<'_, Vec<Vec<AlignOp>>> {
     if s == b"*" {
         Ok((vec![], &s[1..]))
     } else {
@@ -111,22 +725,22 @@ pub fn parse_maybe_overlap_list(s: &[u8]) -> PartialParseResult<Vec<Vec<AlignOp>
 /// Parse a comma-separated list of CIGAR strings.
 ///
 /// TODO: This could be optimized to avoid accumulating into a vector.
-fn parse_overlap_list(s: &[u8]) -> PartialParseResult<Vec<Vec<AlignOp>>> {
+fn parse_overlap_list(s: &[u8]) -> PartialParseResult<'_, Vec<Vec<AlignOp>>> {
     let mut rest = s;
     let mut overlaps = vec![];
     while !rest.is_empty() {
         let overlap;
         (overlap, rest) = parse_align(rest)?;
-        overlaps.push(overlap);
+        overlaps.push(overlap.to_vec());
         if !rest.is_empty() {
-            rest = parse_byte(rest, b',')?;
+            ((), rest) = parse_byte(rest, b',')?;
         }
     }
     Ok((overlaps, rest))
 }
 
 /// Consume a chunk of a string up to a given marker byte.
-fn parse_until(line: &[u8], marker: u8) -> PartialParseResult<&[u8]> {
+fn parse_until(line: &[u8], marker: u8) -> PartialParseResult<'_, &[u8]> {
     let end = memchr::memchr(marker, line).unwrap_or(line.len());
     let rest = if end == line.len() {
         &[]
@@ -137,58 +751,46 @@ fn parse_until(line: &[u8], marker: u8) -> PartialParseResult<&[u8]> {
 }
 
 /// Consume a string from the line, until a tab (or the end of the line).
-pub fn parse_field(line: &[u8]) -> PartialParseResult<&[u8]> {
+pub fn parse_field(line: &[u8]) -> PartialParseResult<'_, &[u8]> {
     parse_until(line, b'\t')
 }
 
 /// Consume a specific byte.
-fn parse_byte(s: &[u8], byte: u8) -> ParseResult<&[u8]> {
+fn parse_byte(s: &[u8], byte: u8) -> PartialParseResult<'_, ()> {
     if s.is_empty() || s[0] != byte {
-        return Err("expected byte");
-    }
-    Ok(&s[1..])
-}
-
-/// Parse a single integer.
-fn parse_num<T: FromRadix10>(s: &[u8]) -> PartialParseResult<T> {
-    match T::from_radix_10(s) {
-        (_, 0) => Err("expected number"),
-        (num, used) => Ok((num, &s[used..])),
+        return Err(LineError::BadField("separator", s));
     }
+    Ok(((), &s[1..]))
 }
 
-/// Parse a segment orientation (+ or -).
-fn parse_orient(line: &[u8]) -> PartialParseResult<Orientation> {
-    if line.is_empty() {
-        return Err("expected orientation");
+/// Parse a single integer. A number too large for `T` is an error.
+fn parse_num<T: FromRadix10Checked>(s: &[u8]) -> PartialParseResult<'_, T> {
+    match T::from_radix_10_checked(s) {
+        (Some(num), used) if used > 0 => Ok((num, &s[used..])),
+        _ => Err(LineError::BadField("number", s)),
     }
-    let orient = match line[0] {
-        b'+' => Orientation::Forward,
-        b'-' => Orientation::Backward,
-        _ => return Err("expected orient"),
-    };
-    Ok((orient, &line[1..]))
 }
 
 /// Parse a single CIGAR alignment operation (like `4D`).
-fn parse_align_op(s: &[u8]) -> PartialParseResult<AlignOp> {
-    let (len, rest) = parse_num::<u32>(s)?;
-    let op = match rest[0] {
+fn parse_align_op(s: &[u8]) -> PartialParseResult<'_, AlignOp> {
+    let (len, rest) = match u32::from_radix_10_checked(s) {
+        (Some(len), used) if used > 0 && len <= AlignOp::MAX_LEN => (len, &s[used..]),
+        _ => return Err(LineError::BadField("alignment length", s)),
+    };
+    let op = match rest.first().copied().unwrap_or_default() {
         b'M' => crate::flatgfa::AlignOpcode::Match,
         b'N' => crate::flatgfa::AlignOpcode::Gap,
         b'D' => crate::flatgfa::AlignOpcode::Deletion,
         b'I' => crate::flatgfa::AlignOpcode::Insertion,
-        _ => return Err("expected align op"),
+        _ => return Err(LineError::BadField("alignment operation", rest)),
     };
     Ok((AlignOp::new(op, len), &rest[1..]))
 }
 
 /// Parse a complete CIGAR alignment string (like `3M2I`).
-///
-/// TODO This could be optimized to avoid collecting into a vector.
-fn parse_align(s: &[u8]) -> PartialParseResult<Vec<AlignOp>> {
+fn parse_align(s: &[u8]) -> PartialParseResult<'_, AlignOps> {
     let mut rest = s;
-    let mut align = vec![];
+    let mut align = AlignOps::new();
     while !rest.is_empty() && rest[0].is_ascii_digit() {
         let op;
         (op, rest) = parse_align_op(rest)?;
@@ -197,19 +799,44 @@ fn parse_align(s: &[u8]) -> PartialParseResult<Vec<AlignOp>> {
     Ok((align, rest))
 }
 
+/// A malformed step in a path's segment list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepError<'a> {
+    /// The text of the step, which is empty for a missing step (as in `1+,,2+`).
+    pub step: &'a [u8],
+
+    /// The byte offset of the step within the segment list.
+    pub offset: usize,
+}
+
+impl std::fmt::Display for StepError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.step.is_empty() {
+            write!(f, "missing path step at byte {}", self.offset)
+        } else {
+            write!(
+                f,
+                "bad path step `{}` at byte {}",
+                String::from_utf8_lossy(self.step),
+                self.offset
+            )
+        }
+    }
+}
+
 /// Parse GFA paths' segment lists. These look like `1+,2-,3+`.
+///
+/// Segment names may be arbitrary strings (without whitespace), so each step is
+/// everything up to the next comma, and its last byte is the orientation. A name may
+/// contain `+` and `-`, so `12+13-` is a single step for a segment named `12+13`.
+///
+/// An empty list has no steps (for a placeholder path, for example). Otherwise, every
+/// step must have a name and an orientation, so a trailing comma or a step like `12`
+/// or `+12` is an error. The iterator stops after the first error.
 pub struct StepsParser<'a> {
     str: &'a [u8],
     index: usize,
-    state: StepsParseState,
-    seg: usize,
-}
-
-/// The parser state: we're either looking for a segment name (or a +/- terminator),
-/// or we're expecting a comma (or end of string).
-enum StepsParseState {
-    Seg,
-    Comma,
+    done: bool,
 }
 
 impl<'a> StepsParser<'a> {
@@ -217,56 +844,294 @@ impl<'a> StepsParser<'a> {
         StepsParser {
             str,
             index: 0,
-            state: StepsParseState::Seg,
-            seg: 0,
+            done: str.is_empty(),
+        }
+    }
+}
+
+impl<'a> Iterator for StepsParser<'a> {
+    type Item = Result<(&'a [u8], Orientation), StepError<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // Find the end of the step and consume it, along with its comma.
+        let offset = self.index;
+        let rest = &self.str[offset..];
+        let len = memchr::memchr(b',', rest).unwrap_or(rest.len());
+        let step = &rest[..len];
+        self.index += len + 1;
+        self.done = len == rest.len();
+
+        // Split off the orientation.
+        let valid = |name: &[u8]| !name.is_empty() && !name.iter().any(u8::is_ascii_whitespace);
+        match step.split_last() {
+            Some((b'+', name)) if valid(name) => Some(Ok((name, Orientation::Forward))),
+            Some((b'-', name)) if valid(name) => Some(Ok((name, Orientation::Backward))),
+            _ => {
+                self.done = true;
+                Some(Err(StepError { step, offset }))
+            }
         }
     }
+}
+
+/// Parse GFA walks' segment lists. These look like `>1<2>3`.
+///
+/// Each step is an orientation (`>` for forward or `<` for backward) followed by a
+/// segment name, which extends to the next orientation marker.
+pub struct WalkStepsParser<'a> {
+    str: &'a [u8],
+    index: usize,
+}
+
+impl<'a> WalkStepsParser<'a> {
+    pub fn new(str: &'a [u8]) -> Self {
+        WalkStepsParser { str, index: 0 }
+    }
 
-    pub fn rest(&self) -> &[u8] {
+    pub fn rest(&self) -> &'a [u8] {
         &self.str[self.index..]
     }
 }
 
-impl<'a> Iterator for StepsParser<'a> {
-    type Item = (usize, bool);
-    fn next(&mut self) -> Option<(usize, bool)> {
-        while self.index < self.str.len() {
-            // Consume one byte.
-            let byte = self.str[self.index];
-            self.index += 1;
-
-            match self.state {
-                StepsParseState::Seg => {
-                    if byte == b'+' || byte == b'-' {
-                        self.state = StepsParseState::Comma;
-                        return Some((self.seg, byte == b'+'));
-                    } else if byte.is_ascii_digit() {
-                        self.seg *= 10;
-                        self.seg += (byte - b'0') as usize;
-                    } else {
-                        return None;
-                    }
-                }
-                StepsParseState::Comma => {
-                    if byte == b',' {
-                        self.state = StepsParseState::Seg;
-                        self.seg = 0;
-                    } else {
-                        return None;
-                    }
-                }
-            }
+impl<'a> Iterator for WalkStepsParser<'a> {
+    type Item = (&'a [u8], Orientation);
+    fn next(&mut self) -> Option<(&'a [u8], Orientation)> {
+        let (&marker, rest) = self.str[self.index..].split_first()?;
+        let orient = match marker {
+            b'>' => Orientation::Forward,
+            b'<' => Orientation::Backward,
+            _ => return None,
+        };
+        let len = memchr::memchr2(b'>', b'<', rest).unwrap_or(rest.len());
+        if len == 0 {
+            // Stop at the malformed step, so `rest` starts with it.
+            return None;
         }
+        self.index += 1 + len;
+        Some((&rest[..len], orient))
+    }
+}
+
+#[test]
+fn test_check_cigar() {
+    for good in [
+        &b"*"[..],
+        b"0M",
+        b"3M1I2D",
+        b"5S10=1X2H3P4N",
+        b"4294967295M",
+    ] {
+        assert!(
+            check_cigar(good).is_ok(),
+            "{}",
+            String::from_utf8_lossy(good)
+        );
+    }
+    let bad = |s, token| {
+        assert_eq!(
+            check_cigar(s),
+            Err(LineError::BadField("CIGAR operation", token))
+        )
+    };
+    bad(&b"garbage"[..], &b"garbage"[..]);
+    bad(b"12X3", b"3");
+    bad(b"3M*", b"*");
+    bad(b"M", b"M");
+    bad(b"3M4294967296M", b"4294967296M");
+    bad(b"99999999999999999999999M", b"99999999999999999999999M");
+    assert_eq!(check_cigar(b""), Err(LineError::BadField("CIGAR", b"")));
 
-        None
+    assert!(check_overlaps(b"L\t1\t+\t2\t-\t3M").is_ok());
+    assert!(check_overlaps(b"P\tp\t1+,2-\t*").is_ok());
+    assert!(check_overlaps(b"P\tp\t1+,2-").is_ok());
+    assert_eq!(
+        check_overlaps(b"P\tp\t1+,2-,3+\t1M,2Q\tXX:i:1"),
+        Err(LineError::BadField("CIGAR operation", b"2Q"))
+    );
+    assert_eq!(
+        check_overlaps(b"C\t1\t+\t2\t-\t0\t1M1"),
+        Err(LineError::BadField("CIGAR operation", b"1"))
+    );
+
+    // Long operations are fine, up to a point.
+    assert_eq!(parse_align(b"300M").unwrap().0[0].len(), 300);
+    assert_eq!(
+        parse_align(b"99999999999M").err(),
+        Some(LineError::BadField("alignment length", b"99999999999M"))
+    );
+}
+
+#[test]
+fn test_parse_number_overflow() {
+    // Numbers that don't fit are errors, rather than wrapping around (or panicking in
+    // debug builds).
+    let bad = |line: &[u8], field| match parse_line(line) {
+        Err(LineError::BadField(name, _)) => assert_eq!(name, field),
+        _ => panic!("expected a bad {}", field),
+    };
+    bad(b"C\t1\t+\t2\t+\t4294967296\t1M", "position");
+    bad(b"J\t1\t+\t2\t+\t9223372036854775808", "distance");
+    bad(
+        b"W\ts\t99999999999999999999\tc\t*\t*\t>1",
+        "haplotype index",
+    );
+    bad(b"W\ts\t0\tc\t18446744073709551616\t*\t>1", "start position");
+    assert!(parse_line(b"C\t1\t+\t2\t+\t4294967295\t1M").is_ok());
+    assert!(parse_line(b"J\t1\t+\t2\t+\t-9223372036854775808").is_ok());
+    assert!(parse_line(b"W\ts\t0\tc\t18446744073709551615\t*\t>1").is_ok());
+}
+
+#[test]
+fn test_parse_tag() {
+    let tag = parse_tag(b"DP:i:-12").unwrap();
+    assert_eq!((tag.name, tag.kind, tag.value), (*b"DP", b'i', &b"-12"[..]));
+    for good in [
+        &b"SN:Z:chr 1"[..],
+        b"fl:f:1.5e-3",
+        b"st:A:x",
+        b"hx:H:1F0a",
+        b"ar:B:i,1,-2",
+        b"ar:B:f,0.5",
+    ] {
+        assert!(parse_tag(good).is_ok(), "{}", String::from_utf8_lossy(good));
     }
+    for bad in [
+        &b"DP:i:twelve"[..],
+        b"DP:x:1",
+        b"D:i:1",
+        b"1D:i:1",
+        b"DP:i",
+        b"st:A:xy",
+        b"hx:H:0g",
+        b"ar:B:q,1",
+        b"sn:Z:",
+    ] {
+        assert_eq!(parse_tag(bad), Err(LineError::BadField("tag", bad)));
+    }
+    assert_eq!(
+        check_tags(b"LN:i:4\tbad"),
+        Err(LineError::BadField("tag", b"bad"))
+    );
+}
+
+#[test]
+fn test_parse_walk_steps() {
+    use Orientation::{Backward, Forward};
+
+    let s = b">1<23>s4";
+    let mut parser = WalkStepsParser::new(s);
+    let walk: Vec<_> = (&mut parser).collect();
+    assert_eq!(
+        walk,
+        vec![
+            (&b"1"[..], Forward),
+            (&b"23"[..], Backward),
+            (&b"s4"[..], Forward)
+        ]
+    );
+    assert_eq!(parser.rest(), b"");
+
+    let mut parser = WalkStepsParser::new(b">1<>2");
+    assert_eq!((&mut parser).count(), 1);
+    assert_eq!(parser.rest(), b"<>2");
 }
 
 #[test]
 fn test_parse_steps() {
-    let s = b"1+,23-,4+ suffix";
-    let mut parser = StepsParser::new(s);
-    let path: Vec<_> = (&mut parser).collect();
-    assert_eq!(path, vec![(1, true), (23, false), (4, true)]);
-    assert_eq!(parser.rest(), b"suffix");
+    use Orientation::{Backward, Forward};
+
+    let path: Result<Vec<_>, _> = StepsParser::new(b"1+,23-,4+").collect();
+    assert_eq!(
+        path.unwrap(),
+        vec![
+            (&b"1"[..], Forward),
+            (&b"23"[..], Backward),
+            (&b"4"[..], Forward)
+        ]
+    );
+
+    let path: Result<Vec<_>, _> = StepsParser::new(b"utg000013l+,NC_000001.11-").collect();
+    assert_eq!(
+        path.unwrap(),
+        vec![
+            (&b"utg000013l"[..], Forward),
+            (&b"NC_000001.11"[..], Backward)
+        ]
+    );
+
+    // Without a comma, two steps are one step with a longer name.
+    let path: Result<Vec<_>, _> = StepsParser::new(b"12+13-").collect();
+    assert_eq!(path.unwrap(), vec![(&b"12+13"[..], Backward)]);
+
+    // Malformed steps are errors, with their offsets.
+    let bad = |s: &'static [u8], step: &'static [u8], offset| {
+        let mut parser = StepsParser::new(s);
+        let err = parser.find_map(Result::err);
+        assert_eq!(err, Some(StepError { step, offset }));
+        assert!(parser.next().is_none());
+    };
+    bad(b"1+,2x,3-", b"2x", 3);
+    bad(b"12", b"12", 0);
+    bad(b"+12", b"+12", 0);
+    assert_eq!(StepsParser::new(b"").count(), 0);
+    bad(b"1+,", b"", 3);
+    bad(b"1+,,2-", b"", 3);
+    bad(b"1+,-", b"-", 3);
+    bad(b"1+,23-,4+ suffix", b"4+ suffix", 7);
+    assert_eq!(
+        StepError {
+            step: b"2x",
+            offset: 3
+        }
+        .to_string(),
+        "bad path step `2x` at byte 3"
+    );
+}
+
+#[test]
+fn test_parse_steps_fuzz() {
+    // Random soup of names, orientations, and commas. A successful parse must account
+    // for every byte of the input, and an error must point at the input.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut rand = |n: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n) as usize
+    };
+    let alphabet = b"12+-,, x";
+    for _ in 0..20_000 {
+        let len = rand(12);
+        let text: Vec<u8> = (0..len)
+            .map(|_| alphabet[rand(alphabet.len() as u64)])
+            .collect();
+
+        let mut rebuilt = Vec::new();
+        let mut failed = false;
+        for step in StepsParser::new(&text) {
+            match step {
+                Ok((name, orient)) => {
+                    assert!(!name.is_empty() && !name.contains(&b','));
+                    if !rebuilt.is_empty() {
+                        rebuilt.push(b',');
+                    }
+                    rebuilt.extend_from_slice(name);
+                    rebuilt.push(match orient {
+                        Orientation::Forward => b'+',
+                        Orientation::Backward => b'-',
+                    });
+                }
+                Err(err) => {
+                    assert!(text[err.offset..].starts_with(err.step));
+                    failed = true;
+                }
+            }
+        }
+        if !failed {
+            assert_eq!(rebuilt, text, "{}", String::from_utf8_lossy(&text));
+        }
+    }
 }
@@ -1,12 +1,21 @@
+pub mod adjacency;
+pub mod bgzf;
+pub mod bubble;
 pub mod cmds;
+pub mod complexity;
+pub mod composition;
+pub mod diff;
 pub mod file;
 pub mod flatgfa;
 pub mod gaf;
 pub mod gfaline;
+pub mod lint;
 pub mod memfile;
 pub mod namemap;
 pub mod parse;
 pub mod pool;
 pub mod print;
+pub mod search;
+pub mod unitig;
 
 pub use flatgfa::*;
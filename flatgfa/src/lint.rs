@@ -0,0 +1,251 @@
+use crate::flatgfa::{FlatGFA, Handle, Link, Path, Segment};
+use crate::pool::Id;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A suspicious (but parseable) condition in a graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintWarning {
+    /// A link endpoint refers to a segment that does not exist.
+    DanglingLink(Id<Link>),
+
+    /// A path's range of steps lies outside the step pool.
+    PathStepsOutOfRange(Id<Path>),
+
+    /// A path step refers to a segment that does not exist. `step` is the step's
+    /// index within the path.
+    DanglingStep { path: Id<Path>, step: usize },
+
+    /// A segment is not an endpoint of any link.
+    UnlinkedSegment(Id<Segment>),
+
+    /// Consecutive path steps visit segments that are linked, but not in the
+    /// orientations that the path uses. `step` is the index of the second step.
+    OrientationMismatch { path: Id<Path>, step: usize },
+
+    /// Consecutive path steps visit segments that are not linked at all. `step` is
+    /// the index of the second step.
+    MissingLink { path: Id<Path>, step: usize },
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::DanglingLink(link) => {
+                write!(f, "link {} refers to a nonexistent segment", link.index())
+            }
+            Self::PathStepsOutOfRange(path) => {
+                write!(f, "path {} has steps out of range", path.index())
+            }
+            Self::DanglingStep { path, step } => write!(
+                f,
+                "path {} step {} refers to a nonexistent segment",
+                path.index(),
+                step
+            ),
+            Self::UnlinkedSegment(seg) => write!(f, "segment {} has no links", seg.index()),
+            Self::OrientationMismatch { path, step } => write!(
+                f,
+                "path {} step {} follows a link in the wrong orientation",
+                path.index(),
+                step
+            ),
+            Self::MissingLink { path, step } => write!(
+                f,
+                "path {} step {} is not linked to the previous step",
+                path.index(),
+                step
+            ),
+        }
+    }
+}
+
+impl<'a> FlatGFA<'a> {
+    /// Check the graph for suspicious conditions, like links to nonexistent segments
+    /// or paths that traverse edges that no link allows.
+    ///
+    /// This takes time linear in the size of the graph. Graphs with no links at all
+    /// (e.g., plain collections of sequences) are exempt from the checks that involve
+    /// links. Warnings come grouped by kind: links, then segments, then paths.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = vec![];
+        let seg_count = self.segs.len();
+        let valid = |handle: Handle| handle.segment().index() < seg_count;
+
+        // Index the edges that links allow, in both directions, and the (unordered)
+        // pairs of segments that are linked at all.
+        let mut edges = HashSet::new();
+        let mut seg_pairs = HashSet::new();
+        let mut linked = vec![false; seg_count];
+        for (id, link) in self.links.items() {
+            if !valid(link.from) || !valid(link.to) {
+                warnings.push(LintWarning::DanglingLink(id));
+                continue;
+            }
+            edges.insert((link.from, link.to));
+            edges.insert((link.to.flip(), link.from.flip()));
+            seg_pairs.insert(seg_pair(link.from, link.to));
+            linked[link.from.segment().index()] = true;
+            linked[link.to.segment().index()] = true;
+        }
+        let has_links = !self.links.is_empty();
+
+        if has_links {
+            for (idx, &is_linked) in linked.iter().enumerate() {
+                if !is_linked {
+                    warnings.push(LintWarning::UnlinkedSegment(Id::new(idx)));
+                }
+            }
+        }
+
+        for (path_id, path) in self.paths.items() {
+            let span = path.steps;
+            if span.start.index() > span.end.index() || span.end.index() > self.steps.len() {
+                warnings.push(LintWarning::PathStepsOutOfRange(path_id));
+                continue;
+            }
+
+            let mut prev: Option<Handle> = None;
+            for (step, &handle) in self.steps[span].iter().enumerate() {
+                if !valid(handle) {
+                    warnings.push(LintWarning::DanglingStep {
+                        path: path_id,
+                        step,
+                    });
+                    prev = None;
+                    continue;
+                }
+                if let (true, Some(prev)) = (has_links, prev) {
+                    if !edges.contains(&(prev, handle)) {
+                        warnings.push(if seg_pairs.contains(&seg_pair(prev, handle)) {
+                            LintWarning::OrientationMismatch {
+                                path: path_id,
+                                step,
+                            }
+                        } else {
+                            LintWarning::MissingLink {
+                                path: path_id,
+                                step,
+                            }
+                        });
+                    }
+                }
+                prev = Some(handle);
+            }
+        }
+
+        warnings
+    }
+
+    /// Find the segments that no link, jump, containment, or path refers to, in ID
+    /// order.
+    ///
+    /// In a graph that is supposed to be connected, these usually mean that a
+    /// sub-graph was extracted incorrectly. This takes one pass over the edges and
+    /// steps.
+    pub fn orphan_segments(&self) -> Vec<Id<Segment>> {
+        let mut used = vec![false; self.segs.len()];
+        let mut mark = |handle: Handle| {
+            if let Some(used) = used.get_mut(handle.segment().index()) {
+                *used = true;
+            }
+        };
+        for link in self.links.all() {
+            mark(link.from);
+            mark(link.to);
+        }
+        for jump in self.jumps.all() {
+            mark(jump.from);
+            mark(jump.to);
+        }
+        for containment in self.containments.all() {
+            mark(containment.container);
+            mark(containment.contained);
+        }
+        for &step in self.steps.all() {
+            mark(step);
+        }
+        used.iter()
+            .enumerate()
+            .filter(|(_, &used)| !used)
+            .map(|(idx, _)| Id::new(idx))
+            .collect()
+    }
+}
+
+impl crate::flatgfa::HeapGFAStore {
+    /// Remove the segments that nothing refers to (see `FlatGFA::orphan_segments`),
+    /// and reclaim their space, returning how many there were.
+    ///
+    /// Like `retain_segments`, this renumbers the remaining segments.
+    pub fn drop_orphan_segments(&mut self) -> usize {
+        let orphans: HashSet<_> = self.as_ref().orphan_segments().into_iter().collect();
+        if !orphans.is_empty() {
+            self.retain_segments(|id| !orphans.contains(&id));
+            self.compact();
+        }
+        orphans.len()
+    }
+}
+
+/// Get the segments at the two ends of an edge as an unordered pair.
+fn seg_pair(a: Handle, b: Handle) -> (u32, u32) {
+    let (a, b): (u32, u32) = (a.segment().into(), b.segment().into());
+    (a.min(b), a.max(b))
+}
+
+#[test]
+fn test_lint() {
+    use crate::flatgfa::Orientation;
+
+    // The first path follows the first link backward, which is fine.
+    let text = b"S\t1\tA\nS\t2\tA\nS\t3\tA\nS\t4\tA\n\
+        L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\n\
+        P\tgood\t2-,1-\t*\nP\tbad\t1+,2+,3+,4+\t*\n";
+    let mut store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let segs: Vec<Id<Segment>> = (0..4).map(Id::new).collect();
+    let bad = Id::new(1);
+
+    // The parser never produces a link to a missing segment, so add one by hand.
+    let dangling = store.add_link(
+        Handle::new(segs[0], Orientation::Forward),
+        Handle::new(Id::new(9), Orientation::Forward),
+        vec![],
+        &[],
+    );
+
+    let warnings = store.as_ref().lint();
+    assert_eq!(
+        warnings,
+        vec![
+            LintWarning::DanglingLink(dangling),
+            LintWarning::UnlinkedSegment(segs[3]),
+            LintWarning::OrientationMismatch { path: bad, step: 2 },
+            LintWarning::MissingLink { path: bad, step: 3 },
+        ]
+    );
+    assert_eq!(
+        warnings[2].to_string(),
+        "path 1 step 2 follows a link in the wrong orientation"
+    );
+}
+
+#[test]
+fn test_orphan_segments() {
+    let text = b"S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\nS\t5\tA\n\
+        L\t1\t+\t2\t+\t0M\nP\tp\t4-\t*\nS\t6\tC\nJ\t6\t+\t1\t-\t*\n";
+    let mut store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    assert_eq!(
+        store.as_ref().orphan_segments(),
+        vec![Id::new(2), Id::new(4)]
+    );
+
+    // Dropping them keeps the rest of the file intact.
+    assert_eq!(store.drop_orphan_segments(), 2);
+    assert!(store.as_ref().orphan_segments().is_empty());
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\t2\tC\nS\t4\tT\nL\t1\t+\t2\t+\t0M\nP\tp\t4-\t*\nS\t6\tC\nJ\t6\t+\t1\t-\t*\n"
+    );
+    assert_eq!(store.drop_orphan_segments(), 0);
+}
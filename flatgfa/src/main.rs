@@ -1,9 +1,11 @@
 use argh::FromArgs;
 use flatgfa::flatgfa::FlatGFA;
 use flatgfa::gaf;
-use flatgfa::parse::Parser;
+use flatgfa::gfaline::Version;
+use flatgfa::parse::{BluntOverlap, DuplicateLinks, Parser, ParserConfig};
 use flatgfa::pool::Store;
-use flatgfa::{cmds, file, memfile, parse}; // TODO: hopefully remove at some point, this breaks a lot of principles
+use flatgfa::{bgzf, cmds, file, memfile, parse, print}; // TODO: hopefully remove at some point, this breaks a lot of principles
+use std::io::Write;
 
 #[derive(FromArgs)]
 /// Convert between GFA text and FlatGFA binary formats.
@@ -12,7 +14,7 @@ struct PolBin {
     #[argh(option, short = 'i')]
     input: Option<String>,
 
-    /// read from a text GFA file
+    /// read from a text GFA file (`-` for stdin)
     #[argh(option, short = 'I')]
     input_gfa: Option<String>,
 
@@ -24,10 +26,70 @@ struct PolBin {
     #[argh(switch, short = 'm')]
     mutate: bool,
 
+    /// write GFA text in a normalized order (segments, paths, then links) instead
+    /// of the input's line order
+    #[argh(switch)]
+    sorted: bool,
+
+    /// parse text input as GFA 2, even without a `VN:Z:2.0` header
+    #[argh(switch)]
+    gfa2: bool,
+
+    /// reject optional fields that are not well-formed tags
+    #[argh(switch)]
+    strict: bool,
+
+    /// keep the first of several segments with the same name (with a warning)
+    /// instead of failing
+    #[argh(switch)]
+    keep_first_duplicates: bool,
+
+    /// keep lines with unknown record types (like comments) verbatim instead of
+    /// failing
+    #[argh(switch)]
+    keep_unknown: bool,
+
+    /// warn about segments that no link or path uses
+    #[argh(switch)]
+    warn_orphans: bool,
+
+    /// remove segments that no link or path uses (not with -m)
+    #[argh(switch)]
+    drop_orphans: bool,
+
+    /// leave out segment sequences when parsing GFA text, keeping only their lengths
+    #[argh(switch)]
+    skip_sequences: bool,
+
+    /// leave out paths and walks when parsing GFA text, for commands that only need
+    /// segments and links
+    #[argh(switch)]
+    skip_paths: bool,
+
+    /// drop repeated links when parsing GFA text: keep (the default), exact, or
+    /// symmetric (also reverse complements)
+    #[argh(option, default = "DuplicateLinks::Keep")]
+    duplicate_links: DuplicateLinks,
+
+    /// rewrite blunt overlaps when parsing GFA text, as 0M, *, or empty (instead of
+    /// keeping whichever one the file uses)
+    #[argh(option)]
+    normalize_overlaps: Option<BluntOverlap>,
+
+    /// print statistics about parsing GFA text (record counts, time spent in each
+    /// phase, etc.) to stderr
+    #[argh(switch)]
+    stats: bool,
+
     /// preallocation size factor
     #[argh(option, short = 'p', default = "32")]
     prealloc_factor: usize,
 
+    /// with -m, size the output file exactly using an extra pass over the GFA input
+    /// (GFA 1 only; other input falls back to an estimate)
+    #[argh(switch)]
+    exact_size: bool,
+
     #[argh(subcommand)]
     command: Option<Command>,
 }
@@ -46,18 +108,37 @@ enum Command {
     Bench(cmds::Bench),
 }
 
-fn main() -> Result<(), &'static str> {
+fn main() {
     let args: PolBin = argh::from_env();
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: PolBin) -> Result<(), Box<dyn std::error::Error>> {
+    let config = parser_config(&args);
+    if args.drop_orphans && (args.mutate || args.input.is_some()) {
+        return Err("--drop-orphans only works when parsing GFA text into memory".into());
+    }
 
     // A special case for converting from GFA text to an in-place FlatGFA binary.
     if args.mutate {
         if let (None, None, Some(out_name)) = (&args.command, &args.input, &args.output) {
-            prealloc_translate(args.input_gfa.as_deref(), out_name, args.prealloc_factor);
+            prealloc_translate(
+                args.input_gfa.as_deref(),
+                out_name,
+                args.prealloc_factor,
+                &config,
+                args.exact_size,
+                args.stats,
+            )?;
             return Ok(());
         }
     }
 
-    // Load the input from a file (binary) or stdin (text).
+    // Load the input from a file (binary) or stdin (text, possibly gzip- or
+    // BGZF-compressed).
     let mmap;
     let mut mmap_mut;
     let store;
@@ -66,25 +147,26 @@ fn main() -> Result<(), &'static str> {
         Some(name) => {
             if args.mutate {
                 mmap_mut = memfile::map_file_mut(&name);
-                slice_store = file::view_store(&mut mmap_mut);
+                slice_store = file::view_store(&mut mmap_mut)?;
                 slice_store.as_ref()
             } else {
                 mmap = memfile::map_file(&name);
-                file::view(&mmap)
+                file::view(&mmap)?
             }
         }
         None => {
             // Parse from stdin or a file.
-            store = match args.input_gfa {
+            let mut parsed = match args.input_gfa {
                 Some(name) => {
-                    let file = memfile::map_file(&name);
-                    Parser::for_heap().parse_mem(file.as_ref())
-                }
-                None => {
-                    let stdin = std::io::stdin();
-                    Parser::for_heap().parse_stream(stdin.lock())
+                    text_parser(Parser::for_heap(), &config, args.stats).parse_file(name)?
                 }
+                None => text_parser(Parser::for_heap(), &config, args.stats)
+                    .parse_stream(std::io::stdin().lock())?,
             };
+            if args.drop_orphans {
+                parsed.drop_orphan_segments();
+            }
+            store = parsed;
             store.as_ref()
         }
     };
@@ -94,19 +176,22 @@ fn main() -> Result<(), &'static str> {
             cmds::toc(&gfa);
         }
         Some(Command::Paths(_)) => {
-            cmds::paths(&gfa);
+            gfa.check_paths()?;
+            cmds::paths(&gfa)?;
         }
         Some(Command::Stats(sub_args)) => {
             cmds::stats(&gfa, sub_args);
         }
         Some(Command::Position(sub_args)) => {
+            gfa.check_paths()?;
             cmds::position(&gfa, sub_args)?;
         }
         Some(Command::Extract(sub_args)) => {
             let store = cmds::extract(&gfa, sub_args)?;
-            dump(&store.as_ref(), &args.output);
+            dump(&store.as_ref(), &args.output, args.sorted)?;
         }
         Some(Command::Depth(_)) => {
+            gfa.check_paths()?;
             cmds::depth(&gfa);
         }
         Some(Command::Chop(sub_args)) => {
@@ -120,59 +205,123 @@ fn main() -> Result<(), &'static str> {
                 seq_data: gfa.seq_data,
                 name_data: gfa.name_data,
                 segs: store.segs.as_ref(),
+                rgfa: store.rgfa.as_ref(),
                 paths: store.paths.as_ref(),
+                walks: store.walks.as_ref(),
                 links: store.links.as_ref(),
+                jumps: store.jumps.as_ref(),
+                containments: store.containments.as_ref(),
                 steps: store.steps.as_ref(),
                 overlaps: store.overlaps.as_ref(),
                 alignment: store.alignment.as_ref(),
                 optional_data: store.optional_data.as_ref(),
+                tags: store.tags.as_ref(),
+                other_lines: gfa.other_lines,
                 line_order: store.line_order.as_ref(),
+                sequences_skipped: gfa.sequences_skipped,
             };
-            dump(&flat, &args.output);
+            dump(&flat, &args.output, args.sorted)?;
         }
         Some(Command::GafLookup(sub_args)) => {
-            gaf::gaf_lookup(&gfa, sub_args);
+            gaf::gaf_lookup(&gfa, sub_args)?;
         }
         Some(Command::Bench(sub_args)) => {
-            cmds::bench(sub_args);
+            cmds::bench(&gfa, sub_args);
         }
         None => {
             // Just emit the GFA or FlatGFA file.
-            dump(&gfa, &args.output);
+            dump(&gfa, &args.output, args.sorted)?;
         }
     }
 
     Ok(())
 }
 
-/// Write a FlatGFA either to a GFA text file to stdout or a binary FlatGFA file given
-/// with a name.
-fn dump(gfa: &FlatGFA, output: &Option<String>) {
+/// Get the policies for parsing GFA text from the command-line options.
+fn parser_config(args: &PolBin) -> ParserConfig {
+    ParserConfig {
+        version: args.gfa2.then_some(Version::Gfa2),
+        strict: args.strict,
+        keep_first_duplicates: args.keep_first_duplicates,
+        keep_unknown_records: args.keep_unknown,
+        warn_orphans: args.warn_orphans,
+        skip_sequences: args.skip_sequences,
+        skip_paths: args.skip_paths,
+        duplicate_links: args.duplicate_links,
+        normalize_overlaps: args.normalize_overlaps,
+        ..ParserConfig::default()
+    }
+}
+
+/// Create a parser for GFA text with the given policies. Warnings (and, optionally,
+/// statistics about the parse) go to stderr.
+fn text_parser<'a, P: flatgfa::flatgfa::StoreFamily<'a>>(
+    parser: Parser<'a, P>,
+    config: &ParserConfig,
+    stats: bool,
+) -> Parser<'a, P> {
+    let parser = parser
+        .with_config(config.clone())
+        .with_warnings(|warning| eprintln!("warning: {}", warning));
+    if stats {
+        parser.with_stats(|stats| eprint!("{}", stats))
+    } else {
+        parser
+    }
+}
+
+/// Write a FlatGFA either to a GFA text file to stdout (optionally in sorted order) or
+/// a binary FlatGFA file given with a name.
+fn dump(gfa: &FlatGFA, output: &Option<String>, sorted: bool) -> std::io::Result<()> {
     match output {
         Some(name) => {
             let mut mmap = memfile::map_new_file(name, file::size(gfa) as u64);
             file::dump(gfa, &mut mmap);
-            mmap.flush().unwrap();
+            mmap.flush()
         }
         None => {
-            print!("{}", gfa);
+            // Write the exact bytes: GFA text need not be UTF-8.
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+            if sorted {
+                print::Sorted(gfa).write(&mut out)?;
+            } else {
+                gfa.write_gfa(&mut out)?;
+            }
+            out.flush()
         }
     }
 }
 
 /// A special-case fast-path transformation from a GFA text file to a *preallocated*
 /// FlatGFA, with sizes based on estimates of the input counts.
-fn prealloc_translate(in_name: Option<&str>, out_name: &str, prealloc_factor: usize) {
+fn prealloc_translate(
+    in_name: Option<&str>,
+    out_name: &str,
+    prealloc_factor: usize,
+    config: &ParserConfig,
+    exact_size: bool,
+    stats: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let file;
-    let (input_buf, empty_toc) = match in_name {
+    let data;
+    let (input_buf, empty_toc) = match in_name.filter(|name| memfile::is_regular_file(name)) {
         // If we have an input GFA file, we can estimate its sizes for the TOC.
         Some(name) => {
             file = memfile::map_file(name);
-            let toc = parse::estimate_toc(file.as_ref());
-            (Some(file.as_ref()), toc)
+            data = bgzf::decompress_mem(file.as_ref())?;
+            // The scan's sizes are exact only for GFA 1 text with sequences.
+            let exact =
+                (exact_size && config.version != Some(Version::Gfa2) && !config.skip_sequences)
+                    .then(|| parse::SizeEstimate::scan(&data))
+                    .flatten();
+            let toc = match exact {
+                Some(size) => file::Toc::exact(&size),
+                None => parse::estimate_toc(&data),
+            };
+            (Some(data.as_ref()), toc)
         }
 
-        // Otherwise, we need to guess.
+        // Otherwise (for stdin or a pipe), we need to guess.
         None => (None, file::Toc::guess(prealloc_factor)),
     };
 
@@ -183,15 +332,19 @@ fn prealloc_translate(in_name: Option<&str>, out_name: &str, prealloc_factor: us
     // Parse the input into the file.
     match input_buf {
         Some(buf) => {
-            let store = Parser::for_slice(store).parse_mem(buf);
+            let store = text_parser(Parser::for_slice(store), config, stats).parse_mem(buf)?;
             *toc = file::Toc::for_fixed_store(&store)
         }
         None => {
-            let stdin = std::io::stdin();
-            let store = Parser::for_slice(store).parse_stream(stdin.lock());
+            let parser = text_parser(Parser::for_slice(store), config, stats);
+            let store = match in_name {
+                Some(name) => parser.parse_file(name)?,
+                None => parser.parse_stream(std::io::stdin().lock())?,
+            };
             *toc = file::Toc::for_fixed_store(&store)
         }
     };
 
     mmap.flush().unwrap();
+    Ok(())
 }
@@ -9,11 +9,18 @@ pub fn map_file(name: &str) -> Mmap {
     unsafe { Mmap::map(&file) }.unwrap()
 }
 
+/// Check whether a path names a regular file, which we can memory-map (unlike, for
+/// example, a pipe).
+pub fn is_regular_file(name: &str) -> bool {
+    std::fs::metadata(name).is_ok_and(|meta| meta.is_file())
+}
+
 pub fn map_new_file(name: &str, size: u64) -> MmapMut {
     let file = std::fs::OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
+        .truncate(true)
         .open(name)
         .unwrap();
     file.set_len(size).unwrap();
@@ -37,7 +44,7 @@ pub struct MemchrSplit<'a> {
 }
 
 impl MemchrSplit<'_> {
-    pub fn new(needle: u8, haystack: &[u8]) -> MemchrSplit {
+    pub fn new(needle: u8, haystack: &[u8]) -> MemchrSplit<'_> {
         MemchrSplit {
             needle,
             haystack,
@@ -54,8 +61,9 @@ impl<'a> Iterator for MemchrSplit<'a> {
         if self.pos >= self.haystack.len() {
             return None;
         }
+        // The last piece need not end with the needle.
         let start = self.pos;
-        let end = self.memchr.next()?;
+        let end = self.memchr.next().unwrap_or(self.haystack.len());
         self.pos = end + 1;
         Some(&self.haystack[start..end])
     }
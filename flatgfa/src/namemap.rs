@@ -1,34 +1,75 @@
-use crate::flatgfa::{FlatGFA, Segment};
+use crate::flatgfa::{FlatGFA, SegName, Segment};
 use crate::pool::Id;
 use std::collections::HashMap;
 
-/// A fast way to look up segment IDs by their (integer) names.
+/// A fast way to look up segment IDs by their names.
+///
+/// Names are usually integers, which we handle specially. Textual names go in a
+/// separate, byte-keyed table.
 #[derive(Default)]
 pub struct NameMap {
-    /// Names at most this are assigned *sequential* IDs, i.e., the ID is just the name
-    /// minus one.
-    sequential_max: usize,
+    /// The first name in the run of names with *sequential* IDs, i.e., where the ID
+    /// is just the name minus `first`. This is usually 1, but some tools number
+    /// segments from 0.
+    first: usize,
+
+    /// The number of names with sequential IDs.
+    sequential_len: usize,
 
     /// Non-sequential names go here.
     others: HashMap<usize, u32>,
+
+    /// Non-numeric names go here.
+    text: HashMap<Box<[u8]>, u32>,
 }
 
 impl NameMap {
+    /// Record a numeric segment name. If the name is already present, it keeps
+    /// the ID it was first given.
     pub fn insert(&mut self, name: usize, id: Id<Segment>) {
+        if self.find(name).is_some() {
+            return;
+        }
+
         // Is this the next sequential name? If so, no need to record it in our hash table;
-        // just bump the number of sequential names we've seen.
-        if (name - 1) == self.sequential_max && (name - 1) == id.index() {
-            self.sequential_max += 1;
+        // just bump the number of sequential names we've seen. The first segment
+        // determines where the sequence starts.
+        if self.sequential_len == 0 && id.index() == 0 {
+            self.first = name;
+        }
+        if id.index() == self.sequential_len
+            && name.checked_sub(self.first) == Some(self.sequential_len)
+        {
+            self.sequential_len += 1;
         } else {
             self.others.insert(name, id.into());
         }
     }
 
-    pub fn get(&self, name: usize) -> Id<Segment> {
-        if name <= self.sequential_max {
-            ((name - 1) as u32).into()
-        } else {
-            self.others[&name].into()
+    /// Look up a segment by its numeric name, or return `None` if there is none.
+    pub fn find(&self, name: usize) -> Option<Id<Segment>> {
+        match name.checked_sub(self.first) {
+            Some(offset) if offset < self.sequential_len => Some((offset as u32).into()),
+            _ => self.others.get(&name).map(|&id| id.into()),
+        }
+    }
+
+    /// Record a segment name, which may be numeric or textual. As with `insert`,
+    /// an existing name keeps its ID.
+    pub fn insert_name(&mut self, name: SegName, id: Id<Segment>) {
+        match name {
+            SegName::Num(num) => self.insert(num, id),
+            SegName::Text(text) => {
+                self.text.entry(text.to_vec().into()).or_insert(id.into());
+            }
+        }
+    }
+
+    /// Look up a segment by a name that may be numeric or textual.
+    pub fn find_name(&self, name: SegName) -> Option<Id<Segment>> {
+        match name {
+            SegName::Num(num) => self.find(num),
+            SegName::Text(text) => self.text.get(text.as_ref() as &[u8]).map(|&id| id.into()),
         }
     }
 
@@ -36,8 +77,76 @@ impl NameMap {
     pub fn build(gfa: &FlatGFA) -> Self {
         let mut name_map = NameMap::default();
         for (id, seg) in gfa.segs.items() {
-            name_map.insert(seg.name, id);
+            name_map.insert_name(gfa.get_seg_name(seg), id);
         }
         name_map
     }
 }
+
+/// Insert names and IDs in a scrambled order and check that every lookup
+/// agrees with a plain hash map.
+#[test]
+fn test_shuffled_names() {
+    use crate::flatgfa::SegName;
+    use bstr::BStr;
+
+    // A small linear congruential generator, so the test is deterministic.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut rand = move |n: usize| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((state >> 33) as usize) % n
+    };
+
+    for round in 0..200 {
+        let count = 1 + rand(50);
+        let first = rand(3);
+
+        // Mostly sequential names, with a few swapped or replaced.
+        let mut names: Vec<usize> = (first..first + count).collect();
+        for _ in 0..rand(count + 1) {
+            let (a, b) = (rand(count), rand(count));
+            names.swap(a, b);
+        }
+        if round % 3 == 0 {
+            let i = rand(count);
+            names[i] = first + rand(count * 2);
+        }
+
+        // Insert the IDs themselves in a shuffled order, too.
+        let mut order: Vec<usize> = (0..count).collect();
+        if round % 2 == 0 {
+            for i in (1..count).rev() {
+                order.swap(i, rand(i + 1));
+            }
+        }
+
+        let mut map = NameMap::default();
+        let mut expected = HashMap::new();
+        for &i in &order {
+            map.insert(names[i], Id::from(i as u32));
+            expected.entry(names[i]).or_insert(i as u32);
+        }
+
+        for name in 0..first + count * 2 + 1 {
+            assert_eq!(
+                map.find(name),
+                expected.get(&name).map(|&id| Id::from(id)),
+                "round {}, name {}",
+                round,
+                name
+            );
+        }
+    }
+
+    // Textual names follow the same first-one-wins rule.
+    let mut map = NameMap::default();
+    map.insert_name(SegName::Text(BStr::new("x")), Id::from(0));
+    map.insert_name(SegName::Text(BStr::new("x")), Id::from(1));
+    assert_eq!(
+        map.find_name(SegName::Text(BStr::new("x"))),
+        Some(Id::from(0))
+    );
+    assert_eq!(map.find_name(SegName::Text(BStr::new("y"))), None);
+}
@@ -1,128 +1,1472 @@
-use crate::flatgfa::{self, Handle, LineKind, Orientation};
-use crate::gfaline;
+use crate::bgzf;
+use crate::flatgfa::{
+    self, AlignOp, AlignOpcode, Containment, Handle, Jump, LineKind, Link, Orientation,
+    OverlapSpelling, Path, SegName, Segment,
+};
+use crate::gfaline::{self, LineError, Version};
 use crate::memfile::MemchrSplit;
 use crate::namemap::NameMap;
-use std::io::BufRead;
+use crate::pool::{CompactSpan, Id, Span, Store};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// An error encountered while parsing a GFA text file.
+///
+/// Every error records the (1-based) line number where it occurred. Errors that are
+/// about a specific field include that field's text.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A line starts with a record type that we do not support.
+    UnexpectedRecord { line: usize, record: String },
+
+    /// A line ends before a required field.
+    MissingField { line: usize, field: &'static str },
+
+    /// A line has a record type from a different GFA version than the file's.
+    WrongVersion {
+        line: usize,
+        record: String,
+        version: Version,
+    },
+
+    /// A header declares a different GFA version than the one we're parsing.
+    VersionConflict {
+        line: usize,
+        declared: String,
+        version: Version,
+    },
+
+    /// An orientation is something other than `+` or `-`.
+    BadOrientation { line: usize, value: String },
+
+    /// A segment name is invalid.
+    BadName { line: usize, name: String },
+
+    /// A field has an invalid value.
+    BadField {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+
+    /// A path's segment list has a malformed step, which starts at `offset` bytes into
+    /// the list.
+    BadStep {
+        line: usize,
+        step: String,
+        offset: usize,
+    },
+
+    /// A link, jump, containment, or path refers to a segment that the file never
+    /// defines. `record` is the kind of line with the reference, like `"link"`.
+    UnknownSegment {
+        line: usize,
+        record: &'static str,
+        name: String,
+    },
+
+    /// A path step refers to a segment that the file never defines. `step` is the
+    /// step's index within the path.
+    UnknownStep {
+        line: usize,
+        path: String,
+        step: usize,
+        name: String,
+    },
+
+    /// Reading the input failed.
+    Io { line: usize, error: std::io::Error },
+
+    /// Reading a FASTA file of external sequences failed. The line is the first
+    /// segment that needs a sequence from the file.
+    SequenceSource {
+        line: usize,
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    /// A segment's external sequence is missing from its FASTA file.
+    MissingSequence {
+        line: usize,
+        name: String,
+        path: PathBuf,
+    },
+
+    /// A segment's sequence does not match the length that its `LN` tag declares. For
+    /// a sequence in the GFA text, this is an error in strict mode. Otherwise, it is a
+    /// warning, and we keep the sequence. An external sequence must always match.
+    SequenceLength {
+        line: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Two files that we're merging define the same segment with different sequences.
+    ConflictingSegment { line: usize, name: String },
+
+    /// Two paths have the same name (in strict mode).
+    DuplicatePath { line: usize, name: String },
+
+    /// A path has a different number of overlaps than the one fewer than its steps
+    /// that GFA requires (or one per step, for a path that wraps around). This is an
+    /// error in strict mode. Otherwise, it is a warning, and we drop the extra
+    /// overlaps or pad the list with `0M`.
+    OverlapCount {
+        line: usize,
+        path: String,
+        steps: usize,
+        overlaps: usize,
+    },
+
+    /// A link's overlap has more than `CompactSpan::MAX_LEN` operations.
+    LongOverlap { line: usize, ops: usize },
+
+    /// A path with the same name as an earlier one got a new name (outside of strict
+    /// mode). This is only a warning.
+    RenamedPath {
+        line: usize,
+        name: String,
+        new_name: String,
+    },
+
+    /// A segment that no link, jump, containment, or path refers to (with
+    /// `warn_orphans`). This is only a warning.
+    OrphanSegment { line: usize, name: String },
+
+    /// A link that repeats an earlier one, on line `first`, which we dropped (see
+    /// `Parser::with_duplicate_links`). This is only a warning.
+    DuplicateLink { line: usize, first: usize },
+
+    /// A header gives a tag a different value than an earlier header field did.
+    /// `first` is the earlier field.
+    HeaderConflict {
+        line: usize,
+        field: String,
+        first: String,
+    },
+
+    /// A file defines a segment name twice. `first` is the line with the first
+    /// definition.
+    DuplicateSegment {
+        line: usize,
+        name: String,
+        first: usize,
+    },
+
+    /// `IncrementalParser::feed_line` got a line while the text from `feed` ended in
+    /// the middle of this line.
+    UnfinishedLine { line: usize },
+
+    /// An error in one of several files that we're merging.
+    File {
+        path: PathBuf,
+        error: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    /// Attach a line number to an error from parsing that line.
+    fn at(line: usize, error: LineError) -> Self {
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        match error {
+            LineError::UnexpectedRecord(record) => Self::UnexpectedRecord {
+                line,
+                record: text(record),
+            },
+            LineError::MissingField(field) => Self::MissingField { line, field },
+            LineError::WrongVersion(record, version) => Self::WrongVersion {
+                line,
+                record: text(record),
+                version,
+            },
+            LineError::VersionConflict(declared, version) => Self::VersionConflict {
+                line,
+                declared: text(declared),
+                version,
+            },
+            LineError::BadOrientation(value) => Self::BadOrientation {
+                line,
+                value: text(value),
+            },
+            LineError::BadName(name) => Self::BadName {
+                line,
+                name: text(name),
+            },
+            LineError::BadField(field, value) => Self::BadField {
+                line,
+                field,
+                value: text(value),
+            },
+            LineError::BadStep(err) => Self::BadStep {
+                line,
+                step: text(err.step),
+                offset: err.offset,
+            },
+            LineError::ConflictingSegment(name) => Self::ConflictingSegment {
+                line,
+                name: text(name),
+            },
+            LineError::DuplicatePath(name) => Self::DuplicatePath {
+                line,
+                name: text(&name),
+            },
+            LineError::HeaderConflict(field, first) => Self::HeaderConflict {
+                line,
+                field: text(field),
+                first: text(&first),
+            },
+            LineError::OverlapCount(path, steps, overlaps) => Self::OverlapCount {
+                line,
+                path: text(path),
+                steps,
+                overlaps,
+            },
+            LineError::LongOverlap(ops) => Self::LongOverlap { line, ops },
+            LineError::SequenceLength(name, expected, found) => Self::SequenceLength {
+                line,
+                name: text(name),
+                expected,
+                found,
+            },
+            LineError::DuplicateSegment(name, first) => Self::DuplicateSegment {
+                line,
+                name: text(name),
+                first,
+            },
+        }
+    }
+
+    /// The line number where the error occurred (within its file, for a `File` error).
+    pub fn line(&self) -> usize {
+        match self {
+            Self::UnexpectedRecord { line, .. }
+            | Self::MissingField { line, .. }
+            | Self::WrongVersion { line, .. }
+            | Self::VersionConflict { line, .. }
+            | Self::BadOrientation { line, .. }
+            | Self::BadName { line, .. }
+            | Self::BadField { line, .. }
+            | Self::BadStep { line, .. }
+            | Self::UnknownSegment { line, .. }
+            | Self::UnknownStep { line, .. }
+            | Self::Io { line, .. }
+            | Self::SequenceSource { line, .. }
+            | Self::MissingSequence { line, .. }
+            | Self::SequenceLength { line, .. }
+            | Self::ConflictingSegment { line, .. }
+            | Self::DuplicatePath { line, .. }
+            | Self::OverlapCount { line, .. }
+            | Self::LongOverlap { line, .. }
+            | Self::RenamedPath { line, .. }
+            | Self::OrphanSegment { line, .. }
+            | Self::DuplicateLink { line, .. }
+            | Self::HeaderConflict { line, .. }
+            | Self::DuplicateSegment { line, .. }
+            | Self::UnfinishedLine { line } => *line,
+            Self::File { error, .. } => error.line(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Self::File { path, error } = self {
+            return write!(f, "{}: {}", path.display(), error);
+        }
+        write!(f, "line {}: ", self.line())?;
+        match self {
+            Self::UnexpectedRecord { record, .. } => {
+                write!(f, "unexpected record type `{}`", record)
+            }
+            Self::MissingField { field, .. } => write!(f, "missing {} field", field),
+            Self::WrongVersion {
+                record, version, ..
+            } => write!(f, "`{}` records are not part of {}", record, version),
+            Self::VersionConflict {
+                declared, version, ..
+            } => write!(
+                f,
+                "declared version `{}` conflicts with {}",
+                declared, version
+            ),
+            Self::BadOrientation { value, .. } => {
+                write!(f, "bad orientation `{}` (expected + or -)", value)
+            }
+            Self::BadName { name, .. } => write!(f, "bad segment name `{}`", name),
+            Self::BadField { field, value, .. } => write!(f, "bad {} `{}`", field, value),
+            Self::BadStep { step, offset, .. } if step.is_empty() => {
+                write!(f, "missing path step at byte {}", offset)
+            }
+            Self::BadStep { step, offset, .. } => {
+                write!(f, "bad path step `{}` at byte {}", step, offset)
+            }
+            Self::UnknownSegment { record, name, .. } => {
+                write!(f, "unknown segment `{}` in {}", name, record)
+            }
+            Self::UnknownStep {
+                path, step, name, ..
+            } => write!(
+                f,
+                "path `{}` step {} references unknown segment `{}`",
+                path, step, name
+            ),
+            Self::Io { error, .. } => write!(f, "{}", error),
+            Self::SequenceSource { path, error, .. } => {
+                write!(f, "reading `{}`: {}", path.display(), error)
+            }
+            Self::MissingSequence { name, path, .. } => write!(
+                f,
+                "no sequence for segment `{}` in `{}`",
+                name,
+                path.display()
+            ),
+            Self::SequenceLength {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "segment `{}` has length {} but its sequence has length {}",
+                name, expected, found
+            ),
+            Self::ConflictingSegment { name, .. } => write!(
+                f,
+                "segment `{}` has a different sequence than in an earlier file",
+                name
+            ),
+            Self::DuplicatePath { name, .. } => write!(f, "duplicate path name `{}`", name),
+            Self::OverlapCount {
+                path,
+                steps,
+                overlaps,
+                ..
+            } => write!(
+                f,
+                "path `{}` has {} overlaps for {} steps (expected {})",
+                path,
+                overlaps,
+                steps,
+                steps.saturating_sub(1)
+            ),
+            Self::LongOverlap { ops, .. } => write!(
+                f,
+                "bad overlap: too many operations ({}, but at most {})",
+                ops,
+                CompactSpan::<AlignOp>::MAX_LEN
+            ),
+            Self::OrphanSegment { name, .. } => {
+                write!(f, "segment `{}` is not used by any link or path", name)
+            }
+            Self::DuplicateLink { first, .. } => {
+                write!(f, "dropped duplicate of the link on line {}", first)
+            }
+            Self::RenamedPath { name, new_name, .. } => write!(
+                f,
+                "duplicate path name `{}` (renamed to `{}`)",
+                name, new_name
+            ),
+            Self::DuplicateSegment { name, first, .. } => {
+                write!(f, "segment `{}` is already defined on line {}", name, first)
+            }
+            Self::HeaderConflict { field, first, .. } => write!(
+                f,
+                "header field `{}` conflicts with earlier field `{}`",
+                field, first
+            ),
+            Self::UnfinishedLine { .. } => {
+                write!(f, "unfinished line from `feed` before `feed_line`")
+            }
+            Self::File { .. } => unreachable!(),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } | Self::SequenceSource { error, .. } => Some(error),
+            Self::File { error, .. } => error.source(),
+            _ => None,
+        }
+    }
+}
+
+/// The policies a `Parser` follows for input that is malformed, ambiguous, or
+/// unneeded.
+///
+/// The default is the same as a fresh parser's: permissive about malformed tags,
+/// strict about duplicate segments and unknown records. Each field corresponds to a
+/// `Parser` builder method, which documents it in more detail.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// The GFA version to parse, or `None` to use the header's `VN` tag. See
+    /// `Parser::with_version`.
+    pub version: Option<Version>,
+
+    /// Reject malformed optional fields and repeated path names. See
+    /// `Parser::strict`.
+    pub strict: bool,
+
+    /// Keep the first of several segments with the same name, instead of reporting an
+    /// error. See `Parser::keep_first_duplicates`.
+    pub keep_first_duplicates: bool,
+
+    /// Keep lines with unknown record types verbatim, instead of reporting an error.
+    /// See `Parser::keep_unknown_records`.
+    pub keep_unknown_records: bool,
+
+    /// Omit segment sequences, keeping only their lengths. See
+    /// `Parser::skip_sequences`.
+    pub skip_sequences: bool,
+
+    /// Define placeholders for segments that are referenced but never defined,
+    /// instead of reporting an error. See `Parser::with_placeholder_segments`.
+    pub placeholder_segments: bool,
+
+    /// A FASTA file with sequences for segments whose sequence is `*`. See
+    /// `Parser::with_sequence_source`.
+    pub sequence_source: Option<PathBuf>,
+
+    /// Warn about segments that nothing refers to. See `Parser::warn_orphans`.
+    pub warn_orphans: bool,
+
+    /// Leave out the paths and walks, keeping only segments and edges. See
+    /// `Parser::skip_paths`.
+    pub skip_paths: bool,
+
+    /// Which repeated links to drop. See `Parser::with_duplicate_links`.
+    pub duplicate_links: DuplicateLinks,
+
+    /// How to rewrite blunt overlaps, or `None` to keep them as they are written. See
+    /// `Parser::normalize_overlaps`.
+    pub normalize_overlaps: Option<BluntOverlap>,
+}
+
+/// Which links count as duplicates, for `Parser::with_duplicate_links`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateLinks {
+    /// Keep every link, even exact repeats.
+    #[default]
+    Keep,
+
+    /// Drop links with the same endpoints, orientations, and overlap as an earlier
+    /// link.
+    Exact,
+
+    /// Also drop links that describe an earlier link from the other strand: `L a + b -`
+    /// is the same adjacency as `L b + a -`, with the overlap reversed (and its
+    /// insertions and deletions swapped).
+    Symmetric,
+}
+
+impl std::str::FromStr for DuplicateLinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "exact" => Ok(Self::Exact),
+            "symmetric" => Ok(Self::Symmetric),
+            _ => Err(format!(
+                "unknown duplicate link policy `{}` (expected keep, exact, or symmetric)",
+                s
+            )),
+        }
+    }
+}
 
 pub struct Parser<'a, P: flatgfa::StoreFamily<'a>> {
     /// The flat representation we're building.
     flat: flatgfa::GFAStore<'a, P>,
 
-    /// All segment IDs, indexed by their names, which we need to refer to segments in paths.
-    seg_ids: NameMap,
+    /// References to segments that were not yet defined when we encountered them.
+    fixups: Vec<Fixup>,
+
+    /// The number of the line we're currently parsing.
+    line: usize,
+
+    /// The GFA version we're parsing, once we know it.
+    version: Option<Version>,
+
+    /// How to handle input that is not quite right.
+    config: ParserConfig,
+
+    /// Segments whose sequences we need to fetch from a FASTA file at the end.
+    external_seqs: Vec<ExternalSeq>,
+
+    /// The files we have read so far, when merging several files with `parse_many`.
+    files: Vec<PathBuf>,
+
+    /// The names of all the paths (and walks) so far, to find duplicates.
+    path_names: HashSet<Box<[u8]>>,
+
+    /// The number of segments from the files before the current one, when merging.
+    file_segs: usize,
+
+    /// The line number where each segment was defined, for reporting duplicates.
+    seg_lines: Vec<usize>,
+
+    /// The line number where each link was defined, when we're dropping duplicates.
+    link_lines: Vec<usize>,
+
+    /// Which segments some link, jump, containment, or path step refers to, when
+    /// we're warning about orphans. We mark them as we resolve segment names.
+    used_segs: Vec<bool>,
+
+    /// A callback for reporting problems that are not errors, if any.
+    warn: Option<WarningFn<'a>>,
+
+    /// A callback for reporting our progress, if any.
+    report: Option<ProgressFn<'a>>,
+
+    /// A callback for reporting statistics at the end, if any, and the statistics we
+    /// collect along the way.
+    report_stats: Option<StatsFn<'a>>,
+    stats: ParseStats,
+
+    /// When we started parsing.
+    start: Instant,
+
+    /// The number of bytes and lines we have read.
+    bytes: u64,
+    lines: usize,
+
+    /// Units of work (lines read or deferred lines added) since the start, and the
+    /// count at which to report our progress next. This is `usize::MAX` when there is
+    /// nothing to report to, so checking is cheap.
+    ticks: usize,
+    next_report: usize,
+}
+
+/// A reader that counts the bytes consumed from it.
+struct CountingReader<'c, R> {
+    inner: R,
+    count: &'c Cell<u64>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count.set(self.count.get() + len as u64);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<'_, R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.count.set(self.count.get() + amt as u64);
+    }
+}
+
+/// How far along the parser is, as reported to `Parser::with_progress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes of input read so far. For compressed input, this counts
+    /// the compressed bytes.
+    pub bytes: u64,
+
+    /// The number of lines read so far.
+    pub lines: usize,
+
+    /// The number of segments, links, and paths in the graph so far.
+    pub segs: usize,
+    pub links: usize,
+    pub paths: usize,
+}
+
+/// A callback for `Parser::with_progress`.
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// Statistics about a finished parse, as reported to `Parser::with_stats`.
+///
+/// These are meant for finding out why a particular input is slow to parse.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// The number of bytes of input read. For compressed input, this counts the
+    /// compressed bytes.
+    pub bytes: u64,
+
+    /// The number of lines read.
+    pub lines: usize,
+
+    /// The number of records of each type, including ones that we did not load (like
+    /// paths, with `skip_paths`). A GFA 2 edge counts as a link or a containment, and
+    /// a group counts as a path.
+    pub headers: usize,
+    pub segs: usize,
+    pub links: usize,
+    pub paths: usize,
+    pub walks: usize,
+    pub jumps: usize,
+    pub containments: usize,
+    pub other_lines: usize,
+
+    /// The total length of the segments' sequences.
+    pub seq_len: usize,
+
+    /// The number of segments whose `LN` tag disagrees with their sequence (outside of
+    /// strict mode, where it is an error).
+    pub length_mismatches: usize,
+
+    /// The number of links and paths whose blunt overlaps we rewrote, with
+    /// `Parser::normalize_overlaps`.
+    pub normalized_overlaps: usize,
+
+    /// The number of lines (links, paths, etc.) that we set aside until we had seen
+    /// every segment, when parsing text in memory.
+    pub deferred_lines: usize,
+
+    /// The most bytes of deferred lines that we held at once. These are references
+    /// into the input, so we did not have to copy them.
+    pub deferred_bytes: usize,
+
+    /// The number of references to segments that were not defined yet when we read
+    /// them, so we resolved them at the end, and the bytes of segment names that we
+    /// kept for them. (These only occur when streaming.)
+    pub fixups: usize,
+    pub fixup_bytes: usize,
+
+    /// The time we spent reading and adding lines, adding the deferred lines, and
+    /// finishing up at the end (resolving references, dropping duplicate links, and
+    /// fetching external sequences).
+    pub scan_time: Duration,
+    pub deferred_time: Duration,
+    pub finish_time: Duration,
+}
+
+impl fmt::Display for ParseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#stat\tvalue")?;
+        for (name, value) in [
+            ("bytes", self.bytes as usize),
+            ("lines", self.lines),
+            ("headers", self.headers),
+            ("segs", self.segs),
+            ("links", self.links),
+            ("paths", self.paths),
+            ("walks", self.walks),
+            ("jumps", self.jumps),
+            ("containments", self.containments),
+            ("other_lines", self.other_lines),
+            ("seq_len", self.seq_len),
+            ("length_mismatches", self.length_mismatches),
+            ("normalized_overlaps", self.normalized_overlaps),
+            ("deferred_lines", self.deferred_lines),
+            ("deferred_bytes", self.deferred_bytes),
+            ("fixups", self.fixups),
+            ("fixup_bytes", self.fixup_bytes),
+        ] {
+            writeln!(f, "{}\t{}", name, value)?;
+        }
+        for (name, time) in [
+            ("scan_time", self.scan_time),
+            ("deferred_time", self.deferred_time),
+            ("finish_time", self.finish_time),
+        ] {
+            writeln!(f, "{}\t{:.3}", name, time.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// How to write blunt overlaps (ones that overlap no bases), for
+/// `Parser::normalize_overlaps`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BluntOverlap {
+    /// `*`, which means no overlaps at all on a path.
+    Star,
+
+    /// `0M`, which on a path means one `0M` between each pair of steps.
+    #[default]
+    ZeroMatch,
+
+    /// An empty field.
+    Empty,
+}
+
+impl std::str::FromStr for BluntOverlap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "*" => Ok(Self::Star),
+            "0M" => Ok(Self::ZeroMatch),
+            "empty" => Ok(Self::Empty),
+            _ => Err(format!(
+                "unknown blunt overlap `{}` (expected 0M, *, or empty)",
+                s
+            )),
+        }
+    }
+}
+
+/// Check whether an overlap is blunt: it has no operations, or only empty ones
+/// (like `0M`).
+fn is_blunt(overlap: &[AlignOp]) -> bool {
+    overlap.iter().all(|op| op.is_empty())
+}
+
+/// A callback for `Parser::with_stats`.
+type StatsFn<'a> = Box<dyn FnOnce(&ParseStats) + 'a>;
+
+/// Record a path's name. If an earlier path already has the name, record and return
+/// a new one instead: the name with the first unused suffix among `.1`, `.2`, etc.
+fn dedup_path_name(names: &mut HashSet<Box<[u8]>>, name: &[u8]) -> Option<Vec<u8>> {
+    if names.insert(name.into()) {
+        return None;
+    }
+    (1..)
+        .map(|n| [name, format!(".{}", n).as_bytes()].concat())
+        .find(|new_name| names.insert(new_name.as_slice().into()))
+}
+
+/// Get the sequence we store for a segment's sequence field: empty for `*`.
+fn stored_seq(seq: &[u8]) -> &[u8] {
+    if seq == b"*" {
+        b""
+    } else {
+        seq
+    }
+}
+
+/// A callback for `Parser::with_warnings`.
+type WarningFn<'a> = Box<dyn FnMut(&ParseError) + 'a>;
+
+/// The number of ticks between progress reports.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// A segment whose sequence is `*` in the GFA text, to be filled in from a FASTA file.
+struct ExternalSeq {
+    seg: Id<Segment>,
+    name: Box<[u8]>,
+    line: usize,
+
+    /// The FASTA file to look in: the segment's `UR` tag or the parser's default.
+    source: PathBuf,
+
+    /// The length from the segment's `LN` tag (or GFA 2 length field), if any.
+    length: Option<usize>,
+}
+
+/// A reference to a segment that we could not resolve when we first saw it, because
+/// the segment's definition comes later in the file.
+///
+/// We store a placeholder handle (with the right orientation) in the reference's
+/// place, and we patch it once we know all the segments.
+struct Fixup {
+    target: FixupTarget,
+    name: Box<[u8]>,
+    line: usize,
+
+    /// The index of the reference's file in `files`, when merging files.
+    file: usize,
+}
+
+/// What makes two links duplicates: their endpoints and overlap, as `(opcode, len)`
+/// pairs.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct LinkKey(usize, usize, Vec<(u8, u32)>);
+
+impl LinkKey {
+    /// Get a link's key. With `symmetric`, a link and its reverse complement get the
+    /// same key: the lesser of the two.
+    fn new(gfa: &flatgfa::FlatGFA, link: &Link, symmetric: bool) -> Self {
+        let ops = gfa.get_alignment(link.overlap).ops;
+        let key = Self(
+            link.from.index(),
+            link.to.index(),
+            ops.iter().map(|op| (op.op().into(), op.len())).collect(),
+        );
+        if !symmetric {
+            return key;
+        }
+
+        // Traversing the link backward swaps the roles of the two segments.
+        let swap = |op: AlignOpcode| match op {
+            AlignOpcode::Insertion => AlignOpcode::Deletion,
+            AlignOpcode::Deletion => AlignOpcode::Insertion,
+            op => op,
+        };
+        let rev = Self(
+            link.to.flip().index(),
+            link.from.flip().index(),
+            ops.iter()
+                .rev()
+                .map(|op| (swap(op.op()).into(), op.len()))
+                .collect(),
+        );
+        key.min(rev)
+    }
+}
+
+/// The location of a handle that needs a fixup.
+enum FixupTarget {
+    Step { path: Id<Path>, step: Id<Handle> },
+    LinkFrom(Id<Link>),
+    LinkTo(Id<Link>),
+    JumpFrom(Id<Jump>),
+    JumpTo(Id<Jump>),
+    Container(Id<Containment>),
+    Contained(Id<Containment>),
+}
+
+impl FixupTarget {
+    /// Describe the kind of record that contains the reference, for error messages.
+    /// (Walks and GFA 2 groups become paths, and GFA 2 edges become links or
+    /// containments.)
+    fn record(&self) -> &'static str {
+        match self {
+            Self::Step { .. } => "path",
+            Self::LinkFrom(_) | Self::LinkTo(_) => "link",
+            Self::JumpFrom(_) | Self::JumpTo(_) => "jump",
+            Self::Container(_) | Self::Contained(_) => "containment",
+        }
+    }
 }
 
 impl<'a, P: flatgfa::StoreFamily<'a>> Parser<'a, P> {
     pub fn new(builder: flatgfa::GFAStore<'a, P>) -> Self {
         Self {
             flat: builder,
-            seg_ids: NameMap::default(),
+            fixups: Vec::new(),
+            line: 0,
+            version: None,
+            config: ParserConfig::default(),
+            external_seqs: Vec::new(),
+            files: Vec::new(),
+            path_names: HashSet::new(),
+            file_segs: 0,
+            seg_lines: Vec::new(),
+            link_lines: Vec::new(),
+            used_segs: Vec::new(),
+            warn: None,
+            report: None,
+            report_stats: None,
+            stats: ParseStats::default(),
+            start: Instant::now(),
+            bytes: 0,
+            lines: 0,
+            ticks: 0,
+            next_report: usize::MAX,
         }
     }
 
+    /// Parse the input as a specific version of GFA.
+    ///
+    /// By default, we use the version from the header's `VN` tag, or GFA 1 if there
+    /// is none. A GFA 2 graph becomes the equivalent GFA 1 graph: segments, edges
+    /// become links or containments, and groups become paths. See `add_edge` and
+    /// `add_group` for the details.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self.config.version = Some(version);
+        self
+    }
+
+    /// Use all the policies in `config` at once, replacing any that were set before.
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.version = config.version;
+        self.config = config;
+        self
+    }
+
+    /// Reject optional fields that are not well-formed `TAG:TYPE:VALUE` tags and
+    /// repeated path names, and report the exact malformed operation in bad CIGAR
+    /// overlaps.
+    ///
+    /// By default, we keep malformed fields verbatim (they just do not appear among
+    /// the parsed tags), a bad overlap is an error about the entire field, and a path
+    /// with the same name as an earlier one gets a `.1` suffix (or `.2`, etc.), with a
+    /// warning. Walks share the namespace with paths.
+    pub fn strict(mut self) -> Self {
+        self.config.strict = true;
+        self
+    }
+
+    /// Keep the first of several segments in a file with the same name, and drop the
+    /// rest (with a warning, if there is a `with_warnings` callback).
+    ///
+    /// By default, a repeated segment name is an error, since references to it would
+    /// be ambiguous.
+    pub fn keep_first_duplicates(mut self) -> Self {
+        self.config.keep_first_duplicates = true;
+        self
+    }
+
+    /// Keep lines with record types we don't understand (like comments or records from
+    /// other tools' extensions) verbatim, so printing the graph writes them back in
+    /// place. Blank lines are dropped.
+    ///
+    /// By default, an unknown record type is an error. Records from the other GFA
+    /// version are still errors.
+    pub fn keep_unknown_records(mut self) -> Self {
+        self.config.keep_unknown_records = true;
+        self
+    }
+
+    /// Do not store segment sequences, to save memory when only the graph's topology
+    /// matters.
+    ///
+    /// Segments get empty sequences, and we record each sequence's length in an `LN`
+    /// tag (unless there already is one), so `FlatGFA::seg_len` still works. This
+    /// overrides `with_sequence_source`.
+    pub fn skip_sequences(mut self) -> Self {
+        self.config.skip_sequences = true;
+        self
+    }
+
+    /// Define an empty placeholder segment for each name that is referenced (by a
+    /// link, path, etc.) but never defined by an `S` line.
+    ///
+    /// This is useful for subsets of larger graphs. By default, such references are
+    /// errors. The placeholders come after all the other lines in the output.
+    pub fn with_placeholder_segments(mut self) -> Self {
+        self.config.placeholder_segments = true;
+        self
+    }
+
+    /// Fill in the sequences of segments whose sequence is `*` from a FASTA file.
+    ///
+    /// After parsing, we stream through the FASTA file (which may be gzip-compressed)
+    /// and use the records whose names match these segments' names. A segment with a
+    /// `UR:Z:<path>` tag uses that file instead. (We support plain paths and `file://`
+    /// URIs.) Segments with inline sequences are unaffected. It is an error if a
+    /// segment's record is missing or its length disagrees with the segment's `LN` tag.
+    pub fn with_sequence_source(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.sequence_source = Some(path.into());
+        self
+    }
+
+    /// When we're done, report a warning (to the `with_warnings` callback) for each
+    /// segment that no link, jump, containment, or path refers to.
+    ///
+    /// See `FlatGFA::orphan_segments`, which finds them, and
+    /// `HeapGFAStore::drop_orphan_segments`, which removes them.
+    pub fn warn_orphans(mut self) -> Self {
+        self.config.warn_orphans = true;
+        self
+    }
+
+    /// Do not load paths or walks (or GFA 2 groups), to save time and memory when only
+    /// the segments and edges matter.
+    ///
+    /// We record where the path lines were, but we never parse them, so the graph has
+    /// no paths at all. `FlatGFA::paths_loaded` tells such a graph apart from one
+    /// that really has no paths, and printing it leaves out the path lines.
+    pub fn skip_paths(mut self) -> Self {
+        self.config.skip_paths = true;
+        self
+    }
+
+    /// When we're done, drop each link that repeats an earlier one, and report a
+    /// warning (to the `with_warnings` callback) for each.
+    ///
+    /// Some assemblers emit the same link twice, or emit a link along with its
+    /// reverse complement, which inflates degree and edge counts.
+    /// `DuplicateLinks::Exact` catches the former, and `DuplicateLinks::Symmetric`
+    /// catches both. A dropped link's optional fields are lost, even if they differ
+    /// from the earlier link's. By default, we keep every link.
+    pub fn with_duplicate_links(mut self, policy: DuplicateLinks) -> Self {
+        self.config.duplicate_links = policy;
+        self
+    }
+
+    /// Rewrite blunt overlaps (`*`, `0M`, an empty field, or a list of these on a
+    /// path) to use the spelling `target`, on links and paths.
+    ///
+    /// Different tools write blunt overlaps in different ways, and we otherwise keep
+    /// whichever one the file uses so we can write it back out the same way. That
+    /// makes graphs from different tools look different when they are not.
+    /// `ParseStats::normalized_overlaps` counts the links and paths that we rewrote.
+    /// Overlaps that cover any bases are never changed.
+    pub fn normalize_overlaps(mut self, target: BluntOverlap) -> Self {
+        self.config.normalize_overlaps = Some(target);
+        self
+    }
+
+    /// Call `report` with our progress periodically (every 65,536 lines or so), and
+    /// once more when we're done.
+    ///
+    /// This is useful for long-running parses. `parse_mem` and `parse_many` add links
+    /// and paths after reading all the lines in each file, so the counts of links and
+    /// paths keep growing in reports after `bytes` and `lines` stop.
+    pub fn with_progress(mut self, report: impl FnMut(&Progress) + 'a) -> Self {
+        self.report = Some(Box::new(report));
+        self.next_report = PROGRESS_INTERVAL;
+        self
+    }
+
+    /// Call `report` once, when we're done, with statistics about the parse: how many
+    /// records of each type we read, how much we had to defer, and where the time
+    /// went. See `ParseStats`.
+    pub fn with_stats(mut self, report: impl FnOnce(&ParseStats) + 'a) -> Self {
+        self.report_stats = Some(Box::new(report));
+        self
+    }
+
+    /// Call `report` for each problem that we tolerate instead of reporting as an
+    /// error, like a repeated segment name with `keep_first_duplicates`.
+    pub fn with_warnings(mut self, report: impl FnMut(&ParseError) + 'a) -> Self {
+        self.warn = Some(Box::new(report));
+        self
+    }
+
     /// Parse a GFA text file from an I/O stream.
-    pub fn parse_stream<R: BufRead>(mut self, stream: R) -> flatgfa::GFAStore<'a, P> {
-        // We can parse segments immediately, but we need to defer links and paths until we have all
-        // the segment names that they might refer to.
-        let mut deferred_links = Vec::new();
-        let mut deferred_paths = Vec::new();
-
-        // Parse or defer each line.
-        for line in stream.split(b'\n') {
-            let line = line.unwrap();
-
-            // Avoid parsing paths entirely for now; just preserve the entire line for later.
-            if line[0] == b'P' {
-                self.flat.record_line(LineKind::Path);
-                deferred_paths.push(line);
-                continue;
+    ///
+    /// This handles every line as soon as we read it, so it does not need to buffer the
+    /// text of the file, and peak memory use is (roughly) just the size of the FlatGFA
+    /// we produce. The exception is a reference to a segment that is defined later in
+    /// the file: we must hold on to its name until the end. This is cheap for typical
+    /// files, which define segments before using them, but a file that puts (say) all
+    /// of its paths before all of its segments will need memory for every step's name.
+    ///
+    /// The stream may be gzip- or BGZF-compressed; we detect this from its first bytes.
+    pub fn parse_stream<R: BufRead>(
+        mut self,
+        stream: R,
+    ) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        self.add_stream(stream)?;
+        self.finish()
+    }
+
+    /// Parse GFA text that we receive piece by piece, instead of reading it from a
+    /// stream. See `IncrementalParser`.
+    pub fn incremental(self) -> IncrementalParser<'a, P> {
+        IncrementalParser {
+            parser: self,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Parse a GFA text file from an in-memory buffer, such as a memory-mapped file.
+    ///
+    /// Every field borrows from `buf`, so unlike `parse_stream`, this does not copy
+    /// any lines. Compressed data is the exception: we fall back to streaming it
+    /// through a decompressor.
+    pub fn parse_mem(mut self, buf: &[u8]) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        if bgzf::is_gzip(buf) {
+            self.add_stream(buf)?;
+        } else {
+            self.add_mem(buf)?;
+        }
+        self.finish()
+    }
+
+    /// Parse a GFA text file, which may be gzip- or BGZF-compressed.
+    ///
+    /// We memory-map uncompressed files and parse them like `parse_mem`. Compressed
+    /// files are streamed through a decompressor instead, like `parse_stream`, as are
+    /// pipes and other files that are not regular files. The path `-` means stdin.
+    pub fn parse_file(
+        mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        self.add_file(path.as_ref())?;
+        self.finish()
+    }
+
+    /// Parse several GFA text files, which may be compressed, into a single graph.
+    ///
+    /// Segments with the same name in different files are the same segment, so each
+    /// file may refer to segments from the others. (For example, two files that both
+    /// number their segments from 1 share those segments.) It is an error if the
+    /// sequences differ, and we keep only the first definition. The other lines are
+    /// concatenated in order, and paths with the same name are handled as they are
+    /// within one file (see `strict`). Errors say which file they come from.
+    pub fn parse_many(mut self, paths: &[PathBuf]) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        for path in paths {
+            self.files.push(path.clone());
+            self.file_segs = self.flat.segs.len();
+            self.line = 0;
+            self.add_file(path).map_err(|error| ParseError::File {
+                path: path.clone(),
+                error: Box::new(error),
+            })?;
+        }
+        self.finish()
+    }
+
+    /// Read and add all the lines in a (possibly compressed) GFA file, or in stdin if
+    /// the path is `-`.
+    fn add_file(&mut self, path: &std::path::Path) -> Result<(), ParseError> {
+        if path.as_os_str() == "-" {
+            return self.add_stream(std::io::stdin().lock());
+        }
+        let io_err = |error| ParseError::Io { line: 1, error };
+        let file = File::open(path).map_err(io_err)?;
+        let metadata = file.metadata().map_err(io_err)?;
+        let mut reader = BufReader::new(file);
+
+        // Only regular files can be memory-mapped. Pipes (like those from process
+        // substitution) and other special files get streamed.
+        if !metadata.is_file() || bgzf::is_gzip(reader.fill_buf().map_err(io_err)?) {
+            return self.add_stream(reader);
+        }
+        if metadata.len() == 0 {
+            return Ok(());
+        }
+        let file = reader.into_inner();
+        let mmap = unsafe { memmap::Mmap::map(&file) }.map_err(io_err)?;
+        self.add_mem(&mmap)
+    }
+
+    /// Read and add all the lines in a stream, which may be compressed.
+    ///
+    /// Progress reports count the bytes consumed from `stream`, so for compressed
+    /// input they measure the compressed data.
+    fn add_stream<R: BufRead>(&mut self, stream: R) -> Result<(), ParseError> {
+        let consumed = Cell::new(0);
+        let start_bytes = self.bytes;
+        let counted = CountingReader {
+            inner: stream,
+            count: &consumed,
+        };
+        let mut stream =
+            bgzf::decompress_stream(counted).map_err(|error| ParseError::Io { line: 1, error })?;
+
+        // Reuse one buffer for every line.
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let len = stream
+                .read_until(b'\n', &mut buf)
+                .map_err(|error| ParseError::Io {
+                    line: self.line + 1,
+                    error,
+                })?;
+            if len == 0 {
+                break;
             }
+            self.push_line(&buf)?;
+            self.bytes = start_bytes + consumed.get();
+        }
+        Ok(())
+    }
 
-            // Parse other kinds of lines.
-            let gfa_line = gfaline::parse_line(line.as_ref()).unwrap();
-            self.record_line(&gfa_line);
+    /// Add the next line of a stream, which may end with a newline.
+    fn push_line(&mut self, line: &[u8]) -> Result<(), ParseError> {
+        self.line += 1;
+        self.add_line(trim_line_end(line))
+            .map_err(|e| ParseError::at(self.line, e))?;
+        self.lines += 1;
+        self.tick();
+        Ok(())
+    }
 
-            match gfa_line {
-                gfaline::Line::Header(data) => {
-                    self.flat.add_header(data);
-                }
-                gfaline::Line::Segment(seg) => {
-                    self.add_seg(seg);
-                }
-                gfaline::Line::Link(link) => {
-                    deferred_links.push(link);
+    /// Add all the lines in an in-memory buffer.
+    fn add_mem(&mut self, buf: &[u8]) -> Result<(), ParseError> {
+        let mut deferred_lines = Vec::new();
+
+        let start_bytes = self.bytes;
+        let mut pos = 0;
+        for (idx, line) in MemchrSplit::new(b'\n', buf).enumerate() {
+            pos += line.len() + 1;
+            self.bytes = start_bytes + pos.min(buf.len()) as u64;
+            self.lines += 1;
+            self.tick();
+
+            let line = trim_line_end(line);
+            // When parsing from memory, it's easy to entirely defer parsing of any line: we
+            // just keep pointers to them. So we defer paths, walks, links, jumps, and
+            // containments until we have all the segment names that they might refer to.
+            // This way, we never need fixups (except for references to segments that
+            // don't exist at all).
+            if let Some(kind) = deferred_kind(line) {
+                self.flat.record_line(kind);
+                if self.skipped_kind(line).is_none() {
+                    deferred_lines.push((idx + 1, line));
                 }
-                gfaline::Line::Path(_) => {
-                    unreachable!("paths handled separately")
+                continue;
+            }
+
+            self.line = idx + 1;
+            self.add_line(line)
+                .map_err(|e| ParseError::at(self.line, e))?;
+        }
+
+        // "Unwind" the deferred lines.
+        let start = Instant::now();
+        self.stats.deferred_lines += deferred_lines.len();
+        let deferred_bytes = deferred_lines.iter().map(|(_, line)| line.len()).sum();
+        self.stats.deferred_bytes = self.stats.deferred_bytes.max(deferred_bytes);
+        for (line_num, line) in deferred_lines {
+            self.line = line_num;
+            self.add_deferred(line)
+                .map_err(|e| ParseError::at(self.line, e))?;
+            self.tick();
+        }
+        self.stats.deferred_time += start.elapsed();
+        Ok(())
+    }
+
+    /// Finish the graph after adding all the lines.
+    fn finish(mut self) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        let start = Instant::now();
+        if self.report_stats.is_some() {
+            self.count_records();
+        }
+        self.resolve_fixups()?;
+        self.drop_duplicate_links();
+        self.flat.sequences_skipped |= self.config.skip_sequences;
+        if self.config.warn_orphans && self.warn.is_some() {
+            for idx in 0..self.flat.segs.len() {
+                if self.used_segs.get(idx).copied().unwrap_or(false) {
+                    continue;
                 }
+                let gfa = self.flat.as_ref();
+                let warning = ParseError::OrphanSegment {
+                    line: self.seg_lines.get(idx).copied().unwrap_or(0),
+                    name: gfa.get_seg_name(&gfa.segs[Id::new(idx)]).to_string(),
+                };
+                self.warn(warning);
             }
         }
+        self.fetch_external_seqs()?;
+        self.report_progress();
+        if let Some(report) = self.report_stats.take() {
+            let gfa = self.flat.as_ref();
+            self.stats.seq_len = gfa.segs.all().iter().map(|seg| gfa.seg_len(seg)).sum();
+            self.stats.finish_time = start.elapsed();
+            report(&self.stats);
+        }
+        Ok(self.flat)
+    }
 
-        // "Unwind" the deferred links and paths.
-        for link in deferred_links {
-            self.add_link(link);
+    /// Fill in the statistics that we can get from the parser's state before finishing
+    /// up: the counts of lines, records, and fixups, and the time spent scanning.
+    fn count_records(&mut self) {
+        let stats = &mut self.stats;
+        stats.bytes = self.bytes;
+        stats.lines = self.lines;
+        for kind in self.flat.as_ref().get_line_order() {
+            let count = match kind {
+                LineKind::Header => &mut stats.headers,
+                LineKind::Segment => &mut stats.segs,
+                LineKind::Path => &mut stats.paths,
+                LineKind::Link => &mut stats.links,
+                LineKind::Jump => &mut stats.jumps,
+                LineKind::Containment => &mut stats.containments,
+                LineKind::Walk => &mut stats.walks,
+                LineKind::Other => &mut stats.other_lines,
+            };
+            *count += 1;
         }
-        for line in deferred_paths {
-            if let gfaline::Line::Path(path) = gfaline::parse_line(&line).unwrap() {
-                self.add_path(path);
+        stats.fixups = self.fixups.len();
+        stats.fixup_bytes = self.fixups.iter().map(|fixup| fixup.name.len()).sum();
+        stats.scan_time = self.start.elapsed().saturating_sub(stats.deferred_time);
+    }
+
+    /// Drop the links that repeat earlier ones, according to the configured policy.
+    fn drop_duplicate_links(&mut self) {
+        let symmetric = match self.config.duplicate_links {
+            DuplicateLinks::Keep => return,
+            DuplicateLinks::Exact => false,
+            DuplicateLinks::Symmetric => true,
+        };
+
+        // Find the first link with each key.
+        let gfa = self.flat.as_ref();
+        let mut firsts: HashMap<LinkKey, usize> = HashMap::new();
+        let mut dups = vec![];
+        for (idx, link) in gfa.links.all().iter().enumerate() {
+            let key = LinkKey::new(&gfa, link, symmetric);
+            if let Some(&first) = firsts.get(&key) {
+                dups.push((idx, first));
             } else {
-                unreachable!("unexpected deferred line")
+                firsts.insert(key, idx);
             }
         }
+        if dups.is_empty() {
+            return;
+        }
 
+        // Drop them from the links and the line order alike.
+        let mut keep = vec![true; gfa.links.len()];
+        for &(idx, _) in &dups {
+            keep[idx] = false;
+        }
+        let mut keep_link = keep.iter();
+        self.flat.links.retain(|_| *keep_link.next().unwrap());
+        let mut keep_line = keep.iter();
+        let link_kind = u8::from(LineKind::Link);
         self.flat
+            .line_order
+            .retain(|&kind| kind != link_kind || *keep_line.next().unwrap_or(&true));
+
+        for (idx, first) in dups {
+            let line = |idx: usize| self.link_lines.get(idx).copied().unwrap_or(0);
+            let warning = ParseError::DuplicateLink {
+                line: line(idx),
+                first: line(first),
+            };
+            self.warn(warning);
+        }
     }
 
-    /// Parse a GFA text file from an in-memory buffer.
-    pub fn parse_mem(mut self, buf: &[u8]) -> flatgfa::GFAStore<'a, P> {
-        let mut deferred_lines = Vec::new();
+    /// Count a unit of work, and report our progress if it's time.
+    fn tick(&mut self) {
+        self.ticks += 1;
+        if self.ticks >= self.next_report {
+            self.next_report += PROGRESS_INTERVAL;
+            self.report_progress();
+        }
+    }
 
-        for line in MemchrSplit::new(b'\n', buf) {
-            // When parsing from memory, it's easy to entirely defer parsing of any line: we just keep
-            // pointers to them. So we defer both paths and links.
-            if line[0] == b'P' || line[0] == b'L' {
-                self.flat.record_line(if line[0] == b'P' {
-                    LineKind::Path
-                } else {
-                    LineKind::Link
-                });
-                deferred_lines.push(line);
-                continue;
+    /// Report a warning, saying which file it comes from when we're merging files.
+    fn warn(&mut self, warning: ParseError) {
+        if let Some(report) = &mut self.warn {
+            match self.files.last() {
+                Some(path) => report(&ParseError::File {
+                    path: path.clone(),
+                    error: Box::new(warning),
+                }),
+                None => report(&warning),
             }
+        }
+    }
 
-            // Actually parse other lines.
-            let gfa_line = gfaline::parse_line(line).unwrap();
-            self.record_line(&gfa_line);
-            match gfa_line {
-                gfaline::Line::Header(data) => {
-                    self.flat.add_header(data);
-                }
-                gfaline::Line::Segment(seg) => {
-                    self.add_seg(seg);
-                }
-                gfaline::Line::Link(_) | gfaline::Line::Path(_) => {
-                    unreachable!("paths and links handled separately")
+    fn report_progress(&mut self) {
+        if let Some(report) = &mut self.report {
+            report(&Progress {
+                bytes: self.bytes,
+                lines: self.lines,
+                segs: self.flat.segs.len(),
+                links: self.flat.links.len(),
+                paths: self.flat.paths.len(),
+            });
+        }
+    }
+
+    /// Parse a line according to the file's GFA version.
+    ///
+    /// The version is fixed by the first header with a `VN` tag or, if a non-header
+    /// line comes first, defaults to GFA 1. A header that declares a different
+    /// version after that is an error.
+    fn parse_line<'b>(&mut self, line: &'b [u8]) -> Result<gfaline::Line<'b>, LineError<'b>> {
+        // Headers look the same in every version.
+        if line.first() == Some(&b'H') {
+            let gfa_line = gfaline::parse_line(line)?;
+            if let gfaline::Line::Header(data) = gfa_line {
+                if let Some((version, value)) = gfaline::header_version(data)? {
+                    match self.version {
+                        Some(cur) if cur != version => {
+                            return Err(LineError::VersionConflict(value, cur))
+                        }
+                        _ => self.version = Some(version),
+                    }
                 }
             }
+            return self.check_tags(gfa_line);
         }
 
-        // "Unwind" the deferred lines.
-        for line in deferred_lines {
-            let gfa_line = gfaline::parse_line(line).unwrap();
-            match gfa_line {
-                gfaline::Line::Link(link) => {
-                    self.add_link(link);
+        let gfa_line = match self.version.get_or_insert(Version::Gfa1) {
+            Version::Gfa1 => {
+                // Check the overlaps first, for more precise errors than the parser's.
+                if self.config.strict {
+                    gfaline::check_overlaps(line)?;
                 }
-                gfaline::Line::Path(path) => {
-                    self.add_path(path);
-                }
-                gfaline::Line::Header(_) | gfaline::Line::Segment(_) => {
-                    unreachable!("unexpected deferred line")
+                gfaline::parse_line(line)
+            }
+            Version::Gfa2 => gfaline::parse_line_gfa2(line),
+        }?;
+        self.check_tags(gfa_line)
+    }
+
+    /// If we're skipping paths and this line would define one, get its kind.
+    fn skipped_kind(&mut self, line: &[u8]) -> Option<LineKind> {
+        let first = record_marker(line)?;
+        if !self.config.skip_paths || first == b'H' {
+            return None;
+        }
+        match (first, *self.version.get_or_insert(Version::Gfa1)) {
+            (b'P', Version::Gfa1) => Some(LineKind::Path),
+            (b'W', Version::Gfa1) => Some(LineKind::Walk),
+            (b'O' | b'U', Version::Gfa2) => Some(LineKind::Path),
+            _ => None,
+        }
+    }
+
+    /// In strict mode, check that a line's optional fields are all valid tags.
+    fn check_tags<'b>(&self, line: gfaline::Line<'b>) -> Result<gfaline::Line<'b>, LineError<'b>> {
+        if self.config.strict {
+            gfaline::check_tags(line.data())?;
+        }
+        Ok(line)
+    }
+
+    /// Parse and add a single line.
+    fn add_line<'b>(&mut self, line: &'b [u8]) -> Result<(), LineError<'b>> {
+        if let Some(kind) = self.skipped_kind(line) {
+            self.flat.record_line(kind);
+            return Ok(());
+        }
+        let gfa_line = match self.parse_line(line) {
+            Err(LineError::UnexpectedRecord(_)) if self.config.keep_unknown_records => {
+                if !line.is_empty() {
+                    self.flat.add_other_line(line);
                 }
+                return Ok(());
+            }
+            result => result?,
+        };
+        match &gfa_line {
+            // A graph may have several header lines, which we keep in order (and merge
+            // when printing). But when merging files, we only keep the first file's
+            // headers. We also drop empty header lines, which say nothing.
+            gfaline::Line::Header(data)
+                if data.is_empty()
+                    || (!self.files.is_empty() && !self.flat.header.as_ref().is_empty()) =>
+            {
+                return Ok(());
             }
+            gfaline::Line::Segment(seg) if self.is_repeated_seg(seg)? => return Ok(()),
+            _ => {}
         }
+        self.record_line(&gfa_line);
+        match gfa_line {
+            gfaline::Line::Header(data) => self.add_header(data)?,
+            gfaline::Line::Segment(seg) => self.add_seg(seg)?,
+            gfaline::Line::Link(link) => self.add_link(link)?,
+            gfaline::Line::Path(path) => self.add_path(path)?,
+            gfaline::Line::Jump(jump) => self.add_jump(jump),
+            gfaline::Line::Containment(containment) => self.add_containment(containment),
+            gfaline::Line::Walk(walk) => self.add_walk(walk)?,
+            gfaline::Line::Edge(edge) => self.add_edge(edge)?,
+            gfaline::Line::Group(group) => self.add_group(group)?,
+        }
+        Ok(())
+    }
 
-        self.flat
+    /// Parse and add a line that we deferred until all segments were known. (The line
+    /// order was already recorded.)
+    fn add_deferred<'b>(&mut self, line: &'b [u8]) -> Result<(), LineError<'b>> {
+        match self.parse_line(line)? {
+            gfaline::Line::Link(link) => self.add_link(link)?,
+            gfaline::Line::Path(path) => self.add_path(path)?,
+            gfaline::Line::Jump(jump) => self.add_jump(jump),
+            gfaline::Line::Containment(containment) => self.add_containment(containment),
+            gfaline::Line::Walk(walk) => self.add_walk(walk)?,
+            gfaline::Line::Header(_)
+            | gfaline::Line::Segment(_)
+            | gfaline::Line::Edge(_)
+            | gfaline::Line::Group(_) => {
+                unreachable!("unexpected deferred line")
+            }
+        }
+        Ok(())
     }
 
     /// Record a marker that captures the original GFA line ordering.
@@ -132,37 +1476,819 @@ impl<'a, P: flatgfa::StoreFamily<'a>> Parser<'a, P> {
             gfaline::Line::Segment(_) => self.flat.record_line(LineKind::Segment),
             gfaline::Line::Link(_) => self.flat.record_line(LineKind::Link),
             gfaline::Line::Path(_) => self.flat.record_line(LineKind::Path),
+            gfaline::Line::Jump(_) => self.flat.record_line(LineKind::Jump),
+            gfaline::Line::Containment(_) => self.flat.record_line(LineKind::Containment),
+            gfaline::Line::Walk(_) => self.flat.record_line(LineKind::Walk),
+            gfaline::Line::Group(_) => self.flat.record_line(LineKind::Path),
+            gfaline::Line::Edge(_) => {
+                // An edge can become a link or a containment; `add_edge` records it.
+            }
+        }
+    }
+
+    /// Check whether a segment's name was already defined, in which case we skip it.
+    ///
+    /// When merging files, a segment from an earlier file must have the same sequence
+    /// (or, if we're skipping sequences, the same length). A name that repeats within
+    /// a file is an error, unless we're keeping the first definition.
+    fn is_repeated_seg<'b>(&mut self, seg: &gfaline::Segment<'b>) -> Result<bool, LineError<'b>> {
+        let Some(id) = self.flat.names.find_name(SegName::parse(seg.name)) else {
+            return Ok(false);
+        };
+        if id.index() >= self.file_segs {
+            let first = self.seg_lines[id.index()];
+            if !self.config.keep_first_duplicates {
+                return Err(LineError::DuplicateSegment(seg.name, first));
+            }
+            self.warn(ParseError::at(
+                self.line,
+                LineError::DuplicateSegment(seg.name, first),
+            ));
+            return Ok(true);
+        }
+        let gfa = self.flat.as_ref();
+        let old = &gfa.segs[id];
+        let same = if self.config.skip_sequences {
+            seg.seq == b"*" || gfa.seg_len(old) == seg.seq.len()
+        } else {
+            gfa.get_seq(old) == stored_seq(seg.seq)
+        };
+        if same {
+            Ok(true)
+        } else {
+            Err(LineError::ConflictingSegment(seg.name))
+        }
+    }
+
+    /// Check that a path's name is new, and get the name to use for it.
+    ///
+    /// A repeated name is an error in strict mode. Otherwise, we rename the path.
+    fn path_name<'n, 'b>(&mut self, name: &'n [u8]) -> Result<Cow<'n, [u8]>, LineError<'b>> {
+        let Some(new_name) = dedup_path_name(&mut self.path_names, name) else {
+            return Ok(name.into());
+        };
+        if self.config.strict {
+            return Err(LineError::DuplicatePath(name.into()));
+        }
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        self.warn(ParseError::RenamedPath {
+            line: self.line,
+            name: text(name),
+            new_name: text(&new_name),
+        });
+        Ok(new_name.into())
+    }
+
+    fn add_header<'b>(&mut self, data: &'b [u8]) -> Result<(), LineError<'b>> {
+        self.check_header_conflicts(data)?;
+        if self.version == Some(Version::Gfa2) {
+            // We convert GFA 2 to GFA 1, so the header should say so.
+            let fields: Vec<&[u8]> = data
+                .split(|&c| c == b'\t')
+                .map(|field| match field {
+                    b"VN:Z:2.0" => b"VN:Z:1.0",
+                    _ => field,
+                })
+                .collect();
+            self.flat.add_header(&fields.join(&b'\t'));
+        } else {
+            self.flat.add_header(data);
+        }
+        Ok(())
+    }
+
+    /// Check that a header line's tags agree with the earlier headers' (and with each
+    /// other), so we can merge them. Only `VN` may differ: the first version wins.
+    fn check_header_conflicts<'b>(&self, data: &'b [u8]) -> Result<(), LineError<'b>> {
+        let gfa = self.flat.as_ref();
+        let mut tags: Vec<(gfaline::Tag, &[u8])> = vec![];
+        for line in gfa.get_header_lines() {
+            for field in line.split(|&c| c == b'\t') {
+                if let Ok(tag) = gfaline::parse_tag(field) {
+                    tags.push((tag, field));
+                }
+            }
+        }
+        for field in data.split(|&c| c == b'\t') {
+            let Ok(tag) = gfaline::parse_tag(field) else {
+                continue;
+            };
+            if &tag.name == b"VN" {
+                continue;
+            }
+            let conflict = tags.iter().find(|(old, _)| {
+                old.name == tag.name && (old.kind, old.value) != (tag.kind, tag.value)
+            });
+            if let Some((_, first)) = conflict {
+                return Err(LineError::HeaderConflict(field, (*first).into()));
+            }
+            tags.push((tag, field));
+        }
+        Ok(())
+    }
+
+    fn add_seg<'b>(&mut self, seg: gfaline::Segment<'b>) -> Result<(), LineError<'b>> {
+        let name = SegName::parse(seg.name);
+
+        // An `LN` tag should agree with the sequence. (For a `*` sequence, the tag is
+        // the only length we have.)
+        if seg.seq != b"*" {
+            let declared = seg
+                .data
+                .split(|&c| c == b'\t')
+                .filter_map(|field| gfaline::parse_tag(field).ok())
+                .find(|tag| &tag.name == b"LN" && tag.kind == b'i')
+                .and_then(|tag| std::str::from_utf8(tag.value).ok()?.parse().ok());
+            if let Some(declared) = declared.filter(|&len: &usize| len != seg.seq.len()) {
+                let mismatch = LineError::SequenceLength(seg.name, declared, seg.seq.len());
+                if self.config.strict {
+                    return Err(mismatch);
+                }
+                self.stats.length_mismatches += 1;
+                self.warn(ParseError::at(self.line, mismatch));
+            }
+        }
+
+        // Keep GFA 2's explicit length (or, when we're skipping sequences, the
+        // sequence's length) as an `LN` tag, unless there already is one.
+        let length = match seg.length {
+            None if self.config.skip_sequences && seg.seq != b"*" => Some(seg.seq.len() as u64),
+            length => length,
+        };
+        let mut data = Vec::new();
+        if let Some(length) = length {
+            if !seg
+                .data
+                .split(|&c| c == b'\t')
+                .any(|f| f.starts_with(b"LN:"))
+            {
+                push_field(&mut data, format!("LN:i:{}", length).as_bytes());
+            }
+        }
+        let data = if data.is_empty() {
+            seg.data
+        } else {
+            push_field(&mut data, seg.data);
+            &data
+        };
+
+        // An unknown (`*`) sequence is empty.
+        let seq = if self.config.skip_sequences {
+            b""
+        } else {
+            stored_seq(seg.seq)
+        };
+        let seg_id = self.flat.add_named_seg(name, seq, data);
+        self.seg_lines.push(self.line);
+
+        if let (Some(default), b"*", false) = (
+            &self.config.sequence_source,
+            seg.seq,
+            self.config.skip_sequences,
+        ) {
+            let tag = |name: &[u8; 2]| {
+                data.split(|&c| c == b'\t')
+                    .filter_map(|field| gfaline::parse_tag(field).ok())
+                    .find(|tag| &tag.name == name)
+            };
+            let source = match tag(b"UR") {
+                Some(tag) if tag.kind == b'Z' => {
+                    let uri = tag.value.strip_prefix(b"file://").unwrap_or(tag.value);
+                    PathBuf::from(String::from_utf8_lossy(uri).into_owned())
+                }
+                _ => default.clone(),
+            };
+            let length = match tag(b"LN") {
+                Some(tag) if tag.kind == b'i' => std::str::from_utf8(tag.value)
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+                _ => None,
+            };
+            self.external_seqs.push(ExternalSeq {
+                seg: seg_id,
+                name: seg.name.into(),
+                line: self.line,
+                source,
+                length,
+            });
         }
+        Ok(())
+    }
+
+    /// Get a handle for a segment reference, which may need a fixup.
+    fn handle(&mut self, name: &[u8], orient: Orientation, target: FixupTarget) -> Handle {
+        seg_handle(
+            &self.flat.names,
+            &mut self.fixups,
+            self.config.warn_orphans.then_some(&mut self.used_segs),
+            (self.files.len().saturating_sub(1), self.line),
+            name,
+            orient,
+            target,
+        )
+    }
+
+    fn add_link(&mut self, mut link: gfaline::Link) -> Result<(), LineError<'static>> {
+        // Links store their overlaps in a `CompactSpan`.
+        if link.overlap.len() > CompactSpan::<AlignOp>::MAX_LEN {
+            return Err(LineError::LongOverlap(link.overlap.len()));
+        }
+        if self.config.duplicate_links != DuplicateLinks::Keep {
+            self.link_lines.push(self.line);
+        }
+        if let Some(target) = self.config.normalize_overlaps {
+            if is_blunt(&link.overlap) {
+                let (overlap, spelling) = match target {
+                    BluntOverlap::ZeroMatch => (
+                        [AlignOp::new(AlignOpcode::Match, 0)].into_iter().collect(),
+                        OverlapSpelling::Default,
+                    ),
+                    BluntOverlap::Star => (Default::default(), OverlapSpelling::Star),
+                    BluntOverlap::Empty => (Default::default(), OverlapSpelling::Empty),
+                };
+                if overlap != link.overlap || spelling != link.overlap_spelling {
+                    self.stats.normalized_overlaps += 1;
+                }
+                link.overlap = overlap;
+                link.overlap_spelling = spelling;
+            }
+        }
+
+        let id = self.flat.links.next_id();
+        let from = self.handle(link.from_seg, link.from_orient, FixupTarget::LinkFrom(id));
+        let to = self.handle(link.to_seg, link.to_orient, FixupTarget::LinkTo(id));
+        self.flat.add_link(from, to, link.overlap, link.data);
+        self.flat.links.get_mut(id).overlap_spelling = link.overlap_spelling.into();
+        Ok(())
+    }
+
+    fn add_jump(&mut self, jump: gfaline::Jump) {
+        let id = self.flat.jumps.next_id();
+        let from = self.handle(jump.from_seg, jump.from_orient, FixupTarget::JumpFrom(id));
+        let to = self.handle(jump.to_seg, jump.to_orient, FixupTarget::JumpTo(id));
+        self.flat.add_jump(from, to, jump.distance, jump.data);
+    }
+
+    fn add_containment(&mut self, containment: gfaline::Containment) {
+        let id = self.flat.containments.next_id();
+        let container = self.handle(
+            containment.container_seg,
+            containment.container_orient,
+            FixupTarget::Container(id),
+        );
+        let contained = self.handle(
+            containment.contained_seg,
+            containment.contained_orient,
+            FixupTarget::Contained(id),
+        );
+        self.flat.add_containment(
+            container,
+            contained,
+            containment.pos,
+            containment.overlap,
+            containment.data,
+        );
+    }
+
+    /// Add the steps for a path or walk, given the segment names and directions.
+    ///
+    /// The steps belong to the next path we will add.
+    fn add_named_steps<'b>(
+        &mut self,
+        steps: impl Iterator<Item = (&'b [u8], Orientation)>,
+    ) -> Span<Handle> {
+        let start = self.flat.steps.next_id();
+        let path = self.flat.paths.next_id();
+        let names = &self.flat.names;
+        let file = self.files.len().saturating_sub(1);
+        let mut used = self.config.warn_orphans.then_some(&mut self.used_segs);
+        self.flat
+            .steps
+            .add_iter(steps.enumerate().map(|(idx, (name, orient))| {
+                let target = FixupTarget::Step {
+                    path,
+                    step: start + idx as u32,
+                };
+                seg_handle(
+                    names,
+                    &mut self.fixups,
+                    used.as_deref_mut(),
+                    (file, self.line),
+                    name,
+                    orient,
+                    target,
+                )
+            }))
+    }
+
+    fn add_path<'b>(&mut self, path: gfaline::Path<'b>) -> Result<(), LineError<'b>> {
+        let name = self.path_name(path.name)?;
+
+        // Parse the steps.
+        let mut error = None;
+        let step_parser = gfaline::StepsParser::new(path.steps)
+            .map_while(|step| step.map_err(|err| error = Some(err)).ok());
+        let steps = self.add_named_steps(step_parser);
+        if let Some(err) = error {
+            return Err(LineError::BadStep(err));
+        }
+
+        // There is an overlap between each pair of steps, unless they are all `*`.
+        // Some files also give one from the last step back to the first.
+        let mut overlaps = path.overlaps;
+        let expected = steps.len().saturating_sub(1);
+        if !overlaps.is_empty() && overlaps.len() != expected && overlaps.len() != steps.len() {
+            let mismatch = LineError::OverlapCount(path.name, steps.len(), overlaps.len());
+            if self.config.strict {
+                return Err(mismatch);
+            }
+            self.warn(ParseError::at(self.line, mismatch));
+            overlaps.resize(expected, vec![AlignOp::new(AlignOpcode::Match, 0)]);
+        }
+
+        let mut spelling = path.overlap_spelling;
+        if let Some(target) = self.config.normalize_overlaps {
+            if overlaps.iter().all(|overlap| is_blunt(overlap)) {
+                let (new_overlaps, new_spelling) = match target {
+                    BluntOverlap::ZeroMatch => (
+                        vec![vec![AlignOp::new(AlignOpcode::Match, 0)]; expected],
+                        OverlapSpelling::Default,
+                    ),
+                    BluntOverlap::Star => (vec![], OverlapSpelling::Default),
+                    BluntOverlap::Empty => (vec![], OverlapSpelling::Empty),
+                };
+                if new_overlaps != overlaps || new_spelling != spelling {
+                    self.stats.normalized_overlaps += 1;
+                }
+                (overlaps, spelling) = (new_overlaps, new_spelling);
+            }
+        }
+
+        let id = self
+            .flat
+            .add_path(&name, steps, overlaps.into_iter(), path.data);
+        self.flat.paths.get_mut(id).overlap_spelling = spelling.into();
+        Ok(())
+    }
+
+    fn add_walk<'b>(&mut self, walk: gfaline::Walk<'b>) -> Result<(), LineError<'b>> {
+        let mut step_parser = gfaline::WalkStepsParser::new(walk.steps);
+        let steps = self.add_named_steps(&mut step_parser);
+        let rest = step_parser.rest();
+        if !rest.is_empty() {
+            return Err(LineError::BadField("walk step", rest));
+        }
+
+        let name = flatgfa::walk_path_name(
+            walk.sample,
+            walk.hap_index,
+            walk.seq_id,
+            walk.seq_start,
+            walk.seq_end,
+        );
+        let name = self.path_name(&name)?;
+        let path = self
+            .flat
+            .add_path(&name, steps, std::iter::empty(), walk.data);
+        self.flat.add_walk(
+            path,
+            walk.sample,
+            walk.hap_index,
+            walk.seq_id,
+            walk.seq_start,
+            walk.seq_end,
+        );
+        Ok(())
+    }
+
+    /// Add a GFA 2 edge, which becomes a link or a containment.
+    ///
+    /// Which one depends on where the alignment falls on each segment (in the
+    /// orientation the edge gives it). If it covers all of one segment, the edge is
+    /// a containment; if it covers the end of one and the start of the other, it is
+    /// a link (a "dovetail" overlap) in that direction. Other edges are errors. We
+    /// keep the edge's ID as an `ID` tag, its coordinates as an `ec` tag, and its
+    /// trace alignment (if any) as a `tr` tag.
+    fn add_edge<'b>(&mut self, edge: gfaline::Edge<'b>) -> Result<(), LineError<'b>> {
+        let mut data = Vec::new();
+        if edge.id != b"*" {
+            push_field(&mut data, &[b"ID:Z:", edge.id].concat());
+        }
+        let coords = format!(
+            "ec:Z:{},{},{},{}",
+            edge.beg1, edge.end1, edge.beg2, edge.end2
+        );
+        push_field(&mut data, coords.as_bytes());
+        if let Some(trace) = edge.trace {
+            push_field(&mut data, &[b"tr:Z:", trace].concat());
+        }
+        push_field(&mut data, edge.data);
+
+        // Does the alignment touch the (oriented) start and end of each segment?
+        let touches = |beg: gfaline::EdgePos, end: gfaline::EdgePos, orient| {
+            let (start, end) = (beg.offset == 0, end.at_end);
+            match orient {
+                Orientation::Forward => (start, end),
+                Orientation::Backward => (end, start),
+            }
+        };
+        let (start1, end1) = touches(edge.beg1, edge.end1, edge.orient1);
+        let (start2, end2) = touches(edge.beg2, edge.end2, edge.orient2);
+
+        let seg1 = (edge.seg1, edge.orient1, edge.beg1);
+        let seg2 = (edge.seg2, edge.orient2, edge.beg2);
+        if (start1 && end1) || (start2 && end2) {
+            let (container, contained) = if start2 && end2 {
+                (seg1, seg2)
+            } else {
+                (seg2, seg1)
+            };
+            let Ok(pos) = container.2.offset.try_into() else {
+                return Err(LineError::BadField("edge position", edge.id));
+            };
+            self.flat.record_line(LineKind::Containment);
+            self.add_containment(gfaline::Containment {
+                container_seg: container.0,
+                container_orient: container.1,
+                contained_seg: contained.0,
+                contained_orient: contained.1,
+                pos,
+                overlap: edge.overlap,
+                data: &data,
+            });
+        } else if (end1 && start2) || (end2 && start1) {
+            let (from, to) = if end1 && start2 {
+                (seg1, seg2)
+            } else {
+                (seg2, seg1)
+            };
+            self.flat.record_line(LineKind::Link);
+            self.add_link(gfaline::Link {
+                from_seg: from.0,
+                from_orient: from.1,
+                to_seg: to.0,
+                to_orient: to.1,
+                overlap: edge.overlap,
+                overlap_spelling: OverlapSpelling::Default,
+                data: &data,
+            })?;
+        } else {
+            return Err(LineError::BadField(
+                "edge (neither a dovetail nor a containment)",
+                edge.id,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Add a GFA 2 group as a path.
+    ///
+    /// Groups may only refer to segments. Unordered groups (U lines) become paths
+    /// whose steps are all forward, marked with a `gt:Z:U` tag.
+    fn add_group<'b>(&mut self, group: gfaline::Group<'b>) -> Result<(), LineError<'b>> {
+        let name = self.path_name(group.name)?;
+        let members = group
+            .members
+            .split(|&c| c == b' ')
+            .filter(|member| !member.is_empty());
+        let steps: Vec<(&[u8], Orientation)> = if group.ordered {
+            members
+                .map(gfaline::parse_reference)
+                .collect::<Result<_, _>>()?
+        } else {
+            members
+                .map(|member| (member, Orientation::Forward))
+                .collect()
+        };
+        let steps = self.add_named_steps(steps.into_iter());
+
+        let mut data = Vec::new();
+        if !group.ordered {
+            push_field(&mut data, b"gt:Z:U");
+        }
+        push_field(&mut data, group.data);
+        self.flat.add_path(&name, steps, std::iter::empty(), &data);
+        Ok(())
+    }
+
+    /// Patch all the references to segments that were not defined when we saw them.
+    fn resolve_fixups(&mut self) -> Result<(), ParseError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let name = SegName::parse(&fixup.name);
+            let seg_id = match self.flat.names.find_name(name) {
+                Some(seg_id) => seg_id,
+                None if self.config.placeholder_segments => {
+                    self.flat.record_line(LineKind::Segment);
+                    self.flat.add_named_seg(name, b"", b"")
+                }
+                None => return Err(self.unknown_segment(fixup)),
+            };
+            if self.config.warn_orphans {
+                mark_used(&mut self.used_segs, seg_id);
+            }
+            let fix = |handle: Handle| Handle::new(seg_id, handle.orient());
+            match fixup.target {
+                FixupTarget::Step { step: id, .. } => {
+                    let step = self.flat.steps.get_mut(id);
+                    *step = fix(*step);
+                }
+                FixupTarget::LinkFrom(id) => {
+                    let link = self.flat.links.get_mut(id);
+                    link.from = fix(link.from);
+                }
+                FixupTarget::LinkTo(id) => {
+                    let link = self.flat.links.get_mut(id);
+                    link.to = fix(link.to);
+                }
+                FixupTarget::JumpFrom(id) => {
+                    let jump = self.flat.jumps.get_mut(id);
+                    jump.from = fix(jump.from);
+                }
+                FixupTarget::JumpTo(id) => {
+                    let jump = self.flat.jumps.get_mut(id);
+                    jump.to = fix(jump.to);
+                }
+                FixupTarget::Container(id) => {
+                    let containment = self.flat.containments.get_mut(id);
+                    containment.container = fix(containment.container);
+                }
+                FixupTarget::Contained(id) => {
+                    let containment = self.flat.containments.get_mut(id);
+                    containment.contained = fix(containment.contained);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the error for a reference to a segment that does not exist.
+    fn unknown_segment(&self, fixup: Fixup) -> ParseError {
+        let name = String::from_utf8_lossy(&fixup.name).into_owned();
+        let error = match fixup.target {
+            FixupTarget::Step { path, step } => {
+                let gfa = self.flat.as_ref();
+                let path = &gfa.paths[path];
+                ParseError::UnknownStep {
+                    line: fixup.line,
+                    path: gfa.get_path_name(path).to_string(),
+                    step: step.index() - path.steps.start.index(),
+                    name,
+                }
+            }
+            target => ParseError::UnknownSegment {
+                line: fixup.line,
+                record: target.record(),
+                name,
+            },
+        };
+        match self.files.get(fixup.file) {
+            Some(path) => ParseError::File {
+                path: path.clone(),
+                error: Box::new(error),
+            },
+            None => error,
+        }
+    }
+
+    /// Fill in the sequences for segments whose sequence is `*` from their FASTA files.
+    ///
+    /// We read each file once, as a stream, appending the sequences we need to the
+    /// sequence pool as we go.
+    fn fetch_external_seqs(&mut self) -> Result<(), ParseError> {
+        let mut sources: BTreeMap<PathBuf, HashMap<Box<[u8]>, ExternalSeq>> = BTreeMap::new();
+        for ext in std::mem::take(&mut self.external_seqs) {
+            sources
+                .entry(ext.source.clone())
+                .or_default()
+                .insert(ext.name.clone(), ext);
+        }
+
+        for (path, mut wanted) in sources {
+            let line = wanted.values().map(|ext| ext.line).min().unwrap_or(0);
+            let io_err = |error| ParseError::SequenceSource {
+                line,
+                path: path.clone(),
+                error,
+            };
+            let file = File::open(&path).map_err(io_err)?;
+            let mut stream = bgzf::decompress_stream(BufReader::new(file)).map_err(io_err)?;
+
+            // The segment whose record we're reading, if we need it, and where its
+            // sequence starts.
+            let mut current: Option<(ExternalSeq, Id<u8>)> = None;
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                let len = stream.read_until(b'\n', &mut buf).map_err(io_err)?;
+                let text = trim_line_end(&buf);
+                if len == 0 || text.first() == Some(&b'>') {
+                    if let Some((ext, start)) = current.take() {
+                        self.set_external_seq(ext, start)?;
+                    }
+                    if len == 0 {
+                        break;
+                    }
+                    let name = text[1..]
+                        .split(|c| c.is_ascii_whitespace())
+                        .next()
+                        .unwrap_or_default();
+                    current = wanted
+                        .remove(name)
+                        .map(|ext| (ext, self.flat.seq_data.next_id()));
+                } else if current.is_some() {
+                    self.flat.seq_data.add_slice(text);
+                }
+            }
+
+            if let Some(ext) = wanted.into_values().min_by_key(|ext| ext.line) {
+                return Err(ParseError::MissingSequence {
+                    line: ext.line,
+                    name: String::from_utf8_lossy(&ext.name).into_owned(),
+                    path,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Point a segment at the external sequence we just added, starting at `start`.
+    fn set_external_seq(&mut self, ext: ExternalSeq, start: Id<u8>) -> Result<(), ParseError> {
+        let seq = Span::new(start, self.flat.seq_data.next_id());
+        if let Some(expected) = ext.length.filter(|&len| len != seq.len()) {
+            return Err(ParseError::SequenceLength {
+                line: ext.line,
+                name: String::from_utf8_lossy(&ext.name).into_owned(),
+                expected,
+                found: seq.len(),
+            });
+        }
+        self.flat.segs.get_mut(ext.seg).seq = seq;
+        Ok(())
+    }
+}
+
+/// Get a handle for a segment referenced by name in a link, jump, containment, or
+/// path.
+///
+/// If the segment is not (yet) defined, record a fixup for `target` and return a
+/// placeholder handle. The fixup remembers the reference's `(file, line)`. Otherwise,
+/// mark the segment in `used`, if we're tracking which segments are in use.
+fn seg_handle(
+    names: &NameMap,
+    fixups: &mut Vec<Fixup>,
+    used: Option<&mut Vec<bool>>,
+    (file, line): (usize, usize),
+    name: &[u8],
+    orient: Orientation,
+    target: FixupTarget,
+) -> Handle {
+    match names.find_name(SegName::parse(name)) {
+        Some(seg_id) => {
+            if let Some(used) = used {
+                mark_used(used, seg_id);
+            }
+            Handle::new(seg_id, orient)
+        }
+        None => {
+            fixups.push(Fixup {
+                target,
+                name: name.into(),
+                line,
+                file,
+            });
+            Handle::new(Id::new(0), orient)
+        }
+    }
+}
+
+/// Record that a segment is in use, growing the table of used segments as needed.
+fn mark_used(used: &mut Vec<bool>, seg_id: Id<Segment>) {
+    if used.len() <= seg_id.index() {
+        used.resize(seg_id.index() + 1, false);
+    }
+    used[seg_id.index()] = true;
+}
+
+/// Remove the end of a line: a `\n`, if any, and a `\r` before it, for files with
+/// Windows line endings.
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Append a field to some tab-separated optional data.
+fn push_field(data: &mut Vec<u8>, field: &[u8]) {
+    if field.is_empty() {
+        return;
+    }
+    if !data.is_empty() {
+        data.push(b'\t');
+    }
+    data.extend_from_slice(field);
+}
+
+/// Check whether a line refers to segments by name, so we can defer it until all the
+/// segments are known. If so, get its kind. We defer paths, walks, links, jumps,
+/// and containments.
+fn deferred_kind(line: &[u8]) -> Option<LineKind> {
+    match record_marker(line)? {
+        b'P' => Some(LineKind::Path),
+        b'L' => Some(LineKind::Link),
+        b'J' => Some(LineKind::Jump),
+        b'C' => Some(LineKind::Containment),
+        b'W' => Some(LineKind::Walk),
+        _ => None,
+    }
+}
+
+/// Get a line's record type when it is a single letter, as for every record that we
+/// parse. A longer first field (like `PX`) is an unknown record type, not a path.
+fn record_marker(line: &[u8]) -> Option<u8> {
+    match line {
+        [marker] | [marker, b'\t', ..] => Some(*marker),
+        _ => None,
+    }
+}
+
+/// A parser that accepts GFA text as it arrives, such as in chunks from a network
+/// connection.
+///
+/// Push text with `feed`, in chunks of any size (a line may span several chunks), or
+/// with `feed_line`, one line at a time. Then call `finish` to get the graph. This
+/// works like `Parser::parse_stream`, which is a wrapper around the same line-by-line
+/// process: each line is added as soon as it is complete, and references to segments
+/// that are defined later get resolved in `finish`. Unlike `parse_stream`, the text
+/// must be uncompressed.
+pub struct IncrementalParser<'a, P: flatgfa::StoreFamily<'a>> {
+    parser: Parser<'a, P>,
+
+    /// The start of a line that the chunks so far have not finished.
+    partial: Vec<u8>,
+}
+
+impl IncrementalParser<'static, flatgfa::HeapFamily> {
+    /// Create an incremental parser with the default options. Use
+    /// `Parser::incremental` for a configured parser.
+    pub fn new() -> Self {
+        Parser::for_heap().incremental()
     }
+}
 
-    fn add_seg(&mut self, seg: gfaline::Segment) {
-        let seg_id = self.flat.add_seg(seg.name, seg.seq, seg.data);
-        self.seg_ids.insert(seg.name, seg_id);
+impl Default for IncrementalParser<'static, flatgfa::HeapFamily> {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn add_link(&mut self, link: gfaline::Link) {
-        let from = Handle::new(self.seg_ids.get(link.from_seg), link.from_orient);
-        let to = Handle::new(self.seg_ids.get(link.to_seg), link.to_orient);
-        self.flat.add_link(from, to, link.overlap);
+impl<'a, P: flatgfa::StoreFamily<'a>> IncrementalParser<'a, P> {
+    /// Add a chunk of GFA text, which need not start or end at a line boundary.
+    ///
+    /// We add each line once it is complete, so an error refers to a line that ends
+    /// in this chunk. We hold on to an incomplete line at the end of the chunk until
+    /// a later chunk (or `finish`) completes it.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ParseError> {
+        self.parser.bytes += chunk.len() as u64;
+        let mut rest = chunk;
+        while let Some(pos) = memchr::memchr(b'\n', rest) {
+            let line;
+            (line, rest) = rest.split_at(pos + 1);
+            if self.partial.is_empty() {
+                self.parser.push_line(line)?;
+            } else {
+                // Reuse the buffer for the next partial line.
+                let mut buf = std::mem::take(&mut self.partial);
+                buf.extend_from_slice(line);
+                self.parser.push_line(&buf)?;
+                buf.clear();
+                self.partial = buf;
+            }
+        }
+        self.partial.extend_from_slice(rest);
+        Ok(())
     }
 
-    fn add_path(&mut self, path: gfaline::Path) {
-        // Parse the steps.
-        let mut step_parser = gfaline::StepsParser::new(&path.steps);
-        let steps = self.flat.add_steps((&mut step_parser).map(|(name, dir)| {
-            Handle::new(
-                self.seg_ids.get(name),
-                if dir {
-                    Orientation::Forward
-                } else {
-                    Orientation::Backward
-                },
-            )
-        }));
-        assert!(step_parser.rest().is_empty());
+    /// Add a single complete line of GFA text, with or without its line ending.
+    ///
+    /// This saves copying when the caller already splits its input into lines. If a
+    /// `feed` chunk has left a line unfinished, we cannot tell whether `line` is meant
+    /// to continue it, so this returns an error and adds nothing.
+    pub fn feed_line(&mut self, line: &[u8]) -> Result<(), ParseError> {
+        if !self.partial.is_empty() {
+            return Err(ParseError::UnfinishedLine {
+                line: self.parser.line + 1,
+            });
+        }
+        self.parser.bytes += line.len() as u64;
+        self.parser.push_line(line)
+    }
 
-        self.flat
-            .add_path(path.name, steps, path.overlaps.into_iter());
+    /// Add the last line (if it has no line ending) and produce the graph.
+    pub fn finish(mut self) -> Result<flatgfa::GFAStore<'a, P>, ParseError> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.parser.push_line(&line)?;
+        }
+        self.parser.finish()
     }
 }
 
@@ -182,11 +2308,18 @@ impl<'a> Parser<'a, flatgfa::FixedFamily> {
 /// that are useful in estimating the final size of the FlatGFA file.
 pub fn estimate_toc(buf: &[u8]) -> crate::file::Toc {
     let mut segs = 0;
+    let mut rgfa_segs = 0;
     let mut links = 0;
+    let mut jumps = 0;
+    let mut containments = 0;
     let mut paths = 0;
+    let mut walks = 0;
     let mut header_bytes = 0;
     let mut seg_bytes = 0;
+    let mut seg_name_bytes = 0;
     let mut path_bytes = 0;
+    let mut optional_bytes = 0;
+    let mut other_bytes = 0;
 
     let mut rest = buf;
     while !rest.is_empty() {
@@ -200,16 +2333,56 @@ pub fn estimate_toc(buf: &[u8]) -> crate::file::Toc {
             b'S' => {
                 segs += 1;
                 seg_bytes += next;
+                let line = &rest[..next.min(rest.len())];
+                let (name, _) = gfaline::parse_field(line.get(2..).unwrap_or_default()).unwrap();
+                if let SegName::Text(text) = SegName::parse(name) {
+                    seg_name_bytes += text.len();
+                }
+                if memchr::memmem::find(line, b"\tSN:Z:").is_some() {
+                    rgfa_segs += 1;
+                }
+
+                // Everything after the third field is optional data (or, for GFA 2,
+                // the sequence and then optional data). Leave room for an `LN` tag
+                // too, which we add to GFA 2 segments.
+                if let Some(tab) = memchr::memchr_iter(b'\t', line).nth(2) {
+                    optional_bytes += line.len() - tab;
+                }
+                optional_bytes += 16;
             }
             b'L' => {
                 links += 1;
             }
+            b'E' => {
+                // GFA 2 edges become links or containments, with their coordinates
+                // in the optional data.
+                links += 1;
+                containments += 1;
+                optional_bytes += next;
+            }
+            b'J' => {
+                jumps += 1;
+            }
+            b'C' => {
+                containments += 1;
+            }
             b'P' => {
                 paths += 1;
                 path_bytes += next;
             }
+            b'W' => {
+                walks += 1;
+                path_bytes += next;
+            }
+            b'O' | b'U' => {
+                paths += 1;
+                path_bytes += next;
+                optional_bytes += next;
+            }
             _ => {
-                panic!("unknown line type")
+                // Leave room to keep anything else verbatim, with a separator. The
+                // parser will report it if it's a problem.
+                other_bytes += next + 1;
             }
         }
 
@@ -219,5 +2392,1713 @@ pub fn estimate_toc(buf: &[u8]) -> crate::file::Toc {
         rest = &rest[next + 1..];
     }
 
-    crate::file::Toc::estimate(segs, links, paths, header_bytes, seg_bytes, path_bytes)
+    crate::file::Toc::estimate(
+        segs,
+        rgfa_segs,
+        links,
+        jumps,
+        containments,
+        paths,
+        walks,
+        header_bytes,
+        seg_bytes,
+        seg_name_bytes,
+        path_bytes,
+        optional_bytes,
+        other_bytes,
+    )
+}
+
+/// The sizes of all the pools in a FlatGFA parsed from some GFA 1 text.
+///
+/// `SizeEstimate::scan` measures these with a quick pass that splits lines into fields
+/// but does not fully parse them. For well-formed GFA 1 text, parsed without
+/// `skip_sequences`, placeholder segments, or a sequence source, the sizes are exact:
+/// a fixed-size store allocated with `Toc::exact` fits the graph with no spare space.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SizeEstimate {
+    pub header_bytes: usize,
+    pub segs: usize,
+    pub rgfa: usize,
+    pub paths: usize,
+    pub walks: usize,
+    pub links: usize,
+    pub jumps: usize,
+    pub containments: usize,
+    pub steps: usize,
+    pub seq_bytes: usize,
+    pub overlaps: usize,
+    pub align_ops: usize,
+    pub name_bytes: usize,
+    pub optional_bytes: usize,
+    pub tags: usize,
+    pub other_bytes: usize,
+    pub lines: usize,
+}
+
+impl SizeEstimate {
+    /// Measure a GFA text.
+    ///
+    /// Returns `None` for GFA 2 text (the parser converts its records in ways this
+    /// quick scan cannot size). Lines with unknown record types count as if the parser
+    /// keeps them (see `Parser::keep_unknown_records`). Other lines that the parser
+    /// would reject are ignored.
+    pub fn scan(buf: &[u8]) -> Option<Self> {
+        let mut size = Self::default();
+        let mut path_names = HashSet::new();
+        for line in MemchrSplit::new(b'\n', buf) {
+            let line = trim_line_end(line);
+            let mut fields = line.split(|&c| c == b'\t');
+            let mut field = || fields.next().unwrap_or_default();
+            match field() {
+                b"H" => {
+                    let data = line.get(2..).unwrap_or_default();
+                    if data.is_empty() {
+                        continue; // The parser drops empty header lines.
+                    }
+                    if let Ok(Some((Version::Gfa2, _))) = gfaline::header_version(data) {
+                        return None;
+                    }
+                    // Header lines are separated by newlines.
+                    size.header_bytes += (size.header_bytes > 0) as usize + data.len();
+                }
+                b"S" => {
+                    if let SegName::Text(name) = SegName::parse(field()) {
+                        size.name_bytes += name.len();
+                    }
+                    size.seq_bytes += stored_seq(field()).len();
+                    size.segs += 1;
+                    let data = fields_after(line, 3);
+                    size.add_tagged(data);
+                    let tags = data
+                        .split(|&c| c == b'\t')
+                        .filter_map(|field| gfaline::parse_tag(field).ok())
+                        .map(|tag| (tag.name, tag.kind, tag.value));
+                    size.rgfa += flatgfa::find_rgfa_tags(tags).is_some() as usize;
+                }
+                b"L" => {
+                    size.align_ops += count_align_ops(fields_after(line, 5));
+                    size.links += 1;
+                    size.add_tagged(fields_after(line, 6));
+                }
+                b"J" => {
+                    size.jumps += 1;
+                    size.optional_bytes += fields_after(line, 6).len();
+                }
+                b"C" => {
+                    size.align_ops += count_align_ops(fields_after(line, 6));
+                    size.containments += 1;
+                    size.optional_bytes += fields_after(line, 7).len();
+                }
+                b"P" => {
+                    let name = field();
+                    size.name_bytes += name.len();
+                    if let Some(new_name) = dedup_path_name(&mut path_names, name) {
+                        size.name_bytes += new_name.len() - name.len();
+                    }
+                    let steps = field();
+                    let step_count = match steps {
+                        b"" => 0,
+                        _ => memchr::memchr_iter(b',', steps).count() + 1,
+                    };
+                    size.steps += step_count;
+                    let overlaps = field();
+                    if !overlaps.is_empty() && overlaps != b"*" {
+                        // Like the parser, fix up lists with the wrong length.
+                        let count = memchr::memchr_iter(b',', overlaps).count() + 1;
+                        let expected = step_count.saturating_sub(1);
+                        if count == expected || count == step_count {
+                            size.overlaps += count;
+                            size.align_ops += count_align_ops(overlaps);
+                        } else {
+                            size.overlaps += expected;
+                            size.align_ops += overlaps
+                                .split(|&c| c == b',')
+                                .take(expected)
+                                .map(count_align_ops)
+                                .sum::<usize>()
+                                + expected.saturating_sub(count);
+                        }
+                    }
+                    size.paths += 1;
+                    size.add_tagged(fields_after(line, 4));
+                }
+                b"W" => {
+                    let sample = field();
+                    let hap_index = parse_scanned(field()).unwrap_or_default();
+                    let seq_id = field();
+                    let (start, end) = (parse_scanned(field()), parse_scanned(field()));
+                    let name = flatgfa::walk_path_name(sample, hap_index, seq_id, start, end);
+                    let name = dedup_path_name(&mut path_names, &name).unwrap_or(name);
+                    size.name_bytes += name.len() + sample.len() + seq_id.len();
+                    size.steps += field().iter().filter(|&&c| c == b'>' || c == b'<').count();
+                    size.paths += 1;
+                    size.walks += 1;
+                    size.add_tagged(fields_after(line, 7));
+                }
+                b"E" | b"O" | b"U" | b"F" | b"G" => return None,
+                b"" => continue,
+                _ => {
+                    // Kept verbatim with `keep_unknown_records`, separated by newlines.
+                    size.other_bytes += (size.other_bytes > 0) as usize + line.len();
+                }
+            }
+            size.lines += 1;
+        }
+        Some(size)
+    }
+
+    /// Count the optional data for a segment, link, or path, which also has tags.
+    fn add_tagged(&mut self, data: &[u8]) {
+        self.optional_bytes += data.len();
+        self.tags += data
+            .split(|&c| c == b'\t')
+            .filter(|field| gfaline::parse_tag(field).is_ok())
+            .count();
+    }
+}
+
+/// Get the text after the first `n` tab-separated fields of a line.
+fn fields_after(line: &[u8], n: usize) -> &[u8] {
+    match memchr::memchr_iter(b'\t', line).nth(n - 1) {
+        Some(tab) => &line[tab + 1..],
+        None => b"",
+    }
+}
+
+/// Count the operations in a CIGAR string (or a list of them), which each end in a
+/// letter. Only the first field of `text` counts.
+fn count_align_ops(text: &[u8]) -> usize {
+    text.iter()
+        .take_while(|&&c| c != b'\t')
+        .filter(|c| c.is_ascii_alphabetic())
+        .count()
+}
+
+/// Parse a number in a walk line, where `*` means an unknown value.
+fn parse_scanned<T: std::str::FromStr>(field: &[u8]) -> Option<T> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+#[test]
+fn test_parse_errors() {
+    let parse = |text: &[u8]| {
+        Parser::for_heap()
+            .parse_mem(text)
+            .err()
+            .map(|e| e.to_string())
+    };
+    assert_eq!(parse(b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n"), None);
+    assert_eq!(
+        parse(b"S\t1\tA\nX\tfoo\n"),
+        Some("line 2: unexpected record type `X`".into())
+    );
+    assert_eq!(
+        parse(b"S\t1\tA\nL\t1\t+\t1\n"),
+        Some("line 2: missing sink orientation field".into())
+    );
+    assert_eq!(
+        parse(b"S\t1\tA\nL\t1\t?\t1\t+\t0M\n"),
+        Some("line 2: bad orientation `?` (expected + or -)".into())
+    );
+    assert_eq!(
+        parse(b"S\t1\tA\nP\tp\t1+,2+\t*\nS\t3\tG\n"),
+        Some("line 2: path `p` step 1 references unknown segment `2`".into())
+    );
+    assert_eq!(
+        parse(b"S\t1\tA\nP\tp\t1+,1x\t*\n"),
+        Some("line 2: bad path step `1x` at byte 3".into())
+    );
+    assert_eq!(
+        parse(b"S\t1\tA\nP\tp\t1+,\t*\n"),
+        Some("line 2: missing path step at byte 3".into())
+    );
+    assert_eq!(parse(b"S\t1\tA\nL\t1\t+\t1\t+\t0M\tXX:i:1\n"), None);
+
+    // Errors from streams also carry line numbers.
+    let err = Parser::for_heap()
+        .parse_stream(&b"H\tVN:Z:1.0\nS\t\tA\n"[..])
+        .err()
+        .unwrap();
+    assert_eq!(err.line(), 2);
+}
+
+#[test]
+fn test_stream_forward_refs() {
+    let text =
+        b"H\tVN:Z:1.0\nP\tp\t2+,1-\t*\nL\t1\t+\t2\t-\t0M\nJ\t2\t-\t1\t+\t*\nS\t1\tA\nS\t2\tC\n";
+    let streamed = Parser::for_heap().parse_stream(&text[..]).unwrap();
+    let in_mem = Parser::for_heap().parse_mem(text).unwrap();
+    let streamed = format!("{}", &streamed.as_ref());
+    assert_eq!(streamed, format!("{}", &in_mem.as_ref()));
+    assert_eq!(streamed.as_bytes(), text);
+
+    let err = Parser::for_heap()
+        .parse_stream(&b"S\t1\tA\nP\tp\t1+,3+\t*\nS\t2\tC\n"[..])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: path `p` step 1 references unknown segment `3`"
+    );
+}
+
+#[test]
+fn test_unknown_segment_fields() {
+    // Undefined names come back as structured errors, whether or not the parser
+    // had to defer the lookup.
+    let text = b"S\ta\tA\nS\tb\tC\nL\ta\t+\tb\t+\t0M\nP\tp\ta+,zz-,b+\t*\n";
+    for err in [
+        Parser::for_heap().parse_mem(text).err().unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).err().unwrap(),
+    ] {
+        match err {
+            ParseError::UnknownStep {
+                line,
+                path,
+                step,
+                name,
+            } => assert_eq!((line, &path[..], step, &name[..]), (4, "p", 1, "zz")),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    let text = b"S\ta\tA\nJ\ta\t+\tq\t-\t*\n";
+    match Parser::for_heap().parse_mem(text).err().unwrap() {
+        ParseError::UnknownSegment { line, record, name } => {
+            assert_eq!((line, record, &name[..]), (2, "jump", "q"))
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_crlf() {
+    let fixtures: [&[u8]; 4] = [
+        include_bytes!("../../tests/basic/ex1.gfa"),
+        include_bytes!("../../tests/basic/ex2.gfa"),
+        include_bytes!("../../tests/handmade/crush1.gfa"),
+        b"H\tVN:Z:1.0\nS\t1\tACGT\tLN:i:4\nS\t2\tC\nL\t1\t+\t2\t-\t3M\nP\tp\t1+,2-\t3M\tSO:Z:x\n",
+    ];
+    let binary = |store: flatgfa::HeapGFAStore| {
+        let gfa = store.as_ref();
+        let mut buf = vec![0; crate::file::size(&gfa)];
+        crate::file::dump(&gfa, &mut buf);
+        buf
+    };
+    for text in fixtures {
+        let crlf = text
+            .split_inclusive(|&c| c == b'\n')
+            .flat_map(|line| match line.strip_suffix(b"\n") {
+                Some(line) => [line, b"\r\n"].concat(),
+                None => line.to_vec(),
+            })
+            .collect::<Vec<u8>>();
+        let expected = binary(Parser::for_heap().parse_mem(text).unwrap());
+        assert_eq!(
+            binary(Parser::for_heap().parse_mem(&crlf).unwrap()),
+            expected
+        );
+        assert_eq!(
+            binary(Parser::for_heap().parse_stream(&crlf[..]).unwrap()),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_length_tags() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // The tag on segment 3 is wrong. For a `*` sequence, the tag is the length.
+    let text = b"S\t1\tACGT\tLN:i:4\nS\t2\t*\tLN:i:9\nS\t3\tAC\tRC:i:1\tLN:i:5\n";
+    let err = Parser::for_heap().strict().parse_mem(text).err().unwrap();
+    assert_eq!(err.line(), 3);
+    assert_eq!(
+        err.to_string(),
+        "line 3: segment `3` has length 5 but its sequence has length 2"
+    );
+
+    // Otherwise, we keep the sequence, with a warning.
+    let warnings = Rc::new(RefCell::new(vec![]));
+    let stats = Rc::new(RefCell::new(None));
+    let (warning_sink, stats_sink) = (warnings.clone(), stats.clone());
+    let store = Parser::for_heap()
+        .with_warnings(move |w| warning_sink.borrow_mut().push(w.to_string()))
+        .with_stats(move |s| *stats_sink.borrow_mut() = Some(s.clone()))
+        .parse_mem(text)
+        .unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(gfa.get_seq(&gfa.segs[Id::new(2)]), "AC");
+    assert_eq!(warnings.borrow().len(), 1);
+    assert_eq!(stats.take().unwrap().length_mismatches, 1);
+}
+
+#[test]
+fn test_exact_size() {
+    let fixtures: [&[u8]; 8] = [
+        include_bytes!("../../tests/basic/ex1.gfa"),
+        include_bytes!("../../tests/basic/ex2.gfa"),
+        include_bytes!("../../tests/handmade/crush1.gfa"),
+        b"H\tVN:Z:1.0\tXX:i:1\nH\nS\t1\tACGT\tLN:i:4\tbad\nH\tXY:i:2\nS\tx\t*\r\nL\t1\t+\tx\t-\t3M1I\tID:Z:e\n\
+          J\t1\t+\tx\t+\t*\tSC:i:1\nC\t1\t+\tx\t+\t2\t1M\n\
+          P\tp\t1+,x-\t3M,2M1D\tSO:Z:a\nP\tq\t1+\nW\ts\t1\tchr\t0\t9\t>1<x\tWT:i:2\n",
+        b"# comment\nS\t1\tA\n\nX\tfoo\nL\t1\t+\t1\t-\t0M\n#\n",
+        b"S\t1\tA\tSN:Z:chr1\tSO:i:0\tSR:i:0\nS\t2\tC\tSN:Z:chr1\tSO:i:1\nS\t3\tG\tSN:i:1\tSO:i:2\tSR:i:0\n",
+        b"S\t1\tA\nS\t2\tC\nP\tp\t1+,2+,1+\t1M\nP\tq\t1+,2+\t1M,2M3I,3M\nP\tr\t1+,2+\t1M,1M\n",
+        b"",
+    ];
+    for text in fixtures {
+        let heap = Parser::for_heap()
+            .keep_unknown_records()
+            .parse_mem(text)
+            .unwrap();
+        let gfa = heap.as_ref();
+        let mut expected = vec![0; crate::file::size(&gfa)];
+        crate::file::dump(&gfa, &mut expected);
+
+        // The estimated sizes fit exactly, so the fixed-size file (with its table of
+        // contents updated after parsing) matches the heap-parsed one byte for byte.
+        let size = SizeEstimate::scan(text).unwrap();
+        let toc = crate::file::Toc::exact(&size);
+        assert_eq!(toc.size(), expected.len());
+        let mut buf = vec![0; toc.size()];
+        let (toc, store) = crate::file::init(&mut buf, toc);
+        let store = Parser::for_slice(store)
+            .keep_unknown_records()
+            .parse_mem(text)
+            .unwrap();
+        *toc = crate::file::Toc::for_fixed_store(&store);
+        assert!(buf == expected);
+    }
+
+    assert_eq!(SizeEstimate::scan(b"H\tVN:Z:2.0\nS\t1\t1\tA\n"), None);
+}
+
+#[test]
+fn test_compressed() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let text = include_bytes!("../../tests/basic/ex2.gfa");
+    let binary = |store: flatgfa::HeapGFAStore| {
+        let gfa = store.as_ref();
+        let mut buf = vec![0; crate::file::size(&gfa)];
+        crate::file::dump(&gfa, &mut buf);
+        buf
+    };
+    let expected = binary(Parser::for_heap().parse_mem(text).unwrap());
+
+    let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(text).unwrap();
+    let gzip = gzip.finish().unwrap();
+    let bgzf = crate::bgzf::bgzf(text, 64);
+
+    let path = std::env::temp_dir().join(format!("flatgfa-gz-{}.gfa.gz", std::process::id()));
+    for compressed in [gzip, bgzf] {
+        assert_eq!(
+            binary(Parser::for_heap().parse_mem(&compressed).unwrap()),
+            expected
+        );
+        assert_eq!(
+            binary(Parser::for_heap().parse_stream(&compressed[..]).unwrap()),
+            expected
+        );
+        std::fs::write(&path, &compressed).unwrap();
+        assert_eq!(
+            binary(Parser::for_heap().parse_file(&path).unwrap()),
+            expected
+        );
+
+        // Progress reports count the compressed bytes.
+        let last = std::rc::Rc::new(Cell::new(0));
+        let sink = last.clone();
+        Parser::for_heap()
+            .with_progress(move |p| sink.set(p.bytes))
+            .parse_mem(&compressed)
+            .unwrap();
+        assert_eq!(last.get(), compressed.len() as u64);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // Uncompressed files work too.
+    let path = std::env::temp_dir().join(format!("flatgfa-gz-{}.gfa", std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    assert_eq!(
+        binary(Parser::for_heap().parse_file(&path).unwrap()),
+        expected
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_placeholder_segments() {
+    let text = b"S\t1\tA\nL\t1\t+\t2\t-\t0M\nC\t1\t+\tx\t+\t0\t1M\nP\tp\t1+,2-\t*\n";
+    let err = Parser::for_heap().parse_mem(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: unknown segment `2` in link");
+    let err = Parser::for_heap()
+        .parse_mem(b"S\t1\tA\nC\t1\t+\tx\t+\t0\t1M\n")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: unknown segment `x` in containment"
+    );
+
+    for store in [
+        Parser::for_heap()
+            .with_placeholder_segments()
+            .parse_mem(text)
+            .unwrap(),
+        Parser::for_heap()
+            .with_placeholder_segments()
+            .parse_stream(&text[..])
+            .unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        assert_eq!(
+            format!("{}", &gfa),
+            "S\t1\tA\nL\t1\t+\t2\t-\t0M\nC\t1\t+\tx\t+\t0\t1M\nP\tp\t1+,2-\t*\nS\t2\t*\nS\tx\t*\n"
+        );
+        let placeholder = gfa.find_seg(2).unwrap();
+        assert!(gfa.get_seq(&gfa.segs[placeholder]).is_empty());
+    }
+}
+
+#[test]
+fn test_zero_based_names() {
+    // Some tools (like `vg chunk`) number segments from 0.
+    let text = b"S\t0\tA\nS\t1\tC\nS\t2\tG\nS\t7\tT\nS\t3\tN\n\
+        L\t0\t+\t3\t+\t0M\nP\tp\t0+,1+,2-,7+,3+\t*\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        let names = NameMap::build(&gfa);
+        for (id, seg) in gfa.segs.items() {
+            assert_eq!(names.find_name(gfa.get_seg_name(seg)), Some(id));
+        }
+        assert_eq!(names.find(4), None);
+        assert_eq!(format!("{}", &gfa).as_bytes(), text);
+
+        let path = &gfa.paths[Id::new(0)];
+        let seqs: Vec<_> = gfa.steps[path.steps]
+            .iter()
+            .map(|step| gfa.get_seq(gfa.get_handle_seg(*step)).to_string())
+            .collect();
+        assert_eq!(seqs, ["A", "C", "G", "T", "N"]);
+    }
+}
+
+#[test]
+fn test_skip_sequences() {
+    let text = b"S\t1\tACGT\nS\t2\tCC\tLN:i:2\tRC:i:5\nS\t3\t*\tLN:i:7\nL\t1\t+\t2\t+\t0M\n";
+    let store = Parser::for_heap().skip_sequences().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    assert!(gfa.seq_data.is_empty());
+    let lens: Vec<_> = gfa.segs.all().iter().map(|seg| gfa.seg_len(seg)).collect();
+    assert_eq!(lens, [4, 2, 7]);
+    assert_eq!(
+        format!("{}", &gfa),
+        "S\t1\t*\tLN:i:4\nS\t2\t*\tLN:i:2\tRC:i:5\nS\t3\t*\tLN:i:7\nL\t1\t+\t2\t+\t0M\n"
+    );
+}
+
+#[test]
+fn test_skip_paths() {
+    // We never parse the path lines, so even a malformed one is fine.
+    let text = b"S\t1\tA\nS\t2\tC\nP\tp\t1+,2+\t*\nL\t1\t+\t2\t+\t0M\nP\tq\t1x\t*\n\
+        W\ts\t0\tc\t0\t2\t>1>2\n";
+    let store = Parser::for_heap().skip_paths().parse_mem(text).unwrap();
+    let streamed = Parser::for_heap()
+        .skip_paths()
+        .parse_stream(&text[..])
+        .unwrap();
+    for gfa in [store.as_ref(), streamed.as_ref()] {
+        assert!(gfa.paths.is_empty() && gfa.steps.is_empty() && gfa.walks.is_empty());
+        assert!(!gfa.paths_loaded());
+        assert_eq!(
+            gfa.check_paths().unwrap_err().to_string(),
+            "paths were not loaded"
+        );
+        assert_eq!(format!("{}", &gfa), "S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n");
+    }
+
+    // GFA 2 groups become paths, so we skip them too.
+    let text = b"H\tVN:Z:2.0\nS\t1\t1\tA\nO\tg\t1+\n";
+    let store = Parser::for_heap().skip_paths().parse_mem(text).unwrap();
+    assert!(!store.as_ref().paths_loaded());
+
+    // A graph that really has no paths is different.
+    let store = Parser::for_heap().parse_mem(b"S\t1\tA\n").unwrap();
+    assert!(store.as_ref().paths_loaded());
+    assert!(store.as_ref().check_paths().is_ok());
+}
+
+#[test]
+fn test_jumps() {
+    let text = b"H\tVN:Z:1.2\nS\t1\tA\nS\t2\tC\nJ\t1\t+\t2\t-\t-42\nJ\t2\t+\t1\t+\t*\tSC:i:1\nJ\t1\t-\t2\t+\t0\n";
+    let store = Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let distances: Vec<_> = gfa.jumps.all().iter().map(|j| j.distance()).collect();
+    assert_eq!(distances, [Some(-42), None, Some(0)]);
+    assert_eq!(format!("{}", &gfa).as_bytes(), text);
+}
+
+#[test]
+fn test_tags() {
+    let text = b"S\t1\tACGT\tSO:i:12\tbogus\tRC:f:0.5\nS\t2\tC\nL\t1\t+\t2\t+\t0M\tSN:Z:chr 1\nP\tp\t1+,2+\t*\tSO:Z:x\n";
+
+    // By default, we keep malformed fields but do not parse them.
+    let store = Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let seg = &gfa.segs[Id::new(0)];
+    assert_eq!(gfa.get_tags(seg).len(), 2);
+    assert_eq!(gfa.get_tag_int(seg, b"SO"), Some(12));
+    assert_eq!(gfa.get_tag_float(seg, b"RC"), Some(0.5));
+    assert_eq!(gfa.get_tag_int(seg, b"RC"), None);
+    assert_eq!(gfa.get_tag_int(&gfa.segs[Id::new(1)], b"SO"), None);
+    let link = &gfa.links[Id::new(0)];
+    assert_eq!(gfa.get_tag_str(link, b"SN").unwrap(), "chr 1");
+    assert_eq!(gfa.get_tag_str(&gfa.paths[Id::new(0)], b"SO").unwrap(), "x");
+    assert_eq!(format!("{}", &gfa).as_bytes(), text);
+
+    // In strict mode, they are errors.
+    let err = Parser::for_heap().strict().parse_mem(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 1: bad tag `bogus`");
+    let err = Parser::for_heap()
+        .strict()
+        .parse_stream(&b"H\tVN:Z:1.0\nS\t1\tA\nL\t1\t+\t1\t+\t0M\tXX:i:one\n"[..])
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "line 3: bad tag `XX:i:one`");
+
+    // Strict mode also pinpoints bad CIGAR operations.
+    let text = b"S\t1\tA\nL\t1\t+\t1\t+\t12X3\n";
+    let err = Parser::for_heap().parse_mem(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: bad overlap `12X3`");
+    let err = Parser::for_heap().strict().parse_mem(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: bad CIGAR operation `3`");
+}
+
+#[test]
+fn test_external_seqs() {
+    let dir = std::env::temp_dir().join(format!("flatgfa-seqs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fasta = dir.join("seqs.fa");
+    std::fs::write(
+        &fasta,
+        b">x\nAAAA\n>2 second segment\r\nAC\r\nGT\r\n>3\nTTT\n",
+    )
+    .unwrap();
+    let other = dir.join("other.fa");
+    std::fs::write(&other, b">3\nGG\n").unwrap();
+
+    // Segment 1 is inline, segment 2 is in the default file, and segment 3's `UR` tag
+    // points somewhere else.
+    let text = format!(
+        "S\t1\tC\nS\t2\t*\tLN:i:4\nS\t3\t*\tUR:Z:file://{}\n",
+        other.display()
+    );
+    let parser = || Parser::for_heap().with_sequence_source(&fasta);
+    let parse = |text: &str| {
+        (
+            parser().parse_stream(text.as_bytes()),
+            parser().parse_mem(text.as_bytes()),
+        )
+    };
+    let (streamed, in_mem) = parse(&text);
+    for store in [streamed.unwrap(), in_mem.unwrap()] {
+        let gfa = store.as_ref();
+        let seqs: Vec<_> = gfa.segs.all().iter().map(|s| gfa.get_seq(s)).collect();
+        assert_eq!(seqs, [&b"C"[..], b"ACGT", b"GG"]);
+    }
+
+    let (err, _) = parse("S\t1\t*\n");
+    assert_eq!(
+        err.err().unwrap().to_string(),
+        format!(
+            "line 1: no sequence for segment `1` in `{}`",
+            fasta.display()
+        )
+    );
+    let (err, _) = parse("S\t1\tC\nS\t3\t*\tLN:i:4\n");
+    assert_eq!(
+        err.err().unwrap().to_string(),
+        "line 2: segment `3` has length 4 but its sequence has length 3"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_many() {
+    let dir = std::env::temp_dir().join(format!("flatgfa-many-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, text: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    };
+    let chr1 = write(
+        "chr1.gfa",
+        "H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n",
+    );
+    // The second file shares segment 2 and refers to segment 1 from the first file.
+    let chr2 = write(
+        "chr2.gfa",
+        "H\tVN:Z:1.0\nS\t2\tC\nS\t3\tG\nL\t2\t+\t3\t+\t0M\nP\tp\t1+,2+,3+\t*\n",
+    );
+    let store = Parser::for_heap()
+        .parse_many(&[chr1.clone(), chr2])
+        .unwrap();
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nS\t3\tG\nL\t2\t+\t3\t+\t0M\nP\tp\t1+,2+,3+\t*\n"
+    );
+
+    let conflict = write("conflict.gfa", "S\t1\tT\n");
+    let err = Parser::for_heap()
+        .parse_many(&[chr1.clone(), conflict.clone()])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}: line 1: segment `1` has a different sequence than in an earlier file",
+            conflict.display()
+        )
+    );
+
+    let path = write("path.gfa", "P\tp\t1+\t*\n");
+    let store = Parser::for_heap()
+        .parse_many(&[chr1.clone(), path.clone(), path.clone()])
+        .unwrap();
+    let gfa = store.as_ref();
+    let names: Vec<_> = gfa
+        .paths
+        .all()
+        .iter()
+        .map(|p| gfa.get_path_name(p))
+        .collect();
+    assert_eq!(names, ["p", "p.1"]);
+    let err = Parser::for_heap()
+        .strict()
+        .parse_many(&[chr1.clone(), path.clone(), path.clone()])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!("{}: line 1: duplicate path name `p`", path.display())
+    );
+
+    let missing = write("missing.gfa", "L\t1\t+\t4\t+\t0M\n");
+    let err = Parser::for_heap()
+        .parse_many(&[missing.clone(), chr1])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!("{}: line 1: unknown segment `4` in link", missing.display())
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_duplicate_segments() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // Names on the sequential fast path, other numbers, and text names.
+    for (text, msg) in [
+        (
+            &b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nS\t1\tG\n"[..],
+            "line 4: segment `1` is already defined on line 1",
+        ),
+        (
+            b"S\t5\tA\nS\t9\tC\nS\t9\tG\n",
+            "line 3: segment `9` is already defined on line 2",
+        ),
+        (
+            b"S\tx\tA\nS\ty\tC\nS\tx\tA\n",
+            "line 3: segment `x` is already defined on line 1",
+        ),
+    ] {
+        let err = Parser::for_heap().parse_mem(text).err().unwrap();
+        assert_eq!(err.to_string(), msg);
+        let err = Parser::for_heap().parse_stream(text).err().unwrap();
+        assert_eq!(err.to_string(), msg);
+    }
+
+    // Optionally, we keep the first definition and warn about the rest.
+    let text = b"S\t1\tA\nS\t2\tC\nS\t1\tG\nL\t1\t+\t2\t+\t0M\n";
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let sink = warnings.clone();
+    let store = Parser::for_heap()
+        .keep_first_duplicates()
+        .with_warnings(move |w| sink.borrow_mut().push(w.to_string()))
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n"
+    );
+    assert_eq!(
+        warnings.take(),
+        ["line 3: segment `1` is already defined on line 1"]
+    );
+
+    // When merging, a name that repeats within one file is still a duplicate.
+    let dir = std::env::temp_dir().join(format!("flatgfa-dups-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (first, second) = (dir.join("first.gfa"), dir.join("second.gfa"));
+    std::fs::write(&first, "S\t1\tA\n").unwrap();
+    std::fs::write(&second, "S\t1\tA\nS\t2\tC\nS\t2\tC\n").unwrap();
+    let err = Parser::for_heap()
+        .parse_many(&[first, second.clone()])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}: line 3: segment `2` is already defined on line 2",
+            second.display()
+        )
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_stats() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let text = b"H\tVN:Z:1.0\nP\tp\t1+,2+\t*\nS\t1\tACGT\nL\t1\t+\t2\t+\t0M\nS\t2\tCA\n";
+    let parse = |stream: bool| {
+        let stats = Rc::new(RefCell::new(None));
+        let sink = stats.clone();
+        let parser = Parser::for_heap().with_stats(move |s| *sink.borrow_mut() = Some(s.clone()));
+        if stream {
+            parser.parse_stream(&text[..]).unwrap();
+        } else {
+            parser.parse_mem(text).unwrap();
+        }
+        stats.take().unwrap()
+    };
+
+    // In memory, we defer the path and link until we have seen the segments.
+    let stats = parse(false);
+    assert_eq!(
+        (stats.bytes, stats.lines, stats.seq_len),
+        (text.len() as u64, 5, 6)
+    );
+    assert_eq!(
+        (stats.headers, stats.segs, stats.links, stats.paths),
+        (1, 2, 1, 1)
+    );
+    assert_eq!((stats.deferred_lines, stats.deferred_bytes), (2, 23));
+    assert_eq!(stats.fixups, 0);
+
+    // When streaming, we need fixups for the references to segments that come later.
+    let stats = parse(true);
+    assert_eq!(stats.deferred_lines, 0);
+    assert_eq!((stats.fixups, stats.fixup_bytes), (3, 3));
+}
+
+#[test]
+fn test_duplicate_links() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // For every combination of orientations, a link repeated exactly and the same
+    // link from the other strand.
+    for (from, to) in [("+", "+"), ("+", "-"), ("-", "+"), ("-", "-")] {
+        let flip = |o| if o == "+" { "-" } else { "+" };
+        let text = format!(
+            "S\t1\tA\nS\t2\tC\nL\t1\t{from}\t2\t{to}\t0M\n\
+            L\t2\t{}\t1\t{}\t0M\nL\t1\t{from}\t2\t{to}\t0M\n",
+            flip(to),
+            flip(from),
+        );
+        for (policy, count) in [
+            (DuplicateLinks::Keep, 3),
+            (DuplicateLinks::Exact, 2),
+            (DuplicateLinks::Symmetric, 1),
+        ] {
+            let store = Parser::for_heap()
+                .with_duplicate_links(policy)
+                .parse_mem(text.as_bytes())
+                .unwrap();
+            assert_eq!(store.links.len(), count, "{from}{to} {policy:?}");
+            let store = Parser::for_heap()
+                .with_duplicate_links(policy)
+                .parse_stream(text.as_bytes())
+                .unwrap();
+            assert_eq!(store.links.len(), count, "{from}{to} {policy:?}");
+        }
+    }
+
+    // Overlaps must match, with insertions and deletions swapped on the other strand.
+    // The line order keeps the surviving links in place.
+    let text = b"S\t1\tACGT\nS\t2\tCGTA\nL\t1\t+\t2\t+\t1M2I\nL\t1\t+\t2\t+\t3M\n\
+        S\t3\tT\nL\t2\t-\t1\t-\t2D1M\nL\t2\t-\t1\t-\t1M2D\n";
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let sink = warnings.clone();
+    let store = Parser::for_heap()
+        .with_duplicate_links(DuplicateLinks::Symmetric)
+        .with_warnings(move |w| sink.borrow_mut().push(w.to_string()))
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tACGT\nS\t2\tCGTA\nL\t1\t+\t2\t+\t1M2I\nL\t1\t+\t2\t+\t3M\n\
+        S\t3\tT\nL\t2\t-\t1\t-\t1M2D\n"
+    );
+    assert_eq!(
+        warnings.take(),
+        ["line 6: dropped duplicate of the link on line 3"]
+    );
+
+    // The policy also applies to fixed-size stores.
+    let text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t2\t+\t0M\n";
+    let toc = estimate_toc(text);
+    let mut buf = vec![0; toc.size()];
+    let store = Parser::for_slice(crate::file::init(&mut buf, toc).1)
+        .with_duplicate_links(DuplicateLinks::Exact)
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\n"
+    );
+}
+
+#[test]
+fn test_overlap_spellings() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // Every way of writing a blunt overlap survives a round trip.
+    let text = b"S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t-\t2\t+\t*\nL\t2\t+\t1\t+\t\n\
+        L\t2\t-\t1\t-\t\tID:Z:e\nL\t1\t+\t1\t+\t2M\nP\tp\t1+,2+\t*\nP\tq\t1+,2+\t0M\n\
+        P\tr\t1+,2+\t\nP\ts\t1+,2+\nP\tt\t1+,2+\t\tXX:i:1\nP\tu\t2+,1+\t1M\n";
+    let store = Parser::for_heap().parse_mem(text).unwrap();
+    assert_eq!(format!("{}", &store.as_ref()).as_bytes(), text);
+    let store = Parser::for_heap().parse_stream(&text[..]).unwrap();
+    assert_eq!(format!("{}", &store.as_ref()).as_bytes(), text);
+
+    // Normalizing rewrites them all one way, but leaves real overlaps alone.
+    for (target, o, count) in [
+        (BluntOverlap::ZeroMatch, "0M", 7),
+        (BluntOverlap::Star, "*", 7),
+        (BluntOverlap::Empty, "", 5),
+    ] {
+        let expected = format!(
+            "S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t{o}\nL\t1\t-\t2\t+\t{o}\nL\t2\t+\t1\t+\t{o}\n\
+            L\t2\t-\t1\t-\t{o}\tID:Z:e\nL\t1\t+\t1\t+\t2M\nP\tp\t1+,2+\t{o}\nP\tq\t1+,2+\t{o}\n\
+            P\tr\t1+,2+\t{o}\nP\ts\t1+,2+\t{o}\nP\tt\t1+,2+\t{o}\tXX:i:1\nP\tu\t2+,1+\t1M\n",
+        );
+        for stream in [false, true] {
+            let stats = Rc::new(RefCell::new(None));
+            let sink = stats.clone();
+            let parser = Parser::for_heap()
+                .normalize_overlaps(target)
+                .with_stats(move |s| *sink.borrow_mut() = Some(s.clone()));
+            let store = if stream {
+                parser.parse_stream(&text[..]).unwrap()
+            } else {
+                parser.parse_mem(text).unwrap()
+            };
+            assert_eq!(format!("{}", &store.as_ref()), expected, "{target:?}");
+            let stats = stats.take().unwrap();
+            assert_eq!(stats.normalized_overlaps, count, "{target:?}");
+        }
+    }
+
+    // Paths with several blunt overlaps become one `0M` between each pair of steps.
+    let text = b"S\t1\tA\nS\t2\tC\nP\tp\t1+,2+,1-\t*\nP\tq\t1+,2+,1-\t0M,0M\nP\tr\t2-\t*\n";
+    let store = Parser::for_heap()
+        .normalize_overlaps(BluntOverlap::ZeroMatch)
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\t2\tC\nP\tp\t1+,2+,1-\t0M,0M\nP\tq\t1+,2+,1-\t0M,0M\nP\tr\t2-\t*\n"
+    );
+}
+
+#[test]
+fn test_duplicate_paths() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // Repeated names get the first free suffix, and walks share the namespace.
+    let text = b"S\t1\tA\nP\tp\t1+\t*\nP\tp.1\t1+\t*\nP\tp\t1-\t*\n\
+        W\ts\t0\tc\t*\t*\t>1\nP\ts#0#c\t1+\t*\n";
+    for streamed in [false, true] {
+        let warnings = Rc::new(RefCell::new(Vec::new()));
+        let sink = warnings.clone();
+        let parser =
+            Parser::for_heap().with_warnings(move |w| sink.borrow_mut().push(w.to_string()));
+        let store = if streamed {
+            parser.parse_stream(&text[..])
+        } else {
+            parser.parse_mem(text)
+        }
+        .unwrap();
+        let gfa = store.as_ref();
+        let names: Vec<_> = gfa
+            .paths
+            .all()
+            .iter()
+            .map(|path| gfa.get_path_name(path).to_string())
+            .collect();
+        assert_eq!(names, ["p", "p.1", "p.2", "s#0#c", "s#0#c.1"]);
+        assert_eq!(
+            warnings.take(),
+            [
+                "line 4: duplicate path name `p` (renamed to `p.2`)",
+                "line 6: duplicate path name `s#0#c` (renamed to `s#0#c.1`)"
+            ]
+        );
+
+        // The exact size estimate accounts for the new names.
+        let size = SizeEstimate::scan(text).unwrap();
+        assert_eq!(size.name_bytes, gfa.name_data.len());
+    }
+
+    // In strict mode, they are errors.
+    let err = Parser::for_heap().strict().parse_mem(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 4: duplicate path name `p`");
+}
+
+#[test]
+fn test_progress() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut text = String::new();
+    for i in 1..=100_000 {
+        text.push_str(&format!("S\t{}\tA\n", i));
+    }
+    text.push_str("L\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*");
+
+    let parse = |streamed: bool| {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let sink = reports.clone();
+        let parser = Parser::for_heap().with_progress(move |p| sink.borrow_mut().push(*p));
+        if streamed {
+            parser.parse_stream(text.as_bytes()).unwrap();
+        } else {
+            parser.parse_mem(text.as_bytes()).unwrap();
+        }
+        reports.take()
+    };
+    for reports in [parse(true), parse(false)] {
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].lines, 1 << 16);
+        assert_eq!(
+            reports[1],
+            Progress {
+                bytes: text.len() as u64,
+                lines: 100_002,
+                segs: 100_000,
+                links: 1,
+                paths: 1,
+            }
+        );
+    }
+}
+
+#[test]
+fn test_overlaps() {
+    // Short overlaps are stored inline while parsing, and longer ones spill over.
+    let text = b"S\t1\tACGT\nS\t2\tCGTA\nL\t1\t+\t2\t+\t3M\nL\t2\t+\t1\t+\t1M2I3M1D1M4N\nP\tp\t1+,2+\t3M\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        let long = gfa.get_alignment(gfa.links[Id::new(1)].overlap);
+        assert_eq!(long.ops.len(), 6);
+        assert_eq!(format!("{}", &gfa).as_bytes(), text);
+    }
+}
+
+#[test]
+fn test_overlap_count() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let text = b"S\t1\tA\nS\t2\tC\nS\t3\tG\n\
+        P\tp\t1+,2+,3+\t1M\nP\tq\t1+,2+\t1M,2M,3M\nP\tr\t1+,2+,3+\t*\nP\ts\t1+,2+\t1M,2M\n";
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let sink = warnings.clone();
+    let store = Parser::for_heap()
+        .with_warnings(move |w| sink.borrow_mut().push(w.to_string()))
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(
+        *warnings.borrow(),
+        [
+            "line 4: path `p` has 1 overlaps for 3 steps (expected 2)",
+            "line 5: path `q` has 3 overlaps for 2 steps (expected 1)",
+        ]
+    );
+
+    // We pad short lists and truncate long ones, but leave `*` and lists that wrap
+    // around alone.
+    assert_eq!(
+        format!("{}", &store.as_ref()),
+        "S\t1\tA\nS\t2\tC\nS\t3\tG\n\
+        P\tp\t1+,2+,3+\t1M,0M\nP\tq\t1+,2+\t1M\nP\tr\t1+,2+,3+\t*\nP\ts\t1+,2+\t1M,2M\n"
+    );
+
+    // Strict mode rejects the mismatch.
+    let err = Parser::for_heap().strict().parse_mem(text).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 4: path `p` has 1 overlaps for 3 steps (expected 2)"
+    );
+}
+
+#[test]
+fn test_long_link_overlap() {
+    // A link overlap that does not fit in a `CompactSpan` is an error, not a panic.
+    let cigar = "1M".repeat(CompactSpan::<AlignOp>::MAX_LEN + 1);
+    let text = format!("S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t{}\n", cigar);
+    for err in [
+        Parser::for_heap().parse_mem(text.as_bytes()).err().unwrap(),
+        Parser::for_heap()
+            .parse_stream(text.as_bytes())
+            .err()
+            .unwrap(),
+    ] {
+        assert_eq!(
+            err.to_string(),
+            "line 3: bad overlap: too many operations (65536, but at most 65535)"
+        );
+    }
+
+    // The longest overlap that fits is fine.
+    let cigar = "1M".repeat(CompactSpan::<AlignOp>::MAX_LEN);
+    let text = format!("S\t1\tA\nS\t2\tC\nL\t1\t+\t2\t+\t{}\n", cigar);
+    let store = Parser::for_heap().parse_mem(text.as_bytes()).unwrap();
+    assert_eq!(format!("{}", &store.as_ref()), text);
+}
+
+#[test]
+fn test_path_overlaps() {
+    let text = b"S\t1\tACGT\nS\t2\tCGTA\nS\t3\tT\nP\tp\t1+,2-,3+\t2M1I1D,0M\nP\tq\t1+,3+\t*\n";
+    let store = Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+
+    let overlaps: Vec<_> = gfa
+        .path_overlaps(&gfa.paths[Id::new(0)])
+        .map(|align| align.to_string())
+        .collect();
+    assert_eq!(overlaps, ["2M1I1D", "0M"]);
+    let ops: Vec<_> = gfa
+        .path_overlaps(&gfa.paths[Id::new(0)])
+        .next()
+        .unwrap()
+        .ops
+        .iter()
+        .map(|op| (op.op(), op.len()))
+        .collect();
+    assert!(matches!(
+        ops[..],
+        [
+            (flatgfa::AlignOpcode::Match, 2),
+            (flatgfa::AlignOpcode::Insertion, 1),
+            (flatgfa::AlignOpcode::Deletion, 1)
+        ]
+    ));
+
+    // Unspecified overlaps take no space.
+    assert_eq!(gfa.path_overlaps(&gfa.paths[Id::new(1)]).count(), 0);
+    assert_eq!(gfa.overlaps.len(), 2);
+    assert_eq!(format!("{}", &gfa).as_bytes(), text);
+}
+
+#[test]
+fn test_path_overlap_forms() {
+    // Overlaps may be `*`, a list with one CIGAR per step boundary, or missing. We
+    // write them back out the same way. (The last line here has no newline.)
+    let text =
+        b"S\t1\tA\nS\t2\tC\nS\t3\tG\nP\tp\t1+,2+,3+\t*\nP\tq\t1+,2+,3+\t1M,0M\nP\tr\t1+,2+,3+";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        let counts: Vec<_> = gfa
+            .paths
+            .all()
+            .iter()
+            .map(|path| gfa.path_overlaps(path).count())
+            .collect();
+        assert_eq!(counts, [0, 2, 0]);
+        assert_eq!(
+            format!("{}", &gfa),
+            "S\t1\tA\nS\t2\tC\nS\t3\tG\nP\tp\t1+,2+,3+\t*\nP\tq\t1+,2+,3+\t1M,0M\nP\tr\t1+,2+,3+\n"
+        );
+    }
+}
+
+#[test]
+fn test_containments() {
+    let text = b"H\tVN:Z:1.0\nS\t1\tACGTACGT\nC\t1\t+\t2\t-\t3\t4M\tID:Z:c1\nS\t2\tTACG\nL\t1\t+\t2\t+\t0M\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        assert_eq!(gfa.containments.len(), 1);
+        let containment = &gfa.containments.all()[0];
+        assert_eq!(containment.contained.segment(), Id::new(1));
+        assert_eq!(containment.contained.orient(), Orientation::Backward);
+        assert_eq!(format!("{}", &gfa).as_bytes(), text);
+    }
+}
+
+#[test]
+fn test_walks() {
+    let text = b"H\tVN:Z:1.1\nS\t1\tACGT\nW\tHG002\t1\tchr1\t100\t107\t>1<s2>1\tXX:Z:y\nW\tHG002\t2\tchr1\t*\t*\t>s2\nS\ts2\tGG\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        let names: Vec<_> = gfa
+            .paths
+            .all()
+            .iter()
+            .map(|p| gfa.get_path_name(p))
+            .collect();
+        assert_eq!(names, ["HG002#1#chr1:100-107", "HG002#2#chr1"]);
+
+        let walk = gfa.get_walk(Id::new(0)).unwrap();
+        assert_eq!(gfa.get_walk_sample(walk), "HG002");
+        assert_eq!(gfa.get_walk_seq_id(walk), "chr1");
+        assert_eq!((walk.seq_start(), walk.seq_end()), (Some(100), Some(107)));
+        let steps: Vec<_> = gfa
+            .get_path_steps(&gfa.paths[Id::new(0)])
+            .copied()
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                Handle::new(Id::new(0), Orientation::Forward),
+                Handle::new(Id::new(1), Orientation::Backward),
+                Handle::new(Id::new(0), Orientation::Forward),
+            ]
+        );
+        assert_eq!(gfa.get_walk(Id::new(1)).unwrap().seq_start(), None);
+
+        assert_eq!(format!("{}", &gfa).as_bytes(), text);
+    }
+
+    assert_eq!(
+        Parser::for_heap()
+            .parse_mem(b"S\t1\tA\nW\ts\t0\tc\t*\t*\t>1<\n")
+            .err()
+            .map(|e| e.to_string()),
+        Some("line 2: bad walk step `<`".into())
+    );
+}
+
+#[test]
+fn test_gfa2() {
+    let text = b"H\tVN:Z:2.0\nS\t1\t4\tACGT\nS\t2\t4\tCGTA\tLN:i:4\nS\t3\t2\tGT\n\
+E\te1\t1+\t2+\t1\t4$\t0\t3\t3M\nE\te2\t3-\t1-\t0\t1\t3\t4$\t*\tXY:i:1\n\
+E\t*\t1+\t3-\t1\t3\t0\t2$\t2,2\nO\tp\t1+ 2+\nU\tu\t1 3\n";
+    let expected = "H\tVN:Z:1.0\nS\t1\tACGT\tLN:i:4\nS\t2\tCGTA\tLN:i:4\nS\t3\tGT\tLN:i:2\n\
+L\t1\t+\t2\t+\t3M\tID:Z:e1\tec:Z:1,4$,0,3\n\
+L\t3\t-\t1\t-\t0M\tID:Z:e2\tec:Z:0,1,3,4$\tXY:i:1\n\
+C\t1\t+\t3\t-\t1\t0M\tec:Z:1,3,0,2$\ttr:Z:2,2\nP\tp\t1+,2+\t*\nP\tu\t1+,3+\t*\tgt:Z:U\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        assert_eq!(format!("{}", &store.as_ref()), expected);
+    }
+
+    // The version can also come from the caller.
+    let store = Parser::for_heap()
+        .with_version(Version::Gfa2)
+        .parse_mem(b"S\ts1\t3\tGAT\n")
+        .unwrap();
+    assert_eq!(format!("{}", &store.as_ref()), "S\ts1\tGAT\tLN:i:3\n");
+
+    let parse = |parser: Parser<'static, flatgfa::HeapFamily>, text: &[u8]| {
+        parser.parse_mem(text).err().map(|e| e.to_string())
+    };
+    assert_eq!(
+        parse(
+            Parser::for_heap(),
+            b"S\t1\tA\nE\t*\t1+\t1+\t0\t1$\t0\t1$\t*\n"
+        ),
+        Some("line 2: `E` records are not part of GFA 1".into())
+    );
+    assert_eq!(
+        parse(
+            Parser::for_heap(),
+            b"H\tVN:Z:2.0\nS\t1\t1\tA\nL\t1\t+\t1\t+\t0M\n"
+        ),
+        Some("line 3: `L` records are not part of GFA 2".into())
+    );
+    assert_eq!(
+        parse(Parser::for_heap(), b"H\tVN:Z:3.0\n"),
+        Some("line 1: bad version `3.0`".into())
+    );
+    assert_eq!(
+        parse(Parser::for_heap(), b"S\t1\tA\nH\tVN:Z:2.0\n"),
+        Some("line 2: declared version `2.0` conflicts with GFA 1".into())
+    );
+    assert_eq!(
+        parse(
+            Parser::for_heap().with_version(Version::Gfa1),
+            b"H\tVN:Z:2.0\n"
+        ),
+        Some("line 1: declared version `2.0` conflicts with GFA 1".into())
+    );
+    assert_eq!(
+        parse(
+            Parser::for_heap(),
+            b"H\tVN:Z:2.0\nS\t1\t4\tACGT\nE\tx\t1+\t1+\t1\t2\t1\t2\t*\n"
+        ),
+        Some("line 3: bad edge (neither a dovetail nor a containment) `x`".into())
+    );
+}
+
+#[test]
+fn test_headers() {
+    // We keep the original header lines, but their fields merge into one header.
+    let text = b"H\tVN:Z:1.0\tXX:i:1\nS\t1\tA\nH\tXX:i:1\tYY:Z:two\tbad\nH\tVN:Z:1.2\tbad\nH\n";
+    let store = Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(
+        gfa.get_header_lines().collect::<Vec<_>>(),
+        ["VN:Z:1.0\tXX:i:1", "XX:i:1\tYY:Z:two\tbad", "VN:Z:1.2\tbad"]
+    );
+    assert_eq!(gfa.get_header().unwrap(), "VN:Z:1.0\tXX:i:1\tYY:Z:two\tbad");
+    let tags: Vec<_> = gfa
+        .get_header_tags()
+        .iter()
+        .map(|tag| (tag.name, tag.value))
+        .collect();
+    assert_eq!(
+        tags,
+        [
+            (*b"VN", &b"1.0"[..]),
+            (*b"XX", &b"1"[..]),
+            (*b"YY", &b"two"[..])
+        ]
+    );
+
+    // The merged header goes where the first one was.
+    assert_eq!(
+        format!("{}", &gfa),
+        "H\tVN:Z:1.0\tXX:i:1\tYY:Z:two\tbad\nS\t1\tA\n"
+    );
+    assert_eq!(
+        format!("{}", crate::print::Sorted(&gfa)),
+        "H\tVN:Z:1.0\tXX:i:1\tYY:Z:two\tbad\nS\t1\tA\n"
+    );
+
+    // Headers are optional.
+    let store = Parser::for_heap().parse_mem(b"S\t1\tA\n").unwrap();
+    assert_eq!(store.as_ref().get_header_lines().count(), 0);
+    assert!(store.as_ref().get_header_tags().is_empty());
+    assert_eq!(store.as_ref().get_header(), None);
+
+    // A tag may not have two values (or types), in different lines or the same one.
+    for (text, msg) in [
+        (
+            &b"H\tXX:i:1\nS\t1\tA\nH\tXX:i:2\n"[..],
+            "line 3: header field `XX:i:2` conflicts with earlier field `XX:i:1`",
+        ),
+        (
+            b"H\tXX:i:1\nH\tXX:Z:1\n",
+            "line 2: header field `XX:Z:1` conflicts with earlier field `XX:i:1`",
+        ),
+        (
+            b"H\tXX:i:1\tXX:i:2\n",
+            "line 1: header field `XX:i:2` conflicts with earlier field `XX:i:1`",
+        ),
+    ] {
+        let err = Parser::for_heap().parse_mem(text).err().unwrap();
+        assert_eq!(err.to_string(), msg);
+        let err = Parser::for_heap().parse_stream(text).err().unwrap();
+        assert_eq!(err.to_string(), msg);
+    }
+
+    // Every header's version must agree with the first one's.
+    let err = Parser::for_heap()
+        .parse_mem(b"H\tVN:Z:1.0\nH\tVN:Z:2.0\n")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: declared version `2.0` conflicts with GFA 1"
+    );
+}
+
+#[test]
+fn test_unknown_records() {
+    let text = b"H\tVN:Z:1.0\n# a comment\nS\t1\tA\nX\tx\ty\n\nL\t1\t+\t1\t-\t0M\n#\n";
+
+    // By default, an unknown record type is an error.
+    let err = Parser::for_heap().parse_mem(text).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: unexpected record type `# a comment`"
+    );
+
+    // Otherwise, the lines stay in place (except for the blank one).
+    let store = Parser::for_heap()
+        .keep_unknown_records()
+        .parse_mem(text)
+        .unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(
+        gfa.get_other_lines().collect::<Vec<_>>(),
+        ["# a comment", "X\tx\ty", "#"]
+    );
+    assert_eq!(
+        format!("{}", &gfa),
+        "H\tVN:Z:1.0\n# a comment\nS\t1\tA\nX\tx\ty\nL\t1\t+\t1\t-\t0M\n#\n"
+    );
+    assert_eq!(
+        format!("{}", crate::print::Sorted(&gfa)),
+        "H\tVN:Z:1.0\nS\t1\tA\nL\t1\t+\t1\t-\t0M\n# a comment\nX\tx\ty\n#\n"
+    );
+
+    // A record type only has to start with a known letter to be unknown, even in the
+    // lines that in-memory parsing defers.
+    let text = b"S\t1\tA\nPX\tp\t1+\t*\nL\t1\t+\t1\t-\t0M\n";
+    for store in [
+        Parser::for_heap().keep_unknown_records().parse_mem(text),
+        Parser::for_heap()
+            .keep_unknown_records()
+            .parse_stream(&text[..]),
+    ] {
+        assert_eq!(format!("{}", &store.unwrap().as_ref()).as_bytes(), text);
+    }
+
+    // Records from the other version of GFA are still errors.
+    let err = Parser::for_heap()
+        .keep_unknown_records()
+        .parse_mem(b"S\t1\tA\nE\t*\t1+\t1-\t0\t1\t0\t1\t*\n")
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("line 2:"));
+}
+
+#[test]
+fn test_config() {
+    let text = b"S\t1\tACGT\tbad\nS\t1\tACGT\n# a comment\nL\t1\t+\t2\t+\t0M\n";
+
+    // The default configuration rejects the duplicate segment.
+    let err = Parser::for_heap()
+        .with_config(ParserConfig::default())
+        .parse_mem(text)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: segment `1` is already defined on line 1"
+    );
+
+    // The same policies as the builder methods.
+    let config = ParserConfig {
+        keep_first_duplicates: true,
+        keep_unknown_records: true,
+        skip_sequences: true,
+        placeholder_segments: true,
+        ..ParserConfig::default()
+    };
+    let store = Parser::for_heap()
+        .with_config(config.clone())
+        .parse_mem(text)
+        .unwrap();
+    let gfa = store.as_ref();
+    assert_eq!(gfa.segs.len(), 2);
+    assert_eq!(gfa.seg_len(&gfa.segs[Id::new(0)]), 4);
+    assert!(gfa.get_seq(&gfa.segs[Id::new(0)]).is_empty());
+    assert_eq!(gfa.get_other_lines().collect::<Vec<_>>(), ["# a comment"]);
+    let built = Parser::for_heap()
+        .keep_first_duplicates()
+        .keep_unknown_records()
+        .skip_sequences()
+        .with_placeholder_segments()
+        .parse_mem(text)
+        .unwrap();
+    assert_eq!(format!("{}", &gfa), format!("{}", &built.as_ref()));
+
+    // Strictness rejects the malformed tag.
+    let err = Parser::for_heap()
+        .with_config(ParserConfig {
+            strict: true,
+            ..config
+        })
+        .parse_mem(text)
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("line 1:"));
+}
+
+#[test]
+fn test_incremental() {
+    let text = b"H\tVN:Z:1.0\r\nP\tp\t2+,1-\t*\nS\t1\tACGT\nL\t1\t+\t2\t-\t0M\nS\t2\tCC";
+    let expected = format!("{}", &Parser::for_heap().parse_mem(text).unwrap().as_ref());
+
+    // Any way of splitting the text into chunks gives the same graph.
+    for size in 1..=text.len() {
+        let mut parser = IncrementalParser::new();
+        for chunk in text.chunks(size) {
+            parser.feed(chunk).unwrap();
+        }
+        let store = parser.finish().unwrap();
+        assert_eq!(format!("{}", &store.as_ref()), expected);
+    }
+
+    // Whole lines work too.
+    let mut parser = Parser::for_heap().strict().incremental();
+    for line in text.split_inclusive(|&b| b == b'\n') {
+        parser.feed_line(line).unwrap();
+    }
+    assert_eq!(format!("{}", &parser.finish().unwrap().as_ref()), expected);
+
+    // A whole line cannot follow a chunk that ends in the middle of a line.
+    let mut parser = IncrementalParser::new();
+    parser.feed(b"S\t1\tA\nS\t2").unwrap();
+    let err = parser.feed_line(b"S\t3\tG\n").err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: unfinished line from `feed` before `feed_line`"
+    );
+    parser.feed(b"\tC\n").unwrap();
+    parser.feed_line(b"S\t3\tG\n").unwrap();
+    assert_eq!(parser.finish().unwrap().segs.len(), 3);
+
+    // Errors refer to the line where they occur, once it is complete.
+    let mut parser = IncrementalParser::new();
+    parser.feed(b"S\t1\tA\nS\t2").unwrap();
+    let err = parser.feed(b"\tC\tbad:tag\nX\n").err().unwrap();
+    assert_eq!(err.to_string(), "line 3: unexpected record type `X`");
+    let mut parser = IncrementalParser::new();
+    parser.feed(b"S\t1\tA\nP\tp\t1+,3+\t*").unwrap();
+    let err = parser.finish().err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: path `p` step 1 references unknown segment `3`"
+    );
+}
+
+#[test]
+fn test_orphan_warnings() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let text = b"S\t1\tA\nS\tx\tC\nS\t3\tG\nP\tp\t3+,1-\t*\n";
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let sink = warnings.clone();
+    Parser::for_heap()
+        .warn_orphans()
+        .with_warnings(move |w| sink.borrow_mut().push(w.to_string()))
+        .parse_stream(&text[..])
+        .unwrap();
+    assert_eq!(
+        *warnings.borrow(),
+        ["line 2: segment `x` is not used by any link or path"]
+    );
+
+    // A reference to a segment that comes later also counts.
+    let text = b"L\t4\t+\t1\t+\t0M\nS\t1\tA\nS\t2\tC\nS\t4\tT\n";
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let sink = warnings.clone();
+    Parser::for_heap()
+        .warn_orphans()
+        .with_warnings(move |w| sink.borrow_mut().push(w.to_string()))
+        .parse_stream(&text[..])
+        .unwrap();
+    assert_eq!(
+        *warnings.borrow(),
+        ["line 3: segment `2` is not used by any link or path"]
+    );
+}
+
+#[test]
+fn test_empty_paths() {
+    // Placeholder paths and walks with no steps.
+    let text = b"S\t1\tACG\nP\tp\t\t*\nP\tq\t1+,1-\t*\nW\tHG\t1\tchr1\t0\t0\t\tXX:i:1\n";
+    for store in [
+        Parser::for_heap().parse_mem(text).unwrap(),
+        Parser::for_heap().parse_stream(&text[..]).unwrap(),
+    ] {
+        let gfa = store.as_ref();
+        assert_eq!(format!("{}", &gfa).as_bytes(), text);
+        let counts: Vec<_> = gfa.paths.all().iter().map(|p| p.step_count()).collect();
+        assert_eq!(counts, [0, 2, 0]);
+
+        let empty = &gfa.paths[Id::new(0)];
+        assert_eq!(gfa.get_path_seq(empty).unwrap(), b"");
+        assert_eq!(gfa.path_position(empty, 0), None);
+        assert_eq!(gfa.coverage(), [2]);
+    }
+
+    // A trailing comma is still an error.
+    let err = Parser::for_heap()
+        .parse_mem(b"S\t1\tA\nP\tp\t1+,\t*\n")
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "line 2: missing path step at byte 3");
+}
+
+#[test]
+fn test_fuzz() {
+    // Mutate some small graphs at random and parse them every way we can. Whatever the
+    // bytes, parsing must return an error instead of panicking, the ways of parsing
+    // must agree, and every graph we get must be safe to use and print. Set
+    // `FLATGFA_FUZZ_ITERATIONS` for a longer search.
+    let iterations = std::env::var("FLATGFA_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(2_000);
+
+    // A link whose overlap is just short enough to fit, so a few more operations make
+    // it too long.
+    let long_overlap = format!(
+        "S\t1\tACGT\nS\t2\tTT\nL\t1\t+\t2\t-\t{}\nP\tp\t1+,2-\t1M\n",
+        "1M".repeat(CompactSpan::<AlignOp>::MAX_LEN - 2)
+    );
+    let seeds: [&[u8]; 7] = [
+        include_bytes!("../../tests/basic/ex1.gfa"),
+        include_bytes!("../../tests/handmade/flip1.gfa"),
+        b"H\tVN:Z:1.1\nS\t1\tACGT\tLN:i:4\tRC:i:10\nS\tx\t*\tLN:i:3\n\
+        L\t1\t+\tx\t-\t2M1I\tID:Z:e\nJ\tx\t-\t1\t+\t12\tSC:i:1\nC\t1\t+\tx\t-\t1\t2M\n\
+        P\tp\t1+,x-,1+\t1M,*\tXX:B:i,1,2\nW\tsam\t0\tchr1\t5\t12\t>1<x>1\n#c\n",
+        b"S\t0\t*\tLN:i:1000000\nS\t1\t*\tLN:i:5\nP\tp\t0+;1-\t*\nW\ts\t1\tc\t*\t*\t<1>0>1\n\
+        J\t0\t+\t1\t+\t*\n",
+        b"S\tc1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\r\nS\tc2\tAA\tSN:Z:chr1\tSO:i:4\tSR:i:1\r\n\
+        L\tc1\t+\tc2\t+\t*\r\n",
+        b"H\tVN:Z:2.0\nS\ts1\t4\tACGT\nS\ts2\t3\tGGA\tRC:i:2\nE\te1\ts1+\ts2-\t2\t4$\t0\t2\t2M\n\
+        E\t*\ts2+\ts1+\t0\t3$\t1\t4$\t*\nO\tp\ts1+ s2-\nU\tu\ts1 s2\n",
+        long_overlap.as_bytes(),
+    ];
+    let snippets: [&[u8]; 17] = [
+        b"\t",
+        b"\n",
+        b"\r",
+        b"+",
+        b"-",
+        b",",
+        b"*",
+        b":",
+        b"0",
+        b"P",
+        b"L",
+        b"W",
+        b">",
+        b"LN:i:",
+        b"99999999999999999999",
+        b"-9223372036854775809",
+        b"1M",
+    ];
+
+    // A small linear congruential generator, so the test is deterministic.
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut rand = move |n: usize| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((state >> 33) as usize) % n.max(1)
+    };
+
+    for _ in 0..iterations {
+        let mut text = seeds[rand(seeds.len())].to_vec();
+        for _ in 0..1 + rand(6) {
+            let pos = rand(text.len() + 1);
+            match rand(5) {
+                0 if pos < text.len() => text[pos] = rand(256) as u8,
+                1 => {
+                    let end = (pos + rand(8)).min(text.len());
+                    text.drain(pos..end);
+                }
+                2 => {
+                    // Splice in a piece of some graph.
+                    let other = seeds[rand(seeds.len())];
+                    let start = rand(other.len());
+                    let end = (start + rand(40)).min(other.len());
+                    text.splice(pos..pos, other[start..end].iter().copied());
+                }
+                3 if rand(8) == 0 => {
+                    // Repeat a piece of the text many times, to make very long fields
+                    // (like an overlap with too many operations).
+                    let end = (pos + 1 + rand(4)).min(text.len());
+                    let piece = text[pos..end].to_vec();
+                    let times = 1 + rand(2 * CompactSpan::<AlignOp>::MAX_LEN / piece.len().max(1));
+                    text.splice(pos..pos, piece.repeat(times));
+                }
+                _ => {
+                    let snippet = snippets[rand(snippets.len())];
+                    text.splice(pos..pos, snippet.iter().copied());
+                }
+            }
+        }
+        if rand(10) == 0 {
+            // Compress, and maybe corrupt the compressed data.
+            use std::io::Write;
+            let mut gzip = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+            gzip.write_all(&text).unwrap();
+            text = gzip.finish().unwrap();
+            if rand(2) == 0 {
+                let pos = rand(text.len());
+                text[pos] = rand(256) as u8;
+            }
+        }
+
+        let option = rand(7);
+        let chunk_size = 1 + rand(16);
+        let parser = || {
+            let parser = Parser::for_heap();
+            match option {
+                0 => parser,
+                1 => parser.strict(),
+                2 => parser.skip_sequences(),
+                3 => parser.skip_paths().warn_orphans(),
+                4 => parser.keep_first_duplicates(),
+                _ => parser.with_placeholder_segments().keep_unknown_records(),
+            }
+        };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let in_mem = parser().parse_mem(&text);
+            let streamed = parser().parse_stream(&text[..]);
+            assert_eq!(in_mem.is_ok(), streamed.is_ok());
+            if let (Ok(a), Ok(b)) = (&in_mem, &streamed) {
+                assert!(a.as_ref().content_eq(&b.as_ref()));
+            }
+            if !bgzf::is_gzip(&text) {
+                let mut incremental = parser().incremental();
+                let fed = text
+                    .chunks(chunk_size)
+                    .try_for_each(|chunk| incremental.feed(chunk));
+                assert_eq!(
+                    fed.and_then(|_| incremental.finish()).is_ok(),
+                    streamed.is_ok()
+                );
+            }
+
+            for store in [in_mem, streamed].into_iter().flatten() {
+                let gfa = store.as_ref();
+                gfa.check_bounds().unwrap();
+                let mut out = vec![];
+                gfa.write_gfa(&mut out).unwrap();
+                crate::print::Sorted(&gfa).write(&mut out).unwrap();
+                let mut binary = vec![0; crate::file::size(&gfa)];
+                crate::file::dump(&gfa, &mut binary);
+                crate::file::view(&binary).unwrap().check_bounds().unwrap();
+            }
+        }));
+        if result.is_err() {
+            panic!("failed on input {:?}", bstr::BStr::new(&text));
+        }
+    }
 }
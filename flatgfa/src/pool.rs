@@ -65,10 +65,14 @@ impl<T> From<Id<T>> for u32 {
 
 /// A range of indices into a pool.
 ///
+/// The parser and stores only create spans with `start <= end`. Spans read from a
+/// binary file can violate that if the file is corrupt, so `len` and `is_empty` treat
+/// an inverted span as empty instead of underflowing.
+///
 /// TODO: Consider smaller indices for this, and possibly base/offset instead
 /// of start/end.
 #[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Span<T> {
     pub start: Id<T>,
     pub end: Id<T>,
@@ -88,12 +92,15 @@ impl<T> From<&Span<T>> for std::ops::Range<usize> {
 }
 
 impl<T> Span<T> {
+    /// Check whether the span has no elements. An inverted span (which can only come
+    /// from corrupt data) counts as empty.
     pub fn is_empty(&self) -> bool {
-        self.start.0 == self.end.0
+        self.start.0 >= self.end.0
     }
 
+    /// Get the number of elements in the span, or 0 for an inverted span.
     pub fn len(&self) -> usize {
-        (self.end.0 - self.start.0) as usize
+        self.end.0.saturating_sub(self.start.0) as usize
     }
 
     pub fn contains(&self, id: Id<T>) -> bool {
@@ -113,6 +120,74 @@ impl<T> Span<T> {
     }
 }
 
+/// A compact range of indices into a pool, for spans that are known to be short.
+///
+/// A `Span` takes 8 bytes. A `CompactSpan` stores a 32-bit base index and a 16-bit
+/// length instead, so it takes 6 bytes. The longest span it can represent has
+/// `CompactSpan::MAX_LEN` (65,535) elements. Converting a longer `Span` fails with
+/// a `SpanTooLong` error rather than silently truncating.
+#[derive(Debug, FromZeroes, FromBytes, AsBytes, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C, packed)]
+pub struct CompactSpan<T> {
+    pub base: Id<T>,
+    pub len: u16,
+    _marker: PhantomData<T>,
+}
+
+/// The error for a `Span` that is too long to fit in a `CompactSpan`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanTooLong;
+
+impl<T> CompactSpan<T> {
+    /// The maximum number of elements in a `CompactSpan`.
+    pub const MAX_LEN: usize = u16::MAX as usize;
+
+    pub fn new(base: Id<T>, len: u16) -> Self {
+        Self {
+            base,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn new_empty() -> Self {
+        Self::new(Id::new(0), 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+}
+
+impl<T> From<CompactSpan<T>> for Span<T> {
+    /// A span from a corrupt file can run past the largest ID. We clamp its end
+    /// instead of overflowing, so it stays out of bounds for every pool.
+    fn from(span: CompactSpan<T>) -> Span<T> {
+        let base = span.base.0;
+        let end = base.saturating_add(span.len as u32);
+        Span::new(base.into(), end.into())
+    }
+}
+
+impl<T> TryFrom<Span<T>> for CompactSpan<T> {
+    type Error = SpanTooLong;
+
+    fn try_from(span: Span<T>) -> Result<Self, SpanTooLong> {
+        let len = span.len().try_into().or(Err(SpanTooLong))?;
+        Ok(Self::new(span.start.0.into(), len))
+    }
+}
+
+impl<T> From<CompactSpan<T>> for std::ops::Range<usize> {
+    fn from(span: CompactSpan<T>) -> std::ops::Range<usize> {
+        Span::from(span).into()
+    }
+}
+
 /// A simple arena for objects of a single type.
 ///
 /// This trait provides convenient accessors for treating Vec and Vec-like objects
@@ -121,7 +196,7 @@ impl<T> Span<T> {
 /// access to the current set of objects (but not addition of new objects).
 pub trait Store<T: Clone> {
     /// Get a fixed-size view of the arena.
-    fn as_ref(&self) -> Pool<T>;
+    fn as_ref(&self) -> Pool<'_, T>;
 
     /// Add an item to the pool and get the new id.
     fn add(&mut self, item: T) -> Id<T>;
@@ -133,6 +208,9 @@ pub trait Store<T: Clone> {
     /// Like `add_iter`, but for slices.
     fn add_slice(&mut self, slice: &[T]) -> Span<T>;
 
+    /// Get a mutable reference to an item in the pool.
+    fn get_mut(&mut self, id: Id<T>) -> &mut T;
+
     /// Get the number of items in the pool.
     fn len(&self) -> usize;
 
@@ -145,6 +223,10 @@ pub trait Store<T: Clone> {
     fn next_id(&self) -> Id<T> {
         Id::new(self.len())
     }
+
+    /// Remove the items that do not satisfy a predicate, in place. The remaining
+    /// items keep their order but move to lower IDs.
+    fn retain(&mut self, keep: impl FnMut(&T) -> bool);
 }
 
 /// A store that uses a `Vec` to allocate objects on the heap.
@@ -154,7 +236,7 @@ pub trait Store<T: Clone> {
 pub struct HeapStore<T>(Vec<T>);
 
 impl<T: Clone> Store<T> for HeapStore<T> {
-    fn as_ref(&self) -> Pool<T> {
+    fn as_ref(&self) -> Pool<'_, T> {
         Pool(&self.0)
     }
 
@@ -176,9 +258,30 @@ impl<T: Clone> Store<T> for HeapStore<T> {
         Span::new(start, self.as_ref().next_id())
     }
 
+    fn get_mut(&mut self, id: Id<T>) -> &mut T {
+        &mut self.0[id.index()]
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
+
+    fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+        self.0.retain(keep);
+    }
+}
+
+impl<T> HeapStore<T> {
+    /// Remove all the items from the store, returning them as a `Vec`.
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl<T> From<Vec<T>> for HeapStore<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self(vec)
+    }
 }
 
 impl<T> Default for HeapStore<T> {
@@ -196,7 +299,7 @@ impl<T> Default for HeapStore<T> {
 pub struct FixedStore<'a, T>(SliceVec<'a, T>);
 
 impl<'a, T: Clone> Store<T> for FixedStore<'a, T> {
-    fn as_ref(&self) -> Pool<T> {
+    fn as_ref(&self) -> Pool<'_, T> {
         Pool(&self.0)
     }
 
@@ -218,9 +321,26 @@ impl<'a, T: Clone> Store<T> for FixedStore<'a, T> {
         Span::new(start, self.next_id())
     }
 
+    fn get_mut(&mut self, id: Id<T>) -> &mut T {
+        &mut self.0[id.index()]
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
+
+    fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        // Shift the kept items down. The slots past the new end keep stale copies,
+        // like any other unused capacity.
+        let mut len = 0;
+        for idx in 0..self.0.len() {
+            if keep(&self.0[idx]) {
+                self.0[len] = self.0[idx].clone();
+                len += 1;
+            }
+        }
+        self.0.set_len(len);
+    }
 }
 
 impl<'a, T> FixedStore<'a, T> {
@@ -264,6 +384,27 @@ impl<'a, T> Pool<'a, T> {
         self.0
     }
 
+    /// Get the item with a given ID, or `None` if it is out of range.
+    ///
+    /// Unlike indexing, this does not panic, so it is useful for IDs that come from
+    /// untrusted data, like a binary file that might be corrupt.
+    pub fn get(&self, id: Id<T>) -> Option<&'a T> {
+        self.0.get(id.index())
+    }
+
+    /// Get the items in a span, or `None` if the span is inverted or extends past
+    /// the end of the pool.
+    ///
+    /// This is the checked counterpart of indexing with a `Span`. An empty span is
+    /// fine anywhere up to the end of the pool, including in an empty pool.
+    pub fn get_span(&self, span: Span<T>) -> Option<&'a [T]> {
+        let (start, end) = (span.start.index(), span.end.index());
+        if start > end {
+            return None;
+        }
+        self.0.get(start..end)
+    }
+
     /// Find the first item in the pool that satisfies a predicate.
     pub fn search(&self, pred: impl Fn(&T) -> bool) -> Option<Id<T>> {
         self.0.iter().position(pred).map(|i| Id::new(i))
@@ -290,6 +431,20 @@ impl<T> Index<Span<T>> for Pool<'_, T> {
     type Output = [T];
 
     fn index(&self, span: Span<T>) -> &[T] {
+        debug_assert!(
+            { span.start.0 } <= { span.end.0 },
+            "inverted span {}..{}",
+            { span.start.0 },
+            { span.end.0 }
+        );
+        &self.0[std::ops::Range::from(span)]
+    }
+}
+
+impl<T> Index<CompactSpan<T>> for Pool<'_, T> {
+    type Output = [T];
+
+    fn index(&self, span: CompactSpan<T>) -> &[T] {
         &self.0[std::ops::Range::from(span)]
     }
 }
@@ -299,3 +454,77 @@ impl<'a, T> From<&'a [T]> for Pool<'a, T> {
         Self(slice)
     }
 }
+
+#[test]
+fn test_compact_span() {
+    let span: Span<u8> = Span::new(Id::new(10), Id::new(25));
+    let compact = CompactSpan::try_from(span).unwrap();
+    assert_eq!(compact.len(), 15);
+    assert_eq!(Span::from(compact), span);
+
+    let long: Span<u8> = Span::new(Id::new(0), Id::new(CompactSpan::<u8>::MAX_LEN + 1));
+    assert_eq!(CompactSpan::try_from(long), Err(SpanTooLong));
+
+    // A corrupt span that would run past the largest ID stays out of bounds.
+    let corrupt: CompactSpan<u8> = CompactSpan::new(Id::from(u32::MAX - 1), 10);
+    let span = Span::from(corrupt);
+    assert_eq!(u32::from(span.end), u32::MAX);
+    assert_eq!(Pool::from(&[1u8, 2, 3][..]).get_span(span), None);
+}
+
+#[test]
+fn test_checked_access() {
+    let data = [10u8, 20, 30];
+    let pool = Pool::from(&data[..]);
+    assert_eq!(pool.get(Id::new(2)), Some(&30));
+    assert_eq!(pool.get(Id::new(3)), None);
+
+    // Empty spans are fine, even at the end of the pool or in an empty pool.
+    assert_eq!(
+        pool.get_span(Span::new(Id::new(1), Id::new(3))),
+        Some(&data[1..])
+    );
+    assert_eq!(
+        pool.get_span(Span::new(Id::new(3), Id::new(3))),
+        Some(&[][..])
+    );
+    let empty: Pool<u8> = Pool::from(&[][..]);
+    assert_eq!(empty.get_span(Span::new_empty()), Some(&[][..]));
+    assert_eq!(&empty[Span::new_empty()], &[]);
+
+    // Oversized spans.
+    assert_eq!(pool.get_span(Span::new(Id::new(2), Id::new(4))), None);
+    assert_eq!(pool.get_span(Span::new(Id::new(4), Id::new(4))), None);
+    assert_eq!(empty.get_span(Span::new(Id::new(0), Id::new(1))), None);
+
+    // Inverted spans have no length, and checked access rejects them.
+    let inverted: Span<u8> = Span::new(Id::new(2), Id::new(1));
+    assert_eq!(inverted.len(), 0);
+    assert!(inverted.is_empty());
+    assert_eq!(pool.get_span(inverted), None);
+    assert!(std::panic::catch_unwind(|| pool[inverted].len()).is_err());
+}
+
+#[test]
+fn test_inverted_span() {
+    let inverted: Span<u8> = Span::new(Id::new(7), Id::new(3));
+    assert_eq!(inverted.len(), 0);
+    assert!(inverted.is_empty());
+    assert_eq!(CompactSpan::try_from(inverted).unwrap().len(), 0);
+
+    let max: Span<u8> = Span::new(Id::from(u32::MAX), Id::new(0));
+    assert_eq!(max.len(), 0);
+}
+
+#[test]
+fn test_id_layout() {
+    // The type parameters cost nothing: IDs are bare `u32`s on disk, and spans are
+    // pairs of them.
+    assert_eq!(std::mem::size_of::<Id<crate::flatgfa::Segment>>(), 4);
+    assert_eq!(std::mem::size_of::<Span<crate::flatgfa::Link>>(), 8);
+
+    let id: Id<crate::flatgfa::Path> = Id::new(0x01020304);
+    assert_eq!(id.as_bytes(), 0x01020304u32.as_bytes());
+    let span: Span<u8> = Span::new(Id::new(5), Id::new(9));
+    assert_eq!(span.as_bytes(), [5u32, 9].as_bytes());
+}
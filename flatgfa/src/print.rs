@@ -1,5 +1,9 @@
 use crate::flatgfa;
+use crate::pool::Id;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
+use std::io;
 
 impl fmt::Display for flatgfa::Orientation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -15,15 +19,15 @@ impl fmt::Display for flatgfa::AlignOpcode {
         match self {
             flatgfa::AlignOpcode::Match => write!(f, "M"),
             flatgfa::AlignOpcode::Gap => write!(f, "N"),
-            flatgfa::AlignOpcode::Insertion => write!(f, "D"),
-            flatgfa::AlignOpcode::Deletion => write!(f, "I"),
+            flatgfa::AlignOpcode::Insertion => write!(f, "I"),
+            flatgfa::AlignOpcode::Deletion => write!(f, "D"),
         }
     }
 }
 
 impl<'a> fmt::Display for flatgfa::Alignment<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.ops.len() == 0 {
+        if self.ops.is_empty() {
             write!(f, "0M")?;
         }
         for op in self.ops {
@@ -34,120 +38,729 @@ impl<'a> fmt::Display for flatgfa::Alignment<'a> {
 }
 
 /// A wrapper for displaying components from FlatGFA.
+///
+/// GFA text is bytes: names, sequences, and optional fields do not have to be UTF-8.
+/// The `write` methods produce the exact bytes. Formatting with `{}` is for
+/// presentation, so it replaces any invalid UTF-8 with U+FFFD.
 pub struct Display<'a, T>(pub &'a flatgfa::FlatGFA<'a>, pub T);
 
-impl<'a> fmt::Display for Display<'a, flatgfa::Handle> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// An `io::Write` destination that sends GFA text to a `fmt::Formatter`.
+///
+/// We write every field in one piece, so we can decode each write on its own. Invalid
+/// UTF-8 becomes U+FFFD.
+struct Lossy<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl io::Write for Lossy<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_str(&String::from_utf8_lossy(buf))
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Implement `fmt::Display` for types with a `write` method, by way of `Lossy`.
+macro_rules! display_lossy {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> fmt::Display for $t {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.write(&mut Lossy(f)).map_err(|_| fmt::Error)
+                }
+            }
+        )*
+    };
+}
+
+display_lossy!(
+    Display<'a, flatgfa::Handle>,
+    Display<'a, &flatgfa::Path>,
+    Display<'a, &flatgfa::Walk>,
+    Display<'a, &flatgfa::Link>,
+    Display<'a, &flatgfa::Jump>,
+    Display<'a, &flatgfa::Containment>,
+    Display<'a, &flatgfa::Segment>,
+    Sorted<'a>,
+    SortedBy<'a>
+);
+
+/// Write a segment's name, exactly as it appeared in the GFA text.
+fn write_name<W: io::Write>(out: &mut W, name: flatgfa::SegName) -> io::Result<()> {
+    match name {
+        flatgfa::SegName::Num(num) => write!(out, "{}", num),
+        flatgfa::SegName::Text(text) => out.write_all(text),
+    }
+}
+
+/// Write a tab and then some optional fields, if there are any.
+fn write_optional<W: io::Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    if !data.is_empty() {
+        out.write_all(b"\t")?;
+        out.write_all(data)?;
+    }
+    Ok(())
+}
+
+impl<'a> Display<'a, flatgfa::Handle> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         let seg = self.0.get_handle_seg(self.1);
-        let name = seg.name;
-        write!(f, "{}{}", name, self.1.orient())
+        write_name(out, self.0.get_seg_name(seg))?;
+        write!(out, "{}", self.1.orient())
     }
 }
 
-impl<'a> fmt::Display for Display<'a, &flatgfa::Path> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "P\t{}\t", self.0.get_path_name(&self.1))?;
-        let steps = &self.0.steps[self.1.steps];
-        write!(f, "{}", Display(self.0, steps[0]))?;
-        for step in steps[1..].iter() {
-            write!(f, ",{}", Display(self.0, *step))?;
-        }
-        write!(f, "\t")?;
-        let overlaps = &self.0.overlaps[self.1.overlaps];
-        if overlaps.is_empty() {
-            write!(f, "*")?;
-        } else {
-            write!(f, "{}", self.0.get_alignment(overlaps[0]))?;
-            for overlap in overlaps[1..].iter() {
-                write!(f, ",{}", self.0.get_alignment(*overlap))?;
+impl<'a> Display<'a, &flatgfa::Path> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"P\t")?;
+        out.write_all(self.0.get_path_name(self.1))?;
+        out.write_all(b"\t")?;
+        // An empty path has an empty steps field.
+        for (idx, step) in self.0.steps[self.1.steps].iter().enumerate() {
+            if idx > 0 {
+                out.write_all(b",")?;
             }
+            Display(self.0, *step).write(out)?;
         }
-        Ok(())
+        let optional = self.0.get_path_optional_data(self.1);
+        let mut overlaps = self.0.path_overlaps(self.1);
+        match overlaps.next() {
+            // Write an empty overlap list the way it was written in the input.
+            None => match self.1.overlap_spelling() {
+                flatgfa::OverlapSpelling::Empty => out.write_all(b"\t")?,
+                flatgfa::OverlapSpelling::Missing if optional.is_empty() => {}
+                _ => out.write_all(b"\t*")?,
+            },
+            Some(first) => {
+                out.write_all(b"\t")?;
+                write!(out, "{}", first)?;
+                for overlap in overlaps {
+                    write!(out, ",{}", overlap)?;
+                }
+            }
+        }
+        write_optional(out, optional)
     }
 }
 
-impl<'a> fmt::Display for Display<'a, &flatgfa::Link> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let from = self.1.from;
-        let from_name = self.0.get_handle_seg(from).name;
-        let to = self.1.to;
-        let to_name = self.0.get_handle_seg(to).name;
-        write!(
-            f,
-            "L\t{}\t{}\t{}\t{}\t{}",
-            from_name,
-            from.orient(),
-            to_name,
-            to.orient(),
-            self.0.get_alignment(self.1.overlap)
-        )
-    }
-}
-
-impl<'a> fmt::Display for Display<'a, &flatgfa::Segment> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let name = self.1.name;
-        write!(f, "S\t{}\t{}", name, self.0.get_seq(self.1))?;
-        if !self.1.optional.is_empty() {
-            write!(f, "\t{}", self.0.get_optional_data(self.1))?;
+impl<'a> Display<'a, &flatgfa::Walk> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let path = &self.0.paths[self.1.path];
+        out.write_all(b"W\t")?;
+        out.write_all(self.0.get_walk_sample(self.1))?;
+        let hap_index = self.1.hap_index;
+        write!(out, "\t{}\t", hap_index)?;
+        out.write_all(self.0.get_walk_seq_id(self.1))?;
+        out.write_all(b"\t")?;
+        for pos in [self.1.seq_start(), self.1.seq_end()] {
+            match pos {
+                Some(pos) => write!(out, "{}\t", pos)?,
+                None => out.write_all(b"*\t")?,
+            }
         }
-        Ok(())
+        for step in &self.0.steps[path.steps] {
+            let marker = match step.orient() {
+                flatgfa::Orientation::Forward => b">",
+                flatgfa::Orientation::Backward => b"<",
+            };
+            out.write_all(marker)?;
+            write_name(out, self.0.get_seg_name(self.0.get_handle_seg(*step)))?;
+        }
+        write_optional(out, self.0.get_path_optional_data(path))
+    }
+}
+
+/// Write the two ends of a link or jump, as a segment name and an orientation each.
+fn write_ends<W: io::Write>(
+    out: &mut W,
+    gfa: &flatgfa::FlatGFA,
+    from: flatgfa::Handle,
+    to: flatgfa::Handle,
+) -> io::Result<()> {
+    for (idx, end) in [from, to].into_iter().enumerate() {
+        if idx > 0 {
+            out.write_all(b"\t")?;
+        }
+        write_name(out, gfa.get_seg_name(gfa.get_handle_seg(end)))?;
+        write!(out, "\t{}", end.orient())?;
+    }
+    Ok(())
+}
+
+impl<'a> Display<'a, &flatgfa::Link> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"L\t")?;
+        write_ends(out, self.0, self.1.from, self.1.to)?;
+        let overlap = self.0.get_alignment(self.1.overlap);
+        match (overlap.ops.is_empty(), self.1.overlap_spelling()) {
+            (true, flatgfa::OverlapSpelling::Star) => out.write_all(b"\t*")?,
+            (true, flatgfa::OverlapSpelling::Empty) => out.write_all(b"\t")?,
+            _ => write!(out, "\t{}", overlap)?,
+        }
+        write_optional(out, self.0.get_link_optional_data(self.1))
     }
 }
 
+impl<'a> Display<'a, &flatgfa::Jump> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"J\t")?;
+        write_ends(out, self.0, self.1.from, self.1.to)?;
+        match self.1.distance() {
+            Some(distance) => write!(out, "\t{}", distance)?,
+            None => out.write_all(b"\t*")?,
+        }
+        write_optional(out, self.0.get_jump_optional_data(self.1))
+    }
+}
+
+impl<'a> Display<'a, &flatgfa::Containment> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"C\t")?;
+        write_ends(out, self.0, self.1.container, self.1.contained)?;
+        let pos = self.1.pos;
+        write!(out, "\t{}\t{}", pos, self.0.get_alignment(self.1.overlap))?;
+        write_optional(out, self.0.get_containment_optional_data(self.1))
+    }
+}
+
+impl<'a> Display<'a, &flatgfa::Segment> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"S\t")?;
+        write_name(out, self.0.get_seg_name(self.1))?;
+        let seq = self.0.get_seq(self.1);
+        if seq.is_empty() {
+            // An empty sequence field is not valid GFA, so write an absent sequence.
+            out.write_all(b"\t*")?;
+        } else {
+            out.write_all(b"\t")?;
+            out.write_all(seq)?;
+        }
+        write_optional(out, self.0.get_optional_data(self.1))
+    }
+}
+
+/// Write one line of GFA text: a component, then a newline.
+macro_rules! write_line {
+    ($out:expr, $gfa:expr, $item:expr) => {{
+        Display($gfa, $item).write($out)?;
+        $out.write_all(b"\n")
+    }};
+}
+
 /// Print a graph in the order preserved from an original GFA file.
-fn write_preserved(gfa: &flatgfa::FlatGFA, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+fn write_preserved<W: io::Write>(gfa: &flatgfa::FlatGFA, out: &mut W) -> io::Result<()> {
     let mut seg_iter = gfa.segs.all().iter();
     let mut path_iter = gfa.paths.all().iter();
     let mut link_iter = gfa.links.all().iter();
+    let mut jump_iter = gfa.jumps.all().iter();
+    let mut containment_iter = gfa.containments.all().iter();
+    let mut walk_iter = gfa.walks.all().iter();
+    let mut header = gfa.get_header();
+    let mut other_iter = gfa.get_other_lines();
+    let paths_loaded = gfa.paths_loaded();
     for kind in gfa.get_line_order() {
         match kind {
             flatgfa::LineKind::Header => {
-                let version = gfa.header;
-                assert!(!version.is_empty());
-                writeln!(f, "H\t{}", bstr::BStr::new(version.all()))?;
+                // The merged header goes where the first header line was.
+                if let Some(line) = header.take() {
+                    write_header_line(line.as_ref(), out)?;
+                }
             }
             flatgfa::LineKind::Segment => {
                 let seg = seg_iter.next().expect("too few segments");
-                writeln!(f, "{}", Display(gfa, seg))?;
+                write_line!(out, gfa, seg)?;
             }
+            flatgfa::LineKind::Path | flatgfa::LineKind::Walk if !paths_loaded => {}
             flatgfa::LineKind::Path => {
                 let path = path_iter.next().expect("too few paths");
-                writeln!(f, "{}", Display(gfa, path))?;
+                write_line!(out, gfa, path)?;
             }
             flatgfa::LineKind::Link => {
                 let link = link_iter.next().expect("too few links");
-                writeln!(f, "{}", Display(gfa, link))?;
+                write_line!(out, gfa, link)?;
+            }
+            flatgfa::LineKind::Jump => {
+                let jump = jump_iter.next().expect("too few jumps");
+                write_line!(out, gfa, jump)?;
+            }
+            flatgfa::LineKind::Containment => {
+                let containment = containment_iter.next().expect("too few containments");
+                write_line!(out, gfa, containment)?;
+            }
+            flatgfa::LineKind::Walk => {
+                // Walks are stored as paths, so this consumes a path too.
+                path_iter.next().expect("too few paths");
+                let walk = walk_iter.next().expect("too few walks");
+                write_line!(out, gfa, walk)?;
+            }
+            flatgfa::LineKind::Other => {
+                let line = other_iter.next().expect("too few other lines");
+                out.write_all(line)?;
+                out.write_all(b"\n")?;
             }
         }
     }
     Ok(())
 }
 
-/// Print a graph in a normalized order, ignoring the original GFA line order.
-pub fn write_normalized(gfa: &flatgfa::FlatGFA, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    if !gfa.header.is_empty() {
-        writeln!(f, "H\t{}", bstr::BStr::new(gfa.header.all()))?;
+/// Print a graph's header, merged into one line.
+fn write_header<W: io::Write>(gfa: &flatgfa::FlatGFA, out: &mut W) -> io::Result<()> {
+    match gfa.get_header() {
+        Some(line) => write_header_line(line.as_ref(), out),
+        None => Ok(()),
     }
+}
+
+fn write_header_line<W: io::Write>(line: &[u8], out: &mut W) -> io::Result<()> {
+    out.write_all(b"H\t")?;
+    out.write_all(line)?;
+    out.write_all(b"\n")
+}
+
+/// Print the lines with unknown record types, which have no place of their own in a
+/// normalized order, so they go at the end.
+fn write_other_lines<W: io::Write>(gfa: &flatgfa::FlatGFA, out: &mut W) -> io::Result<()> {
+    for line in gfa.get_other_lines() {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Print a graph in a normalized order, ignoring the original GFA line order.
+pub fn write_normalized<W: io::Write>(gfa: &flatgfa::FlatGFA, out: &mut W) -> io::Result<()> {
+    write_header(gfa, out)?;
     for seg in gfa.segs.all().iter() {
-        writeln!(f, "{}", Display(gfa, seg))?;
+        write_line!(out, gfa, seg)?;
     }
-    for path in gfa.paths.all().iter() {
-        writeln!(f, "{}", Display(gfa, path))?;
+    let mut walk_iter = gfa.walks.all().iter().peekable();
+    for (id, path) in gfa.paths.items() {
+        match walk_iter.next_if(|walk| { walk.path } == id) {
+            Some(walk) => write_line!(out, gfa, walk)?,
+            None => write_line!(out, gfa, path)?,
+        }
     }
     for link in gfa.links.all().iter() {
-        writeln!(f, "{}", Display(gfa, link))?;
+        write_line!(out, gfa, link)?;
     }
-    Ok(())
+    for jump in gfa.jumps.all().iter() {
+        write_line!(out, gfa, jump)?;
+    }
+    for containment in gfa.containments.all().iter() {
+        write_line!(out, gfa, containment)?;
+    }
+    write_other_lines(gfa, out)
 }
 
-/// Print our flat representation as in GFA text format.
-impl<'a> fmt::Display for &'a flatgfa::FlatGFA<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl<'a> flatgfa::FlatGFA<'a> {
+    /// Write the graph as GFA text, byte for byte (see `Display`).
+    ///
+    /// When the graph records its original line order (i.e., it came from a GFA file),
+    /// we follow that order. Otherwise, we use the normalized order (see `Sorted`).
+    pub fn write_gfa<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         if self.line_order.is_empty() {
-            write_normalized(self, f)
+            write_normalized(self, out)
         } else {
-            write_preserved(self, f)
+            write_preserved(self, out)
+        }
+    }
+}
+
+/// Print our flat representation as in GFA text format. See `FlatGFA::write_gfa`.
+impl<'a> fmt::Display for &'a flatgfa::FlatGFA<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_gfa(&mut Lossy(f)).map_err(|_| fmt::Error)
+    }
+}
+
+/// A wrapper for printing a graph as GFA text in a normalized order, regardless of
+/// the original line order: the header, then all the segments, paths (and walks),
+/// links, jumps, containments, and any lines with unknown record types.
+pub struct Sorted<'a>(pub &'a flatgfa::FlatGFA<'a>);
+
+impl<'a> Sorted<'a> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write_normalized(self.0, out)
+    }
+}
+
+/// An order for the segments in `SortedBy` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentOrder {
+    /// By name, in natural order (see `natural_cmp`).
+    Name,
+
+    /// By sequence length, shortest first. Segments with the same length are in name
+    /// order.
+    Length,
+}
+
+/// A wrapper for printing a graph as GFA text in a canonical order, for comparing
+/// graphs with `diff`.
+///
+/// This is like `Sorted`, but the segments are in the given order, and the other
+/// lines follow suit: links, jumps, and containments are sorted by their endpoints'
+/// positions in the segment order (and then orientations, forward first). Paths and
+/// walks are sorted by name, in natural order.
+pub struct SortedBy<'a>(pub &'a flatgfa::FlatGFA<'a>, pub SegmentOrder);
+
+impl<'a> SortedBy<'a> {
+    pub fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let gfa = self.0;
+        write_header(gfa, out)?;
+
+        // Sort the segments. The sorts are stable, so equal segments stay in ID order.
+        let segs = gfa.segs.all();
+        let names: Vec<Vec<u8>> = segs
+            .iter()
+            .map(|seg| gfa.get_seg_name(seg).to_bytes().into_owned())
+            .collect();
+        let mut order: Vec<usize> = (0..segs.len()).collect();
+        match self.1 {
+            SegmentOrder::Name => order.sort_by(|&a, &b| natural_cmp(&names[a], &names[b])),
+            SegmentOrder::Length => order.sort_by(|&a, &b| {
+                gfa.seg_len(&segs[a])
+                    .cmp(&gfa.seg_len(&segs[b]))
+                    .then_with(|| natural_cmp(&names[a], &names[b]))
+            }),
+        }
+        for &idx in &order {
+            write_line!(out, gfa, &segs[idx])?;
+        }
+
+        // Each segment's position in the new order determines the edges' order.
+        let mut rank = vec![0; segs.len()];
+        for (pos, &idx) in order.iter().enumerate() {
+            rank[idx] = pos;
+        }
+        let key = |handle: flatgfa::Handle| {
+            (
+                rank[handle.segment().index()],
+                handle.orient() == flatgfa::Orientation::Backward,
+            )
+        };
+
+        let mut paths: Vec<_> = gfa.paths.items().collect();
+        paths.sort_by(|(_, a), (_, b)| natural_cmp(gfa.get_path_name(a), gfa.get_path_name(b)));
+        for (id, path) in paths {
+            match gfa.get_walk(id) {
+                Some(walk) => write_line!(out, gfa, walk)?,
+                None => write_line!(out, gfa, path)?,
+            }
+        }
+
+        let mut links: Vec<_> = gfa.links.all().iter().collect();
+        links.sort_by_key(|link| (key(link.from), key(link.to)));
+        for link in links {
+            write_line!(out, gfa, link)?;
+        }
+        let mut jumps: Vec<_> = gfa.jumps.all().iter().collect();
+        jumps.sort_by_key(|jump| (key(jump.from), key(jump.to)));
+        for jump in jumps {
+            write_line!(out, gfa, jump)?;
+        }
+        let mut containments: Vec<_> = gfa.containments.all().iter().collect();
+        containments.sort_by_key(|c| (key(c.container), key(c.contained)));
+        for containment in containments {
+            write_line!(out, gfa, containment)?;
+        }
+        write_other_lines(gfa, out)
+    }
+}
+
+impl<'a> flatgfa::FlatGFA<'a> {
+    /// Get the IDs of all the segments, sorted by name in natural order (see
+    /// `natural_cmp`), so `s9` comes before `s10`.
+    ///
+    /// This sorts the segments each time, so callers that need the order repeatedly
+    /// should collect it.
+    pub fn segments_by_name(&self) -> impl Iterator<Item = Id<flatgfa::Segment>> {
+        let names: Vec<Vec<u8>> = self
+            .segs
+            .all()
+            .iter()
+            .map(|seg| self.get_seg_name(seg).to_bytes().into_owned())
+            .collect();
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        order.sort_by(|&a, &b| natural_cmp(&names[a], &names[b]));
+        order.into_iter().map(Id::new)
+    }
+
+    /// Write GFA text with only some of the graph's paths (and walks), in the order
+    /// given, in the normalized order otherwise (see `Sorted`).
+    ///
+    /// Without `prune`, this includes everything else in the graph. With `prune`, it
+    /// drops the segments that the chosen paths do not visit and the links that they
+    /// do not traverse (in either direction), along with jumps, containments, and
+    /// lines with unknown record types, since those may refer to dropped segments. Jumps
+    /// and containments between kept segments stay.
+    pub fn write_gfa_paths<W: io::Write>(
+        &self,
+        out: &mut W,
+        path_ids: &[Id<flatgfa::Path>],
+        prune: bool,
+    ) -> io::Result<()> {
+        // Find the segments and links that the paths use.
+        let mut segs = HashSet::new();
+        let mut edges = HashSet::new();
+        for &id in path_ids {
+            let steps = &self.steps[self.paths[id].steps];
+            segs.extend(steps.iter().map(|step| step.segment()));
+            for pair in steps.windows(2) {
+                edges.insert((pair[0], pair[1]));
+            }
+        }
+        let keep_seg = |id| !prune || segs.contains(&id);
+        let keep_link = |link: &flatgfa::Link| {
+            !prune
+                || edges.contains(&(link.from, link.to))
+                || edges.contains(&(link.to.flip(), link.from.flip()))
+        };
+
+        write_header(self, out)?;
+        for (id, seg) in self.segs.items() {
+            if keep_seg(id) {
+                write_line!(out, self, seg)?;
+            }
+        }
+        for &id in path_ids {
+            match self.get_walk(id) {
+                Some(walk) => write_line!(out, self, walk)?,
+                None => write_line!(out, self, &self.paths[id])?,
+            }
+        }
+        for link in self.links.all().iter().filter(|link| keep_link(link)) {
+            write_line!(out, self, link)?;
+        }
+        for jump in self.jumps.all() {
+            if keep_seg(jump.from.segment()) && keep_seg(jump.to.segment()) {
+                write_line!(out, self, jump)?;
+            }
+        }
+        for c in self.containments.all() {
+            if keep_seg(c.container.segment()) && keep_seg(c.contained.segment()) {
+                write_line!(out, self, c)?;
+            }
         }
+        if !prune {
+            write_other_lines(self, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare names in "natural" order, where runs of digits compare by their numeric
+/// value, so `s2` comes before `s10`. Other bytes compare as usual.
+///
+/// Numbers can be arbitrarily long. When two names differ only in their numbers'
+/// leading zeros, the one with fewer zeros (at the first difference) comes first.
+pub fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    /// Split a slice into its leading digits and the rest.
+    fn digits(s: &[u8]) -> (&[u8], &[u8]) {
+        let len = s.iter().take_while(|c| c.is_ascii_digit()).count();
+        s.split_at(len)
+    }
+
+    /// Remove leading zeros from a number.
+    fn trim(num: &[u8]) -> &[u8] {
+        let zeros = num.iter().take_while(|&&c| c == b'0').count();
+        &num[zeros..]
     }
+
+    let (mut a, mut b) = (a, b);
+    let mut zeros_tie = Ordering::Equal;
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return zeros_tie,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (num_a, rest_a) = digits(a);
+                let (num_b, rest_b) = digits(b);
+                let (val_a, val_b) = (trim(num_a), trim(num_b));
+                let ord = val_a.len().cmp(&val_b.len()).then_with(|| val_a.cmp(val_b));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                zeros_tie = zeros_tie.then(num_a.len().cmp(&num_b.len()));
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_line_order() {
+    use flatgfa::LineKind;
+
+    let text =
+        b"H\tVN:Z:1.0\nS\t1\tA\nL\t1\t+\t2\t+\t0M\nP\tp\t1+,2+\t*\nS\t2\tC\nL\t2\t+\t1\t+\t0M\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+
+    // By default, we preserve the input's interleaved order.
+    assert!(matches!(
+        gfa.get_line_order().collect::<Vec<_>>()[..],
+        [
+            LineKind::Header,
+            LineKind::Segment,
+            LineKind::Link,
+            LineKind::Path,
+            LineKind::Segment,
+            LineKind::Link
+        ]
+    ));
+    assert_eq!(format!("{}", &gfa).as_bytes(), text);
+
+    assert_eq!(
+        Sorted(&gfa).to_string(),
+        "H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nP\tp\t1+,2+\t*\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t1\t+\t0M\n"
+    );
+}
+
+#[test]
+fn test_natural_cmp() {
+    let mut names: Vec<&[u8]> = vec![
+        b"s10",
+        b"s2",
+        b"s1a",
+        b"t",
+        b"s01",
+        b"s1",
+        b"10",
+        b"9",
+        b"s",
+        b"s100000000000000000000",
+    ];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(
+        names,
+        [
+            &b"9"[..],
+            b"10",
+            b"s",
+            b"s1",
+            b"s01",
+            b"s1a",
+            b"s2",
+            b"s10",
+            b"s100000000000000000000",
+            b"t",
+        ]
+    );
+}
+
+#[test]
+fn test_sorted_by() {
+    let sorted = |text: &[u8], order| {
+        let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+        SortedBy(&store.as_ref(), order).to_string()
+    };
+
+    let text = b"S\ts10\tA\nS\ts2\tCCC\nS\ts1\tGG\nP\tb\ts1+,s2+\t*\nP\ta\ts2-\t*\n\
+        L\ts10\t+\ts2\t+\t0M\nL\ts1\t-\ts10\t+\t0M\nL\ts1\t+\ts10\t+\t0M\n";
+    let shuffled = b"L\ts1\t+\ts10\t+\t0M\nS\ts2\tCCC\nP\ta\ts2-\t*\nS\ts1\tGG\n\
+        L\ts10\t+\ts2\t+\t0M\nS\ts10\tA\nL\ts1\t-\ts10\t+\t0M\nP\tb\ts1+,s2+\t*\n";
+
+    let by_name = sorted(text, SegmentOrder::Name);
+    assert_eq!(
+        by_name,
+        "S\ts1\tGG\nS\ts2\tCCC\nS\ts10\tA\nP\ta\ts2-\t*\nP\tb\ts1+,s2+\t*\n\
+        L\ts1\t+\ts10\t+\t0M\nL\ts1\t-\ts10\t+\t0M\nL\ts10\t+\ts2\t+\t0M\n"
+    );
+    assert_eq!(sorted(shuffled, SegmentOrder::Name), by_name);
+
+    let by_length = sorted(text, SegmentOrder::Length);
+    assert!(by_length.starts_with("S\ts10\tA\nS\ts1\tGG\nS\ts2\tCCC\n"));
+    assert!(
+        by_length.ends_with("L\ts10\t+\ts2\t+\t0M\nL\ts1\t+\ts10\t+\t0M\nL\ts1\t-\ts10\t+\t0M\n")
+    );
+    assert_eq!(sorted(shuffled, SegmentOrder::Length), by_length);
+}
+
+#[test]
+fn test_write_gfa_paths() {
+    let text = b"H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\n\
+        P\tp\t1+,2+,4-\t*\nP\tq\t1+,3+,4-\t*\nP\tr\t3-\t*\n\
+        L\t1\t+\t2\t+\t0M\nL\t4\t+\t2\t-\t0M\nL\t1\t+\t3\t+\t0M\nL\t3\t+\t4\t-\t0M\n\
+        J\t1\t+\t4\t+\t*\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let write = |ids: &[u32], prune| {
+        let ids: Vec<_> = ids.iter().map(|&id| Id::from(id)).collect();
+        let mut out = Vec::new();
+        gfa.write_gfa_paths(&mut out, &ids, prune).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    // Pruning keeps only the segments and links that `p` uses. The `4+ 2-` link is
+    // the reverse complement of the `2+ 4-` step.
+    assert_eq!(
+        write(&[0], true),
+        "H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nS\t4\tT\nP\tp\t1+,2+,4-\t*\n\
+        L\t1\t+\t2\t+\t0M\nL\t4\t+\t2\t-\t0M\nJ\t1\t+\t4\t+\t*\n"
+    );
+
+    // Without pruning, everything else stays. Paths come in the order given.
+    let all = write(&[2, 0], false);
+    assert!(all.contains("S\t4\tT\nP\tr\t3-\t*\nP\tp\t1+,2+,4-\t*\nL\t1"));
+    assert!(!all.contains("P\tq"));
+    assert_eq!(all.lines().filter(|l| l.starts_with('L')).count(), 4);
+}
+
+#[test]
+fn test_segments_by_name() {
+    let text = b"S\ts10\tA\nS\ts9\tA\nS\ts100\tA\nS\ts1\tA\nS\tr2\tA\nS\ts09\tA\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let gfa = store.as_ref();
+    let names: Vec<_> = gfa
+        .segments_by_name()
+        .map(|id| gfa.get_seg_name(&gfa.segs[id]).to_string())
+        .collect();
+    assert_eq!(names, ["r2", "s1", "s9", "s09", "s10", "s100"]);
+
+    // Numeric names sort by value.
+    let store = crate::parse::Parser::for_heap()
+        .parse_mem(b"S\t10\tA\nS\t2\tA\nS\t1\tA\n")
+        .unwrap();
+    let ids: Vec<usize> = store.as_ref().segments_by_name().map(Id::index).collect();
+    assert_eq!(ids, [2, 1, 0]);
+}
+
+#[test]
+fn test_write_non_utf8() {
+    // Names and tags in Latin-1, which is not valid UTF-8.
+    let text = b"H\tVN:Z:1.0\nS\tcaf\xe9\tACGT\tXN:Z:na\xefve\nS\t2\tGG\n\
+        L\tcaf\xe9\t+\t2\t-\t0M\nP\tp\xe4th\tcaf\xe9+,2-\t*\n";
+    let write = |gfa: &flatgfa::FlatGFA| {
+        let mut out = vec![];
+        gfa.write_gfa(&mut out).unwrap();
+        out
+    };
+    let mem = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    assert_eq!(write(&mem.as_ref()), text);
+    let stream = crate::parse::Parser::for_heap()
+        .parse_stream(&text[..])
+        .unwrap();
+    assert_eq!(write(&stream.as_ref()), text);
+
+    let mut sorted = vec![];
+    Sorted(&mem.as_ref()).write(&mut sorted).unwrap();
+    assert_eq!(
+        sorted,
+        b"H\tVN:Z:1.0\nS\tcaf\xe9\tACGT\tXN:Z:na\xefve\nS\t2\tGG\n\
+        P\tp\xe4th\tcaf\xe9+,2-\t*\nL\tcaf\xe9\t+\t2\t-\t0M\n"
+    );
+
+    // The `Display` impls substitute replacement characters instead of failing.
+    assert!(format!("{}", &mem.as_ref()).starts_with("H\tVN:Z:1.0\nS\tcaf\u{fffd}\t"));
 }
@@ -0,0 +1,302 @@
+use crate::flatgfa::{nucleotide_complement, FlatGFA, Orientation, Segment};
+use crate::pool::Id;
+use memchr::memmem;
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+/// An occurrence of a query sequence within a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceHit {
+    /// The segment containing the match.
+    pub seg: Id<Segment>,
+
+    /// The offset of the match, counted from the start of the segment's sequence
+    /// *in the given orientation*. So for a `Backward` hit, this is an offset into
+    /// the reverse complement of the segment.
+    pub offset: usize,
+
+    /// The strand where the match occurs.
+    pub orient: Orientation,
+}
+
+/// Compute the reverse complement of a nucleotide sequence.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|&c| nucleotide_complement(c))
+        .collect()
+}
+
+impl<'a> FlatGFA<'a> {
+    /// Find all exact occurrences of `query` in the segment sequences, on both strands.
+    ///
+    /// This is a brute-force scan, parallelized over segments. Hits are reported in
+    /// segment order; within a segment, forward hits precede backward ones. A
+    /// query that is its own reverse complement matches on both strands.
+    pub fn find_sequence(&self, query: &[u8]) -> Vec<SequenceHit> {
+        self.search(query, false)
+    }
+
+    /// Like `find_sequence`, but ignoring case, so (for example) soft-masked
+    /// lowercase bases match an uppercase query.
+    pub fn find_sequence_ignore_case(&self, query: &[u8]) -> Vec<SequenceHit> {
+        self.search(&query.to_ascii_uppercase(), true)
+    }
+
+    /// Build an index of every `k`-mer in the segment sequences.
+    ///
+    /// See `KmerIndex` for its (considerable) memory footprint. Panics if `k` is zero
+    /// or more than `KmerIndex::MAX_K`.
+    pub fn build_kmer_index(&self, k: usize) -> KmerIndex {
+        assert!(
+            (1..=KmerIndex::MAX_K).contains(&k),
+            "k-mer length must be between 1 and {}",
+            KmerIndex::MAX_K
+        );
+        let mask = u64::MAX >> (64 - 2 * k);
+
+        // Encode every k-mer with a rolling hash, restarting after non-nucleotides.
+        let mut pairs: Vec<(u64, KmerLocation)> = self
+            .segs
+            .all()
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(idx, seg)| {
+                let mut code = 0;
+                let mut valid = 0;
+                self.get_seq(seg)
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(pos, &c)| {
+                        match encode_base(c) {
+                            Some(bits) => {
+                                code = ((code << 2) | bits) & mask;
+                                valid += 1;
+                            }
+                            None => valid = 0,
+                        }
+                        (valid >= k).then(|| {
+                            let loc = KmerLocation {
+                                seg: Id::new(idx),
+                                offset: (pos + 1 - k) as u32,
+                            };
+                            (code, loc)
+                        })
+                    })
+            })
+            .collect();
+        pairs.par_sort_unstable_by_key(|&(code, loc)| (code, loc.seg.index(), loc.offset));
+
+        // Group the locations by k-mer.
+        let mut kmers = vec![];
+        let mut starts = vec![];
+        for (idx, &(code, _)) in pairs.iter().enumerate() {
+            if kmers.last() != Some(&code) {
+                kmers.push(code);
+                starts.push(idx as u32);
+            }
+        }
+        starts.push(pairs.len() as u32);
+        KmerIndex {
+            k,
+            kmers,
+            starts,
+            locations: pairs.into_iter().map(|(_, loc)| loc).collect(),
+        }
+    }
+
+    /// Scan for `query`. If `ignore_case` is set, the query must be uppercase, and we
+    /// uppercase any segment sequences that are not.
+    fn search(&self, query: &[u8], ignore_case: bool) -> Vec<SequenceHit> {
+        if query.is_empty() {
+            return vec![];
+        }
+        let fwd = memmem::Finder::new(query);
+        let revcmp = reverse_complement(query);
+        let rev = memmem::Finder::new(&revcmp);
+
+        self.segs
+            .all()
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(idx, seg)| {
+                let seq: &[u8] = self.get_seq(seg).as_ref();
+                let seq: Cow<[u8]> = if ignore_case && seq.iter().any(u8::is_ascii_lowercase) {
+                    seq.to_ascii_uppercase().into()
+                } else {
+                    seq.into()
+                };
+                let fwd_hits = fwd.find_iter(&seq).map(move |offset| SequenceHit {
+                    seg: Id::new(idx),
+                    offset,
+                    orient: Orientation::Forward,
+                });
+                let rev_hits = rev.find_iter(&seq).map(|pos| SequenceHit {
+                    seg: Id::new(idx),
+                    offset: seq.len() - pos - query.len(),
+                    orient: Orientation::Backward,
+                });
+                fwd_hits.chain(rev_hits).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// The place where a k-mer occurs: a segment and an offset into its (forward)
+/// sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KmerLocation {
+    pub seg: Id<Segment>,
+    pub offset: u32,
+}
+
+/// An index of every k-mer in the graph's segment sequences, for fast seed lookups.
+///
+/// This indexes the forward strand of each segment; to find a k-mer on the reverse
+/// strand, query its reverse complement. K-mers that contain anything other than
+/// `A`, `C`, `G`, or `T` (in either case) are skipped, so they never match.
+///
+/// The index is large: it takes about 8 bytes per base of sequence in the graph, plus
+/// 12 bytes per distinct k-mer (and building it temporarily needs 16 bytes per base),
+/// so it is only built on request, with `FlatGFA::build_kmer_index`. K-mers do not
+/// span segments.
+pub struct KmerIndex {
+    k: usize,
+
+    /// The distinct k-mers, 2-bit encoded and sorted.
+    kmers: Vec<u64>,
+
+    /// The start of each k-mer's locations in `locations`, plus an end marker.
+    starts: Vec<u32>,
+
+    /// Every k-mer occurrence, grouped by k-mer and sorted within each group.
+    locations: Vec<KmerLocation>,
+}
+
+/// Encode a nucleotide as 2 bits, or return `None` for anything else.
+fn encode_base(c: u8) -> Option<u64> {
+    match c {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+impl KmerIndex {
+    /// The longest supported k-mer length, where an encoded k-mer fills a `u64`.
+    pub const MAX_K: usize = 32;
+
+    /// Encode a k-mer, or return `None` if it has the wrong length or contains
+    /// something other than a nucleotide.
+    fn encode(&self, kmer: &[u8]) -> Option<u64> {
+        if kmer.len() != self.k {
+            return None;
+        }
+        kmer.iter()
+            .try_fold(0, |code, &c| Some((code << 2) | encode_base(c)?))
+    }
+
+    /// Get the length of the indexed k-mers.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Count the distinct k-mers in the index.
+    pub fn len(&self) -> usize {
+        self.kmers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kmers.is_empty()
+    }
+
+    /// Find every occurrence of a k-mer, in segment and then offset order. A query of
+    /// the wrong length has no occurrences.
+    pub fn query(&self, kmer: &[u8]) -> &[KmerLocation] {
+        let Some(code) = self.encode(kmer) else {
+            return &[];
+        };
+        match self.kmers.binary_search(&code) {
+            Ok(idx) => &self.locations[self.starts[idx] as usize..self.starts[idx + 1] as usize],
+            Err(_) => &[],
+        }
+    }
+}
+
+#[test]
+fn test_find_sequence() {
+    let mut store = crate::HeapGFAStore::default();
+    store.add_seg(1, b"GATTACA", &[]);
+    store.add_seg(2, b"TGTAATCAT", &[]);
+    let gfa = store.as_ref();
+
+    let hits = gfa.find_sequence(b"TTAC");
+    assert_eq!(
+        hits,
+        vec![
+            SequenceHit {
+                seg: Id::new(0),
+                offset: 2,
+                orient: Orientation::Forward,
+            },
+            SequenceHit {
+                seg: Id::new(1),
+                offset: 4,
+                orient: Orientation::Backward,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_find_sequence_ignore_case() {
+    let mut store = crate::HeapGFAStore::default();
+    store.add_seg(1, b"GATTACA", &[]);
+    store.add_seg(2, b"ccgtaaTCAT", &[]);
+    store.add_seg(3, b"CCGGG", &[]);
+    let gfa = store.as_ref();
+
+    // Exact search respects case.
+    assert!(gfa.find_sequence(b"CGTAAT").is_empty());
+    assert_eq!(gfa.find_sequence(b"TTAC").len(), 1);
+
+    // `ATTACG` is the reverse complement of `CGTAAT`.
+    let hits: Vec<_> = gfa
+        .find_sequence_ignore_case(b"attacg")
+        .into_iter()
+        .map(|hit| (hit.seg.index(), hit.offset, hit.orient))
+        .collect();
+    assert_eq!(hits, [(1, 3, Orientation::Backward)]);
+    let hits = gfa.find_sequence_ignore_case(b"CgTaAt");
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].seg, hits[0].offset), (Id::new(1), 1));
+}
+
+#[test]
+fn test_kmer_index() {
+    let mut store = crate::HeapGFAStore::default();
+    store.add_seg(1, b"GATTACA", &[]);
+    store.add_seg(2, b"ACANTTAca", &[]);
+    let gfa = store.as_ref();
+
+    let index = gfa.build_kmer_index(3);
+    assert_eq!(index.k(), 3);
+    let loc = |seg: usize, offset| KmerLocation {
+        seg: Id::new(seg),
+        offset,
+    };
+    assert_eq!(index.query(b"ACA"), [loc(0, 4), loc(1, 0), loc(1, 6)]);
+    assert_eq!(index.query(b"tta"), [loc(0, 2), loc(1, 4)]);
+    assert_eq!(index.query(b"GAT"), [loc(0, 0)]);
+
+    // K-mers with an `N`, or of the wrong length, never match.
+    assert!(index.query(b"CAN").is_empty());
+    assert!(index.query(b"ANT").is_empty());
+    assert!(index.query(b"AC").is_empty());
+    assert!(index.query(b"CCC").is_empty());
+
+    // GATTACA has 5 distinct 3-mers, and the other segment's are all among them.
+    assert_eq!(index.len(), 5);
+}
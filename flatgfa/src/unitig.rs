@@ -0,0 +1,327 @@
+use crate::flatgfa::{Alignment, FlatGFA, Handle, HeapGFAStore, Orientation, Segment};
+use crate::pool::{Id, Span, Store};
+use std::collections::{HashMap, HashSet};
+
+/// One side of a segment: its left (start) or right (end) side, in the segment's
+/// forward orientation.
+type Side = (Id<Segment>, bool);
+
+/// The side where we leave a segment when traversing it as `handle`.
+fn end(handle: Handle) -> Side {
+    (handle.segment(), handle.orient() == Orientation::Forward)
+}
+
+/// The side where we enter a segment when traversing it as `handle`.
+fn start(handle: Handle) -> Side {
+    end(handle.flip())
+}
+
+/// Traverse a segment that has orientation `outer` within a unitig in the
+/// orientation `inner`, and get the resulting orientation with respect to the unitig.
+fn compose(outer: Orientation, inner: Orientation) -> Orientation {
+    if outer == inner {
+        Orientation::Forward
+    } else {
+        Orientation::Backward
+    }
+}
+
+/// Check whether an alignment actually overlaps any bases.
+fn overlaps_bases(alignment: Alignment) -> bool {
+    alignment.ops.iter().any(|op| !op.is_empty())
+}
+
+/// Where a segment ended up in a merged unitig.
+struct Member {
+    /// The segment that stands for the entire unitig.
+    rep: Id<Segment>,
+
+    /// The segment's orientation within the unitig.
+    orient: Orientation,
+
+    /// Is this the first or last segment in the unitig?
+    first: bool,
+    last: bool,
+}
+
+impl<'a> FlatGFA<'a> {
+    /// Merge every maximal non-branching chain of segments (i.e., every unitig) into
+    /// a single segment.
+    ///
+    /// Two segment sides are joined when each is the other's only neighbor, counting
+    /// both links and consecutive path steps as adjacencies. We leave a junction alone
+    /// when its link has a nonzero overlap, when a path starts or ends there, or when
+    /// either segment takes part in a jump or containment. Circular chains are not
+    /// merged.
+    ///
+    /// Each chain becomes its lowest-numbered segment, which keeps its name and takes
+    /// the concatenated sequence of the chain. Its optional fields are dropped, except
+    /// that we add an `LN` tag when some segment in the chain has no stored sequence.
+    /// Links and path steps are rewritten to refer to the merged segments, so every
+    /// path spells out the same sequence as before. Like `retain_segments`, this
+    /// renumbers the segments.
+    pub fn compact_unitigs(&self) -> HeapGFAStore {
+        let chains = self.unitigs();
+        let mut store = HeapGFAStore::from_flat(self);
+        if chains.is_empty() {
+            return store;
+        }
+
+        // Build the merged segments and note where every old segment went.
+        let mut members: HashMap<Id<Segment>, Member> = HashMap::new();
+        let mut reps = HashSet::new();
+        for chain in &chains {
+            let rep = chain
+                .iter()
+                .map(|h| h.segment())
+                .min_by_key(|id| id.index())
+                .unwrap();
+            reps.insert(rep);
+            let mut seq = vec![];
+            let mut len = 0;
+            let mut complete = true;
+            for (idx, &handle) in chain.iter().enumerate() {
+                let seg = self.get_handle_seg(handle);
+                complete &= self.has_seq(seg);
+                len += self.seg_len(seg);
+                seq.extend(self.get_seq_oriented(handle).as_vec());
+                members.insert(
+                    handle.segment(),
+                    Member {
+                        rep,
+                        orient: handle.orient(),
+                        first: idx == 0,
+                        last: idx == chain.len() - 1,
+                    },
+                );
+            }
+
+            let seq = if complete {
+                store.seq_data.add_slice(&seq)
+            } else {
+                Span::new_empty()
+            };
+            let seg = store.segs.get_mut(rep);
+            seg.seq = seq;
+            seg.optional = Span::new_empty();
+            seg.tags = Span::new_empty();
+            if !complete {
+                store
+                    .set_seg_tag(rep, *b"LN", b'i', len.to_string().as_bytes())
+                    .expect("lengths are valid tag values");
+            }
+        }
+
+        // Redirect the links at the outer ends of each chain to the merged segment. We
+        // leave the links inside the chains alone: they refer to segments that we are
+        // about to remove, so `retain_segments` drops them.
+        let translate = |handle: Handle| match members.get(&handle.segment()) {
+            Some(member) => Handle::new(member.rep, compose(member.orient, handle.orient())),
+            None => handle,
+        };
+        let internal = |handle: Handle| {
+            members.get(&handle.segment()).is_some_and(|member| {
+                // Leaving a segment toward the rest of its own chain?
+                match (member.orient == handle.orient(), member.first, member.last) {
+                    (true, _, last) => !last,
+                    (false, first, _) => !first,
+                }
+            })
+        };
+        for idx in 0..store.links.len() {
+            let link = store.links.as_ref()[Id::new(idx)];
+            if !internal(link.from) {
+                let link = store.links.get_mut(Id::new(idx));
+                link.from = translate(link.from);
+                link.to = translate(link.to);
+            }
+        }
+
+        // Replace each traversal of a chain with one step on the merged segment. Paths
+        // only enter chains at their ends, so we keep the step that enters the chain.
+        for idx in 0..store.paths.len() {
+            let path = store.paths.as_ref()[Id::new(idx)];
+            let steps: Vec<Handle> = self.steps[path.steps]
+                .iter()
+                .filter(|&&step| match members.get(&step.segment()) {
+                    Some(member) => match compose(member.orient, step.orient()) {
+                        Orientation::Forward => member.first,
+                        Orientation::Backward => member.last,
+                    },
+                    None => true,
+                })
+                .map(|&step| translate(step))
+                .collect();
+            if steps.len() != path.steps.len() {
+                let new_steps = store.steps.add_slice(&steps);
+                let path = store.paths.get_mut(Id::new(idx));
+                path.steps = new_steps;
+                path.overlaps = Span::new_empty();
+            }
+        }
+
+        store.retain_segments(|id| !members.contains_key(&id) || reps.contains(&id));
+        store.compact();
+        store
+    }
+
+    /// Find the chains of segments that `compact_unitigs` merges. Each chain has at
+    /// least two segments and is oriented so that its lowest-numbered segment appears
+    /// forward.
+    fn unitigs(&self) -> Vec<Vec<Handle>> {
+        // Find each segment side's neighbors, through links and path steps.
+        let mut partners: HashMap<Side, HashSet<Side>> = HashMap::new();
+        let mut pair = |a: Side, b: Side| {
+            partners.entry(a).or_default().insert(b);
+            partners.entry(b).or_default().insert(a);
+        };
+        let mut blocked: HashSet<Side> = HashSet::new();
+        for link in self.links.all() {
+            pair(end(link.from), start(link.to));
+            if overlaps_bases(self.get_alignment(link.overlap)) {
+                blocked.extend([end(link.from), start(link.to)]);
+            }
+        }
+        for path in self.paths.all() {
+            let steps = &self.steps[path.steps];
+            for window in steps.windows(2) {
+                pair(end(window[0]), start(window[1]));
+            }
+            for (window, overlap) in steps.windows(2).zip(self.path_overlaps(path)) {
+                if overlaps_bases(overlap) {
+                    blocked.extend([end(window[0]), start(window[1])]);
+                }
+            }
+            if let (Some(&first), Some(&last)) = (steps.first(), steps.last()) {
+                blocked.extend([start(first), end(last)]);
+            }
+        }
+        let edges = self.jumps.all().iter().map(|j| (j.from, j.to));
+        let edges = edges.chain(
+            self.containments
+                .all()
+                .iter()
+                .map(|c| (c.container, c.contained)),
+        );
+        for (a, b) in edges {
+            for seg in [a.segment(), b.segment()] {
+                blocked.extend([(seg, false), (seg, true)]);
+            }
+        }
+
+        // Get the side that a side can be merged with, if any.
+        let joined = |side: Side| -> Option<Side> {
+            if blocked.contains(&side) {
+                return None;
+            }
+            let mut others = partners.get(&side)?.iter();
+            let other = *others.next()?;
+            if others.next().is_some() || other.0 == side.0 || blocked.contains(&other) {
+                return None;
+            }
+            (partners[&other].len() == 1).then_some(other)
+        };
+        let next = |handle: Handle| {
+            let (seg, right) = joined(end(handle))?;
+            let orient = if right {
+                Orientation::Backward
+            } else {
+                Orientation::Forward
+            };
+            Some(Handle::new(seg, orient))
+        };
+
+        let mut seen = vec![false; self.segs.len()];
+        let mut chains = vec![];
+        for (id, _) in self.segs.items() {
+            if seen[id.index()] {
+                continue;
+            }
+
+            // Back up to the start of the chain, giving up on circular ones.
+            let mut first = Handle::new(id, Orientation::Forward);
+            let mut circular = false;
+            while let Some(prev) = next(first.flip()) {
+                first = prev.flip();
+                if first.segment() == id {
+                    circular = true;
+                    break;
+                }
+            }
+
+            let mut chain = vec![first];
+            let mut handle = first;
+            while let Some(succ) = next(handle) {
+                if succ.segment() == first.segment() {
+                    break;
+                }
+                chain.push(succ);
+                handle = succ;
+            }
+            for handle in &chain {
+                seen[handle.segment().index()] = true;
+            }
+            if circular || chain.len() < 2 {
+                continue;
+            }
+
+            // Orient the chain so that its lowest-numbered segment is forward.
+            let rep = chain
+                .iter()
+                .min_by_key(|handle| handle.segment().index())
+                .unwrap();
+            if rep.orient() == Orientation::Backward {
+                chain.reverse();
+                for handle in chain.iter_mut() {
+                    *handle = handle.flip();
+                }
+            }
+            chains.push(chain);
+        }
+        chains
+    }
+}
+
+#[test]
+fn test_compact_unitigs() {
+    let text = b"S\t1\tAC\nS\t2\tGT\nS\t3\tCA\n\
+        L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\nP\tp\t1+,2+,3-\t*\nP\tq\t3+,2-,1-\t*\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let old = store.as_ref();
+    let merged = old.compact_unitigs();
+    let new = merged.as_ref();
+    assert_eq!(
+        format!("{}", &new),
+        "S\t1\tACGTTG\nP\tp\t1+\t*\nP\tq\t1-\t*\n"
+    );
+    for (old_path, new_path) in old.paths.all().iter().zip(new.paths.all()) {
+        assert_eq!(
+            old.get_path_seq(old_path).unwrap(),
+            new.get_path_seq(new_path).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_compact_unitigs_blocked() {
+    // A branch, an overlap, a jump, and the end of a path all stop merging.
+    let text = b"S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\nS\t5\tA\nS\t6\tC\nS\t7\tG\nS\t8\tT\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\nL\t4\t+\t5\t-\t0M\n\
+        L\t5\t-\t8\t+\t0M\nL\t2\t+\t6\t+\t1M\nL\t6\t+\t7\t+\t0M\nJ\t7\t+\t1\t-\t*\n\
+        P\tp\t1+,3+,4+,5-\t*\n";
+    let store = crate::parse::Parser::for_heap().parse_mem(text).unwrap();
+    let merged = store.as_ref().compact_unitigs();
+    assert_eq!(
+        format!("{}", &merged.as_ref()),
+        "S\t1\tA\nS\t2\tC\nS\t3\tGTT\nS\t6\tC\nS\t7\tG\nS\t8\tT\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t3\t+\t8\t+\t0M\n\
+        L\t2\t+\t6\t+\t1M\nL\t6\t+\t7\t+\t0M\nJ\t7\t+\t1\t-\t*\nP\tp\t1+,3+\t*\n"
+    );
+
+    // Merging again changes nothing.
+    let again = merged.as_ref().compact_unitigs();
+    assert_eq!(
+        format!("{}", &again.as_ref()),
+        format!("{}", &merged.as_ref())
+    );
+}